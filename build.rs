@@ -1,12 +1,22 @@
 use std::path::PathBuf;
 use std::process::Command;
 
+include!("include/git_describe_arguments.rs");
+
 fn main() {
     // Pass `git describe --tags` to programs to be picked up via
-    // `env!("GIT_DESCRIBE")`.
-    let args = include!("include/git_describe_arguments.rs");
+    // `env!("GIT_DESCRIBE")`. Forks with other tag conventions can
+    // replace the arguments via the `XMLHUB_GIT_DESCRIBE_ARGS` env
+    // var (see `git_describe_arguments`).
+    println!("cargo::rerun-if-env-changed=XMLHUB_GIT_DESCRIBE_ARGS");
+    let args: Vec<String> = std::iter::once("describe".into())
+        .chain(
+            git_describe_arguments()
+                .unwrap_or_else(|e| panic!("can't decode XMLHUB_GIT_DESCRIBE_ARGS env var: {e}")),
+        )
+        .collect();
     let output = Command::new("git")
-        .args(args)
+        .args(&args)
         .output()
         .expect("`git` command should be available and not fail to run {args:?}");
 
@@ -1 +1,19 @@
-&["describe", "--tags", "--match", "v[0-9]*"]
+// Included by `build.rs` and `src/bin/make-release.rs`, so that the
+// version compiled into the programs and the one make-release starts
+// from are determined the same way.
+
+/// The arguments to `git describe` (without "describe" itself):
+/// `--tags --match v[0-9]*`, unless replaced via the
+/// `XMLHUB_GIT_DESCRIBE_ARGS` env var (split on whitespace, e.g.
+/// "--tags --match release-[0-9]*"); the output must still parse as
+/// `GitVersion<SemVersion>`.
+fn git_describe_arguments() -> Result<Vec<String>, std::env::VarError> {
+    match std::env::var("XMLHUB_GIT_DESCRIBE_ARGS") {
+        Ok(s) => Ok(s.split_whitespace().map(String::from).collect()),
+        Err(std::env::VarError::NotPresent) => Ok(["--tags", "--match", "v[0-9]*"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()),
+        Err(e) => Err(e),
+    }
+}
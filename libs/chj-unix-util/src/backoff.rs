@@ -50,6 +50,19 @@ pub struct LoopWithBackoff {
     /// The number of seconds to sleep at maximum (should be >
     /// `min_sleep_seconds`).
     pub max_sleep_seconds: f64,
+    /// Give up (return `Err(TooManyErrors)` from `run`) after that
+    /// many errors in a row, i.e. without a successful run in
+    /// between; `None` means never give up (default).
+    pub max_consecutive_errors: Option<u64>,
+}
+
+/// Returned by `LoopWithBackoff::run` when `max_consecutive_errors`
+/// was reached.
+#[derive(thiserror::Error, Debug)]
+#[error("giving up after {count} consecutive errors, the last one was: {last_error}")]
+pub struct TooManyErrors {
+    pub count: u64,
+    pub last_error: String,
 }
 
 impl Default for LoopWithBackoff {
@@ -62,23 +75,38 @@ impl Default for LoopWithBackoff {
             success_sleep_factor: 0.99,
             min_sleep_seconds: 1.,
             max_sleep_seconds: 1000.,
+            max_consecutive_errors: None,
         }
     }
 }
 
 impl LoopWithBackoff {
+    /// The time to sleep after a run that succeeded (`success`) or
+    /// failed, given the previous sleep time: increased on errors,
+    /// decreased on success, within `min_sleep_seconds` and
+    /// `max_sleep_seconds`.
+    pub fn next_sleep_seconds(&self, sleep_seconds: f64, success: bool) -> f64 {
+        if success {
+            (sleep_seconds * self.success_sleep_factor).max(self.min_sleep_seconds)
+        } else {
+            (sleep_seconds * self.error_sleep_factor).min(self.max_sleep_seconds)
+        }
+    }
+
     /// Loop running `job` then sleeping at least `min_seconds`, if
     /// `job` returns an `Err`, increases the sleep time. Runs `until`
-    /// after every run, and returns if it returns true.
+    /// after every run, and returns if it returns true. Returns an
+    /// error if `max_consecutive_errors` is reached.
     pub fn run<E: Display>(
         &self,
         mut job: impl FnMut() -> Result<(), E>,
         until: impl Fn() -> bool,
-    ) {
+    ) -> Result<(), TooManyErrors> {
         let prefix = self.prefix.as_ref();
         let mut sleep_seconds = self.min_sleep_seconds;
         let mut iteration_count: u64 = 0;
         let mut last_lai_time: Option<SystemTime> = None;
+        let mut consecutive_errors: u64 = 0;
         loop {
             let result = job();
             if let Err(e) = result {
@@ -88,13 +116,22 @@ impl LoopWithBackoff {
                 if !self.quiet {
                     eprintln!("{prefix}loop: got error: {e:#}");
                 }
-                sleep_seconds =
-                    (sleep_seconds * self.error_sleep_factor).min(self.max_sleep_seconds);
+                consecutive_errors += 1;
+                if let Some(max) = self.max_consecutive_errors {
+                    if consecutive_errors >= max {
+                        return Err(TooManyErrors {
+                            count: consecutive_errors,
+                            last_error: format!("{e:#}"),
+                        });
+                    }
+                }
+                sleep_seconds = self.next_sleep_seconds(sleep_seconds, false);
             } else {
-                sleep_seconds = (sleep_seconds * 0.99).max(self.min_sleep_seconds);
+                consecutive_errors = 0;
+                sleep_seconds = self.next_sleep_seconds(sleep_seconds, true);
             }
             if until() {
-                return;
+                return Ok(());
             }
             iteration_count += 1;
             let verbose_print = || {
@@ -138,3 +175,73 @@ impl LoopWithBackoff {
         }
     }
 }
+
+#[test]
+fn t_max_consecutive_errors() {
+    use std::cell::Cell;
+
+    let config = LoopWithBackoff {
+        verbosity: LoopVerbosity::Silent,
+        quiet: true,
+        min_sleep_seconds: 0.0001,
+        max_sleep_seconds: 0.001,
+        max_consecutive_errors: Some(3),
+        ..Default::default()
+    };
+
+    // Always failing: gives up after 3 runs
+    let runs = Cell::new(0);
+    let result = config.run(
+        || -> Result<(), String> {
+            runs.set(runs.get() + 1);
+            Err(format!("failure {}", runs.get()))
+        },
+        || false,
+    );
+    let e = result.expect_err("gives up");
+    assert_eq!(runs.get(), 3);
+    assert_eq!(e.count, 3);
+    assert_eq!(e.last_error, "failure 3");
+
+    // Every third run succeeds, which resets the count, so the loop
+    // only ends via `until`
+    let runs = Cell::new(0);
+    let result = config.run(
+        || -> Result<(), String> {
+            runs.set(runs.get() + 1);
+            if runs.get() % 3 == 0 {
+                Ok(())
+            } else {
+                Err("failure".into())
+            }
+        },
+        || runs.get() >= 10,
+    );
+    assert!(result.is_ok());
+    assert_eq!(runs.get(), 10);
+}
+
+#[test]
+fn t_next_sleep_seconds() {
+    let config = LoopWithBackoff {
+        error_sleep_factor: 2.,
+        success_sleep_factor: 0.5,
+        min_sleep_seconds: 10.,
+        max_sleep_seconds: 60.,
+        ..Default::default()
+    };
+    let mut sleep_seconds = config.min_sleep_seconds;
+    let mut sleeps = Vec::new();
+    for _ in 0..4 {
+        sleep_seconds = config.next_sleep_seconds(sleep_seconds, false);
+        sleeps.push(sleep_seconds);
+    }
+    // Capped by the configured maximum
+    assert_eq!(sleeps, [20., 40., 60., 60.]);
+    sleeps.clear();
+    for _ in 0..3 {
+        sleep_seconds = config.next_sleep_seconds(sleep_seconds, true);
+        sleeps.push(sleep_seconds);
+    }
+    assert_eq!(sleeps, [30., 15., 10.]);
+}
@@ -8,6 +8,7 @@ pub mod warrants_restart;
 use std::{
     borrow::Cow,
     cell::OnceCell,
+    ffi::OsString,
     fmt::Debug,
     io::{stderr, stdout, Write},
     num::{NonZeroU32, ParseIntError},
@@ -37,7 +38,7 @@ use crate::{
     forking_loop::forking_loop,
     logging::{Logger, LoggingOpts, TimestampOpts},
     polling_signals::{IPCAtomicError, IPCAtomicU64},
-    re_exec::re_exec,
+    re_exec::re_exec_with_args,
     retry::{retry, retry_n},
     signal::send_signal_to_all_processes_of_session,
     unix::easy_fork,
@@ -383,6 +384,13 @@ pub struct DaemonResult {
 
 impl DaemonResult {
     pub fn daemon_cleanup(self) {
+        self.daemon_cleanup_with_args(std::env::args_os().collect())
+    }
+
+    /// Same as `daemon_cleanup`, but on restart re-executes the
+    /// binary with `args` (including the program name) instead of
+    /// the original command line.
+    pub fn daemon_cleanup_with_args(self, args: Vec<OsString>) {
         let DaemonResult {
             daemon_state_accessor,
         } = self;
@@ -406,7 +414,7 @@ impl DaemonResult {
                 daemon_state_accessor.store(DaemonWant::Up, Some(current_sid));
                 // (Ah, the new instance will overwrite daemon_state
                 // again, with a new sid.)
-                let e = re_exec();
+                let e = re_exec_with_args(args);
                 eprintln!("{e:#}");
                 std::process::exit(1);
             }
@@ -466,12 +474,20 @@ impl ExecutionResult {
     /// action. Otherwise exits, indicating whether this is a daemon
     /// context (same as `is_daemon`).
     pub fn daemon_cleanup(self) -> bool {
+        self.daemon_cleanup_with_args(std::env::args_os().collect())
+    }
+
+    /// Same as `daemon_cleanup`, but a restart re-executes the daemon
+    /// binary with `args` (including the program name) instead of
+    /// the original command line, e.g. when the process was started
+    /// for more work than the daemon is doing.
+    pub fn daemon_cleanup_with_args(self, args: Vec<OsString>) -> bool {
         let Self(er, mut bomb) = self;
         bomb.0 = false;
         match er {
             _ExecutionResult::Initiator => false,
             _ExecutionResult::Daemon(daemon_result) => {
-                daemon_result.daemon_cleanup();
+                daemon_result.daemon_cleanup_with_args(args);
                 true
             }
             _ExecutionResult::Run => false,
@@ -771,7 +787,7 @@ impl<
                     opts,
                     || -> Result<()> { run(daemon_check_exit.clone()) },
                     || daemon_check_exit.want_exit(),
-                );
+                )?;
                 Ok(())
             };
 
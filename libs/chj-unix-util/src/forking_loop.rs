@@ -1,9 +1,10 @@
-use std::fmt::Display;
+use std::{cell::Cell, fmt::Display};
 
 use anyhow::{bail, Result};
+use nix::unistd::Pid;
 
 use crate::{
-    backoff::LoopWithBackoff,
+    backoff::{LoopWithBackoff, TooManyErrors},
     unix::{easy_fork, waitpid_until_gone, Status},
 };
 
@@ -12,7 +13,8 @@ use crate::{
 /// then treat both error returns and crashes / non-0 exits as errors.
 /// Restart but back off before retrying, using the given
 /// `LoopWithBackoff` config. Runs `until` after every run, and
-/// returns if it returns true.
+/// returns if it returns true. Returns an error if the
+/// `max_consecutive_errors` of `config` is reached.
 ///
 /// Note: must be run while there are no running threads, panics
 /// otherwise!
@@ -20,11 +22,41 @@ pub fn forking_loop<E: Display, F: FnOnce() -> Result<(), E>>(
     config: LoopWithBackoff,
     job: F,
     until: impl Fn() -> bool,
-) where
+) -> Result<(), TooManyErrors>
+where
+    anyhow::Error: From<E>,
+{
+    forking_loop_with_stop_exit_codes(config, job, until, &[]).map_err(|e| match e {
+        ForkingLoopError::TooManyErrors(e) => e,
+        ForkingLoopError::StopExitCode { .. } => unreachable!("no stop exit codes given"),
+    })
+}
+
+/// Returned by `forking_loop_with_stop_exit_codes`.
+#[derive(thiserror::Error, Debug)]
+pub enum ForkingLoopError {
+    #[error(transparent)]
+    TooManyErrors(#[from] TooManyErrors),
+    #[error("child {pid} exited with exit code {exit_code}, which means to stop")]
+    StopExitCode { pid: Pid, exit_code: i32 },
+}
+
+/// Same as `forking_loop`, but returns
+/// `Err(ForkingLoopError::StopExitCode)` right away (instead of
+/// retrying) when the child exits with one of the `stop_exit_codes`,
+/// for errors that retrying can't fix.
+pub fn forking_loop_with_stop_exit_codes<E: Display, F: FnOnce() -> Result<(), E>>(
+    config: LoopWithBackoff,
+    job: F,
+    until: impl Fn() -> bool,
+    stop_exit_codes: &[i32],
+) -> Result<(), ForkingLoopError>
+where
     anyhow::Error: From<E>,
 {
     let mut perhaps_job = Some(job);
-    config.run(
+    let stopped: Cell<Option<(Pid, i32)>> = Cell::new(None);
+    let result = config.run(
         || -> Result<()> {
             if let Some(pid) = easy_fork()? {
                 // Parent process
@@ -34,6 +66,9 @@ pub fn forking_loop<E: Display, F: FnOnce() -> Result<(), E>>(
 
                 match waitpid_until_gone(pid)? {
                     Status::Normalexit(code) => {
+                        if stop_exit_codes.contains(&code) {
+                            stopped.set(Some((pid, code)));
+                        }
                         if code != 0 {
                             bail!("child {pid} exited with exit code {code}");
                         }
@@ -69,6 +104,53 @@ pub fn forking_loop<E: Display, F: FnOnce() -> Result<(), E>>(
             }
             Ok(())
         },
-        until,
-    )
+        || stopped.get().is_some() || until(),
+    );
+    if let Some((pid, exit_code)) = stopped.get() {
+        return Err(ForkingLoopError::StopExitCode { pid, exit_code });
+    }
+    Ok(result?)
+}
+
+#[test]
+fn t_forking_loop_with_stop_exit_codes() {
+    use crate::backoff::LoopVerbosity;
+
+    // Runs a loop with children exiting with `exit_code`, returns how
+    // it ended, as an exit code: 0 if it stopped because of the stop
+    // exit code, 1 if it gave up after `max_consecutive_errors`. (The
+    // test runner has other threads, thus `easy_fork` would refuse;
+    // run the loop in a forked-off process.)
+    let run = |exit_code: i32| -> i32 {
+        match unsafe { nix::unistd::fork() }.expect("fork") {
+            nix::unistd::ForkResult::Child => {
+                let result = forking_loop_with_stop_exit_codes(
+                    LoopWithBackoff {
+                        verbosity: LoopVerbosity::Silent,
+                        quiet: true,
+                        min_sleep_seconds: 0.0001,
+                        max_sleep_seconds: 0.001,
+                        max_consecutive_errors: Some(3),
+                        ..Default::default()
+                    },
+                    || -> Result<()> { std::process::exit(exit_code) },
+                    || false,
+                    &[3],
+                );
+                let code = match result {
+                    Err(ForkingLoopError::StopExitCode { exit_code: 3, .. }) => 0,
+                    Err(ForkingLoopError::TooManyErrors(TooManyErrors { count: 3, .. })) => 1,
+                    _ => 2,
+                };
+                unsafe { nix::libc::_exit(code) }
+            }
+            nix::unistd::ForkResult::Parent { child } => match waitpid_until_gone(child) {
+                Ok(Status::Normalexit(code)) => code,
+                Ok(Status::Signalexit(signal)) => panic!("child got signal {signal}"),
+                Err(e) => panic!("waitpid: {e}"),
+            },
+        }
+    };
+    assert_eq!(run(3), 0);
+    assert_eq!(run(4), 1);
 }
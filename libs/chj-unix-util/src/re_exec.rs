@@ -6,7 +6,15 @@ use std::{ffi::OsString, os::unix::process::CommandExt};
 use anyhow::{anyhow, Context, Result};
 
 pub fn re_exec_with_executable(executable_path: PathBuf) -> std::io::Error {
-    let mut args = std::env::args_os();
+    re_exec_with_executable_and_args(executable_path, std::env::args_os())
+}
+
+/// `args` includes the program name (`argv[0]`).
+pub fn re_exec_with_executable_and_args(
+    executable_path: PathBuf,
+    args: impl IntoIterator<Item = OsString>,
+) -> std::io::Error {
+    let mut args = args.into_iter();
     let arg0 = args.next();
     let mut cmd = Command::new(&executable_path);
     cmd.args(args);
@@ -44,14 +52,26 @@ pub fn current_exe() -> Result<PathBuf> {
 
 /// Only returns when there is an error.
 pub fn _re_exec() -> Result<()> {
+    _re_exec_with_args(std::env::args_os())
+}
+
+/// Only returns when there is an error. `args` includes the program
+/// name (`argv[0]`).
+pub fn _re_exec_with_args(args: impl IntoIterator<Item = OsString>) -> Result<()> {
     let path = current_exe()?;
-    Err(re_exec_with_executable(path.clone()))
+    Err(re_exec_with_executable_and_args(path.clone(), args))
         .with_context(|| anyhow!("executing the binary {path:?}"))
 }
 
 /// Only returns when there is an error.
 pub fn re_exec() -> anyhow::Error {
-    match _re_exec() {
+    re_exec_with_args(std::env::args_os())
+}
+
+/// Only returns when there is an error. `args` includes the program
+/// name (`argv[0]`).
+pub fn re_exec_with_args(args: impl IntoIterator<Item = OsString>) -> anyhow::Error {
+    match _re_exec_with_args(args) {
         Ok(()) => unreachable!(),
         Err(e) => e,
     }
@@ -1,31 +1,57 @@
 //! Simple library to handle English pluralization. See the
 //! `pluraless-macro` crate for ergonomic use.
 
-const SPECIAL: &[(&str, &str)] = &[
+use std::{borrow::Cow, collections::HashMap, sync::OnceLock};
+
+const IRREGULAR: &[(&str, &str)] = &[
     // (plural, singular)
     ("these", "this"),
     ("are", "is"),
     ("theses", "thesis"),
     ("exist", "exists"),
     ("them", "it"),
-    // ("", ""),
-    // ("", ""),
+    ("feet", "foot"),
+    ("teeth", "tooth"),
+    ("mice", "mouse"),
+    ("children", "child"),
+    ("people", "person"),
+    ("men", "man"),
+    ("women", "woman"),
+    ("data", "datum"),
+    // Words ending in "ies" whose singular ends in "ie", not "y"
+    ("movies", "movie"),
+    ("cookies", "cookie"),
+    ("series", "series"),
+    ("species", "species"),
     // ("they", "it"), -- m, f, ?
 ];
 
+/// Suffix rules, tried in order after `IRREGULAR`: (plural suffix,
+/// singular suffix, minimal length of the stem before the suffix).
+const SUFFIX_RULES: &[(&str, &str, usize)] = &[
+    // categories -> category (but not ties -> ty)
+    ("ies", "y", 2),
+    // analyses -> analysis
+    ("yses", "ysis", 1),
+    // hypotheses -> hypothesis
+    ("theses", "thesis", 1),
+];
+
 /// Representation of a single word in plural/singular form.
 #[derive(Debug, PartialEq)]
 pub struct PluralizedWord<'s> {
     pub plural: &'s str,
-    pub singular: &'s str,
+    /// Borrowed unless the singular had to be constructed (as for
+    /// `SUFFIX_RULES`)
+    pub singular: Cow<'s, str>,
 }
 
 impl<'s> PluralizedWord<'s> {
-    pub fn n<N: Numeric>(&self, n: N) -> &'s str {
+    pub fn n<N: Numeric>(&self, n: N) -> &str {
         if n.is_plural() {
             self.plural
         } else {
-            self.singular
+            &self.singular
         }
     }
 }
@@ -63,24 +89,47 @@ defnumeric! {i32, 1}
 defnumeric! {i16, 1}
 defnumeric! {i8, 1}
 
-fn special(plural: &str) -> Option<PluralizedWord<'_>> {
-    SPECIAL
-        .into_iter()
-        .find(|(p, _)| *p == plural)
-        .map(|(plural, singular)| PluralizedWord { plural, singular })
+fn irregular(plural: &str) -> Option<PluralizedWord<'_>> {
+    static BY_PLURAL: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+    let (plural, singular) = BY_PLURAL
+        .get_or_init(|| IRREGULAR.iter().copied().collect())
+        .get_key_value(plural)?;
+    Some(PluralizedWord {
+        plural,
+        singular: Cow::Borrowed(singular),
+    })
+}
+
+fn suffix_rule(word_in_plural: &str) -> Option<PluralizedWord<'_>> {
+    SUFFIX_RULES
+        .iter()
+        .find_map(|(plural_suffix, singular_suffix, min_stem_len)| {
+            let stem = word_in_plural.strip_suffix(plural_suffix)?;
+            if stem.len() < *min_stem_len {
+                return None;
+            }
+            Some(PluralizedWord {
+                plural: word_in_plural,
+                singular: Cow::Owned(format!("{stem}{singular_suffix}")),
+            })
+        })
 }
 
 /// `word` should be an English word in the plural form. Returns
 /// `None` if it does not know how to pluralize.
 pub fn english_plural(word_in_plural: &str) -> Option<PluralizedWord<'_>> {
-    if let Some(pl) = special(word_in_plural) {
+    if let Some(pl) = irregular(word_in_plural) {
+        return Some(pl);
+    }
+
+    if let Some(pl) = suffix_rule(word_in_plural) {
         return Some(pl);
     }
 
     if word_in_plural.ends_with('s') {
         return Some(PluralizedWord {
             plural: word_in_plural,
-            singular: &word_in_plural[0..word_in_plural.len() - 1],
+            singular: Cow::Borrowed(&word_in_plural[0..word_in_plural.len() - 1]),
         });
     }
 
@@ -97,7 +146,7 @@ pub fn xenglish_plural(word_in_plural: &str) -> PluralizedWord<'_> {
     }
     panic!(
         "pluraless::english_plural: the word {word_in_plural:?} is not in the \
-         list of special cases and is not ending in 's', please adapt the list"
+         list of irregular cases and is not ending in 's', please adapt the list"
     )
 }
 
@@ -105,7 +154,7 @@ pub fn xenglish_plural(word_in_plural: &str) -> PluralizedWord<'_> {
 fn t_english_plural() {
     let t = |n, s| {
         let w = xenglish_plural(s);
-        w.n(n)
+        w.n(n).to_string()
     };
     assert_eq!(t(0, "fields"), "fields");
     assert_eq!(t(1, "fields"), "field");
@@ -117,22 +166,61 @@ fn t_english_plural() {
         english_plural("these"),
         Some(PluralizedWord {
             plural: "these",
-            singular: "this"
+            singular: "this".into()
         })
     );
     assert_eq!(
         english_plural("theses"),
         Some(PluralizedWord {
             plural: "theses",
-            singular: "thesis"
+            singular: "thesis".into()
         })
     );
     assert_eq!(
         english_plural("theseres"),
         Some(PluralizedWord {
             plural: "theseres",
-            singular: "thesere"
+            singular: "thesere".into()
         })
     );
     assert_eq!(english_plural("thesem"), None);
 }
+
+#[test]
+fn t_english_plural_irregular() {
+    let singular = |s| english_plural(s).map(|w| w.singular.into_owned());
+    for (plural, expected) in [
+        ("feet", "foot"),
+        ("mice", "mouse"),
+        ("children", "child"),
+        ("people", "person"),
+        ("data", "datum"),
+        ("categories", "category"),
+        ("phylogenies", "phylogeny"),
+        ("analyses", "analysis"),
+        ("hypotheses", "hypothesis"),
+        ("movies", "movie"),
+        ("series", "series"),
+        ("ties", "tie"),
+        // Regression: the plain 's' rule still applies
+        ("fields", "field"),
+    ] {
+        assert_eq!(singular(plural).as_deref(), Some(expected), "{plural:?}");
+    }
+    assert_eq!(xenglish_plural("feet").n(1), "foot");
+    assert_eq!(xenglish_plural("feet").n(2), "feet");
+    assert!(matches!(
+        english_plural("categories").expect("known").singular,
+        Cow::Owned(_)
+    ));
+    assert!(matches!(
+        english_plural("fields").expect("known").singular,
+        Cow::Borrowed(_)
+    ));
+}
+
+#[test]
+#[should_panic(expected = "is not in the list of irregular cases")]
+fn t_xenglish_plural_unknown() {
+    xenglish_plural("thesem");
+}
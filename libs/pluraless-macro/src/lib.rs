@@ -1,6 +1,10 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::Stmt;
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    token, Expr, Ident, LitStr, Token,
+};
 
 use pluraless_impl::{xenglish_plural, PluralizedWord};
 
@@ -9,41 +13,85 @@ use pluraless_impl::{xenglish_plural, PluralizedWord};
 //   ->
 //
 // let theses = {
-//     const TMP123: PluralizedWord = PluralizedWord { plural: "theses", singular: "thesis" };
+//     const TMP123: PluralizedWord = PluralizedWord { plural: "theses", singular: Cow::Borrowed("thesis") };
 //     TMP123.n($n)
 // };
+//
+// pluralized_let!{let entries = ("entry", "entries") = n}
+//
+//   -> the same, but with the given words instead of the ones derived
+//      from the variable name.
 
-#[proc_macro]
-pub fn pluralized_let(input: TokenStream) -> TokenStream {
-    let ast: Stmt = syn::parse(input).expect("can't parse as Rust code");
+/// The input of `pluralized_let`: `let IDENT = EXPR;`, or `let IDENT
+/// = ("singular", "plural") = EXPR;`.
+struct PluralizedLet {
+    var_ident: Ident,
+    /// (singular, plural), if given explicitly
+    words: Option<(LitStr, LitStr)>,
+    rhs_expr: Expr,
+}
 
-    if let Stmt::Local(local) = ast {
-        let var_ident = match &local.pat {
-            syn::Pat::Ident(s) => &s.ident,
-            _ => panic!("expecting a plain variable name after `let`"),
+impl Parse for PluralizedLet {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        input.parse::<Token![let]>()?;
+        let var_ident: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let words = if input.peek(token::Paren) && input.peek2(Token![=]) {
+            let content;
+            parenthesized!(content in input);
+            let singular: LitStr = content.parse()?;
+            content.parse::<Token![,]>()?;
+            let plural: LitStr = content.parse()?;
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+            }
+            input.parse::<Token![=]>()?;
+            Some((singular, plural))
+        } else {
+            None
         };
-        let rhs_expr = &local
-            .init
-            .expect("missing right hand side of `let` expression")
-            .expr;
+        let rhs_expr: Expr = input.parse()?;
+        if !input.is_empty() {
+            input.parse::<Token![;]>()?;
+        }
+        Ok(Self {
+            var_ident,
+            words,
+            rhs_expr,
+        })
+    }
+}
 
-        let var_name = var_ident.to_string();
-        let PluralizedWord { plural, singular } = xenglish_plural(&var_name);
+#[proc_macro]
+pub fn pluralized_let(input: TokenStream) -> TokenStream {
+    let PluralizedLet {
+        var_ident,
+        words,
+        rhs_expr,
+    } = syn::parse(input).expect(
+        "expecting `let IDENT = EXPR;` or `let IDENT = (\"singular\", \"plural\") = EXPR;`",
+    );
 
-        let code = quote! {
-            #[allow(non_snake_case)]
-            let #var_ident = {
-                // Relying on pluraless re-exporting these identifiers
-                // from pluraless_impl
-                const GEN123: pluraless::PluralizedWord = pluraless::PluralizedWord {
-                    plural: #plural,
-                    singular: #singular,
-                };
-                GEN123.n(#rhs_expr)
+    let (plural, singular) = match words {
+        Some((singular, plural)) => (plural.value(), singular.value()),
+        None => {
+            let var_name = var_ident.to_string();
+            let PluralizedWord { plural, singular } = xenglish_plural(&var_name);
+            (plural.to_string(), singular.into_owned())
+        }
+    };
+
+    let code = quote! {
+        #[allow(non_snake_case)]
+        let #var_ident = {
+            // Relying on pluraless re-exporting these identifiers
+            // from pluraless_impl
+            const GEN123: pluraless::PluralizedWord = pluraless::PluralizedWord {
+                plural: #plural,
+                singular: ::std::borrow::Cow::Borrowed(#singular),
             };
+            GEN123.n(#rhs_expr)
         };
-        code.into()
-    } else {
-        panic!("expecting `let` statement");
-    }
+    };
+    code.into()
 }
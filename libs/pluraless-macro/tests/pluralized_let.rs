@@ -0,0 +1,42 @@
+use pluraless::{pluralized, pluralized_let};
+
+// The generated code refers to `pluraless`, thus the tests can't be
+// in `src/lib.rs` of this crate.
+
+#[test]
+fn t_pluralized_let_inferred() {
+    let t = |n| {
+        pluralized_let! {let theses = n;}
+        theses
+    };
+    assert_eq!(t(1), "thesis");
+    assert_eq!(t(2), "theses");
+}
+
+#[test]
+fn t_pluralized_let_explicit() {
+    let t = |n| {
+        pluralized_let! {let entries = ("entry", "entries") = n;}
+        entries
+    };
+    assert_eq!(t(0), "entries");
+    assert_eq!(t(1), "entry");
+
+    // Words that the inference would get wrong, or not know at all
+    let t = |n| {
+        pluralized! {n => boxes = ("box", "boxes"), Dateien = ("Datei", "Dateien")}
+        (boxes, Dateien)
+    };
+    assert_eq!(t(1), ("box", "Datei"));
+    assert_eq!(t(2), ("boxes", "Dateien"));
+}
+
+#[test]
+fn t_pluralized_mixed() {
+    let t = |n| {
+        pluralized! {n => these, entries = ("entry", "entries"), files}
+        (these, entries, files)
+    };
+    assert_eq!(t(1), ("this", "entry", "file"));
+    assert_eq!(t(3), ("these", "entries", "files"));
+}
@@ -7,19 +7,29 @@
 // proc-macro crates "currently" cannot do re-exports of anything
 // other than proc-macros. Uh.
 
-pub use pluraless_impl::PluralizedWord;
+pub use pluraless_impl::{english_plural, PluralizedWord};
 pub use pluraless_macro::pluralized_let;
 
 /// `pluralized!{n => theses, these}` binds the variable `theses` to
 /// `"thesis"` if n is 1, or `"theses"` otherwise, and likewise the
-/// variable `these` to `"this"` or `"these"`.
+/// variable `these` to `"this"` or `"these"`. For words that
+/// `english_plural` gets wrong or doesn't know, the singular and
+/// plural can be given explicitly: `pluralized!{n => entries =
+/// ("entry", "entries")}`.
 #[macro_export]
 macro_rules! pluralized {
     { $n:expr => $id:ident } => {
         $crate::pluralized_let!{let $id = $n;}
     };
+    { $n:expr => $id:ident = ($singular:literal, $plural:literal) } => {
+        $crate::pluralized_let!{let $id = ($singular, $plural) = $n;}
+    };
     { $n:expr => $id:ident, $($rest:tt)* } => {
         pluralized!{ $n => $id }
         pluralized!{ $n => $($rest)* }
+    };
+    { $n:expr => $id:ident = ($singular:literal, $plural:literal), $($rest:tt)* } => {
+        pluralized!{ $n => $id = ($singular, $plural) }
+        pluralized!{ $n => $($rest)* }
     }
 }
@@ -21,4 +21,11 @@ fn t_pluralized() {
     };
     assert_eq!(t(0), ("these", "subscriptions", "patterns"));
     assert_eq!(t(1), ("this", "subscription", "pattern"));
+
+    let t = |n| {
+        pluralized! {n => categories, children}
+        (categories, children)
+    };
+    assert_eq!(t(1), ("category", "child"));
+    assert_eq!(t(3), ("categories", "children"));
 }
@@ -237,9 +237,36 @@ pub fn command_with_settings<D: AsRef<Path>, P: AsRef<OsStr> + Debug, A: AsRef<O
     } else {
         Stdio::inherit()
     });
+    #[cfg(unix)]
+    inherit_signal_mask(&mut c);
     c
 }
 
+/// `Command` resets the signal mask in the child; keep the signals
+/// blocked that the calling thread has blocked (as fork and exec
+/// would), so that a caller deferring e.g. SIGINT until an operation
+/// is complete defers it for the programs it runs, too.
+#[cfg(unix)]
+fn inherit_signal_mask(command: &mut Command) {
+    use nix::sys::signal::{pthread_sigmask, SigSet, SigmaskHow, Signal};
+    use std::os::unix::process::CommandExt;
+
+    let mut blocked = SigSet::empty();
+    if pthread_sigmask(SigmaskHow::SIG_BLOCK, None, Some(&mut blocked)).is_err() {
+        return;
+    }
+    // Leave the (faster) default path when nothing is blocked
+    if Signal::iterator().any(|signal| blocked.contains(signal)) {
+        unsafe {
+            // Safe because pthread_sigmask is async-signal-safe
+            command.pre_exec(move || {
+                pthread_sigmask(SigmaskHow::SIG_SETMASK, Some(&blocked), None)?;
+                Ok(())
+            });
+        }
+    }
+}
+
 /// Run `cmd` with `arguments` and the env overridden with the
 /// key-value pairs in `set_env`, return a `Child` handle (does *not*
 /// wait for its completion). If you don't want to capture outputs,
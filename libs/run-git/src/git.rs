@@ -312,6 +312,8 @@ pub struct GitLogEntry {
     pub merge: Option<String>,
     pub author: String,
     pub date: String,
+    /// The commit message, followed by the notes if `--notes` was
+    /// passed to `git log` (after a `Notes:` or `Notes (ref):` line)
     pub message: String,
     // files? Ignore for now
 }
@@ -427,7 +429,10 @@ impl<R: Read, C: ChildWaiter> Iterator for GitLogIterator<R, C> {
                         // Commit message
                         if line == "\n" {
                             // ignore; sigh
-                        } else if let Some(rest) = line.strip_prefix("    ") {
+                        } else if let Some(rest) = line
+                            .strip_prefix("    ")
+                            .or_else(|| line.starts_with("Notes").then_some(&line))
+                        {
                             if parts[parts_i].is_none() {
                                 parts[parts_i] = Some(String::new());
                             }
@@ -761,6 +766,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn t_notes() -> Result<()> {
+        let mut it = gitlog_iterator_from_str(
+            "commit afb6184585974a96688ec42c7f024118fcbc8d86
+Author: Christian Jaeger (Mac) <ch@christianjaeger.ch>
+Date:   Sun Apr 13 21:26:17 2025 +0200
+
+    regenerate index files
+
+Notes (xmlhub-version):
+    xmlhub
+    
+    version: 8.1
+
+commit 49a0c5ceed749fc4ec7a7798af56f19447977c56
+Author: Marcus Overwater <moverwater@ethz.ch>
+Date:   Thu Apr 3 14:59:34 2025 +0200
+
+    Added ReMASTER simulation xml
+",
+        );
+        let entry = it.next().unwrap()?;
+        assert_eq!(
+            entry.message,
+            "regenerate index files\nNotes (xmlhub-version):\nxmlhub\n\nversion: 8.1\n"
+        );
+        Ok(())
+    }
+
     fn t_gitlog_iterator(s: &str) -> Result<()> {
         let mut it = gitlog_iterator_from_str(s);
         let _r = it.next().unwrap()?;
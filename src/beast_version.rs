@@ -1,4 +1,5 @@
 use std::{
+    cmp::Ordering,
     fmt::Display,
     path::{Path, PathBuf},
     str::FromStr,
@@ -85,6 +86,142 @@ impl FromStr for BeastVersion {
     }
 }
 
+/// The numeric parts of a version number string, e.g. "2.7.3" =>
+/// `[2, 7, 3]`. Non-digit characters after the digits of a part
+/// (like in "0-beta") are ignored; a part without digits counts as 0.
+fn version_numbers(s: &str) -> Vec<u32> {
+    s.trim()
+        .split('.')
+        .map(|part| {
+            let digits_end = part
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(part.len());
+            part[..digits_end].parse().unwrap_or(0)
+        })
+        .collect()
+}
+
+/// Compare two lists of version numbers, treating missing parts as
+/// 0 (i.e. "2.7" is equal to "2.7.0").
+fn compare_version_numbers(a: &[u32], b: &[u32]) -> Ordering {
+    let len = a.len().max(b.len());
+    let get = |numbers: &[u32], i| numbers.get(i).copied().unwrap_or(0);
+    (0..len)
+        .map(|i| get(a, i).cmp(&get(b, i)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// The comparison operators allowed in a `BeastVersionRange`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum VersionComparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl VersionComparison {
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            VersionComparison::Less => ordering.is_lt(),
+            VersionComparison::LessOrEqual => ordering.is_le(),
+            VersionComparison::Equal => ordering.is_eq(),
+            VersionComparison::GreaterOrEqual => ordering.is_ge(),
+            VersionComparison::Greater => ordering.is_gt(),
+        }
+    }
+}
+
+/// A range of BEAST versions, given as comma-separated comparisons
+/// that must all hold, e.g. `">=2.6, <3.0"`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct BeastVersionRange {
+    bounds: Vec<(VersionComparison, Vec<u32>)>,
+    /// The range as given
+    string: String,
+}
+
+impl Display for BeastVersionRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.string)
+    }
+}
+
+impl FromStr for BeastVersionRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bounds = s
+            .split(',')
+            .map(|bound| {
+                let bound = bound.trim();
+                let (comparison, version) = [
+                    (">=", VersionComparison::GreaterOrEqual),
+                    ("<=", VersionComparison::LessOrEqual),
+                    ("==", VersionComparison::Equal),
+                    (">", VersionComparison::Greater),
+                    ("<", VersionComparison::Less),
+                    ("=", VersionComparison::Equal),
+                ]
+                .into_iter()
+                .find_map(|(operator, comparison)| {
+                    Some((comparison, bound.strip_prefix(operator)?))
+                })
+                .ok_or_else(|| {
+                    anyhow!(
+                        "version range {s:?}: {bound:?} does not start with one of \
+                         the operators >=, >, <=, <, =="
+                    )
+                })?;
+                // Check the version number for validity
+                BeastVersion::from_str(version).with_context(|| anyhow!("version range {s:?}"))?;
+                Ok((comparison, version_numbers(version)))
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+        Ok(Self {
+            bounds,
+            string: s.trim().into(),
+        })
+    }
+}
+
+impl BeastVersionRange {
+    /// Whether `version` is within the range.
+    pub fn contains(&self, version: &BeastVersion) -> bool {
+        let numbers = version_numbers(&version.string);
+        self.bounds
+            .iter()
+            .all(|(comparison, bound)| comparison.accepts(compare_version_numbers(&numbers, bound)))
+    }
+}
+
+#[test]
+fn t_beast_version_range() -> anyhow::Result<()> {
+    let range = BeastVersionRange::from_str(">=2.6, <3.0")?;
+    assert_eq!(range.to_string(), ">=2.6, <3.0");
+    let contains = |version: &str| -> anyhow::Result<bool> {
+        Ok(range.contains(&BeastVersion::from_str(version)?))
+    };
+    assert!(contains("2.6")?);
+    assert!(contains("2.6.0")?);
+    assert!(contains("2.7.3")?);
+    assert!(contains("2.10")?);
+    assert!(!contains("2.5.2")?);
+    assert!(!contains("3.0")?);
+    assert!(!contains("1.10.4")?);
+
+    let range = BeastVersionRange::from_str("==2.7")?;
+    assert!(range.contains(&BeastVersion::from_str("2.7.0")?));
+    assert!(!range.contains(&BeastVersion::from_str("2.7.1")?));
+
+    assert!(BeastVersionRange::from_str("2.6").is_err());
+    assert!(BeastVersionRange::from_str(">=2").is_err());
+    assert!(BeastVersionRange::from_str(">=2.6,").is_err());
+    Ok(())
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum GetBeastVersionError {
     #[error("not a BEAST file, the root element is not a <beast> element")]
@@ -4,8 +4,7 @@ use anyhow::Result;
 use clap::Parser;
 
 use xmlhub_indexer::{
-    cargo::check_cargo_toml_no_path, clap_styles::clap_styles,
-    get_terminal_width::get_terminal_width,
+    cargo::check_cargo_toml, clap_styles::clap_styles, get_terminal_width::get_terminal_width,
 };
 
 #[derive(clap::Parser, Debug)]
@@ -24,7 +23,7 @@ fn main() -> Result<()> {
     let opts = Opts::parse();
 
     for path in opts.paths {
-        check_cargo_toml_no_path(&path)?;
+        check_cargo_toml(&path)?;
     }
 
     Ok(())
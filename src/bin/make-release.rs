@@ -15,8 +15,7 @@ use run_git::git::GitWorkingDir;
 
 use xmlhub_indexer::{
     cargo::{
-        check_cargo_toml_no_path, run_cargo, CompilationProfile, CompilationTarget, Env,
-        TargetTriple,
+        check_cargo_toml, run_cargo, CompilationProfile, CompilationTarget, Env, TargetTriple,
     },
     changelog::CHANGELOG_FILE_NAME,
     checkout_context::CheckExpectedSubpathsExist,
@@ -31,13 +30,16 @@ use xmlhub_indexer::{
         app_signature::{AppSignaturePrivateKey, SaveLoadKeyFile},
         binaries_repo::BinariesRepoSection,
         json_file::JsonFile,
-        util::{get_creator, get_timestamp},
+        util::{get_creator, get_timestamp, hostname, prog_version},
     },
     sha256::sha256sum_paranoid,
-    util::{ask_yn, create_dir_levels_if_necessary, hostname, prog_version, stringify_error},
-    xmlhub_indexer_defaults::{BINARIES_CHECKOUT, SOURCE_CHECKOUT, XMLHUB_BINARY_FILE_NAME},
+    util::{ask_yn, create_dir_levels_if_necessary, stringify_error},
+    xmlhub_checkouts::{BINARIES_CHECKOUT, SOURCE_CHECKOUT},
+    xmlhub_indexer_defaults::XMLHUB_BINARY_FILE_NAME,
 };
 
+include!("../../include/git_describe_arguments.rs");
+
 #[derive(clap::Parser, Debug)]
 #[clap(next_line_help = true)]
 /// Produce a new release of the xmlhub-indexer repository, both the
@@ -133,6 +135,28 @@ struct Opts {
     /// repository. The default is to push.
     #[clap(long)]
     no_push_binaries: bool,
+
+    /// Build (and publish) a binary for the given target triple
+    /// (e.g. `aarch64-unknown-linux-gnu`) in addition to the default
+    /// targets for the OS this is run on. Can be given multiple
+    /// times. The Rust toolchain (and linker) for the target must be
+    /// installed. Targets that would be placed at the same location
+    /// in the binaries repository as another one (including the
+    /// native one) are refused.
+    #[clap(long)]
+    target: Vec<TargetTriple>,
+
+    /// Do not build the default targets for the OS this is run on,
+    /// only those given via `--target`.
+    #[clap(long)]
+    no_default_targets: bool,
+
+    /// Build each binary twice from scratch and compare the sha256
+    /// sums of the results, stopping the release if they differ. Use
+    /// this to verify that the build is reproducible, e.g. when
+    /// giving `--unchanged-output`.
+    #[clap(long)]
+    verify_reproducible: bool,
 }
 
 #[derive(Debug)]
@@ -321,12 +345,160 @@ impl Effect for PushSourceToRemote {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Binary {
     target: CompilationTarget,
     program_name: &'static str,
 }
 
+/// The binaries to build: the hard-coded default targets for the OS
+/// of `local` (the OS and architecture this is run on, which is where
+/// the native binary is placed in the binaries repository), unless
+/// `no_default_targets` is true, plus those for `extra_targets`
+/// (skipping any that are already included).
+fn binaries_to_build(
+    local: BinariesRepoSection,
+    no_default_targets: bool,
+    extra_targets: &[TargetTriple],
+    program_name: &'static str,
+    profile: CompilationProfile,
+) -> Result<Vec<Binary>> {
+    let binary = |target_triple: Option<TargetTriple>| Binary {
+        target: CompilationTarget {
+            target_triple,
+            profile,
+        },
+        program_name,
+    };
+
+    // Currently hard-code compilation to intel + ARM on macOS,
+    // and just the native platform on Linux.
+    let mut binaries = if no_default_targets {
+        vec![]
+    } else {
+        let os = local.os;
+        match os {
+            Os::MacOS => vec![
+                binary(Some(TargetTriple {
+                    arch: Arch::Aarch64,
+                    os,
+                    env: Env::None,
+                })),
+                // Is it OK to explicitly target X86_64 even if
+                // running there? Is it wasteful? But is it an
+                // advantage for reproducibility? Let's see:
+                binary(Some(TargetTriple {
+                    arch: Arch::X86_64,
+                    os,
+                    env: Env::None,
+                })),
+                // Cross-compile to Linux: besides rustup
+                // --target=... requires `brew install lld`,
+                // ~/.cargo/config.toml with `[target.$target] \n
+                // linker = "lld"` (and perhaps TARGET_CC? no?)
+                binary(Some(TargetTriple {
+                    arch: Arch::X86_64,
+                    os: Os::Linux,
+                    env: Env::Musl,
+                })),
+            ],
+            Os::Linux => vec![binary(None)],
+        }
+    };
+
+    for target_triple in extra_targets {
+        let new = binary(Some(target_triple.clone()));
+        if binaries.contains(&new) {
+            continue;
+        }
+        // The binaries repository only distinguishes OS and
+        // architecture, not the env, thus two binaries would
+        // overwrite each other there.
+        for existing in &binaries {
+            let existing_section = match &existing.target.target_triple {
+                Some(existing_triple) => BinariesRepoSection::from(existing_triple),
+                None => local,
+            };
+            if existing_section == BinariesRepoSection::from(target_triple) {
+                let existing_target = match &existing.target.target_triple {
+                    Some(existing_triple) => format!("target {existing_triple}"),
+                    None => "the native target".into(),
+                };
+                bail!(
+                    "target {target_triple} would be placed at the same location \
+                     in the binaries repository as {existing_target}"
+                )
+            }
+        }
+        binaries.push(new);
+    }
+
+    if binaries.is_empty() {
+        bail!("no targets to build; give `--target` when using `--no-default-targets`")
+    }
+    Ok(binaries)
+}
+
+#[test]
+fn t_binaries_to_build() -> Result<()> {
+    let profile = CompilationProfile::Release;
+    let triples = |binaries: Vec<Binary>| -> Vec<String> {
+        binaries
+            .into_iter()
+            .map(|b| {
+                assert_eq!(b.program_name, "xmlhub");
+                assert_eq!(b.target.profile, profile);
+                b.target
+                    .target_triple
+                    .map(|t| t.to_string())
+                    .unwrap_or_else(|| "native".into())
+            })
+            .collect()
+    };
+    let t = |os, no_default_targets, extra: &[&str]| -> Result<Vec<String>> {
+        let extra: Vec<TargetTriple> = extra.iter().map(|s| s.parse()).collect::<Result<_>>()?;
+        let local = BinariesRepoSection {
+            os,
+            arch: Arch::X86_64,
+        };
+        Ok(triples(binaries_to_build(
+            local,
+            no_default_targets,
+            &extra,
+            "xmlhub",
+            profile,
+        )?))
+    };
+
+    assert_eq!(t(Os::Linux, false, &[])?, ["native"]);
+    assert_eq!(
+        t(Os::Linux, false, &["aarch64-unknown-linux-gnu"])?,
+        ["native", "aarch64-unknown-linux-gnu"]
+    );
+    assert_eq!(
+        t(Os::Linux, true, &["aarch64-unknown-linux-gnu"])?,
+        ["aarch64-unknown-linux-gnu"]
+    );
+    assert_eq!(
+        t(Os::MacOS, false, &["x86_64-apple-darwin"])?,
+        [
+            "aarch64-apple-darwin",
+            "x86_64-apple-darwin",
+            "x86_64-unknown-linux-musl"
+        ]
+    );
+    assert!(t(Os::MacOS, false, &["x86_64-unknown-linux-gnu"]).is_err());
+    assert!(t(Os::Linux, true, &[]).is_err());
+    // The native binary (on x86_64) is placed at the same location
+    let e = t(Os::Linux, false, &["x86_64-unknown-linux-musl"]).expect_err("collision");
+    assert!(e.to_string().contains("as the native target"), "{e:#}");
+    assert_eq!(
+        t(Os::Linux, true, &["x86_64-unknown-linux-musl"])?,
+        ["x86_64-unknown-linux-musl"]
+    );
+    Ok(())
+}
+
 #[derive(Debug)]
 struct BinaryWithSha256sum {
     binary: Binary,
@@ -373,9 +545,71 @@ impl BinaryWithSha256sum {
     }
 }
 
+/// The sha256sums of the results of building a binary twice.
+#[derive(Debug)]
+struct ReproducibilityCheck<'t> {
+    binary: &'t Binary,
+    first_sha256sum: String,
+    second_sha256sum: String,
+}
+
+/// Give an error listing all binaries that did not build
+/// reproducibly, if any.
+fn check_reproducible(checks: &[ReproducibilityCheck]) -> Result<()> {
+    let failures: Vec<String> = checks
+        .iter()
+        .filter(|check| check.first_sha256sum != check.second_sha256sum)
+        .map(
+            |ReproducibilityCheck {
+                 binary:
+                     Binary {
+                         target,
+                         program_name,
+                     },
+                 first_sha256sum,
+                 second_sha256sum,
+             }| {
+                format!("  {program_name:?} for {target}: {first_sha256sum} vs. {second_sha256sum}")
+            },
+        )
+        .collect();
+    if !failures.is_empty() {
+        bail!(
+            "building twice gave differing sha256 sums, the build is not \
+             reproducible:\n{}",
+            failures.join("\n")
+        )
+    }
+    Ok(())
+}
+
+#[test]
+fn t_check_reproducible() {
+    let binary = Binary {
+        target: CompilationTarget {
+            target_triple: None,
+            profile: CompilationProfile::Release,
+        },
+        program_name: "xmlhub",
+    };
+    let check = |first: &str, second: &str| ReproducibilityCheck {
+        binary: &binary,
+        first_sha256sum: first.into(),
+        second_sha256sum: second.into(),
+    };
+    assert!(check_reproducible(&[]).is_ok());
+    assert!(check_reproducible(&[check("abc", "abc"), check("def", "def")]).is_ok());
+    let err = check_reproducible(&[check("abc", "abc"), check("def", "0ef")])
+        .expect_err("differing sums");
+    let msg = err.to_string();
+    assert!(msg.contains("def vs. 0ef"));
+    assert!(!msg.contains("abc"));
+}
+
 #[derive(Debug)]
 struct BuildBinariesGetSha256sums {
     binaries: Vec<Binary>,
+    verify_reproducible: bool,
 }
 
 #[derive(Debug)]
@@ -391,7 +625,10 @@ impl Effect for BuildBinariesGetSha256sums {
     type Provides = BinariesWithSha256sum;
 
     fn show_bullet_points(&self) -> String {
-        let Self { binaries } = self;
+        let Self {
+            binaries,
+            verify_reproducible,
+        } = self;
         let binaries_string: String = binaries
             .into_iter()
             .map(
@@ -402,36 +639,64 @@ impl Effect for BuildBinariesGetSha256sums {
             )
             .collect::<Vec<_>>()
             .join("\n");
-        format!("  * build the following binaries and get their sha256sum:\n{binaries_string}")
+        let twice = if *verify_reproducible {
+            " twice from scratch (verifying that the sha256sums match)"
+        } else {
+            ""
+        };
+        format!(
+            "  * build the following binaries{twice} and get their sha256sum:\n{binaries_string}"
+        )
     }
 
     fn run(self: Box<Self>, provided: Self::Requires) -> Result<Self::Provides> {
         let SourcePushed { source_commit_id } = provided;
-        let BuildBinariesGetSha256sums { binaries } = *self;
+        let BuildBinariesGetSha256sums {
+            binaries,
+            verify_reproducible,
+        } = *self;
 
-        let binaries_with_sha256sum = binaries
+        let binaries_with_sha256sum: Vec<BinaryWithSha256sum> = binaries
             .into_iter()
             .map(|binary| -> Result<_> {
                 let Binary {
                     target,
                     program_name,
                 } = &binary;
-                // Rebuild the binary, so that it picks up on the new Git
-                // tag. We want that both for subsequent usage, but especially
-                // so that it is up to date when copied off via
-                // `ReleaseBinary`.
-                target.run_build_in(SOURCE_CHECKOUT.working_dir_path(), program_name)?;
-
                 let binary_path = SOURCE_CHECKOUT
                     .working_dir_path()
                     .append(target.subpath_to_binary(program_name));
 
-                // Now that the binary is rebuilt, hash it; store errors,
-                // complain about them later when actually needed (this will
-                // be the case on Windows where the `sha256sum` command may
-                // not be available, but we also don't publish the binary.)
-                let sha256sum = sha256sum_paranoid(&binary_path)
-                    .with_context(|| anyhow!("hashing file {binary_path:?}"))?;
+                let build_and_hash = || -> Result<String> {
+                    if verify_reproducible {
+                        target.run_clean_package_in(
+                            SOURCE_CHECKOUT.working_dir_path(),
+                            env!("CARGO_PKG_NAME"),
+                        )?;
+                    }
+                    // Rebuild the binary, so that it picks up on the new Git
+                    // tag. We want that both for subsequent usage, but especially
+                    // so that it is up to date when copied off via
+                    // `ReleaseBinary`.
+                    target.run_build_in(SOURCE_CHECKOUT.working_dir_path(), program_name)?;
+
+                    // Now that the binary is rebuilt, hash it; store errors,
+                    // complain about them later when actually needed (this will
+                    // be the case on Windows where the `sha256sum` command may
+                    // not be available, but we also don't publish the binary.)
+                    sha256sum_paranoid(&binary_path)
+                        .with_context(|| anyhow!("hashing file {binary_path:?}"))
+                };
+
+                let sha256sum = build_and_hash()?;
+                if verify_reproducible {
+                    let second_sha256sum = build_and_hash()?;
+                    check_reproducible(&[ReproducibilityCheck {
+                        binary: &binary,
+                        first_sha256sum: sha256sum.clone(),
+                        second_sha256sum,
+                    }])?;
+                }
 
                 Ok(BinaryWithSha256sum {
                     binary,
@@ -686,6 +951,15 @@ fn main() -> Result<()> {
         }
     }
 
+    // Check the target list before doing anything else
+    let binaries = binaries_to_build(
+        BinariesRepoSection::from_local_os_and_arch()?,
+        opts.no_default_targets,
+        &opts.target,
+        XMLHUB_BINARY_FILE_NAME,
+        CompilationProfile::Release,
+    )?;
+
     let unless_dry_run = |res: Result<()>| -> Result<()> {
         match res {
             Ok(()) => Ok(()),
@@ -723,8 +997,9 @@ fn main() -> Result<()> {
     // as that would fail to compile on other people's machines (if
     // they don't have the source in the same locations; we are not
     // talking "cargo publish" which would remove the path directives,
-    // but people using the clone of this repository directly!)
-    unless_dry_run(check_cargo_toml_no_path("Cargo.toml"))?;
+    // but people using the clone of this repository directly!). Same
+    // for `git =` dependencies and `[patch]` sections.
+    unless_dry_run(check_cargo_toml("Cargo.toml"))?;
 
     // Check everything and run the test suite to make sure we are
     // ready for release.
@@ -733,11 +1008,11 @@ fn main() -> Result<()> {
         run_cargo(source_checkout.working_dir_path(), &["test"])?;
     }
 
-    // Pass "--tags ..." as in `build.rs`, keeping in sync by sharing the code
-    let args = &include!("../../include/git_describe_arguments.rs")[1..];
+    // Pass the same arguments as `build.rs` does
+    let args = git_describe_arguments().context("decoding the XMLHUB_GIT_DESCRIBE_ARGS env var")?;
     let old_version: GitVersion<SemVersion> = source_checkout
         .git_working_dir()
-        .git_describe(args)?
+        .git_describe(&args)?
         .parse()
         .with_context(|| {
             anyhow!(
@@ -855,66 +1130,10 @@ fn main() -> Result<()> {
             )
         };
 
-    let build_binary = {
-        let os = Os::from_local()?;
-        let program_name = XMLHUB_BINARY_FILE_NAME;
-        let profile = CompilationProfile::Release;
-
-        // Currently hard-code compilation to intel + ARM on macOS,
-        // and just the native platform on Linux.
-        let binaries = match os {
-            Os::MacOS => vec![
-                Binary {
-                    target: CompilationTarget {
-                        target_triple: Some(TargetTriple {
-                            arch: Arch::Aarch64,
-                            os,
-                            env: Env::None,
-                        }),
-                        profile,
-                    },
-                    program_name,
-                },
-                // Is it OK to explicitly target X86_64 even if
-                // running there? Is it wasteful? But is it an
-                // advantage for reproducibility? Let's see:
-                Binary {
-                    target: CompilationTarget {
-                        target_triple: Some(TargetTriple {
-                            arch: Arch::X86_64,
-                            os,
-                            env: Env::None,
-                        }),
-                        profile,
-                    },
-                    program_name,
-                },
-                // Cross-compile to Linux: besides rustup
-                // --target=... requires `brew install lld`,
-                // ~/.cargo/config.toml with `[target.$target] \n
-                // linker = "lld"` (and perhaps TARGET_CC? no?)
-                Binary {
-                    target: CompilationTarget {
-                        target_triple: Some(TargetTriple {
-                            arch: Arch::X86_64,
-                            os: Os::Linux,
-                            env: Env::Musl,
-                        }),
-                        profile,
-                    },
-                    program_name,
-                },
-            ],
-            Os::Linux => vec![Binary {
-                target: CompilationTarget {
-                    target_triple: None,
-                    profile,
-                },
-                program_name,
-            }],
-        };
-        Box::new(BuildBinariesGetSha256sums { binaries })
-    };
+    let build_binary = Box::new(BuildBinariesGetSha256sums {
+        binaries,
+        verify_reproducible: opts.verify_reproducible,
+    });
 
     // Collect build information
     let rustc_version = stringify_error(prog_version(source_checkout.working_dir_path(), "rustc"));
@@ -1,12 +1,15 @@
 // Use from the standard library
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, BTreeSet, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ffi::OsString,
+    fmt::Display,
     fs::{create_dir, File},
     io::{stderr, stdout, BufWriter, Write},
+    os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
-    str::FromStr,
     sync::Arc,
+    time::Duration,
 };
 
 // Use from external dependencies
@@ -20,35 +23,48 @@ use chj_unix_util::{
         DaemonPaths, ExecutionResult,
     },
     file_lock::{file_lock_nonblocking, FileLockError},
-    forking_loop::forking_loop,
+    forking_loop::{forking_loop_with_stop_exit_codes, ForkingLoopError},
     logging::{TimestampMode, TimestampOpts},
+    unix::{easy_fork, waitpid_until_gone, Status},
 };
 use cj_path_util::path_util::AppendToPath;
 use clap::{CommandFactory, Parser};
 use itertools::Itertools;
 use lazy_static::lazy_static;
-use nix::sys::resource::{setrlimit, Resource};
+use nix::sys::{
+    resource::{setrlimit, Resource},
+    signal::Signal,
+};
 use pluraless::pluralized;
-use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
-use run_git::git::{BaseAndRelPath, GitStatusItem, GitWorkingDir};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use regex::Regex;
+use run_git::{
+    command::run_outputs,
+    git::{BaseAndRelPath, GitWorkingDir},
+};
 use walkdir::WalkDir;
 
 // Use from src/*.rs
 use xmlhub_indexer::{
-    beast_version::{check_beast_version, BeastProductVersion, BeastVersion},
+    beast_version::{check_beast_version, BeastProductVersion},
     browser::{spawn_browser, spawn_browser_on_path},
-    changelog::Changelog,
+    build_delta::{blob_hashes, BuildDelta, BuildState},
+    build_progress::{BuildProgress, NoProgress},
+    changelog::{Changelog, ChangelogVersion},
     checkout_context::{
-        CheckExpectedSubpathsExist, CheckedCheckoutContext1, CheckedCheckoutContext2,
+        nearest_git_root, CheckExpectedSubpathsExist, CheckedCheckoutContext1,
+        CheckedCheckoutContext2,
     },
     clap_styles::clap_styles,
     const_util::file_name,
+    contacts::{add_inconsistent_email_warnings, ContactsAllowList},
+    deferred_stop::with_signals_deferred,
     fixup_path::CURRENT_DIRECTORY,
-    folder::Folder,
+    folder::{AttributeGroups, Folder},
     get_terminal_width::get_terminal_width,
     git_version::{GitVersion, SemVersion},
     hints::Hints,
-    html_util::anchor,
+    html_util::{anchor, pretty_print_html, split_at_placeholder, STREAMING_PLACEHOLDER},
     installation::{
         binaries_repo::Os,
         defaults::global_app_state_dir,
@@ -56,44 +72,50 @@ use xmlhub_indexer::{
     },
     markdown_paragraphs,
     modified_xml_document::{ClearAction, ClearElementsOpts, ModifiedXMLDocument},
+    output_sink::{FilesystemSink, MemorySink, OutputSink, SizeLimitSink},
     rayon_util::ParRun,
-    section::{Highlight, NumberPath, Section},
+    relaxng::{Schema, SchemaError},
+    section::{Highlight, HighlightColors, NumberPath, Section},
     string_tree::StringTree,
+    tr,
     tuple_transpose::TupleTranspose,
-    util::{append, format_string_list, strip_prefixes, with_output_to_file, InsertValue},
+    util::{append, ask_yn, relative_path, with_output_to_file, InsertValue},
     utillib::{
-        file_util_with_trash::write_file_moving_to_trash_if_exists,
+        expand_path::expand_path,
+        file_util_with_trash::write_file_optionally_moving_to_trash,
         setpriority::{possibly_setpriority, PriorityWhich},
+        temp_dir::TempDir,
     },
     version_info::VersionInfo,
-    xml_document::{read_xml_file, XMLDocumentComment},
+    xml_document::{read_xml_file, XMLDocument},
     xmlhub_attributes::{
-        attribute_specification_by_name, sort_in_definition_order, AttributeName, AttributeNeed,
+        attribute_specification_by_name, AnchorNames, AttributeName, AttributeNeed,
         AttributeSource, AttributeSpecification, KeyStringPreparation, METADATA_SPECIFICATION,
     },
     xmlhub_autolink::Autolink,
-    xmlhub_check_version::XmlhubCheckVersion,
+    xmlhub_check_version::{VersionStatementsNotFound, XmlhubCheckVersion},
+    xmlhub_checkouts::{git_log_version_checker, SOURCE_CHECKOUT, XMLHUB_CHECKOUT},
     xmlhub_clone_to::{clone_to_command, CloneToOpts},
+    xmlhub_config::{XmlhubConfig, CONFIG_FILE_PATH},
     xmlhub_docs::{
         docs_command, help_attributes_command, help_contributing_command, make_attributes_md,
         HelpAttributesOpts, CONTRIBUTE_FILENAME,
     },
-    xmlhub_file_issues::{FileErrors, FileIssues, FileWarnings},
-    xmlhub_fileinfo::{
-        AttributeValue, FileInfo, Issue, Metadata, WithCommentsOnly, WithDerivedValues,
-        WithExtractedValues,
-    },
+    xmlhub_file_issues::{issues_hints, FileErrors, FileIssues, FileWarnings, OwnedFileIssues},
+    xmlhub_fileinfo::{FileInfo, InfoBoxOpts, Issue, WithDerivedValues, WithExtractedValues},
     xmlhub_global_opts::{
-        BlindingOpts, DrynessOpt, OpenOrPrintOpts, QuietOpt, VerbosityOpt, VersionCheckOpt,
+        BlindingOpts, DrynessOpt, ExtensionsOpt, OpenOrPrintOpts, OutputFilesOpts, QuietOpt,
+        TrashOpts, VerbosityOpt, VersionCheckOpt,
     },
     xmlhub_help::print_basic_standalone_html_page,
     xmlhub_indexer_defaults::{
-        css_styles, document_symbol, git_log_version_checker, BACK_TO_INDEX_SYMBOL,
-        GENERATED_MESSAGE, HTML_ALLOCATOR_POOL, HTML_FILE, MD_FILE, PROGRAM_NAME,
-        SEQUENCES_ELEMENT_NAME, SOURCE_CHECKOUT, XMLHUB_CHECKOUT,
+        css_styles, Symbols, DEFAULT_OUTPUT_FILES, DEFAULT_SYMBOLS, GENERATED_MESSAGE,
+        HTML_ALLOCATOR_POOL, PROGRAM_NAME, SEQUENCES_ELEMENT_NAME, VERSION_NOTES_REF,
     },
     xmlhub_install::{install_command, InstallOpts},
-    xmlhub_types::OutputFile,
+    xmlhub_metadata_parser::{parse_header, METADATA_PI_TARGET},
+    xmlhub_read_file::{read_file_infos, AttributeRules},
+    xmlhub_types::{OutputFiles, XmlExtensions},
 };
 
 // -------------------------------------------------------------------------
@@ -109,8 +131,9 @@ const DEFAULT_COMMENT_FOR_BLINDED_DATA: &str =
 /// mode. Keep in sync with the `Opts` docs above!
 const MIN_SLEEP_SECONDS_DEFAULT: f64 = 10.;
 
-/// Do not sleep more than that many seconds between runs.
-const MAX_SLEEP_SECONDS: f64 = 1000.;
+/// Do not sleep more than that many seconds between runs in daemon
+/// mode by default. Keep in sync with the `Opts` docs above!
+const MAX_SLEEP_SECONDS_DEFAULT: f64 = 1000.;
 
 /// In daemon start mode with --quiet, log a single line every given
 /// number of seconds (to give a signal about being alive). Note that
@@ -118,22 +141,37 @@ const MAX_SLEEP_SECONDS: f64 = 1000.;
 /// and it is sleeping a long time due to backing off because of that.
 const DAEMON_ACTIVITY_LOG_INTERVAL_SECONDS: u64 = 120;
 
+/// The file name, in the daemon folder, of the state kept for
+/// `--since-last-build`.
+const SINCE_LAST_BUILD_STATE_FILENAME: &str = "since_last_build_state.json";
+
 /// Address space memory limit set inside every worker child, in
 /// bytes. Much is needed as the HtmlAllocator regions pre-allocate a
 /// lot of virtual memory even if it is never needed. There are no RSS
 /// resource limits in Linux (except via cgroups in some cases).
 const AS_BYTES_LIMIT_IN_WORKER_CHILD: u64 = 6 * 1024 * 1024 * 1024;
 
+/// `build --standalone`: XML files bigger than this many bytes are
+/// not embedded. Keep in sync with the `BuildOpts` docs above!
+const STANDALONE_MAX_FILE_SIZE: u64 = 2_000_000;
+
+/// `build --standalone`: stop embedding files once their total size
+/// would exceed this many bytes. Keep in sync with the `BuildOpts`
+/// docs above!
+const STANDALONE_MAX_TOTAL_SIZE: u64 = 200_000_000;
+
+/// `build`: the default for `--max-output-size`. Keep in sync with
+/// the `BuildOpts` docs above!
+const MAX_OUTPUT_SIZE_DEFAULT: u64 = 100_000_000;
+
 /// Limit on CPU time, for the soft limit (a hard limit is set to 1
 /// second higher than this value).
 const CPU_SECONDS_LIMIT_IN_WORKER_CHILD: u64 = 5;
 
-/// The file describing the attributes (for contributors).
-const ATTRIBUTES_FILE: OutputFile = OutputFile {
-    path_from_repo_top: "attributes.md",
-};
-
-const OUTPUT_FILES: [&OutputFile; 3] = [&HTML_FILE, &MD_FILE, &ATTRIBUTES_FILE];
+/// The exit code when `--strict-version-check` finds no version
+/// statements (distinct from 1 for other failures, and 2 for usage
+/// errors).
+const EXIT_CODE_VERSION_STATEMENTS_NOT_FOUND: i32 = 3;
 
 // -------------------------------------------------------------------------
 // Derived values:
@@ -208,11 +246,16 @@ enum Command {
     /// Rebuild the XML Hub index, and by default commit the changed
     /// index. If you want to check your file while you edit it, use
     /// the `check` subcommand instead first.
-    Build(BuildOpts),
+    Build(Box<BuildOpts>),
     /// Check the correctness of a single file, without
     /// committing. Use this while editing. Once your document yields
     /// no more errors, run the `build` subcommand.
     Check(CheckOpts),
+    /// Check all XML files in the repository, without writing the
+    /// index files nor committing anything. Reports all errors and
+    /// warnings, and exits with code 1 if there were errors. Useful
+    /// as a pre-commit hook or in CI.
+    Validate(ValidateOpts),
     /// Clone the XML Hub repository and apply merge config change.
     CloneTo(CloneToOpts),
     /// Prepare some XML file(s) by adding a metadata template to
@@ -229,6 +272,18 @@ enum Command {
     /// edit the file and run the `check` subcommand until there are
     /// no errors.
     AddTo(AddToOpts),
+    /// Rewrite the metadata comments of some XML file(s) into
+    /// canonical form (attributes in the standard order, one `Key:
+    /// value` comment each, consistent list separators). Careful:
+    /// this replaces the files in place, but keeps the original in
+    /// the system trash bin. Files that are already in canonical form
+    /// are left untouched.
+    Normalize(NormalizeOpts),
+    /// Write only the file describing the attributes
+    /// (`attributes.md`), e.g. after changing the attribute
+    /// definitions, without reading the XML files or committing
+    /// anything (`build` writes it along with the index files).
+    GenAttributes(GenAttributesOpts),
     /// Generate a shell completions file
     Completions {
         /// The shell to generate the completions for
@@ -261,18 +316,30 @@ struct ChangelogOpts {
     #[clap(flatten)]
     open_or_print: OpenOrPrintOpts,
 
-    /// Which version to start from (exclusive)
+    /// Which version to start from (exclusive). `current` means the
+    /// version of this program, e.g. `--from current` to see what's
+    /// new since.
     #[clap(long)]
-    from: Option<GitVersion<SemVersion>>,
-    /// Which version to end with (inclusive)
+    from: Option<ChangelogVersion>,
+    /// Which version to end with (inclusive). `latest` (or `HEAD`)
+    /// means the newest release in the changelog.
     #[clap(long)]
-    to: Option<GitVersion<SemVersion>>,
+    to: Option<ChangelogVersion>,
     /// Whether it's OK to have `--from` > `--to`
     #[clap(long)]
     allow_downgrades: bool,
+    /// Only show the change entries containing the given text (case
+    /// insensitive), e.g. `--grep fix` to only see bug fixes.
+    #[clap(long)]
+    grep: Option<String>,
+    /// Print the changes grouped by release as JSON to stdout
+    /// (instead of opening a browser or printing Markdown), for
+    /// automated processing.
+    #[clap(long)]
+    json: bool,
 }
 
-#[derive(clap::Parser, Debug)]
+#[derive(clap::Parser, Debug, Clone)]
 struct BuildOpts {
     #[clap(flatten)]
     dryness: DrynessOpt,
@@ -284,6 +351,10 @@ struct BuildOpts {
     quietness: QuietOpt,
     #[clap(flatten)]
     daemon_opts: DaemonOpts,
+    #[clap(flatten)]
+    output_files: OutputFilesOpts,
+    #[clap(flatten)]
+    extensions: ExtensionsOpt,
 
     /// Write the index files (and commit them if requested) even if
     /// some files had errors and thus won't be indexed; the errors
@@ -304,6 +375,39 @@ struct BuildOpts {
     #[clap(long)]
     no_commit_errors: bool,
 
+    /// If there are errors that were written to the index files (see
+    /// `--write-errors`), still commit them (unless
+    /// `--no-commit-errors` is given), so that the local state is
+    /// consistent, but do not push until the errors are resolved,
+    /// to avoid propagating broken indexes to others. Also applies
+    /// in `--batch` mode. The withheld commits are pushed with the
+    /// next successful build; except in `--batch` mode (thus also
+    /// with `--daemon`), where the next run's `git reset --hard`
+    /// discards them, and the index files are regenerated and pushed
+    /// once the errors are resolved.
+    #[clap(long)]
+    no_push_on_error: bool,
+
+    /// After each build, print a line to stdout summarizing the
+    /// outcome, in the stable format `files=N errors=E warnings=W
+    /// committed=0|1 pushed=0|1 changed=0|1`, for scripts. `files`
+    /// is the number of XML files processed, `errors` and `warnings`
+    /// the number of files with errors or warnings, and `changed`
+    /// whether any of the index files differ from their committed
+    /// version after writing them.
+    #[clap(long)]
+    summary_line: bool,
+
+    /// After each build, print a line to stdout summarizing what
+    /// changed since the previous build (in `--daemon` mode: since the
+    /// previous iteration), in the stable format `since last build:
+    /// changed=N added=N removed=N errors_appeared=N errors_cleared=N
+    /// committed=0|1 pushed=0|1`. Files are compared by their Git blob
+    /// hash. The state is kept in the daemon folder of the
+    /// repository; on the first build, all files count as added.
+    #[clap(long)]
+    since_last_build: bool,
+
     /// If used together with `--write-errors`, does use exit code 0
     /// even if there were errors that were written to the index
     /// files. Errors are still also written to stderr, though--see
@@ -335,6 +439,13 @@ struct BuildOpts {
     #[clap(long)]
     open_if_changed: bool,
 
+    /// Only open the already existing `README.html` file in a web
+    /// browser (as with `--open`), without reading the XML files,
+    /// rebuilding the index or running Git. Fails if the file doesn't
+    /// exist yet.
+    #[clap(long, conflicts_with_all = ["open", "open_if_changed", "daemon"])]
+    open_existing: bool,
+
     /// Git pull from the default remote into the local Git checkout
     /// before creating the index files.
     #[clap(long)]
@@ -366,6 +477,104 @@ struct BuildOpts {
     #[clap(long)]
     batch: bool,
 
+    /// In `--batch` mode, if the local branch has commits that are
+    /// not on the remote branch (which `git reset --hard` would
+    /// throw away), stop with an error instead of just showing a
+    /// warning listing them. Commits of the index files made by
+    /// `build` itself don't count, since they are regenerated.
+    #[clap(long)]
+    refuse_on_divergence: bool,
+
+    /// If two XML files end up at the same path in the index (which
+    /// should not happen, but might e.g. with case-insensitive
+    /// filesystems), stop with an error instead of reporting the
+    /// later file in the errors section and leaving it out of the
+    /// index. Useful for CI.
+    #[clap(long)]
+    strict_duplicate_paths: bool,
+
+    /// Instead of writing the index files into the repository (and
+    /// committing them), write a single self-contained HTML file to
+    /// the given path, which additionally embeds the contents of the
+    /// indexed XML files in collapsible blocks, for offline
+    /// archival. The sequences in the embedded files are blinded as
+    /// by `prepare` (without `--no-blind`). Files larger than 2 MB
+    /// are not embedded, and embedding stops when 200 MB have been
+    /// reached.
+    #[clap(long, conflicts_with = "daemon")]
+    standalone: Option<PathBuf>,
+
+    /// Instead of writing, committing and pushing the index files,
+    /// explain on stdout whether the build would do so, and why,
+    /// given the current state and the other options. Does not carry
+    /// out any Git actions (like `--pull` or the reset in `--batch`
+    /// mode), only reads the Git status.
+    #[clap(long, conflicts_with = "daemon")]
+    explain: bool,
+
+    /// Write the index files into the directory PATH instead of the
+    /// top of the repository (e.g. into a directory that is
+    /// published). If PATH is inside the repository, the files are
+    /// committed as usual, otherwise committing and pushing are
+    /// skipped. The links to the XML files are made relative to PATH
+    /// (but see `--link-base`).
+    #[clap(long)]
+    output_dir: Option<PathBuf>,
+
+    /// The prefix for the links from the index files to the XML
+    /// files, e.g. the URL of the top of the repository on a web
+    /// server (including the trailing slash). By default, relative
+    /// paths are used.
+    #[clap(long)]
+    link_base: Option<String>,
+
+    /// Preview the index for the files as they are in the given Git
+    /// commit, tag, branch or other tree-ish (e.g. `stash@{0}`)
+    /// instead of in the working directory, without checking it
+    /// out. The files are exported into a new private temporary
+    /// directory, and the index files are written there, too (never
+    /// committed); the path is printed, and the directory is left for
+    /// you to remove.
+    #[clap(long, conflicts_with_all = ["daemon", "output_dir"])]
+    from_ref: Option<String>,
+
+    /// Treat the given optional attributes (comma-separated) as
+    /// required for all files, i.e. report an error for files where
+    /// they are missing or `NA`. Attributes can also be required for
+    /// the files in a folder (and its subfolders) via the
+    /// `require_attributes` table in `xmlhub.toml`.
+    #[clap(long, value_delimiter = ',')]
+    require_attributes: Vec<String>,
+
+    /// Report comments with unknown attribute names (e.g. typos, or
+    /// attributes not yet supported by this version of the program)
+    /// as warnings instead of errors, so that the files are still
+    /// indexed. By default they are errors.
+    #[clap(long)]
+    lenient_unknown_attributes: bool,
+
+    /// Warn about items given more than once in the same list
+    /// attribute of a file (like "phylo, phylo" in "Keywords"), so
+    /// that they can be cleaned up. By default, repetitions are
+    /// silently dropped (the first occurrence is kept).
+    #[clap(long)]
+    warn_duplicate_list_items: bool,
+
+    /// Show the header comments of a file that are not metadata
+    /// (e.g. descriptions of the analysis) as "Notes" in its info
+    /// box. Comments that don't start with an attribute name and ':'
+    /// are then not reported as errors, and with
+    /// `--lenient-unknown-attributes`, comments with unknown
+    /// attribute names are shown, too.
+    #[clap(long)]
+    show_file_comments: bool,
+
+    /// Report files whose "Version" is outside of the
+    /// `allowed_version_range` given in `xmlhub.toml` as
+    /// errors instead of warnings.
+    #[clap(long)]
+    strict_version_range: bool,
+
     /// Run as a daemon, i.e. do not exit, but run batch conversion
     /// repeatedly. The given string must be one of "run", "start",
     /// "start-if-not-running", "stop", "restart", "status". "run"
@@ -386,6 +595,22 @@ struct BuildOpts {
     #[clap(long)]
     daemon_sleep_time: Option<f64>,
 
+    /// When running in one of the `--daemon` modes, use the given
+    /// number of seconds as the maximum time to sleep between
+    /// conversion runs, i.e. the upper bound of the exponential
+    /// backoff on errors. The default is 1000 seconds.
+    #[clap(long)]
+    daemon_max_sleep_time: Option<f64>,
+
+    /// When running in one of the `--daemon` modes, stop the daemon
+    /// with an error after the given number of consecutive failed
+    /// conversion runs (e.g. a worker process crashing repeatedly
+    /// because of a file that makes it run out of memory). Any
+    /// successful run resets the count. By default, the daemon never
+    /// gives up.
+    #[clap(long)]
+    max_consecutive_failures: Option<u64>,
+
     /// Do not check that the correct branch is checked out in the
     /// xmlhub repository. Only use if you're experimenting on another
     /// branch.
@@ -404,10 +629,190 @@ struct BuildOpts {
     #[clap(long)]
     ignore_untracked: bool,
 
+    /// Follow symbolic links when looking for XML files (both links
+    /// to files and to directories). A file reachable via several
+    /// paths is only indexed once, under the first path in sort
+    /// order. By default, symlinks are skipped.
+    #[clap(long)]
+    follow_symlinks: bool,
+
+    /// Only print the sorted list of the paths of the XML files that
+    /// would be indexed (taking `--ignore-untracked`,
+    /// `--follow-symlinks` and `--sample` into account) to stdout,
+    /// then exit without building.
+    #[clap(long, conflicts_with = "daemon")]
+    print_paths: bool,
+
+    /// Only read the XML files and print the number of files, of
+    /// files with errors and of files with warnings as one line
+    /// (`files=N errors=E warnings=W`) to stdout, without generating
+    /// the index files. Exits with code 1 if there were errors.
+    #[clap(long, conflicts_with = "daemon")]
+    count_only: bool,
+
+    /// With `--count-only`, also print how many files there are for
+    /// each value of the given indexed attribute KEY (e.g. `Contact`),
+    /// one `COUNT<TAB>VALUE` line per value, most frequent first. The
+    /// values are normalized as in the index for that attribute.
+    #[clap(long, value_name = "KEY", requires = "count_only")]
+    count_by: Option<String>,
+
+    /// When listing the XML files from the filesystem (i.e. unless
+    /// `--ignore-untracked` is given), do not descend more than N
+    /// directory levels deep: 1 means only the files at the top of
+    /// the repository, 2 also those in its direct subdirectories,
+    /// etc. The directories that were not scanned because of this
+    /// are listed in a note on stderr. By default, there is no limit.
+    #[clap(long, value_name = "N")]
+    scan_max_depth: Option<usize>,
+
     /// The path to the base directory of the Git checkout of the XML
-    /// Hub. The default is `.`.
+    /// Hub. The default is `.`. A leading `~` and `$VAR` or `${VAR}`
+    /// environment variable references are expanded. In `--daemon`
+    /// mode, can be given multiple times to manage several
+    /// repositories from one process: each gets its own build loop
+    /// (with its own lock and logs under its `.xmlhub/` directory),
+    /// and a failure in one of them does not stop the others.
     #[clap(long)]
-    base_path: Option<PathBuf>,
+    base_path: Vec<PathBuf>,
+
+    /// For each indexed attribute, list the files that do not appear
+    /// in its index because they have no value for it (informational
+    /// output on stdout).
+    #[clap(long)]
+    explain_index: bool,
+
+    /// In the index, show only the file name instead of the whole
+    /// path for each file (the whole path is shown as a tooltip).
+    #[clap(long)]
+    short_paths: bool,
+
+    /// Only index a subset of the files, for a quick preview of a
+    /// large repository. MODE is one of `newest` (by the time of the
+    /// last commit touching the file; uncommitted files count as
+    /// newest), `largest` (by file size) or `first` (by path), N is
+    /// the number of files to include. The output is labelled as a
+    /// sample, and is never committed (thus this option can't be
+    /// combined with `--push` or `--daemon`).
+    #[clap(long, num_args = 2, value_names = ["MODE", "N"], conflicts_with_all = ["push", "daemon"])]
+    sample: Option<Vec<String>>,
+
+    /// Record the program version in a Git note (in
+    /// `refs/notes/xmlhub-version`) on the commit with the index
+    /// files, instead of in its commit message. The note ref is
+    /// pushed along when `--push` is given, after merging the notes
+    /// on the remote (which are also fetched with `--pull` or in
+    /// daemon mode, whether or not this option is given). The version
+    /// check reads versions from both places.
+    #[clap(long)]
+    version_notes: bool,
+
+    /// Add a `Signed-off-by` trailer for the committer to the commit
+    /// message (as `git commit --signoff` does), e.g. for projects
+    /// requiring a Developer Certificate of Origin.
+    #[clap(long)]
+    signoff: bool,
+
+    /// Sign the commits with the index files (as `git commit -S`
+    /// does), for repositories requiring signed commits. Uses the
+    /// key configured in Git (`user.signingkey`) unless
+    /// `--signing-key` is given.
+    #[clap(long)]
+    sign_commits: bool,
+
+    /// The key to sign the commits with (`--sign-commits`), as
+    /// accepted by `git commit -S<key>`.
+    #[clap(long, value_name = "KEY", requires = "sign_commits")]
+    signing_key: Option<String>,
+
+    /// Add a `Co-authored-by` trailer with the given `Name <email>`
+    /// to the commit message. Can be given multiple times.
+    #[clap(long, value_name = "NAME_AND_EMAIL")]
+    co_author: Vec<String>,
+
+    /// In batch and daemon mode, stop with an error (exit code 3)
+    /// if no version statements can be found in the Git log of the
+    /// index files, instead of only printing a warning (the daemon
+    /// logs the error and terminates, unless `--restart-on-failures`
+    /// is given, instead of retrying the build). Without
+    /// version statements, two differing versions of this program
+    /// could keep overwriting each other's output.
+    #[clap(long, conflicts_with = "no_version_check")]
+    strict_version_check: bool,
+
+    /// Write the HTML index file with line breaks and indentation,
+    /// e.g. for reviewing diffs. The default is compact output, see
+    /// `--compact-html`.
+    #[clap(long, overrides_with = "compact_html")]
+    pretty_html: bool,
+
+    /// Write the HTML index file without added whitespace, giving
+    /// smaller commits (the default).
+    #[clap(long, overrides_with = "pretty_html")]
+    compact_html: bool,
+
+    /// Check that building the index is deterministic, e.g. in CI:
+    /// build it twice in memory (without writing, committing or
+    /// pushing anything) and fail with a summary of the differences
+    /// unless both builds produced byte-for-byte identical files.
+    /// `--pull` and `--batch` do not update the repository in this
+    /// mode.
+    #[clap(long, conflicts_with_all = ["daemon", "standalone", "explain"])]
+    self_test: bool,
+
+    /// Leave out the "Index by attribute" section (and its
+    /// entries in the table of contents), i.e. only show the
+    /// file info boxes (and errors and warnings, if any).
+    #[clap(long)]
+    no_index_section: bool,
+
+    /// After writing the index files into the working directory,
+    /// print a short summary of how they differ from the committed
+    /// version (files changed, and the number of added and removed
+    /// lines per file, as by `git diff --numstat`) to stderr.
+    #[clap(long)]
+    diff_output: bool,
+
+    /// Group the file info boxes by the given attribute instead of
+    /// by folder: `attribute:KEY` puts the boxes into a section per
+    /// (first) value of the attribute KEY (e.g. `attribute:Contact`),
+    /// the files without a value into a section "not given". The
+    /// default is `folder`.
+    #[clap(long, value_name = "folder|attribute:KEY")]
+    group_by: Option<String>,
+
+    /// Refuse to write (and thus commit) an index file if it would
+    /// be bigger than the given number of bytes, which would hint at
+    /// a bug or at unsuitable metadata (like data pasted into a
+    /// comment). 0 means no limit. The default is 100 MB. Does not
+    /// apply to `--standalone`.
+    #[clap(long, value_name = "BYTES")]
+    max_output_size: Option<u64>,
+
+    /// Leave out the table of contents (the "Contents" section at
+    /// the top), e.g. for short indexes.
+    #[clap(long)]
+    no_toc: bool,
+
+    /// At the top of each section (like "File info by folder"), show
+    /// a line with links to its immediate subsections.
+    #[clap(long)]
+    mini_toc: bool,
+
+    /// Also write the table of contents as a standalone HTML
+    /// fragment (without document wrapper), to `toc.html` next to
+    /// the HTML index file, e.g. to include the navigation
+    /// separately when embedding the index into a larger site. It
+    /// is committed along with the other index files.
+    #[clap(long, conflicts_with = "no_toc")]
+    toc_fragment: bool,
+
+    /// How to lay out the index for each attribute in the HTML
+    /// file: `dl` lists the files below each value, `table` shows a
+    /// two-column table with a row for each value and its files,
+    /// which is easier to scan. The Markdown file is not affected.
+    #[clap(long, value_enum, default_value_t)]
+    index_layout: IndexLayout,
 
     /// The virtual address space limit for the child process carrying
     /// out a build when in daemon mode, in bytes (default: 3
@@ -427,6 +832,10 @@ struct CheckOpts {
     verbosity: VerbosityOpt,
     #[clap(flatten)]
     quietness: QuietOpt,
+    #[clap(flatten)]
+    output_files: OutputFilesOpts,
+    #[clap(flatten)]
+    extensions: ExtensionsOpt,
 
     /// Open the generated `README.html` file in a web browser.
     /// Tries the browsers specified in the `BROWSER` environment
@@ -445,12 +854,76 @@ struct CheckOpts {
     #[clap(long)]
     open_if_changed: bool,
 
+    /// For each indexed attribute, list the files that do not appear
+    /// in its index because they have no value for it (informational
+    /// output on stdout).
+    #[clap(long)]
+    explain_index: bool,
+
+    /// In the index, show only the file name instead of the whole
+    /// path for each file (the whole path is shown as a tooltip).
+    #[clap(long)]
+    short_paths: bool,
+
     /// Omit the check for the Git clone containing the FILE_PATHS to
     /// contain items that make it look like a legit xmlhub repository
     /// clone.
     #[clap(long)]
     no_repo_check: bool,
 
+    /// Only check quickly whether the files have the metadata header
+    /// comments (as added by `prepare`, even if still empty) or
+    /// `<?xmlhub ...?>` processing instructions, without validating
+    /// the values and without rebuilding the index. Useful for
+    /// triaging a freshly imported batch of files.
+    /// Exits with code 1 if any file is missing the headers or has
+    /// incomplete headers.
+    #[clap(long)]
+    headers_only: bool,
+
+    /// Only read the given files and print the number of files, of
+    /// files with errors and of files with warnings as one line
+    /// (`files=N errors=E warnings=W`) to stdout, without rebuilding
+    /// the index. Exits with code 1 if there were errors.
+    #[clap(long)]
+    count_only: bool,
+
+    /// Only print the errors for the given files, not the warnings
+    /// (the exit code is the same).
+    #[clap(long, conflicts_with = "only_warnings")]
+    only_errors: bool,
+
+    /// Only print the warnings for the given files, not the errors
+    /// (the exit code still reflects errors).
+    #[clap(long)]
+    only_warnings: bool,
+
+    /// Treat the given optional attributes (comma-separated) as
+    /// required, as with the same option to `build`.
+    #[clap(long, value_delimiter = ',')]
+    require_attributes: Vec<String>,
+
+    /// Report unknown attribute names as warnings instead of errors,
+    /// as with the same option to `build`.
+    #[clap(long)]
+    lenient_unknown_attributes: bool,
+
+    /// Warn about list items given more than once in the same
+    /// attribute, as with the same option to `build`.
+    #[clap(long)]
+    warn_duplicate_list_items: bool,
+
+    /// Accept header comments that are not metadata, as with the
+    /// same option to `build`.
+    #[clap(long)]
+    show_file_comments: bool,
+
+    /// Report files whose "Version" is outside of the
+    /// `allowed_version_range` as errors, as with the same option to
+    /// `build`.
+    #[clap(long)]
+    strict_version_range: bool,
+
     /// The path(s) to the XML file(s) you're currently working on and
     /// want to check. Must be somewhere in a Git checkout of the XML
     /// Hub (this is because `check` will still rebuild the index, too
@@ -459,12 +932,76 @@ struct CheckOpts {
     file_paths: Vec<PathBuf>,
 }
 
+#[derive(clap::Parser, Debug)]
+struct ValidateOpts {
+    #[clap(flatten)]
+    quietness: QuietOpt,
+    #[clap(flatten)]
+    extensions: ExtensionsOpt,
+
+    /// Omit the check for the Git clone at the `BASE_PATH` directory
+    /// to contain items that make it look like a legit xmlhub
+    /// repository clone.
+    #[clap(long)]
+    no_repo_check: bool,
+
+    /// Ignore untracked files (local files not added to the xmlhub
+    /// repository). By default, files are read regardless of whether
+    /// they are in Git or not.
+    #[clap(long)]
+    ignore_untracked: bool,
+
+    /// Print the report as JSON to stdout instead of the plain text
+    /// report on stderr. The exit code is the same. The keys are
+    /// sorted; the `schema_version` field is increased whenever the
+    /// format changes incompatibly.
+    #[clap(long)]
+    json: bool,
+
+    /// Also check each file against the RELAX NG schema (XML syntax)
+    /// at the given path; files not conforming to it are reported
+    /// with errors. XSD schemas and some RELAX NG features are not
+    /// supported, and datatypes are not checked.
+    #[clap(long)]
+    schema: Option<PathBuf>,
+
+    /// The path to the base directory of the Git checkout of the XML
+    /// Hub. The default is `.`. A leading `~` and `$VAR` or `${VAR}`
+    /// environment variable references are expanded.
+    #[clap(long)]
+    base_path: Option<PathBuf>,
+}
+
+#[derive(clap::Parser, Debug)]
+struct GenAttributesOpts {
+    #[clap(flatten)]
+    quietness: QuietOpt,
+    #[clap(flatten)]
+    output_files: OutputFilesOpts,
+
+    /// Omit the check for the Git clone at the `BASE_PATH` directory
+    /// to contain items that make it look like a legit xmlhub
+    /// repository clone.
+    #[clap(long)]
+    no_repo_check: bool,
+
+    /// The path to the base directory of the Git checkout of the XML
+    /// Hub. The default is `.`. A leading `~` and `$VAR` or `${VAR}`
+    /// environment variable references are expanded.
+    #[clap(long)]
+    base_path: Option<PathBuf>,
+}
+
 #[derive(clap::Parser, Debug)]
 struct PrepareOpts {
     #[clap(flatten)]
     quietness: QuietOpt,
     #[clap(flatten)]
     blinding: BlindingOpts,
+    #[clap(flatten)]
+    trash: TrashOpts,
+    #[clap(flatten)]
+    extensions: ExtensionsOpt,
 
     /// The path(s) to the XML file(s) which should be
     /// modified. Careful: they are modified in place (although the
@@ -479,9 +1016,27 @@ struct PrepareOpts {
     /// only implemented for BEAST2.
     #[clap(long)]
     ignore_version: bool,
+
+    /// Do not modify the file, instead print the prepared content
+    /// to stdout (informational messages go to stderr). Only a
+    /// single file can be given in this mode.
+    #[clap(long)]
+    stdout: bool,
     // XX FUTURE idea: --set "header: value"
 }
 
+#[derive(clap::Parser, Debug)]
+struct NormalizeOpts {
+    #[clap(flatten)]
+    quietness: QuietOpt,
+    #[clap(flatten)]
+    trash: TrashOpts,
+
+    /// The path(s) to the XML file(s) whose metadata should be
+    /// normalized. Their metadata must be free of errors.
+    files_to_normalize: Vec<PathBuf>,
+}
+
 #[derive(clap::Parser, Debug)]
 struct AddToOpts {
     #[clap(flatten)]
@@ -490,9 +1045,15 @@ struct AddToOpts {
     quietness: QuietOpt,
     #[clap(flatten)]
     blinding: BlindingOpts,
+    #[clap(flatten)]
+    trash: TrashOpts,
+    #[clap(flatten)]
+    extensions: ExtensionsOpt,
 
     /// The path to an existing directory *inside* the Git checkout of
-    /// the XML Hub, where the file(s) should be copied to. .
+    /// the XML Hub, where the file(s) should be copied to. A leading
+    /// `~` and `$VAR` or `${VAR}` environment variable references are
+    /// expanded. .
     target_directory: Option<PathBuf>,
 
     /// Omit the check for the `TARGET_PATH` directory to be in a Git
@@ -518,7 +1079,8 @@ struct AddToOpts {
     #[clap(long)]
     ignore_version: bool,
 
-    /// Force overwriting of existing files at the target location. .
+    /// Force overwriting of existing files at the target location
+    /// (also implies `--yes` if `--no-trash` is given). .
     #[clap(long, short)]
     force: bool,
     // XX FUTURE idea: --set "header: value"
@@ -527,319 +1089,901 @@ struct AddToOpts {
 // =============================================================================
 // Parsing
 
-/// Parse all XML comments from above the first XML opening element
-/// out of one file as `Metadata`. The comments are passed as an
-/// iterator over `XMLDocumentComment`, which has the string and
-/// location of the comment. The `XMLDocumentComment` has a limited
-/// lifetime (validity span) indicated by the context of the call to
-/// `parse_comments`, hence passed as lifetime parameter `'a`. If
-/// `dry` is true, does not parse the values; this is used in
-/// `prepare_file` to check whether headers are complete without
-/// checking the validity of the values.
-fn parse_comments<'a>(
-    comments: impl Iterator<Item = XMLDocumentComment<'a>>,
-    dry: bool,
-) -> Result<Metadata<WithCommentsOnly>, Vec<Issue>> {
-    let spec_by_lowercase_key: BTreeMap<String, &AttributeSpecification> = METADATA_SPECIFICATION
-        .iter()
-        .map(|spec| (spec.key.as_ref().to_lowercase(), spec))
-        .collect();
-    let mut unseen_specs_by_lowercase_key = spec_by_lowercase_key.clone();
-    let mut map: BTreeMap<AttributeName, AttributeValue> = BTreeMap::new();
-
-    // Collect all errors instead of stopping at the first one.
-    let mut errors: Vec<Issue> = Vec::new();
-    for comment in comments {
-        // Using a function without arguments and calling it right
-        // away to capture the result (Ok or Err).
-        let result = (|| {
-            if let Some((key_, value)) = comment.string.split_once(":") {
-                let lc_key = key_.trim().to_lowercase();
-                let value = value.trim();
-
-                if let Some(spec) = spec_by_lowercase_key.get(&lc_key) {
-                    unseen_specs_by_lowercase_key.remove(&lc_key);
-                    if map.contains_key(&spec.key) {
-                        bail!("duplicate entry for attribute name {lc_key:?}")
+/// Write the files of `tree_ish` in the repository at
+/// `git_working_dir` into a new private temporary directory (via `git
+/// read-tree` into a temporary index and `git checkout-index`),
+/// without touching the working directory or the index.
+fn export_tree(git_working_dir: &GitWorkingDir, tree_ish: &str) -> Result<TempDir> {
+    let dir = TempDir::new("xmlhub-from-ref")?;
+    let index_dir = TempDir::new("xmlhub-from-ref-index")?;
+    let index_path = index_dir.join("index");
+    let index_path = index_path
+        .to_str()
+        .ok_or_else(|| anyhow!("temporary path is not valid unicode: {index_path:?}"))?;
+    let prefix = format!(
+        "--prefix={}/",
+        dir.to_str()
+            .ok_or_else(|| anyhow!("temporary path is not valid unicode: {:?}", dir.path()))?
+    );
+    let git = |arguments: &[&str]| -> Result<()> {
+        run_outputs(
+            git_working_dir.working_dir_path_ref(),
+            "git",
+            arguments,
+            &[("GIT_INDEX_FILE", index_path)],
+            &[0],
+        )?;
+        Ok(())
+    };
+    git(&["read-tree", tree_ish])
+        .and_then(|()| git(&["checkout-index", "--all", &prefix]))
+        .with_context(|| anyhow!("exporting the files of {tree_ish:?}"))?;
+    Ok(dir)
+}
+
+/// Whether a file has the metadata header comments, see
+/// `headers_status`.
+#[derive(Debug)]
+enum HeadersStatus {
+    /// All required attributes are present (values not checked)
+    Present,
+    /// There are header comments, but not for all required
+    /// attributes, or with unknown or duplicate attribute names
+    Incomplete(Vec<Issue>),
+    /// There are no header comments at all
+    Missing,
+    /// The file could not be read or parsed as XML
+    Unreadable(String),
+}
+
+impl Display for HeadersStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeadersStatus::Present => f.write_str("has the metadata headers"),
+            HeadersStatus::Incomplete(issues) => {
+                f.write_str("has incomplete metadata headers:")?;
+                for Issue {
+                    message,
+                    hint: _,
+                    location,
+                } in issues
+                {
+                    if let Some(location) = location {
+                        write!(f, "\n      * {location}: {message}")?;
                     } else {
-                        if !dry {
-                            let value = AttributeValue::from_str_and_spec(value, spec)?;
-                            map.insert(spec.key, value);
-                        }
+                        write!(f, "\n      * {message}")?;
                     }
-                } else {
-                    bail!("unknown attribute name {lc_key:?} given")
                 }
-            } else {
-                bail!("comment does not start with a keyword name and ':'")
+                Ok(())
             }
-            Ok(())
-        })()
-        .with_context(|| anyhow!("XML comment on {}", comment.location));
-        if let Err(e) = result {
-            errors.push(Issue {
-                message: format!("{e:#}"),
-                hint: None,
-            });
+            HeadersStatus::Missing => {
+                f.write_str("is missing the metadata headers (use `xmlhub prepare` to add them)")
+            }
+            HeadersStatus::Unreadable(e) => write!(f, "could not be read: {e}"),
         }
     }
+}
 
-    let missing: Vec<AttributeName> = unseen_specs_by_lowercase_key
-        .into_values()
-        .filter_map(|spec| {
-            let source_spec = match &spec.source {
-                AttributeSource::Specified(source_spec) => source_spec,
-                AttributeSource::Derived(_) | AttributeSource::Extracted(_) => return None,
+/// Check whether the XML file at `path` has the metadata header
+/// (comments or `<?xmlhub ...?>` processing instructions), without
+/// validating the values (like `prepare_file` does).
+fn headers_status(path: &Path) -> HeadersStatus {
+    let xmldocument = match read_xml_file(path) {
+        Ok(xmldocument) => xmldocument,
+        Err(e) => return HeadersStatus::Unreadable(format!("{e:#}")),
+    };
+    if xmldocument.header_comments().next().is_none()
+        && xmldocument
+            .header_processing_instructions(METADATA_PI_TARGET)
+            .next()
+            .is_none()
+    {
+        return HeadersStatus::Missing;
+    }
+    match parse_header(&xmldocument, true, &[], None, None) {
+        Ok(_) => HeadersStatus::Present,
+        Err(issues) => HeadersStatus::Incomplete(issues),
+    }
+}
+
+/// The result of `xml_file_paths_with_max_depth`.
+struct XmlFilePaths {
+    paths: Vec<BaseAndRelPath>,
+    /// The (relative) paths of the directories whose contents were
+    /// not listed because of `max_depth`
+    skipped_directories: Vec<PathBuf>,
+    /// The (relative) paths of the XML files that were left out
+    /// because they are symlinks and `follow_symlinks` was false
+    skipped_symlinks: Vec<PathBuf>,
+}
+
+/// Get the list of XML files (those with one of the `extensions`) in
+/// the Git working directory `git_working_dir`. Collect them as a
+/// vector of `RelPathWithBase` values, each of which carries both a
+/// path to a base directory (optional) and a relative path from there
+/// (if it contains no base directory, the current working directoy is
+/// the base). If `ignore_untracked` is true, uses `git ls-files`,
+/// otherwise lists the files from the file system, not descending
+/// deeper than `max_depth` levels (1 meaning only the files at the
+/// top of the working directory) if given. Symlinks are skipped
+/// unless `follow_symlinks` is true, in which case files reachable
+/// via multiple paths are only listed once (under the first path in
+/// sort order). The paths are sorted. Also returns what was skipped.
+fn xml_file_paths_with_max_depth(
+    git_working_dir: &GitWorkingDir,
+    ignore_untracked: bool,
+    follow_symlinks: bool,
+    max_depth: Option<usize>,
+    extensions: &XmlExtensions,
+) -> Result<XmlFilePaths> {
+    let working_dir_path = git_working_dir.working_dir_path_ref();
+    let mut skipped_directories = Vec::new();
+    let mut skipped_symlinks = Vec::new();
+
+    // Get the paths from running `git ls-files` inside the directory
+    // at base_path, then ignore all files that don't have one of the
+    // `extensions`
+    let mut paths = if ignore_untracked {
+        // Ask Git for the list of files
+        git_working_dir.git_ls_files()?
+    } else {
+        // Ask the filesystem for the list of files, but do not waste
+        // time listing paths in the .git nor .xmlhub subdirs
+        let ignored_file_names = HashSet::from([".git", &*DAEMON_FOLDER_NAME]);
+        let entries = WalkDir::new(working_dir_path)
+            .follow_links(follow_symlinks)
+            .min_depth(1)
+            .max_depth(max_depth.unwrap_or(usize::MAX))
+            .into_iter()
+            .filter_entry(|entry| {
+                if let Some(file_name) = entry.file_name().to_str() {
+                    !ignored_file_names.contains(file_name)
+                } else {
+                    // invalid encoding; XX: what to do? Try to keep those:
+                    true
+                }
+            });
+        let shared_base_path = git_working_dir.working_dir_path_arc();
+        let mut paths: Vec<BaseAndRelPath> = Vec::new();
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                // Symlink cycles are reported by WalkDir as errors;
+                // the files in the cycle are reached anyway, so just
+                // skip the looping entry.
+                Err(e) if e.loop_ancestor().is_some() => continue,
+                Err(e) => Err(e).with_context(|| {
+                    anyhow!("listing contents of directory {working_dir_path:?}")
+                })?,
             };
-            // Do not report as missing if it's optional
-            if source_spec.need == AttributeNeed::Optional {
-                None
-            } else {
-                Some(spec.key)
+            let relative_path = entry
+                .path()
+                .strip_prefix(working_dir_path)
+                .with_context(|| {
+                    // Could happen via folder rename races, right? So don't panic.
+                    anyhow!(
+                        "listed files of directory {working_dir_path:?} \
+                         should be prefixed with that path, but got {:?}",
+                        entry.path()
+                    )
+                })?;
+            if Some(entry.depth()) == max_depth && entry.file_type().is_dir() {
+                skipped_directories.push(relative_path.to_owned());
             }
-        })
-        .collect();
-    if !missing.is_empty() {
-        let sorted_missing: Vec<AttributeName> =
-            sort_in_definition_order(missing.into_iter().map(|k| (k, ())))
-                .into_iter()
-                .filter_map(|(k, v)| {
-                    v?;
-                    Some(k)
-                })
-                .collect();
-
-        pluralized! { sorted_missing.len() => attributes, these, names, are }
-        errors.push(Issue {
-            message: format!(
-                "{attributes} with {these} {names} {are} missing: {}",
-                // Show just the names, not the AttributeName wrappers
-                format_string_list(&sorted_missing),
-            ),
-            hint: None,
+            paths.push(BaseAndRelPath::new(
+                Some(Arc::clone(&shared_base_path)),
+                relative_path.to_owned(),
+            ));
+        }
+        paths
+    };
+    paths.retain(|path| extensions.matches(&path.rel_path));
+    // Sort entries ourselves out of a worry that git ls-files might
+    // not guarantee a sort order. (The sort order determines the ID
+    // assignment that happens later, and those are used in the HTML
+    // output, hence would lead to useless commits.)
+    paths.sort_by(|a, b| a.rel_path().cmp(b.rel_path()));
+    if follow_symlinks {
+        // Only keep the first path to each file. Paths that can't be
+        // canonicalized (dangling links) are kept, so that reading
+        // them reports the error.
+        let mut seen = HashSet::new();
+        paths.retain(|path| match path.full_path().canonicalize() {
+            Ok(canonical_path) => seen.insert(canonical_path),
+            Err(_) => true,
+        });
+    } else {
+        // `git ls-files` lists symlinks, and WalkDir reports them
+        // even when not following them
+        paths.retain(|path| {
+            let is_symlink = path
+                .full_path()
+                .symlink_metadata()
+                .map(|metadata| metadata.file_type().is_symlink())
+                .unwrap_or(false);
+            if is_symlink {
+                skipped_symlinks.push(path.rel_path.clone());
+            }
+            !is_symlink
         });
     }
+    Ok(XmlFilePaths {
+        paths,
+        skipped_directories,
+        skipped_symlinks,
+    })
+}
 
-    if errors.is_empty() {
-        Ok(Metadata::new(map))
-    } else {
-        Err(errors)
+/// Print the relative paths of `paths`, one per line.
+fn print_paths_plain(paths: &[BaseAndRelPath], out: &mut impl Write) -> Result<()> {
+    for path in paths {
+        writeln!(out, "{}", path.rel_path())?;
     }
+    Ok(())
 }
 
-lazy_static! {
-    static ref VERSION_KEY: AttributeName = attribute_specification_by_name("Version")
-        .map(|spec| spec.key)
-        .expect("'Version' attribute definition should always be present");
-}
-
-/// Map each file to the info extracted from it (or `FileErrors`
-/// when there were errors), including path and an id, held in a
-/// `FileInfo` struct. Generate the ids on the go for each of them
-/// by `enumerate`ing the values (the enumeration number value is
-/// passed as the `id` argument to the function given to `map`).
-/// The id is used to refer to each item in document-local links in
-/// the generated HTML/Markdown files.
-fn read_file_infos(
+/// Read all files at `paths` (see `read_file_infos`), then build
+/// the derived attribute values for the successfully read
+/// ones. Returns the successful and the erroneous results
+/// separately.
+fn read_and_derive_file_infos(
     paths: Vec<BaseAndRelPath>,
-) -> Vec<Result<FileInfo<WithExtractedValues>, FileErrors>> {
-    paths
-        .into_par_iter()
-        .enumerate()
-        .map(
-            |(id, path)| -> Result<FileInfo<WithExtractedValues>, FileErrors> {
-                let xmldocument = read_xml_file(&path.full_path()).map_err(|e| FileErrors {
-                    path: path.clone(),
+    attribute_rules: &AttributeRules,
+) -> (Vec<FileInfo<WithDerivedValues>>, Vec<FileErrors>) {
+    read_and_derive_file_infos_with_progress(paths, attribute_rules, &NoProgress)
+}
+
+/// Same as `read_and_derive_file_infos`, but reports each file to
+/// `progress` as soon as it has been read (with the warnings found
+/// up to then, i.e. not those from the checks across files).
+fn read_and_derive_file_infos_with_progress(
+    paths: Vec<BaseAndRelPath>,
+    attribute_rules: &AttributeRules,
+    progress: &dyn BuildProgress,
+) -> (Vec<FileInfo<WithDerivedValues>>, Vec<FileErrors>) {
+    // The contacts allow-list of the repository, if it has one
+    let contacts_allow_list = paths.first().map(|path| {
+        ContactsAllowList::read(
+            &BaseAndRelPath::new(path.base_path.clone(), PathBuf::new()).full_path(),
+        )
+    });
+
+    // See help text on `read_file_infos` for what it's doing.
+    let fileinfo_or_errors: Vec<Result<FileInfo<WithExtractedValues>, FileErrors>> =
+        read_file_infos(paths, attribute_rules, progress);
+
+    // Partition fileinfo_or_errors into vectors with only the
+    // successful and only the erroneous results.
+    let (file_infos, file_errorss): (Vec<FileInfo<WithExtractedValues>>, Vec<FileErrors>) =
+        fileinfo_or_errors.into_iter().partition_result();
+
+    // Build derived attribute values. Errors during this phase are
+    // stored as warnings, so as to not prevent users from pushing
+    // their changes, since some errors could be temporary.
+    let mut file_infos: Vec<FileInfo<WithDerivedValues>> = file_infos
+        .into_iter()
+        .map(FileInfo::add_derived_attributes)
+        .collect();
+
+    // Warn about contacts that are not on the allow-list. The list
+    // is optional, thus failing to read it is only a warning, too.
+    match contacts_allow_list {
+        None | Some(Ok(None)) => (),
+        Some(Ok(Some(allow_list))) => {
+            let contact_key = attribute_specification_by_name("Contact")
+                .expect("'Contact' attribute definition should always be present")
+                .key;
+            for file_info in &mut file_infos {
+                let warnings: Vec<Issue> = file_info
+                    .metadata
+                    .get(contact_key)
+                    .map(|value| value.as_string_list())
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|contact| allow_list.check(contact))
+                    .collect();
+                file_info.warnings.extend(warnings);
+            }
+        }
+        Some(Err(e)) => {
+            for file_info in &mut file_infos {
+                file_info.warnings.push(Issue {
+                    message: format!("could not check the contact: {e:#}"),
+                    hint: None,
+                    location: None,
+                });
+            }
+        }
+    }
+    if attribute_rules.warn_duplicate_list_items {
+        for file_info in &mut file_infos {
+            let warnings = file_info.metadata.duplicate_list_item_warnings();
+            file_info.warnings.extend(warnings);
+        }
+    }
+    add_inconsistent_email_warnings(&mut file_infos);
+
+    (file_infos, file_errorss)
+}
+
+/// The note about the directories whose contents were not scanned
+/// because of `--scan-max-depth`.
+fn skipped_directories_note(max_depth: usize, skipped_directories: &[PathBuf]) -> String {
+    let num_directories = skipped_directories.len();
+    pluralized! { num_directories => directories }
+    format!(
+        "did not look for XML files in {num_directories} {directories} deeper than \
+         --scan-max-depth {max_depth}: {}",
+        skipped_directories
+            .iter()
+            .map(|path| format!("{:?}", path.to_string_lossy()))
+            .join(", ")
+    )
+}
+
+/// The note about the XML files that were left out because they are
+/// symlinks (without `--follow-symlinks`).
+fn skipped_symlinks_note(skipped_symlinks: &[PathBuf]) -> String {
+    let num_files = skipped_symlinks.len();
+    pluralized! { num_files => files, are }
+    format!(
+        "left out {num_files} XML {files} that {are} symbolic links (use \
+         --follow-symlinks to index them): {}",
+        skipped_symlinks
+            .iter()
+            .map(|path| format!("{:?}", path.to_string_lossy()))
+            .join(", ")
+    )
+}
+
+/// Move the files whose path was already seen in `file_infos` out
+/// into errors, since `Folder` can only hold one file per path.
+fn split_off_duplicate_paths(
+    file_infos: Vec<FileInfo<WithDerivedValues>>,
+) -> (Vec<FileInfo<WithDerivedValues>>, Vec<FileErrors>) {
+    let mut seen = HashSet::new();
+    let mut errorss = Vec::new();
+    let file_infos = file_infos
+        .into_iter()
+        .filter_map(|file_info| {
+            if seen.insert(file_info.path.rel_path().to_string()) {
+                Some(file_info)
+            } else {
+                errorss.push(FileErrors {
+                    path: file_info.path,
                     errors: vec![Issue {
-                        message: format!("{e:#}"),
+                        message: "another file with the same path is already in the index, \
+                                  thus this one is left out"
+                            .into(),
                         hint: None,
+                        location: None,
                     }],
-                })?;
-                let metadata =
-                    parse_comments(xmldocument.header_comments(), false).map_err(|errors| {
-                        FileErrors {
-                            path: path.clone(),
-                            errors,
-                        }
-                    })?;
-
-                let mut warnings: Vec<Issue> = Vec::new();
-
-                let metadata = metadata.add_extracted_attributes(&xmldocument, &mut warnings);
-
-                // Check the version in the XML: verify that it fits
-                // what the user provided in the XML comment.
-                match (|| -> Result<_> {
-                    let att_val: &AttributeValue = metadata
-                        .get(*VERSION_KEY)
-                        .context("missing 'Version' entry")?;
-                    let att_vals = att_val.as_string_list();
-                    let user_specified_str =
-                        att_vals.first().context("'Version' entry is empty")?;
-                    // Have to skip any "BEAST "
-                    let user_specified_version_str = strip_prefixes(
-                        user_specified_str,
-                        &["BEAST2 ", "BEAST2", "BEAST ", "BEAST"],
-                    )
-                    .trim();
-                    let user_specified_version =
-                        BeastVersion::from_str(user_specified_version_str)?;
-                    let user_specified_major: u16 = user_specified_version.major.context(
-                        "provided 'Version' has no BEAST2-major number part \
-                         or is not a BEAST2 version",
-                    )?;
-
-                    let document_version =
-                        check_beast_version(xmldocument.document(), path.rel_path(), false)?;
-                    (|| -> Option<()> {
-                        let found_major: u16 = document_version.major?;
-                        if found_major != user_specified_major {
-                            warnings.push(Issue {
-                                message: format!(
-                                    "the <beast> element in the document specifies version \
-                                     {document_version} with major {found_major}, but the \
-                                     user-provided version {user_specified_version} has \
-                                     major {user_specified_major}"
-                                ),
-                                hint: Some(
-                                    "Please edit the file to make both versions match \
-                                     the BEAST version you're actually using."
-                                        .into(),
-                                ),
-                            });
-                        }
-                        Some(())
-                    })();
-                    Ok(())
-                })() {
-                    Ok(()) => (),
-                    // XX why a warning for an error?
-                    Err(e) => warnings.push(Issue {
-                        message: format!("{e:#}"),
-                        hint: None,
-                    }),
-                }
-
-                Ok(FileInfo {
-                    id,
-                    path,
-                    metadata,
-                    warnings,
-                })
-            },
-        )
-        .collect()
+                });
+                None
+            }
+        })
+        .collect();
+    (file_infos, errorss)
 }
 
 // =============================================================================
 // Building output / implementing the various subcommands
 
 /// Build an index, as human-readable text (thus as `Section`), over
-/// all files for one particular attribute name (`attribute_key`).
+/// all files for one particular attribute name (`attribute_key`),
+/// with the anchor names from `index_anchor_names` (see there).
 fn build_index_section(
     attribute_key: AttributeName,
     key_string_normalization: KeyStringPreparation,
     autolink: Autolink,
     file_infos: &[FileInfo<WithDerivedValues>],
+    index_anchor_names: &BTreeMap<AttributeName, AnchorNames>,
+    opts: IndexSectionOpts,
 ) -> Result<Section> {
-    // Build an index by the value for attribute_key (lower-casing the
-    // key values for consistency if use_lowercase is true). The index
-    // maps from key value to a set of all `FileInfo`s for that
-    // value. The BTreeMap keeps the key values sorted alphabetically,
-    // which is nice so we don't have to sort those afterwards.
-    let mut file_infos_by_key_string: BTreeMap<String, BTreeSet<&FileInfo<WithDerivedValues>>> =
-        BTreeMap::new();
-
+    let file_infos_by_key_string =
+        file_infos_by_key_string(attribute_key, key_string_normalization, file_infos);
+
+    index_section(
+        attribute_key.as_ref(),
+        |key_string| match index_anchor_names.get(&attribute_key) {
+            Some(anchor_names) => anchor_names.get(key_string),
+            None => attribute_key.anchor_name(key_string),
+        },
+        autolink,
+        &file_infos_by_key_string,
+        opts,
+    )
+}
+
+/// The anchor names of the entries in the indices for all indexed
+/// attributes, for the index sections and the links to them from
+/// the file info boxes (different for each entry, see
+/// `AttributeName::anchor_names`).
+fn index_anchor_names(
+    file_infos: &[FileInfo<WithDerivedValues>],
+) -> BTreeMap<AttributeName, AnchorNames> {
+    METADATA_SPECIFICATION
+        .iter()
+        .filter_map(|spec| {
+            let key_string_normalization = spec.key_string_preparation()?;
+            let file_infos_by_key_string =
+                file_infos_by_key_string(spec.key, key_string_normalization, file_infos);
+            Some((
+                spec.key,
+                spec.key
+                    .anchor_names(file_infos_by_key_string.keys().map(String::as_str)),
+            ))
+        })
+        .collect()
+}
+
+/// Build an index by the value for attribute_key (lower-casing the
+/// key values for consistency if use_lowercase is true). The index
+/// maps from key value to a set of all `FileInfo`s for that
+/// value. The BTreeMap keeps the key values sorted alphabetically,
+/// which is nice so we don't have to sort those afterwards.
+fn file_infos_by_key_string(
+    attribute_key: AttributeName,
+    key_string_normalization: KeyStringPreparation,
+    file_infos: &[FileInfo<WithDerivedValues>],
+) -> BTreeMap<String, BTreeSet<&FileInfo<WithDerivedValues>>> {
+    let mut file_infos_by_key_string: BTreeMap<String, BTreeSet<&FileInfo<WithDerivedValues>>> =
+        BTreeMap::new();
+
     for file_info in file_infos {
-        if let Some(attribute_value) = file_info.metadata.get(attribute_key) {
-            for key_string in attribute_value.as_string_list().iter() {
-                file_infos_by_key_string.insert_value(
-                    key_string_normalization.prepare_key_string(key_string),
-                    file_info,
-                );
+        let key_strings = file_info
+            .metadata
+            .get(attribute_key)
+            .map(|attribute_value| attribute_value.as_string_list())
+            .unwrap_or_default();
+        for key_string in key_strings.iter() {
+            file_infos_by_key_string.insert_value(
+                key_string_normalization.prepare_key_string(key_string),
+                file_info,
+            );
+        }
+        if key_strings.is_empty() {
+            if let Some(missing_key_string) = key_string_normalization.missing_key_string() {
+                file_infos_by_key_string.insert_value(missing_key_string.into(), file_info);
             }
         }
     }
+    file_infos_by_key_string
+}
+
+/// The number of files for each value of the indexed attribute
+/// `spec` (for `--count-by`), with the values as in its index, most
+/// frequent first (values with the same count in alphabetical
+/// order).
+fn value_tally(
+    spec: &AttributeSpecification,
+    file_infos: &[FileInfo<WithDerivedValues>],
+) -> Vec<(String, usize)> {
+    let key_string_normalization = spec
+        .key_string_preparation()
+        .expect("checked by count_by_attribute");
+    let mut tally: Vec<(String, usize)> =
+        file_infos_by_key_string(spec.key, key_string_normalization, file_infos)
+            .into_iter()
+            .map(|(key_string, file_infos)| (key_string, file_infos.len()))
+            .collect();
+    // (Stable sort, keeping the alphabetical order for equal counts)
+    tally.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    tally
+}
+
+/// Print `tally` as one `COUNT<TAB>VALUE` line per value.
+fn print_value_tally(tally: &[(String, usize)], out: &mut impl Write) -> Result<()> {
+    for (value, count) in tally {
+        writeln!(out, "{count}\t{value}")?;
+    }
+    Ok(())
+}
 
+/// Build an index of the matches of the `extract_index_regex` of
+/// `spec` (passed compiled as `regex`) in the values of its
+/// attribute. The matches are used as key strings as they are.
+fn build_extracted_index_section(
+    spec: &AttributeSpecification,
+    regex: &Regex,
+    file_infos: &[FileInfo<WithDerivedValues>],
+    opts: IndexSectionOpts,
+) -> Result<Section> {
+    let mut file_infos_by_key_string: BTreeMap<String, BTreeSet<&FileInfo<WithDerivedValues>>> =
+        BTreeMap::new();
+
+    for file_info in file_infos {
+        if let Some(attribute_value) = file_info.metadata.get(spec.key) {
+            for value in attribute_value.as_string_list().iter() {
+                for m in regex.find_iter(value) {
+                    file_infos_by_key_string.insert_value(m.as_str().to_string(), file_info);
+                }
+            }
+        }
+    }
+
+    // Prefix to avoid clashes with anchors of an index on the
+    // attribute itself
+    let prefixed_key_strings: Vec<String> = file_infos_by_key_string
+        .keys()
+        .map(|key_string| format!("id-{key_string}"))
+        .collect();
+    let anchor_names = spec
+        .key
+        .anchor_names(prefixed_key_strings.iter().map(String::as_str));
+    index_section(
+        &spec.extracted_index_title(),
+        |key_string| anchor_names.get(&format!("id-{key_string}")),
+        spec.autolink,
+        &file_infos_by_key_string,
+        opts,
+    )
+}
+
+/// How the index for each attribute is laid out in the HTML file
+/// (`build --index-layout`). The Markdown file always uses the
+/// definition list.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum IndexLayout {
+    /// A definition list, with the files listed below each value
+    #[default]
+    Dl,
+    /// A two-column table, with a row for each value and its files
+    Table,
+}
+
+/// How the index sections are shown, see `index_section`.
+#[derive(Debug, Clone, Copy)]
+struct IndexSectionOpts<'a> {
+    /// Only show the file names (`--short-paths`)
+    short_paths: bool,
+    /// The path from the index files to the top of the repository
+    file_link_base: &'a str,
+    layout: IndexLayout,
+    /// The symbol for the links to the files
+    symbols: &'a Symbols,
+}
+
+impl Default for IndexSectionOpts<'_> {
+    fn default() -> Self {
+        Self {
+            short_paths: false,
+            file_link_base: "",
+            layout: IndexLayout::Dl,
+            symbols: &DEFAULT_SYMBOLS,
+        }
+    }
+}
+
+/// Format an index (as built by `build_index_section` or
+/// `build_extracted_index_section`) as a `Section`, laid out
+/// according to `layout`.
+fn index_section(
+    title: &str,
+    anchor_name: impl Fn(&str) -> String,
+    autolink: Autolink,
+    file_infos_by_key_string: &BTreeMap<String, BTreeSet<&FileInfo<WithDerivedValues>>>,
+    opts: IndexSectionOpts,
+) -> Result<Section> {
+    let IndexSectionOpts {
+        short_paths,
+        file_link_base,
+        layout,
+        symbols,
+    } = opts;
     let html = HTML_ALLOCATOR_POOL.get();
 
-    // The contents of the section, i.e. the list of all key_strings and
-    // the files for the respective key_string.
-    let mut body = html.new_vec();
-    for (key_string, file_infos) in &file_infos_by_key_string {
-        // Output the key value, with an anchor
-        let anchor_name = attribute_key.anchor_name(key_string);
-        body.push(html.dt(
-            // The first list passed to HTML constructor methods like
-            // `dt` is holding attributes, the second the child
-            // elements (but a single child element can also be passed
-            // without putting it into a list). The `?` is needed to
-            // handle errors, because those method calls can fail,
-            // either when they detect nesting of HTML elements that
-            // doesn't conform to the HTML standard, or when the
-            // allocator is running against the allocation limit that
-            // was provided to `HtmlAllocator::new`.
-            [att("class", "key_dt")],
-            html.strong(
-                [att("class", "key")],
-                html.i(
+    // The HTML for one index entry: the key value (with an anchor),
+    // and the links to the files for that key value.
+    let entry_html = |key_string: &str,
+                      file_infos: &BTreeSet<&FileInfo<WithDerivedValues>>|
+     -> Result<(AId<Node>, AId<Node>)> {
+        // The first list passed to HTML constructor methods like
+        // `strong` is holding attributes, the second the child
+        // elements (but a single child element can also be passed
+        // without putting it into a list). The `?` is needed to
+        // handle errors, because those method calls can fail, either
+        // when they detect nesting of HTML elements that doesn't
+        // conform to the HTML standard, or when the allocator is
+        // running against the allocation limit that was provided to
+        // `HtmlAllocator::new`.
+        let key_html = html.strong(
+            [att("class", "key")],
+            html.i(
+                [],
+                html.q(
                     [],
-                    html.q(
-                        [],
-                        anchor(
-                            &anchor_name,
-                            autolink.format_html(key_string, &*html)?,
-                            &html,
-                        )?,
+                    anchor(
+                        &anchor_name(key_string),
+                        autolink.format_html(key_string, &html)?,
+                        &html,
                     )?,
                 )?,
             )?,
-        )?)?;
+        )?;
 
-        // Output all the files for that key value, sorted by path.
+        // All the files for that key value, sorted by path.
         let mut sorted_file_infos: Vec<&FileInfo<WithDerivedValues>> =
             file_infos.iter().copied().collect();
         sorted_file_infos.sort_by_key(|fileinfo| fileinfo.path.full_path());
-        let mut dd_body = html.new_vec();
+        let mut files_body = html.new_vec();
         for file_info in sorted_file_infos {
             // Show the path, and link to the actual XML file, but
             // also provide a link to the box with the extracted
             // metainfo further up the page.
+            // With `short_paths`, only show the file name, and the
+            // path in the tooltip instead.
             let rel_path = file_info.path.rel_path();
+            let (link_text, link_title) = if short_paths {
+                let file_name = rel_path.rsplit('/').next().unwrap_or(rel_path);
+                (file_name, format!("{rel_path} (jump to info box)"))
+            } else {
+                (rel_path, "Jump to info box".into())
+            };
             let path_with_two_links_html = html.div(
                 [att("class", "file_link")],
                 [
                     html.a(
                         [
                             att("href", format!("#box-{}", file_info.id)),
-                            att("title", "Jump to info box"),
+                            att("title", link_title),
                         ],
-                        html.text(rel_path)?,
+                        html.text(link_text)?,
                     )?,
                     html.nbsp()?,
                     html.a(
-                        [att("href", rel_path), att("title", "Open the file")],
-                        document_symbol(&html)?,
+                        [
+                            att("href", format!("{file_link_base}{rel_path}")),
+                            att("title", "Open the file"),
+                        ],
+                        symbols.document_html(&html)?,
                     )?,
                 ],
             )?;
 
-            dd_body.push(path_with_two_links_html)?;
+            files_body.push(path_with_two_links_html)?;
+        }
+        Ok((key_html, html.div([att("class", "key_dd")], files_body)?))
+    };
+
+    // The contents of the section as a definition list, i.e. all
+    // key_strings, each followed by the files for the respective
+    // key_string.
+    let definition_list = || -> Result<SerHtmlFrag> {
+        let mut body = html.new_vec();
+        for (key_string, file_infos) in file_infos_by_key_string {
+            let (key_html, files_html) = entry_html(key_string, file_infos)?;
+            body.push(html.dt([att("class", "key_dt")], key_html)?)?;
+            body.push(html.dd([att("class", "key_dd")], files_html)?)?;
+        }
+        html.preserialize(html.dl([att("class", "key_dl")], body)?)
+    };
+
+    let (intro, markdown_intro) = match layout {
+        IndexLayout::Dl => (definition_list()?, None),
+        IndexLayout::Table => {
+            let mut rows = html.new_vec();
+            for (key_string, file_infos) in file_infos_by_key_string {
+                let (key_html, files_html) = entry_html(key_string, file_infos)?;
+                rows.push(html.tr(
+                    [att("class", "key_tr")],
+                    [
+                        html.td([att("class", "key_td")], key_html)?,
+                        html.td([att("class", "key_files_td")], files_html)?,
+                    ],
+                )?)?;
+            }
+            let table = html.table(
+                [att("class", "key_table")],
+                [
+                    html.thead(
+                        [],
+                        html.tr(
+                            [],
+                            [
+                                html.th([], html.text(title)?)?,
+                                html.th([], html.text("Files")?)?,
+                            ],
+                        )?,
+                    )?,
+                    html.tbody([], rows)?,
+                ],
+            )?;
+            (html.preserialize(table)?, Some(definition_list()?))
         }
-        body.push(html.dd(
-            [att("class", "key_dd")],
-            html.div([att("class", "key_dd")], dd_body)?,
+    };
+
+    Ok(Section {
+        highlight: Highlight::None,
+        title: Some(title.into()),
+        intro: Some(intro),
+        markdown_intro,
+        subsections: vec![],
+    })
+}
+
+/// A compact checklist of the files that have warnings, each linking
+/// to its info box, as a subsection for the warnings section.
+fn files_needing_attention_section(warningss: &[FileWarnings]) -> Result<Section> {
+    let html = HTML_ALLOCATOR_POOL.get();
+    let mut items = html.new_vec();
+    for warnings in warningss {
+        let rel_path = warnings.path.rel_path();
+        items.push(html.li(
+            [],
+            html.a(
+                [
+                    att("href", format!("#box-{}", warnings.id)),
+                    att("title", "Jump to info box"),
+                ],
+                html.text(rel_path)?,
+            )?,
         )?)?;
     }
+    Ok(Section {
+        highlight: Highlight::None,
+        title: Some("Files needing attention".into()),
+        intro: Some(html.preserialize(html.ul([], items)?)?),
+        markdown_intro: None,
+        subsections: vec![],
+    })
+}
+
+/// Shown when no XML files were found at all.
+const NO_XML_FILES_NOTE: &str = "no XML files matched; check the base path (`--base-path`) \
+                                 and the ignore rules (`.gitignore`, `--ignore-untracked`)";
 
+/// The section put at the top of the index when no XML files were
+/// found, to explain why the index is empty.
+fn no_files_found_section() -> Result<Section> {
+    let html = HTML_ALLOCATOR_POOL.get();
+    Ok(Section {
+        highlight: Highlight::Orange,
+        title: Some("No XML files found".into()),
+        intro: Some(html.preserialize(html.p([], html.text(NO_XML_FILES_NOTE)?)?)?),
+        markdown_intro: None,
+        subsections: vec![],
+    })
+}
+
+/// The contents of all files in `file_infos`, each in a collapsible
+/// `<details>` block, for `build --standalone`, with the sequences
+/// blinded (see `blind_document`). Files exceeding
+/// `STANDALONE_MAX_FILE_SIZE`, or coming after the total has reached
+/// `STANDALONE_MAX_TOTAL_SIZE`, only get a note instead.
+fn file_contents_section(file_infos: &[FileInfo<WithDerivedValues>]) -> Result<Section> {
+    let html = HTML_ALLOCATOR_POOL.get();
+    let mut items = html.new_vec();
+    let mut total_size: u64 = 0;
+    for file_info in file_infos {
+        let rel_path = file_info.path.rel_path();
+        let full_path = file_info.path.full_path();
+        let size = std::fs::metadata(&full_path)
+            .with_context(|| anyhow!("getting size of file {full_path:?}"))?
+            .len();
+        let body = if size > STANDALONE_MAX_FILE_SIZE {
+            html.p(
+                [],
+                html.text(format!(
+                    "(Not embedded: the file has {size} bytes, \
+                     the limit is {STANDALONE_MAX_FILE_SIZE} bytes.)"
+                ))?,
+            )?
+        } else if total_size + size > STANDALONE_MAX_TOTAL_SIZE {
+            html.p(
+                [],
+                html.text(format!(
+                    "(Not embedded: the limit of {STANDALONE_MAX_TOTAL_SIZE} bytes \
+                     for all embedded files has been reached.)"
+                ))?,
+            )?
+        } else {
+            total_size += size;
+            // Blind the sequences as `prepare` does by default
+            let xmldocument =
+                read_xml_file(&full_path).with_context(|| anyhow!("reading file {full_path:?}"))?;
+            let mut modified_document = ModifiedXMLDocument::new(&xmldocument);
+            let BlindingOpts {
+                no_blind,
+                blind_all,
+                blind_comment,
+                recommended_max_file_size_bytes: _,
+            } = BlindingOpts::default();
+            if !no_blind {
+                blind_document(
+                    &xmldocument,
+                    &mut modified_document,
+                    blind_all,
+                    blind_comment.as_deref(),
+                );
+            }
+            let (content, _content_has_changed) = modified_document.to_string_and_modified()?;
+            html.pre([], html.text(content)?)?
+        };
+        items.push(html.details(
+            [att("id", format!("content-{}", file_info.id))],
+            [
+                html.summary(
+                    [],
+                    [
+                        html.text(rel_path)?,
+                        html.nbsp()?,
+                        html.a(
+                            [
+                                att("href", format!("#box-{}", file_info.id)),
+                                att("title", "Jump to info box"),
+                            ],
+                            html.text("(info box)")?,
+                        )?,
+                    ],
+                )?,
+                body,
+            ],
+        )?)?;
+    }
     Ok(Section {
         highlight: Highlight::None,
-        title: Some(attribute_key.as_ref().into()),
-        intro: Some(html.preserialize(html.dl([att("class", "key_dl")], body)?)?),
+        title: Some("File contents".into()),
+        intro: Some(html.preserialize(html.div([], items)?)?),
+        markdown_intro: None,
         subsections: vec![],
     })
 }
 
+/// For each attribute that is indexed, the paths of the files that
+/// don't show up in its index because they have no value for it
+/// (i.e. the attribute is missing or `NA`). Attributes for which all
+/// files have values are omitted, as are those whose index lists
+/// the files without a value, too (flags, see
+/// `KeyStringPreparation::missing_key_string`).
+fn index_exclusions(file_infos: &[FileInfo<WithDerivedValues>]) -> Vec<(AttributeName, Vec<&str>)> {
+    METADATA_SPECIFICATION
+        .iter()
+        .filter(|spec| {
+            spec.key_string_preparation()
+                .is_some_and(|preparation| preparation.missing_key_string().is_none())
+        })
+        .filter_map(|spec| {
+            let excluded_paths: Vec<&str> = file_infos
+                .iter()
+                .filter(|file_info| {
+                    file_info
+                        .metadata
+                        .get(spec.key)
+                        .map(|value| value.as_string_list().is_empty())
+                        .unwrap_or(true)
+                })
+                .map(|file_info| file_info.path.rel_path())
+                .collect();
+            if excluded_paths.is_empty() {
+                None
+            } else {
+                Some((spec.key, excluded_paths))
+            }
+        })
+        .collect()
+}
+
+/// Print the result of `index_exclusions` as informational notes.
+fn print_index_exclusions<O: Write>(
+    exclusions: &[(AttributeName, Vec<&str>)],
+    out: &mut O,
+) -> Result<()> {
+    for (attribute_key, excluded_paths) in exclusions {
+        pluralized! { excluded_paths.len() => files, are }
+        writeln!(
+            out,
+            "Note: {} {files} {are} not listed in the index for {:?} (no value given):",
+            excluded_paths.len(),
+            attribute_key.as_ref()
+        )?;
+        for path in excluded_paths {
+            writeln!(out, "    {path:?}")?;
+        }
+    }
+    Ok(())
+}
+
 /// Create a `<div>&nbsp;<br>...</div>` occupying some amount of
 /// whitespace; useful at the end of the document to ensure that
 /// document-internal links (e.g. from the table of contents) always
@@ -856,7 +2000,12 @@ fn empty_space_element(number_of_br_elements: usize, html: &HtmlAllocator) -> Re
 
 /// Make an intro, slightly differently depending on whether it is
 /// for the .md or .html file.
-fn make_intro(making_md: bool, html: &HtmlAllocator) -> Result<AId<Node>> {
+fn make_intro(
+    making_md: bool,
+    output_files: &OutputFiles,
+    symbols: &Symbols,
+    html: &HtmlAllocator,
+) -> Result<AId<Node>> {
     html.div(
         [],
         [
@@ -896,11 +2045,12 @@ fn make_intro(making_md: bool, html: &HtmlAllocator) -> Result<AId<Node>> {
                         "From the index, click on a link to jump to the info box \
                          about that file, or on the ",
                     )?,
-                    document_symbol(html)?,
+                    symbols.document_html(html)?,
                     html.text(format!(
                         " symbol to open the XML file directly. From the info box, \
-                         click on the {BACK_TO_INDEX_SYMBOL} symbol to jump to the \
+                         click on the {} symbol to jump to the \
                          index position for that value.",
+                        symbols.back_to_index
                     ))?,
                 ],
             )?,
@@ -923,7 +2073,7 @@ fn make_intro(making_md: bool, html: &HtmlAllocator) -> Result<AId<Node>> {
                              {:?} instead, it has the same info already \
                              formatted as HTML (and in fact has better formatting than \
                              the view you're seeing here).",
-                            HTML_FILE.path_from_repo_top
+                            output_files.html_file.path_from_repo_top
                         ))?,
                     )?,
                 )?
@@ -934,6 +2084,372 @@ fn make_intro(making_md: bool, html: &HtmlAllocator) -> Result<AId<Node>> {
     )
 }
 
+/// The contents for the README.html document, with `content` as the
+/// main part.
+fn make_htmldocument(
+    title: &str,
+    toc_html: Option<&SerHtmlFrag>,
+    output_files: &OutputFiles,
+    symbols: &Symbols,
+    content: AId<Node>,
+    html: &HtmlAllocator,
+) -> Result<AId<Node>> {
+    html.html(
+        [],
+        [
+            html.head(
+                [],
+                [
+                    html.meta(
+                        [
+                            att("name", "generator"),
+                            att("content", &*GENERATED_MESSAGE),
+                        ],
+                        [],
+                    )?,
+                    html.meta(
+                        [att("name", "author"), att("content", &*GENERATED_MESSAGE)],
+                        [],
+                    )?,
+                    html.title([], html.text("Index - XML Hub")?)?,
+                    html.style([], html.text(css_styles())?)?,
+                ],
+            )?,
+            html.body(
+                [],
+                [
+                    html.h1([], html.text(title)?)?,
+                    make_intro(false, output_files, symbols, html)?,
+                    if toc_html.is_some() {
+                        html.h2([], html.text("Contents")?)?
+                    } else {
+                        html.empty_node()?
+                    },
+                    if let Some(toc_html) = toc_html {
+                        html.preserialized(toc_html.clone())?
+                    } else {
+                        html.empty_node()?
+                    },
+                    content,
+                    empty_space_element(40, html)?,
+                ],
+            )?,
+        ],
+    )
+}
+
+/// Print the README.html document with `toplevel_section` as the
+/// main part to `out`. Only the document frame is built in an
+/// `HtmlAllocator` at once, the sections are streamed (see
+/// `Section::print_html_streaming`), to reduce peak memory use.
+fn print_htmldocument_streaming(
+    title: &str,
+    toc_html: Option<&SerHtmlFrag>,
+    output_files: &OutputFiles,
+    toplevel_section: &Section,
+    highlight_colors: &HighlightColors,
+    symbols: &Symbols,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut document = Vec::new();
+    {
+        let html = HTML_ALLOCATOR_POOL.get();
+        let content = html.div([], html.text(STREAMING_PLACEHOLDER)?)?;
+        html.print_html_document(
+            make_htmldocument(title, toc_html, output_files, symbols, content, &html)?,
+            &mut document,
+        )?;
+    }
+    let (document_start, document_end) = split_at_placeholder(&document)?;
+    out.write_all(document_start)?;
+    toplevel_section.print_html_streaming(NumberPath::empty(), highlight_colors, out)?;
+    out.write_all(document_end)?;
+    Ok(())
+}
+
+/// How to group the file info boxes, as given via `build
+/// --group-by`.
+#[derive(Debug, Clone, Copy)]
+enum BoxGrouping {
+    /// In sections reflecting the folder hierarchy
+    Folder,
+    /// In sections by the (first) value of the attribute
+    Attribute(&'static AttributeSpecification),
+}
+
+impl BoxGrouping {
+    /// Parse the value given to `--group-by`.
+    fn from_arg(arg: &str) -> Result<Self> {
+        if arg == "folder" {
+            Ok(BoxGrouping::Folder)
+        } else if let Some(name) = arg.strip_prefix("attribute:") {
+            let name = name.trim();
+            METADATA_SPECIFICATION
+                .iter()
+                .find(|spec| spec.key.as_ref().eq_ignore_ascii_case(name))
+                .map(BoxGrouping::Attribute)
+                .ok_or_else(|| anyhow!("invalid --group-by: unknown attribute name {name:?}"))
+        } else {
+            bail!("invalid --group-by value {arg:?}, expecting `folder` or `attribute:KEY`")
+        }
+    }
+}
+
+/// Parse the value given to `--count-by`: the name of an indexed
+/// attribute, case-insensitively.
+fn count_by_attribute(name: &str) -> Result<&'static AttributeSpecification> {
+    let name = name.trim();
+    let spec = METADATA_SPECIFICATION
+        .iter()
+        .find(|spec| spec.key.as_ref().eq_ignore_ascii_case(name))
+        .ok_or_else(|| anyhow!("invalid --count-by: unknown attribute name {name:?}"))?;
+    if spec.key_string_preparation().is_none() {
+        bail!(
+            "invalid --count-by: attribute {:?} is not indexed, expecting one of {}",
+            spec.key.as_ref(),
+            METADATA_SPECIFICATION
+                .iter()
+                .filter(|spec| spec.key_string_preparation().is_some())
+                .map(|spec| spec.key.as_ref())
+                .join(", ")
+        )
+    }
+    Ok(spec)
+}
+
+/// How to choose the files for `build --sample`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum SampleMode {
+    /// The files with the most recent commits
+    Newest,
+    /// The biggest files
+    Largest,
+    /// The first files when sorted by path
+    First,
+}
+
+/// The parsed `--sample MODE N` option.
+#[derive(Debug, Clone)]
+struct Sample {
+    mode: SampleMode,
+    count: usize,
+}
+
+impl Sample {
+    /// Parse the two values given to `--sample`.
+    fn from_args(args: &[String]) -> Result<Self> {
+        let [mode, count] = args else {
+            bail!("--sample needs exactly 2 values, MODE and N, got {args:?}")
+        };
+        let mode = <SampleMode as clap::ValueEnum>::from_str(mode, true).map_err(|_| {
+            anyhow!("invalid --sample MODE {mode:?}, expecting one of: newest, largest, first")
+        })?;
+        let count: usize = count
+            .parse()
+            .with_context(|| anyhow!("invalid --sample N {count:?}"))?;
+        if count == 0 {
+            bail!("--sample N must be at least 1")
+        }
+        Ok(Sample { mode, count })
+    }
+
+    /// Reduce `paths` (sorted by path) to at most `count` entries
+    /// according to `mode`, returning them sorted by path again.
+    fn select(
+        &self,
+        mut paths: Vec<BaseAndRelPath>,
+        git_working_dir: &GitWorkingDir,
+    ) -> Result<Vec<BaseAndRelPath>> {
+        let Sample { mode, count } = self;
+        match mode {
+            SampleMode::First => (),
+            SampleMode::Largest => {
+                let mut with_sizes = paths
+                    .into_iter()
+                    .map(|path| -> Result<_> {
+                        let full_path = path.full_path();
+                        let size = std::fs::metadata(&full_path)
+                            .with_context(|| anyhow!("getting size of file {full_path:?}"))?
+                            .len();
+                        Ok((size, path))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                // Stable sort, files of the same size stay in path order
+                with_sizes.sort_by_key(|b| std::cmp::Reverse(b.0));
+                paths = with_sizes.into_iter().map(|(_, path)| path).collect();
+            }
+            SampleMode::Newest => {
+                let commit_times = last_commit_times(git_working_dir)?;
+                // Uncommitted files are the newest of all
+                paths.sort_by_key(|path| {
+                    std::cmp::Reverse(
+                        commit_times
+                            .get(path.rel_path())
+                            .copied()
+                            .unwrap_or(u64::MAX),
+                    )
+                });
+            }
+        }
+        paths.truncate(*count);
+        paths.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+        Ok(paths)
+    }
+
+    /// Text for labelling the output.
+    fn description(&self, number_selected: usize, number_of_files: usize) -> String {
+        let Sample { mode, count: _ } = self;
+        let mode = match mode {
+            SampleMode::Newest => "newest",
+            SampleMode::Largest => "largest",
+            SampleMode::First => "first",
+        };
+        format!("sample: the {mode} {number_selected} of {number_of_files} files")
+    }
+}
+
+/// The time (as unix seconds) of the last commit that touched each
+/// path in the history of HEAD. Paths are relative to the top of the
+/// repository.
+fn last_commit_times(git_working_dir: &GitWorkingDir) -> Result<HashMap<String, u64>> {
+    let stdout =
+        git_working_dir.git_stdout(&["log", "-z", "--format=format:@%ct", "--name-only"])?;
+    let stdout = String::from_utf8(stdout).context("decoding git log output")?;
+    let mut commit_times = HashMap::new();
+    let mut current_time: Option<u64> = None;
+    // Each entry is either a path, empty (end of a commit), or the
+    // commit time followed by a newline and the first path.
+    for entry in stdout.split('\0') {
+        let path = if let Some(rest) = entry.strip_prefix('@') {
+            let (time, path) = rest.split_once('\n').unwrap_or((rest, ""));
+            current_time = Some(
+                time.parse()
+                    .with_context(|| anyhow!("parsing commit time {time:?} from git log"))?,
+            );
+            path
+        } else {
+            entry
+        };
+        if !path.is_empty() {
+            if let Some(time) = current_time {
+                // The log is newest first, so keep the first time seen
+                commit_times.entry(path.to_string()).or_insert(time);
+            }
+        }
+    }
+    Ok(commit_times)
+}
+
+/// The numbers printed by `--count-only`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileCounts {
+    files: usize,
+    /// Number of files with errors
+    errors: usize,
+    /// Number of files with warnings
+    warnings: usize,
+}
+
+impl FileCounts {
+    fn new(file_infos: &[FileInfo<WithDerivedValues>], file_errorss: &[FileErrors]) -> Self {
+        Self {
+            files: file_infos.len() + file_errorss.len(),
+            errors: file_errorss.len(),
+            warnings: file_infos
+                .iter()
+                .filter(|info| !info.warnings.is_empty())
+                .count(),
+        }
+    }
+
+    /// The exit code for `--count-only`.
+    fn exit_code(&self) -> i32 {
+        if self.errors > 0 {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+impl Display for FileCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            files,
+            errors,
+            warnings,
+        } = self;
+        write!(f, "files={files} errors={errors} warnings={warnings}")
+    }
+}
+
+/// How many times `--batch` mode tries `git remote update`.
+const REMOTE_UPDATE_ATTEMPTS: u32 = 3;
+
+/// How long to wait between attempts of `git remote update`.
+const REMOTE_UPDATE_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The start of the subject of the commits of the index files made
+/// by `build`.
+const INDEX_COMMIT_SUBJECT_PREFIX: &str = "regenerate index ";
+
+/// The commits (as `git log --oneline` lines) in HEAD that are not
+/// in `reference`, except for those of the index files made by
+/// `build` (which are regenerated anyway, e.g. after their push
+/// failed).
+fn local_commits_not_in(git_working_dir: &GitWorkingDir, reference: &str) -> Result<Vec<String>> {
+    let range = format!("{reference}..HEAD");
+    let out = git_working_dir.git_stdout_string_trimmed(&["log", "--format=%h %s", &range])?;
+    Ok(out
+        .lines()
+        .filter(|line| {
+            let subject = line.split_once(' ').map_or("", |(_, subject)| subject);
+            !subject.starts_with(INDEX_COMMIT_SUBJECT_PREFIX)
+        })
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+/// The outcome of a `build_index` run, for `--summary-line`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BuildSummary {
+    /// Number of XML files processed
+    files: usize,
+    /// Number of files with errors
+    errors: usize,
+    /// Number of files with warnings
+    warnings: usize,
+    committed: bool,
+    pushed: bool,
+    /// Whether any of the output files differ from the committed
+    /// version after writing them
+    changed: bool,
+    /// The line for `--since-last-build`, if requested
+    since_last_build: Option<String>,
+}
+
+impl Display for BuildSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            files,
+            errors,
+            warnings,
+            committed,
+            pushed,
+            changed,
+            since_last_build: _,
+        } = self;
+        write!(
+            f,
+            "files={files} errors={errors} warnings={warnings} \
+             committed={} pushed={} changed={}",
+            u8::from(*committed),
+            u8::from(*pushed),
+            u8::from(*changed)
+        )
+    }
+}
+
 /// The subset of the options of `BuildOpts` used by `build_index`
 struct BuildIndexOpts {
     dryness: DrynessOpt,
@@ -943,24 +2459,463 @@ struct BuildIndexOpts {
     pull: bool,
     batch: bool,
     ignore_untracked: bool,
+    follow_symlinks: bool,
+    print_paths: bool,
+    count_only: bool,
+    /// Print a tally of the files per value of this attribute with
+    /// `count_only`
+    count_by: Option<&'static AttributeSpecification>,
+    /// The maximum directory depth for the filesystem scan
+    scan_max_depth: Option<usize>,
+    refuse_on_divergence: bool,
+    strict_duplicate_paths: bool,
+    standalone: Option<PathBuf>,
+    explain: bool,
+    output_dir: Option<PathBuf>,
+    link_base: Option<String>,
+    from_ref: Option<String>,
+    attribute_rules: AttributeRules,
     write_errors: bool,
     silent_on_written_errors: bool,
     ok_on_written_errors: bool,
     open_if_changed: bool,
     no_commit: bool,
     no_commit_errors: bool,
+    no_push_on_error: bool,
     no_branch_check: bool,
     open: bool,
+    explain_index: bool,
+    short_paths: bool,
+    output_files: OutputFiles,
+    /// Which files are indexed if not as configured (`--extensions`)
+    extensions: Option<XmlExtensions>,
+    sample: Option<Sample>,
+    version_notes: bool,
+    signoff: bool,
+    /// The `-S` argument for `git commit` (`--sign-commits`), if any
+    gpg_sign_arg: Option<String>,
+    /// `Co-authored-by` etc. trailer lines for the commit message
+    commit_trailers: Vec<String>,
+    /// Whether to indent the HTML index file (`--pretty-html`)
+    pretty_html: bool,
+    /// Whether to leave out the "Index by attribute" section
+    no_index_section: bool,
+    /// Whether to print a summary of the changes to the output
+    /// files (`--diff-output`)
+    diff_output: bool,
+    /// How to group the file info boxes (`--group-by`)
+    group_by: BoxGrouping,
+    /// The maximum size of each index file, `None` for no limit
+    max_output_size: Option<u64>,
+    /// Whether to leave out the table of contents (`--no-toc`)
+    no_toc: bool,
+    /// Whether to show links to the subsections at the top of each
+    /// section (`--mini-toc`)
+    mini_toc: bool,
+    /// Whether to also write the table of contents as a fragment
+    /// (`--toc-fragment`)
+    toc_fragment: bool,
+    /// How to lay out the index for each attribute in the HTML file
+    /// (`--index-layout`)
+    index_layout: IndexLayout,
+    /// Where to keep the state for `--since-last-build`, if given
+    since_last_build_state: Option<PathBuf>,
+    /// Where to write the files to; None means into the Git working
+    /// directory, which is also the only case where the files are
+    /// committed and pushed.
+    output_sink: Option<Arc<dyn OutputSink>>,
+    /// Receives progress events (`NoProgress` to ignore them).
+    progress: Arc<dyn BuildProgress>,
+}
+
+/// The state and options that decide what `build_index` does with
+/// the index files once they are generated.
+#[derive(Debug, Clone, Copy)]
+struct BuildDecisionInputs {
+    have_errors: bool,
+    write_errors: bool,
+    silent_on_written_errors: bool,
+    ok_on_written_errors: bool,
+    /// Writing to an `OutputSink` or to a `--standalone` file
+    /// instead of the Git working directory
+    not_in_working_dir: bool,
+    have_written_files: bool,
+    no_commit: bool,
+    /// Whether `--sample` was given (a partial index is never
+    /// committed)
+    sample: bool,
+    no_commit_errors: bool,
+    /// Whether `--push` was given
+    push: bool,
+    no_push_on_error: bool,
+}
+
+/// What `build_index` does with the index files, together with the
+/// reasons (shown by `--explain`).
+#[derive(Debug)]
+struct BuildDecision {
+    exit_code: i32,
+    write_errors_to_stderr: bool,
+    write_files: bool,
+    commit_files: bool,
+    /// Push if the commit happened (i.e. there were changes)
+    push: bool,
+    reasons: Vec<String>,
+}
+
+impl BuildDecision {
+    /// The reasons followed by the resulting decisions, one per line.
+    fn explanation_lines(&self) -> Vec<String> {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        let mut lines = self.reasons.clone();
+        lines.push(format!("=> write files: {}", yes_no(self.write_files)));
+        lines.push(format!("=> commit: {}", yes_no(self.commit_files)));
+        lines.push(format!("=> push: {}", yes_no(self.push)));
+        lines.push(format!("=> exit code: {}", self.exit_code));
+        lines
+    }
+}
+
+/// Derive what to do with the index files from `inputs`. The
+/// behaviour in the face of errors depends on 3 command line
+/// options; the derivation of the 3 resulting behaviours is not 1:1.
+fn build_decision(inputs: BuildDecisionInputs) -> BuildDecision {
+    let BuildDecisionInputs {
+        have_errors,
+        write_errors,
+        silent_on_written_errors,
+        ok_on_written_errors,
+        not_in_working_dir,
+        have_written_files,
+        no_commit,
+        sample,
+        no_commit_errors,
+        push,
+        no_push_on_error,
+    } = inputs;
+    let mut reasons = Vec::new();
+
+    // The Rust compiler verifies that each of the 3 variables is set
+    // exactly once.
+    let exit_code;
+    let write_errors_to_stderr;
+    let write_files;
+    if !have_errors {
+        reasons.push("There are no errors, thus the files are written.".into());
+        exit_code = 0;
+        write_errors_to_stderr = false;
+        write_files = true;
+    } else {
+        if write_errors {
+            reasons.push(
+                "There are errors, but --write-errors was given, thus the files \
+                 (including the errors) are written."
+                    .into(),
+            );
+            write_files = true;
+            if silent_on_written_errors {
+                reasons.push(
+                    "--silent-on-written-errors was given, thus the errors are not \
+                     shown on stderr and the exit code is 0."
+                        .into(),
+                );
+                exit_code = 0;
+                write_errors_to_stderr = false;
+            } else {
+                write_errors_to_stderr = true;
+                if ok_on_written_errors {
+                    reasons
+                        .push("--ok-on-written-errors was given, thus the exit code is 0.".into());
+                    exit_code = 0;
+                } else {
+                    reasons.push("The errors lead to exit code 1.".into());
+                    exit_code = 1;
+                }
+            }
+        } else {
+            reasons.push(
+                "There are errors and --write-errors was not given, thus the files \
+                 are not written and the exit code is 1."
+                    .into(),
+            );
+            exit_code = 1;
+            write_errors_to_stderr = true;
+            write_files = false;
+        }
+    }
+
+    // Commit files if they were written into the working directory,
+    // and not prevented by --no-commit, and any were written, and
+    // --no-commit-errors was not given or there were no errors.
+    let commit_files = if !write_files {
+        false
+    } else if not_in_working_dir {
+        reasons.push(
+            "The files are not written to the Git working directory, thus they \
+             are not committed."
+                .into(),
+        );
+        false
+    } else if no_commit {
+        reasons.push("--no-commit was given, thus the files are not committed.".into());
+        false
+    } else if sample {
+        reasons
+            .push("--sample was given, thus the files (a partial index) are not committed.".into());
+        false
+    } else if !have_written_files {
+        reasons.push("There are no files to write, thus nothing is committed.".into());
+        false
+    } else if have_errors && no_commit_errors {
+        reasons.push(
+            "There are errors and --no-commit-errors was given, thus the files \
+             are not committed."
+                .into(),
+        );
+        false
+    } else {
+        reasons.push(
+            "The files are committed if they changed (given the current branch is \
+             the expected one and there are no other uncommitted changes)."
+                .into(),
+        );
+        true
+    };
+
+    let push = if !commit_files {
+        false
+    } else if !push {
+        reasons.push("--push was not given, thus the commit is not pushed.".into());
+        false
+    } else if have_errors && no_push_on_error {
+        reasons.push(
+            "There are errors and --no-push-on-error was given, thus the commit \
+             is not pushed."
+                .into(),
+        );
+        false
+    } else {
+        reasons.push("--push was given, thus the commit (if any) is pushed.".into());
+        true
+    };
+
+    BuildDecision {
+        exit_code,
+        write_errors_to_stderr,
+        write_files,
+        commit_files,
+        push,
+        reasons,
+    }
+}
+
+/// The uncommitted changes in the working directory that prevent
+/// committing the index files, ignoring the index files themselves
+/// (`written_files`).
+fn uncommitted_changes(
+    xmlhub_checkout: &CheckedCheckoutContext1<Cow<Path>>,
+    written_files: &[&str],
+    batch: bool,
+) -> Result<Vec<String>> {
+    let items = xmlhub_checkout.git_working_dir().git_status()?;
+    let daemon_folder_name_with_slash = format!("{}/", *DAEMON_FOLDER_NAME);
+    let ignore_path = |path: &str| -> bool {
+        written_files.contains(&path)
+            // An untracked directory (e.g. from `--output-dir`)
+            // holding written files is shown as a whole
+            || (path.ends_with('/') && written_files.iter().any(|file| file.starts_with(path)))
+    };
+    Ok(items
+        .iter()
+        .filter(|item| {
+            // Ignore untracked files in batch mode (they will be
+            // killed by reset --hard if in the way, and if not, then
+            // they could not have been created (XX unless there is a
+            // bug in the app, though, actually))
+            !(ignore_path(item.path.as_str())
+                || (batch && item.is_untracked(false))
+                // The untracked lock, log and state files of the
+                // daemon folder; Git lists them individually if
+                // anything in the folder is tracked
+                || (item.is_untracked(false)
+                    && item.path.starts_with(&daemon_folder_name_with_slash)))
+        })
+        .map(|item| item.to_string())
+        .collect())
+}
+
+/// How an output file differs from the committed version, for
+/// `--diff-output`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OutputFileChange {
+    path: String,
+    /// Number of added and removed lines; None for binary files
+    lines: Option<(usize, usize)>,
+    /// Whether the file is not tracked by Git yet
+    new: bool,
+}
+
+/// The differences of the files at `paths` in the working directory
+/// to the committed version (`HEAD`), as reported by `git diff
+/// --numstat`, plus the untracked ones.
+fn output_file_changes(
+    git_working_dir: &GitWorkingDir,
+    paths: &[&str],
+) -> Result<Vec<OutputFileChange>> {
+    let mut changes = Vec::new();
+    let untracked = {
+        let mut arguments = vec!["ls-files", "--others", "-z", "--"];
+        arguments.extend(paths);
+        git_working_dir.git_stdout(&arguments)?
+    };
+    for path in untracked
+        .split(|b| *b == b'\0')
+        .filter(|path| !path.is_empty())
+    {
+        let path = std::str::from_utf8(path).context("decoding git ls-files output")?;
+        let full_path = git_working_dir.working_dir_path.join(path);
+        let contents =
+            std::fs::read(&full_path).with_context(|| anyhow!("reading file {full_path:?}"))?;
+        let num_lines = contents
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .count();
+        changes.push(OutputFileChange {
+            path: path.into(),
+            lines: Some((num_lines, 0)),
+            new: true,
+        });
+    }
+    if git_working_dir.git(&["rev-parse", "--verify", "--quiet", "HEAD"], true)? {
+        let mut arguments = vec!["diff", "--numstat", "-z", "HEAD", "--"];
+        arguments.extend(paths);
+        let stdout = git_working_dir.git_stdout(&arguments)?;
+        for record in stdout
+            .split(|b| *b == b'\0')
+            .filter(|record| !record.is_empty())
+        {
+            let record = std::str::from_utf8(record).context("decoding git diff output")?;
+            let mut fields = record.splitn(3, '\t');
+            let (Some(added), Some(removed), Some(path)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                bail!("unexpected `git diff --numstat` output {record:?}")
+            };
+            let lines = match (added.parse(), removed.parse()) {
+                (Ok(added), Ok(removed)) => Some((added, removed)),
+                // "-" for binary files
+                _ => None,
+            };
+            changes.push(OutputFileChange {
+                path: path.into(),
+                lines,
+                new: false,
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+/// The summary printed by `--diff-output`.
+fn output_file_changes_summary(changes: &[OutputFileChange]) -> String {
+    if changes.is_empty() {
+        return "Output files: no changes\n".into();
+    }
+    let (total_added, total_removed) = changes
+        .iter()
+        .filter_map(|change| change.lines)
+        .fold((0, 0), |(a, r), (added, removed)| (a + added, r + removed));
+    let num_files = changes.len();
+    pluralized! { num_files => files }
+    let mut summary = format!(
+        "Output files: {num_files} {files} changed, +{total_added} -{total_removed} lines\n"
+    );
+    for OutputFileChange { path, lines, new } in changes {
+        let lines = match lines {
+            Some((added, removed)) => format!("+{added} -{removed}"),
+            None => "binary".into(),
+        };
+        let new = if *new { " (new)" } else { "" };
+        summary.push_str(&format!("  {path}: {lines}{new}\n"));
+    }
+    summary
+}
+
+/// The `Co-authored-by` trailer line for `co_author`, which must be
+/// of the form `Name <email>`.
+fn co_author_trailer(co_author: &str) -> Result<String> {
+    let co_author = co_author.trim();
+    let is_valid = co_author
+        .strip_suffix('>')
+        .and_then(|s| s.split_once(" <"))
+        .is_some_and(|(name, email)| {
+            !name.trim().is_empty() && email.contains('@') && !email.contains(['<', '>'])
+        });
+    if !is_valid {
+        bail!("co-author must be given as `Name <email>`, got {co_author:?}")
+    }
+    Ok(format!("Co-authored-by: {co_author}"))
+}
+
+/// The argument for `git commit` to sign the commit, if
+/// `sign_commits` is true: `-S`, or `-S<key>` with a `signing_key`.
+fn gpg_sign_arg(sign_commits: bool, signing_key: Option<&str>) -> Option<String> {
+    sign_commits.then(|| format!("-S{}", signing_key.unwrap_or("")))
+}
+
+/// Append the `trailers` to the commit `message`, separated by an
+/// empty line as Git expects.
+fn commit_message_with_trailers(message: String, trailers: &[String]) -> String {
+    if trailers.is_empty() {
+        message
+    } else {
+        format!("{message}\n\n{}", trailers.join("\n"))
+    }
 }
 
 /// Run one conversion from the XML files to the index files. Returns
-/// the exit code to exit the program with.
+/// the exit code to exit the program with, and a summary of what
+/// happened.
 fn build_index(
     build_index_opts: BuildIndexOpts,
     git_log_version_checker: &XmlhubCheckVersion,
     xmlhub_checkout: &CheckedCheckoutContext1<Cow<Path>>,
     maybe_checked_xmlhub_checkout: &Option<CheckedCheckoutContext2<Cow<Path>>>,
-) -> Result<i32> {
+) -> Result<(i32, BuildSummary)> {
+    let since_last_build_state = build_index_opts.since_last_build_state.clone();
+    let (exit_code, build_state, mut summary) = build_index_inner(
+        build_index_opts,
+        git_log_version_checker,
+        xmlhub_checkout,
+        maybe_checked_xmlhub_checkout,
+    )?;
+    // Compare with the previous build only now, as the build can end
+    // in many places, and the line is to include whether a commit
+    // and push happened
+    if let (Some(state_path), Some(build_state)) = (&since_last_build_state, build_state) {
+        let previous_state = BuildState::read(state_path)?.unwrap_or_default();
+        let delta = BuildDelta::between(&previous_state, &build_state);
+        summary.since_last_build = Some(format!(
+            "since last build: {delta} committed={} pushed={}",
+            u8::from(summary.committed),
+            u8::from(summary.pushed)
+        ));
+        build_state.write(state_path)?;
+    }
+    Ok((exit_code, summary))
+}
+
+/// The work of `build_index` except for the `--since-last-build`
+/// comparison; also returns the state of this build for that if it
+/// was requested (`None` if the build ended before reading the
+/// files).
+fn build_index_inner(
+    build_index_opts: BuildIndexOpts,
+    git_log_version_checker: &XmlhubCheckVersion,
+    xmlhub_checkout: &CheckedCheckoutContext1<Cow<Path>>,
+    maybe_checked_xmlhub_checkout: &Option<CheckedCheckoutContext2<Cow<Path>>>,
+) -> Result<(i32, Option<BuildState>, BuildSummary)> {
     let BuildIndexOpts {
         dryness: DrynessOpt { dry_run },
         verbosity: VerbosityOpt { verbose },
@@ -968,14 +2923,49 @@ fn build_index(
         pull,
         batch,
         ignore_untracked,
+        follow_symlinks,
+        print_paths,
+        count_only,
+        count_by,
+        scan_max_depth,
+        refuse_on_divergence,
+        strict_duplicate_paths,
+        standalone,
+        explain,
+        output_dir,
+        link_base,
+        from_ref,
+        attribute_rules,
         write_errors,
         silent_on_written_errors,
         ok_on_written_errors,
         open_if_changed,
         no_commit,
         no_commit_errors,
+        no_push_on_error,
         no_branch_check,
         open,
+        explain_index,
+        short_paths,
+        output_files,
+        extensions,
+        sample,
+        version_notes,
+        signoff,
+        gpg_sign_arg,
+        commit_trailers,
+        pretty_html,
+        no_index_section,
+        diff_output,
+        group_by,
+        max_output_size,
+        no_toc,
+        mini_toc,
+        toc_fragment,
+        index_layout,
+        since_last_build_state,
+        output_sink,
+        progress,
     } = build_index_opts;
 
     // Define a macro to only run $body if opts.dry_run is false,
@@ -995,8 +2985,66 @@ fn build_index(
         }
     }
 
-    // Update repository if requested
-    if let Some(checked_xmlhub_checkout) = maybe_checked_xmlhub_checkout {
+    // With `--output-dir`, place the files into that directory;
+    // only if it is inside the repository are they still committed
+    let mut output_files = output_files;
+    let mut output_sink = output_sink;
+    let mut file_link_base = String::new();
+    if let Some(output_dir) = &output_dir {
+        std::fs::create_dir_all(output_dir)
+            .with_context(|| anyhow!("creating output directory {output_dir:?}"))?;
+        let output_dir = output_dir
+            .canonicalize()
+            .with_context(|| anyhow!("resolving output directory {output_dir:?}"))?;
+        let repo_top = xmlhub_checkout
+            .working_dir_path()
+            .canonicalize()
+            .with_context(|| anyhow!("resolving {:?}", xmlhub_checkout.working_dir_path()))?;
+        let to_repo_top = relative_path(&output_dir, &repo_top);
+        let to_repo_top = to_repo_top
+            .to_str()
+            .ok_or_else(|| anyhow!("path is not valid UTF-8: {to_repo_top:?}"))?;
+        if !to_repo_top.is_empty() {
+            file_link_base = format!("{to_repo_top}/");
+        }
+        if let Ok(subdir) = output_dir.strip_prefix(&repo_top) {
+            if let Some(subdir) = subdir.to_str().filter(|s| !s.is_empty()) {
+                output_files = output_files.in_subdir(subdir);
+            }
+        } else {
+            output_sink = Some(Arc::new(FilesystemSink {
+                base_path: output_dir,
+            }));
+        }
+    }
+    if let Some(link_base) = link_base {
+        file_link_base = link_base;
+    }
+
+    // With `--from-ref`, read the files as exported from that
+    // tree-ish into a temporary directory, which also receives the
+    // index files
+    // (deleted again at the end unless the index files are
+    // written there for the user to look at)
+    let keep_from_ref_dir = output_sink.is_none();
+    let mut from_ref_dir = if let Some(tree_ish) = &from_ref {
+        let dir = export_tree(&xmlhub_checkout.git_working_dir(), tree_ish)?;
+        if output_sink.is_none() {
+            output_sink = Some(Arc::new(FilesystemSink {
+                base_path: dir.to_path_buf(),
+            }));
+        }
+        Some(dir)
+    } else {
+        None
+    };
+
+    // Update repository if requested (but `--explain` and
+    // `--from-ref` do not carry out any Git actions)
+    if let Some(checked_xmlhub_checkout) = maybe_checked_xmlhub_checkout
+        .as_ref()
+        .filter(|_| !explain && from_ref.is_none())
+    {
         if pull {
             check_dry_run! {
                 message: "git pull",
@@ -1012,17 +3060,57 @@ fn build_index(
 
             check_dry_run! {
                 message: format!("git remote update {default_remote:?}"),
-                if !xmlhub_checkout.git_working_dir().git(
-
-                    &["remote", "update", default_remote],
-                    quietness.quiet()
-                )? {
-                    bail!("git remote update {default_remote:?} failed")
+                {
+                    // Retry a few times, since the network or the
+                    // remote may be temporarily unavailable
+                    let mut attempt = 1;
+                    while !xmlhub_checkout.git_working_dir().git(
+                        &["remote", "update", default_remote],
+                        quietness.quiet()
+                    )? {
+                        if attempt >= REMOTE_UPDATE_ATTEMPTS {
+                            bail!(
+                                "git remote update {default_remote:?} failed \
+                                 ({attempt} attempts)"
+                            )
+                        }
+                        eprintln!(
+                            "git remote update {default_remote:?} failed, retrying in \
+                             {REMOTE_UPDATE_RETRY_DELAY:?}"
+                        );
+                        std::thread::sleep(REMOTE_UPDATE_RETRY_DELAY);
+                        attempt += 1;
+                    }
                 }
             }
 
             let remote_banch_reference = checked_xmlhub_checkout.remote_branch_reference();
 
+            // Check for local commits that the reset would discard
+            let mut discarded_commits = Vec::new();
+            check_dry_run! {
+                message: format!("git log --oneline {remote_banch_reference:?}..HEAD"),
+                discarded_commits = local_commits_not_in(
+                    &xmlhub_checkout.git_working_dir(),
+                    &remote_banch_reference
+                )?
+            }
+            if !discarded_commits.is_empty() {
+                let commits = discarded_commits.join("\n    ");
+                if refuse_on_divergence {
+                    bail!(
+                        "the local branch has commits that are not in \
+                         {remote_banch_reference:?}, refusing to discard them \
+                         (--refuse-on-divergence):\n    {commits}"
+                    )
+                }
+                eprintln!(
+                    "\n*** WARNING: the local branch has diverged from \
+                     {remote_banch_reference:?}, discarding these local commits via \
+                     `git reset --hard`:\n    {commits}\n"
+                );
+            }
+
             check_dry_run! {
                 message: format!("git reset --hard {remote_banch_reference:?}"),
                 if !xmlhub_checkout.git_working_dir().git(
@@ -1034,171 +3122,295 @@ fn build_index(
                 }
             }
         }
+
+        // Also get the versions recorded by other clones via
+        // `--version-notes`, for the version check
+        if pull || batch {
+            let default_remote = &checked_xmlhub_checkout.default_remote;
+            check_dry_run! {
+                message: format!("git fetch {default_remote:?} {VERSION_NOTES_REF:?} (and merge)"),
+                git_log_version_checker.fetch_version_notes(
+                    VERSION_NOTES_REF,
+                    default_remote,
+                    quietness.quiet()
+                )?
+            }
+        }
+    }
+
+    // The configuration of the repository (at the same place as
+    // the XML files)
+    let mut config = XmlhubConfig::read(
+        from_ref_dir
+            .as_deref()
+            .unwrap_or_else(|| xmlhub_checkout.working_dir_path()),
+    )?;
+    if let Some(extensions) = extensions {
+        config.extensions = extensions;
     }
+    let attribute_rules = attribute_rules.with_config(&config);
 
     // Get the list of files in the Git repo given by the base_path
-    // option. Collect them as a vector of `RelPathWithBase` values,
-    // each of which carries both a path to a base directory
-    // (optional) and a relative path from there (if it contains no
-    // base directory, the current working directoy is the base).
-    let paths: Vec<BaseAndRelPath> = {
+    // option.
+    let XmlFilePaths {
+        paths,
+        skipped_directories,
+        skipped_symlinks,
+    } = {
         git_log_version_checker.check_git_log()?;
-
-        // Get the paths from running `git ls-files` inside the
-        // directory at base_path, then ignore all files that don't
-        // end in .xml
-        let mut paths = if ignore_untracked {
-            // Ask Git for the list of files
-            xmlhub_checkout.git_working_dir().git_ls_files()?
+        if let Some(dir) = &from_ref_dir {
+            // Not a Git working directory, thus list the files
+            // from the filesystem
+            xml_file_paths_with_max_depth(
+                &GitWorkingDir::from(dir.to_path_buf()),
+                false,
+                follow_symlinks,
+                scan_max_depth,
+                &config.extensions,
+            )?
         } else {
-            // Ask the filesystem for the list of files, but do not
-            // waste time listing paths in the .git nor .xmlhub
-            // subdirs
-            let ignored_file_names = HashSet::from([".git", &*DAEMON_FOLDER_NAME]);
-            let entries = WalkDir::new(xmlhub_checkout.working_dir_path())
-                .follow_links(false)
-                .min_depth(1)
-                .into_iter()
-                .filter_entry(|entry| {
-                    if let Some(file_name) = entry.file_name().to_str() {
-                        !ignored_file_names.contains(file_name)
-                    } else {
-                        // invalid encoding; XX: what to do? Try to keep those:
-                        true
-                    }
-                });
-            let shared_base_path = Arc::new(xmlhub_checkout.working_dir_path().to_owned());
-            let mut paths: Vec<BaseAndRelPath> = Vec::new();
-            for entry in entries {
-                let entry = entry.with_context(|| {
-                    anyhow!(
-                        "listing contents of directory {:?}",
-                        xmlhub_checkout.working_dir_path()
-                    )
-                })?;
-                let relative_path = entry
-                    .path()
-                    .strip_prefix(xmlhub_checkout.working_dir_path())
-                    .with_context(|| {
-                        // Could happen via folder rename races, right? So don't panic.
-                        anyhow!(
-                            "listed files of directory {:?} \
-                             should be prefixed with that path, but got {:?}",
-                            xmlhub_checkout.working_dir_path(),
-                            entry.path()
-                        )
-                    })?;
-                paths.push(BaseAndRelPath::new(
-                    Some(Arc::clone(&shared_base_path)),
-                    relative_path.to_owned(),
-                ));
-            }
-            paths
-        };
-        paths.retain(|path| {
-            if let Some(ext) = path.extension() {
-                ext.eq_ignore_ascii_case("xml")
-            } else {
-                false
-            }
-        });
-        // Sort entries ourselves out of a worry that git ls-files
-        // might not guarantee a sort order. (The sort order
-        // determines the ID assignment that happens later, and those
-        // are used in the HTML output, hence would lead to useless
-        // commits.)
-        paths.sort_by(|a, b| a.rel_path().cmp(b.rel_path()));
-        // Move `paths` to the variable with the same name in the
-        // outer scope.
-        paths
+            xml_file_paths_with_max_depth(
+                &xmlhub_checkout.git_working_dir(),
+                ignore_untracked,
+                follow_symlinks,
+                scan_max_depth,
+                &config.extensions,
+            )?
+        }
     };
+    if let Some(max_depth) = scan_max_depth {
+        if !skipped_directories.is_empty() {
+            eprintln!(
+                "NOTE: {}",
+                skipped_directories_note(max_depth, &skipped_directories)
+            );
+        }
+    }
+    if !skipped_symlinks.is_empty() {
+        eprintln!("NOTE: {}", skipped_symlinks_note(&skipped_symlinks));
+    }
 
-    // See help text on `read_file_infos` for what it's doing.
-    let fileinfo_or_errors: Vec<Result<FileInfo<WithExtractedValues>, FileErrors>> =
-        read_file_infos(paths);
+    // An empty index would otherwise not say why it is empty
+    let no_files_found = paths.is_empty();
+    if no_files_found {
+        eprintln!("NOTE: {NO_XML_FILES_NOTE}");
+    }
 
-    // Partition fileinfo_or_errors into vectors with only the
-    // successful and only the erroneous results.
-    let (file_infos, file_errorss): (Vec<FileInfo<WithExtractedValues>>, Vec<FileErrors>) =
-        fileinfo_or_errors.into_iter().partition_result();
+    // Reduce to the requested subset if a sample was requested; keep
+    // the description of it for labelling the output.
+    let (paths, sample_description) = if let Some(sample) = &sample {
+        let number_of_files = paths.len();
+        let paths = sample.select(paths, &xmlhub_checkout.git_working_dir())?;
+        let description = sample.description(paths.len(), number_of_files);
+        (paths, Some(description))
+    } else {
+        (paths, None)
+    };
 
-    // Build derived attribute values. Errors during this phase are
-    // stored as warnings, so as to not prevent users from pushing
-    // their changes, since some errors could be temporary.
-    let file_infos: Vec<FileInfo<WithDerivedValues>> = file_infos
-        .into_iter()
-        .map(|info| {
-            let FileInfo {
-                id,
-                path,
-                metadata,
-                mut warnings,
-            } = info;
-            let metadata = metadata.add_derived_attributes(&mut warnings);
-            FileInfo {
-                id,
-                path,
-                metadata,
-                warnings,
-            }
-        })
-        .collect();
+    if print_paths {
+        print_paths_plain(&paths, &mut stdout().lock()).context("writing to stdout")?;
+        let summary = BuildSummary {
+            files: paths.len(),
+            errors: 0,
+            warnings: 0,
+            committed: false,
+            pushed: false,
+            changed: false,
+            since_last_build: None,
+        };
+        return Ok((0, None, summary));
+    }
+
+    // See help text on `read_and_derive_file_infos` for what it's doing.
+    let (file_infos, mut file_errorss) =
+        read_and_derive_file_infos_with_progress(paths, &attribute_rules, &*progress);
+
+    let (file_infos, duplicate_errorss) = split_off_duplicate_paths(file_infos);
+    if let Some(file_errors) = duplicate_errorss.first() {
+        if strict_duplicate_paths {
+            bail!(
+                "duplicate path in the index: {:?}",
+                file_errors.path.rel_path()
+            )
+        }
+        file_errorss.extend(duplicate_errorss);
+        file_errorss.sort_by(|a, b| a.path.rel_path().cmp(b.path.rel_path()));
+    }
 
     let warningss: Vec<FileWarnings> = file_infos
         .iter()
         .filter_map(|info| info.opt_warnings())
         .collect();
 
+    // The state to compare with the next build, for
+    // `--since-last-build`
+    let build_state = if since_last_build_state.is_some() {
+        let paths: Vec<BaseAndRelPath> = file_infos
+            .iter()
+            .map(|file_info| file_info.path.clone())
+            .chain(
+                file_errorss
+                    .iter()
+                    .map(|file_errors| file_errors.path.clone()),
+            )
+            .collect();
+        Some(BuildState {
+            blob_hashes: blob_hashes(&paths)?,
+            error_paths: file_errorss
+                .iter()
+                .map(|file_errors| file_errors.path.rel_path().to_string())
+                .collect(),
+        })
+    } else {
+        None
+    };
+
+    let mut summary = BuildSummary {
+        files: file_infos.len() + file_errorss.len(),
+        errors: file_errorss.len(),
+        warnings: warningss.len(),
+        committed: false,
+        pushed: false,
+        changed: false,
+        since_last_build: None,
+    };
+
+    if count_only {
+        let counts = FileCounts::new(&file_infos, &file_errorss);
+        println!("{counts}");
+        if let Some(spec) = count_by {
+            print_value_tally(&value_tally(spec, &file_infos), &mut stdout().lock())
+                .context("writing to stdout")?;
+        }
+        return Ok((counts.exit_code(), build_state, summary));
+    }
+
+    if explain_index {
+        print_index_exclusions(&index_exclusions(&file_infos), &mut stdout().lock())
+            .context("writing to stdout")?;
+    }
+
     // Build the HTML fragments to use in the HTML page and the Markdown
     // file.
 
     // Create all the sections making up the output file(s)
 
+    let index_anchor_names = index_anchor_names(&file_infos);
+
+    // How the file info boxes are shown
+    let info_box_opts = InfoBoxOpts {
+        truncate_length: config.box_truncate_length,
+        link_to_index: !no_index_section,
+        box_order: &config.box_order,
+        symbols: &config.symbols,
+        index_anchor_names: &index_anchor_names,
+    };
+    let index_section_opts = IndexSectionOpts {
+        short_paths,
+        file_link_base: &file_link_base,
+        layout: index_layout,
+        symbols: &config.symbols,
+    };
+
     // Calculate the sections in parallel (first make a tuple with
     // argument-less anonymous functions, then call `par_run` on it
     // which evaluates each function potentially in parallel and
     // returns a tuple with the results, which we then call
     // `transpose` on to move error values up so they (well, the first
     // one found) can easily be propagated via `?`)
+    // The sections are reported to `progress` as soon as each is
+    // built.
+    let section_built = |section: &Section| {
+        progress.on_section_built(section.title.as_deref().unwrap_or(""));
+    };
     let (file_info_boxes_section, index_sections_section, errors_section, warnings_section) = (
         // Create a Section with boxes with the metainfo for all XML
         // files, in a hierarchy reflecting the folder hierarchy where
         // they are.
         || -> Result<Section> {
-            // Temporarily create a folder hierarchy from all the paths,
-            // then convert it to a Section.
-
-            let mut folder = Folder::new();
-            for file_info in &file_infos {
-                folder.add(file_info).expect("no duplicates");
-            }
-            // This being the last expression in a { } block returns
-            // (moves) its value to the `file_info_boxes_section`
-            // variable outside.
-            folder.to_section(Some("File info by folder".into()))
+            let section = match group_by {
+                BoxGrouping::Folder => {
+                    // Temporarily create a folder hierarchy from all
+                    // the paths, then convert it to a Section.
+
+                    let mut folder = Folder::new();
+                    for file_info in &file_infos {
+                        folder.add(file_info)?;
+                    }
+                    // This being the last expression in a { } block
+                    // returns (moves) its value to the `section`
+                    // variable outside.
+                    folder.to_section(
+                        Some("File info by folder".into()),
+                        &file_link_base,
+                        info_box_opts,
+                    )
+                }
+                BoxGrouping::Attribute(spec) => {
+                    let mut groups = AttributeGroups::new(spec);
+                    for file_info in &file_infos {
+                        groups.add(file_info)?;
+                    }
+                    groups.to_section(
+                        Some(format!("File info by {}", spec.key.as_ref())),
+                        &file_link_base,
+                        info_box_opts,
+                    )
+                }
+            }?;
+            section_built(&section);
+            Ok(section)
         },
         // Create all indices for those metadata entries for which their
         // specification says to index them. Each index is in a separate
         // `Section`, but all are bundled as subsections in a single `Section`.
-        || -> Result<Section> {
-            let index_sections: Vec<Section> = METADATA_SPECIFICATION
+        // None with `--no-index-section`.
+        || -> Result<Option<Section>> {
+            if no_index_section {
+                return Ok(None);
+            }
+            // In the configured order (`index_order`)
+            let mut index_sections: Vec<Section> = config
+                .index_ordered_specifications()
                 .into_par_iter()
                 .filter_map(|spec| {
                     // Get a `KeyStringPreparation` instance if
                     // indexing is desired, if we got one we build an
                     // index; if we got none, `map` also returns
                     // `None`, which is dropped by `filter_map`.
-                    spec.indexing
-                        .key_string_preparation()
-                        .map(|prep| build_index_section(spec.key, prep, spec.autolink, &file_infos))
+                    spec.key_string_preparation().map(|prep| {
+                        build_index_section(
+                            spec.key,
+                            prep,
+                            spec.autolink,
+                            &file_infos,
+                            &index_anchor_names,
+                            index_section_opts,
+                        )
+                    })
                 })
                 .collect::<Result<Vec<_>>>()?;
-            Ok(Section {
+            // The indices from the `extract_index_regex` matches go
+            // after the normal ones.
+            for spec in METADATA_SPECIFICATION {
+                if let Some(regex) = spec.extract_index_regex() {
+                    index_sections.push(build_extracted_index_section(
+                        spec,
+                        &regex,
+                        &file_infos,
+                        index_section_opts,
+                    )?);
+                }
+            }
+            let section = Section {
                 highlight: Highlight::None,
                 title: Some("Index by attribute".into()),
                 intro: None,
+                markdown_intro: None,
                 subsections: index_sections,
-            })
+            };
+            section_built(&section);
+            Ok(Some(section))
         },
         // Make an optional `Section` with all the errors if there are any
         || -> Result<Option<Section>> {
@@ -1206,22 +3418,29 @@ fn build_index(
                 Ok(None)
             } else {
                 let html = HTML_ALLOCATOR_POOL.get();
-                let mut hints = Hints::new("errors");
+                let mut hints = Hints::with_messages("errors", issues_hints(&file_errorss));
                 let mut items = html.new_vec();
                 for file_errors in &file_errorss {
                     items.push_flat(file_errors.to_html(
                         true, // XX where is this defined?
-                        "box", &mut hints, &html,
+                        "box",
+                        &file_link_base,
+                        &config.symbols,
+                        &mut hints,
+                        &html,
                     )?)?;
                 }
                 let intro_html = html.div([], [html.dl([], items)?, hints.to_html(&html)?])?;
 
-                Ok(Some(Section {
+                let section = Section {
                     highlight: Highlight::Red,
                     title: Some("Errors".into()),
                     intro: Some(html.preserialize(intro_html)?),
+                    markdown_intro: None,
                     subsections: vec![],
-                }))
+                };
+                section_built(&section);
+                Ok(Some(section))
             }
         },
         // Make an optional `Section` with all the warnings if there
@@ -1232,22 +3451,29 @@ fn build_index(
                 Ok(None)
             } else {
                 let html = HTML_ALLOCATOR_POOL.get();
-                let mut hints = Hints::new("warnings");
+                let mut hints = Hints::with_messages("warnings", issues_hints(&warningss));
                 let mut items = html.new_vec();
                 for warnings in &warningss {
                     items.push_flat(warnings.to_html(
                         true, // XX where is this defined?
-                        "box", &mut hints, &html,
+                        "box",
+                        &file_link_base,
+                        &config.symbols,
+                        &mut hints,
+                        &html,
                     )?)?;
                 }
                 let intro_html = html.div([], [html.dl([], items)?, hints.to_html(&html)?])?;
 
-                Ok(Some(Section {
+                let section = Section {
                     highlight: Highlight::Orange,
                     title: Some("Warnings".into()),
                     intro: Some(html.preserialize(intro_html)?),
-                    subsections: vec![],
-                }))
+                    markdown_intro: None,
+                    subsections: vec![files_needing_attention_section(&warningss)?],
+                };
+                section_built(&section);
+                Ok(Some(section))
             }
         },
     )
@@ -1257,10 +3483,11 @@ fn build_index(
     // Create a single section without a title, to enclose all the
     // other sections. This way, creating the table of contents and
     // conversion to HTML vs. Markdown works seamlessly.
-    let toplevel_section = Section {
+    let mut toplevel_section = Section {
         highlight: Highlight::None,
         title: None,
         intro: None,
+        markdown_intro: None,
         subsections: append(
             append(
                 // This converts the optional `errors_section` from an
@@ -1270,60 +3497,48 @@ fn build_index(
                 // Same again,
                 warnings_section.into_iter().collect::<Vec<_>>(),
             ),
-            // Always use the file_info_boxes_section and the index
-            // sections.
-            vec![index_sections_section, file_info_boxes_section],
+            // Always use the file_info_boxes_section, and the index
+            // sections unless `--no-index-section` was given.
+            append(
+                index_sections_section.into_iter().collect::<Vec<_>>(),
+                vec![file_info_boxes_section],
+            ),
         ),
     };
+    if no_files_found {
+        let section = no_files_found_section()?;
+        section_built(&section);
+        toplevel_section.subsections.insert(0, section);
+    }
+    if standalone.is_some() {
+        let section = file_contents_section(&file_infos)?;
+        section_built(&section);
+        toplevel_section.subsections.push(section);
+    }
 
     let html = HTML_ALLOCATOR_POOL.get();
 
     // Some variables used in both the .html and .md documents
-    let title = "XML Hub file index";
-    let toc_html: SerHtmlFrag =
-        html.preserialize(toplevel_section.to_toc_html(NumberPath::empty(), &html)?)?;
+    let title = &match &sample_description {
+        Some(description) => format!("XML Hub file index ({description})"),
+        None => "XML Hub file index".into(),
+    };
+    if mini_toc {
+        toplevel_section.insert_mini_tocs(&config.highlight_colors)?;
+    }
+    let toc_html: Option<SerHtmlFrag> = if no_toc {
+        None
+    } else {
+        Some(html.preserialize(toplevel_section.to_toc_html(
+            NumberPath::empty(),
+            &config.highlight_colors,
+            &html,
+        )?)?)
+    };
 
     // (For an explanation of the HTML creation syntax used below, see
     // the comment "The first list passed" further above.)
 
-    // The contents for the README.html document
-    let make_htmldocument = |html: &HtmlAllocator| -> Result<AId<Node>> {
-        html.html(
-            [],
-            [
-                html.head(
-                    [],
-                    [
-                        html.meta(
-                            [
-                                att("name", "generator"),
-                                att("content", &*GENERATED_MESSAGE),
-                            ],
-                            [],
-                        )?,
-                        html.meta(
-                            [att("name", "author"), att("content", &*GENERATED_MESSAGE)],
-                            [],
-                        )?,
-                        html.title([], html.text("Index - XML Hub")?)?,
-                        html.style([], html.text(css_styles())?)?,
-                    ],
-                )?,
-                html.body(
-                    [],
-                    [
-                        html.h1([], html.text(title)?)?,
-                        make_intro(false, html)?,
-                        html.h2([], html.text("Contents")?)?,
-                        html.preserialized(toc_html.clone())?,
-                        html.div([], toplevel_section.to_html(NumberPath::empty(), html)?)?,
-                        empty_space_element(40, html)?,
-                    ],
-                )?,
-            ],
-        )
-    };
-
     // The contents for the README.md document
     let make_mddocument = || -> Result<StringTree> {
         let html = HTML_ALLOCATOR_POOL.get();
@@ -1334,9 +3549,16 @@ fn build_index(
                 *GENERATED_MESSAGE
             ),
             format!("# {title}"),
-            make_intro(true, &html)?.to_html_fragment_string(&html)?,
-            "## Contents",
-            toc_html.as_arc_str(),
+            StringTree::Branching(vec![
+                make_intro(true, &output_files, &config.symbols, &html)?
+                    .to_html_fragment_string(&html)?
+                    .into(),
+                match &toc_html {
+                    Some(toc_html) =>
+                        markdown_paragraphs!["", "## Contents", toc_html.as_arc_str(),],
+                    None => "".into(),
+                },
+            ]),
             toplevel_section.to_markdown(NumberPath::empty())?,
             empty_space_element(40, &html)?.to_html_fragment_string(&html)?,
         ])
@@ -1345,37 +3567,32 @@ fn build_index(
     let have_errors = !file_errorss.is_empty();
     let have_warnings = !warningss.is_empty();
 
-    // The behaviour of the program in the face of errors depends on 3
-    // command line options. Here's the logic that derives 3
-    // behaviours from the 3 options (it's not 1:1). The Rust compiler
-    // verifies that each of the 3 variables is set exactly once.
-    let exit_code;
-    let write_errors_to_stderr;
-    let write_files;
-    if !have_errors {
-        exit_code = 0;
-        write_errors_to_stderr = false;
-        write_files = true;
-    } else {
-        if write_errors {
-            write_files = true;
-            if silent_on_written_errors {
-                exit_code = 0;
-                write_errors_to_stderr = false;
-            } else {
-                write_errors_to_stderr = true;
-                if ok_on_written_errors {
-                    exit_code = 0;
-                } else {
-                    exit_code = 1;
-                }
-            }
-        } else {
-            exit_code = 1;
-            write_errors_to_stderr = true;
-            write_files = false;
-        }
+    let toc_fragment_path = toc_fragment.then(|| output_files.toc_fragment_path_from_repo_top());
+    let mut written_files: Vec<&str> = output_files.paths_from_repo_top().into();
+    if let Some(toc_fragment_path) = &toc_fragment_path {
+        written_files.push(toc_fragment_path);
     }
+    let decision = build_decision(BuildDecisionInputs {
+        have_errors,
+        write_errors,
+        silent_on_written_errors,
+        ok_on_written_errors,
+        not_in_working_dir: output_sink.is_some() || standalone.is_some(),
+        have_written_files: !written_files.is_empty(),
+        no_commit,
+        sample: sample.is_some(),
+        no_commit_errors,
+        push: maybe_checked_xmlhub_checkout.is_some(),
+        no_push_on_error,
+    });
+    let BuildDecision {
+        exit_code,
+        write_errors_to_stderr,
+        write_files,
+        commit_files,
+        push,
+        reasons: _,
+    } = decision;
 
     let write_warnings_to_stderr;
     if !have_warnings {
@@ -1396,7 +3613,7 @@ fn build_index(
         let mut out = stderr().lock();
         (|| -> Result<()> {
             writeln!(&mut out, "\nIndexing errors:")?;
-            let mut hints = Hints::new("indexingerrors");
+            let mut hints = Hints::with_messages("indexingerrors", issues_hints(&file_errorss));
             for file_errors in file_errorss {
                 file_errors.print_plain(&mut hints, &mut out)?
             }
@@ -1410,7 +3627,7 @@ fn build_index(
         let mut out = stderr().lock();
         (|| -> Result<()> {
             writeln!(&mut out, "\nIndexing warnings:\n")?;
-            let mut hints = Hints::new("indexingwarnings");
+            let mut hints = Hints::with_messages("indexingwarnings", issues_hints(&warningss));
             for warning in warningss {
                 warning.print_plain(&mut hints, &mut out)?
             }
@@ -1421,64 +3638,164 @@ fn build_index(
         .context("writing to stderr")?;
     }
 
+    if explain {
+        let mut lines = decision.explanation_lines();
+        if commit_files {
+            // Read-only checks of the state that could still prevent
+            // the commit
+            if !no_branch_check {
+                if let Err(e) = xmlhub_checkout.check_current_branch() {
+                    lines.push(format!("But the commit would fail: {e:#}"));
+                }
+            }
+            let changed_items = uncommitted_changes(xmlhub_checkout, &written_files, batch)?;
+            if !changed_items.is_empty() {
+                lines.push(format!(
+                    "But the commit would be refused due to uncommitted changes in:\n  {}",
+                    changed_items.join("\n  ")
+                ));
+            }
+        }
+        for line in lines {
+            println!("{line}");
+        }
+        return Ok((exit_code, build_state, summary));
+    }
+
     let html_file_has_changed;
     if write_files {
-        (html_file_has_changed, (), ()) = (
-            || -> Result<_> {
-                let html = HTML_ALLOCATOR_POOL.get();
+        // Streams the HTML document to `out`
+        let write_html = |mut out: &mut dyn Write| -> Result<()> {
+            if pretty_html {
+                // The pretty printer needs the whole document
+                let mut document = Vec::new();
+                print_htmldocument_streaming(
+                    title,
+                    toc_html.as_ref(),
+                    &output_files,
+                    &toplevel_section,
+                    &config.highlight_colors,
+                    &config.symbols,
+                    &mut document,
+                )?;
+                out.write_all(&pretty_print_html(&document))?;
+                Ok(())
+            } else {
+                print_htmldocument_streaming(
+                    title,
+                    toc_html.as_ref(),
+                    &output_files,
+                    &toplevel_section,
+                    &config.highlight_colors,
+                    &config.symbols,
+                    &mut out,
+                )
+            }
+        };
 
-                // Get an owned version of the base path and then
-                // append path segments to it.
-                let mut path = xmlhub_checkout.working_dir_path().to_owned();
-                path.push(HTML_FILE.path_from_repo_top);
-                let mut out = BufWriter::new(File::create(&path)?);
-                html.print_html_document(make_htmldocument(&html)?, &mut out)?;
-                out.flush()?;
-
-                let mut html_file_has_changed = false;
-                if open_if_changed {
-                    // Need to remember whether the file has changed
-                    check_dry_run! {
-                        message: "git diff",
-                        html_file_has_changed = !xmlhub_checkout.git_working_dir().git(
+        if let Some(standalone_path) = &standalone {
+            (|| -> Result<()> {
+                let mut out = BufWriter::new(File::create(standalone_path)?);
+                write_html(&mut out)?;
+                Ok(out.flush()?)
+            })()
+            .with_context(|| anyhow!("writing to file {standalone_path:?}"))?;
+            return Ok((exit_code, build_state, summary));
+        }
 
-                            &["diff", "--no-patch", "--exit-code", "--",
-                              HTML_FILE.path_from_repo_top],
-                            false
-                        )?
-                    }
-                }
-                Ok(html_file_has_changed)
+        let is_filesystem_sink = output_sink.is_none();
+        let mut sink: Arc<dyn OutputSink> = output_sink.unwrap_or_else(|| {
+            Arc::new(FilesystemSink {
+                base_path: xmlhub_checkout.working_dir_path().to_owned(),
+            })
+        });
+        if let Some(max_size) = max_output_size {
+            sink = Arc::new(SizeLimitSink::new(sink, max_size));
+        }
+        let ((), (), ()) = (
+            || -> Result<_> {
+                sink.write_file_streaming(&output_files.html_file.path_from_repo_top, &mut |out| {
+                    write_html(out)
+                })
             },
             || -> Result<_> {
-                let mut path = xmlhub_checkout.working_dir_path().to_owned();
-                path.push(MD_FILE.path_from_repo_top);
-                make_mddocument()?
-                    .write_to_file(&path)
-                    .with_context(|| anyhow!("writing to file {path:?}"))?;
-                Ok(())
+                sink.write_file(
+                    &output_files.md_file.path_from_repo_top,
+                    make_mddocument()?.to_string().as_bytes(),
+                )
             },
             || -> Result<_> {
-                let mut path = xmlhub_checkout.working_dir_path().to_owned();
-                path.push(ATTRIBUTES_FILE.path_from_repo_top);
-                make_attributes_md(true)?
-                    .write_to_file(&path)
-                    .with_context(|| anyhow!("writing to file {path:?}"))?;
-                Ok(())
+                sink.write_file(
+                    &output_files.attributes_file.path_from_repo_top,
+                    make_attributes_md(true)?.to_string().as_bytes(),
+                )
             },
         )
             .par_run()
             .transpose()?;
 
-        let written_files = OUTPUT_FILES.map(|o| o.path_from_repo_top);
+        // The table of contents on its own (`--toc-fragment`)
+        if let (Some(toc_fragment_path), Some(toc_html)) = (&toc_fragment_path, &toc_html) {
+            sink.write_file(toc_fragment_path, toc_html.as_str().as_bytes())?;
+        }
+        sink.finish()?;
+
+        html_file_has_changed = if open_if_changed && is_filesystem_sink {
+            // Need to remember whether the file has changed
+            let mut html_file_has_changed = false;
+            check_dry_run! {
+                message: "git diff",
+                html_file_has_changed = !xmlhub_checkout.git_working_dir().git(
+                    &["diff", "--no-patch", "--exit-code", "--",
+                      &output_files.html_file.path_from_repo_top],
+                    false
+                )?
+            }
+            html_file_has_changed
+        } else {
+            false
+        };
+
+        if !is_filesystem_sink {
+            if let (Some(tree_ish), Some(dir)) = (&from_ref, from_ref_dir.take()) {
+                if keep_from_ref_dir {
+                    let dir = dir.keep();
+                    if !quietness.quiet() {
+                        println!(
+                            "Wrote the index for {tree_ish:?} to {:?} (remove {dir:?} when done)",
+                            dir.join(&*output_files.html_file.path_from_repo_top)
+                        );
+                    }
+                }
+            }
+            // Committing, pushing and opening the browser only make
+            // sense for the files in the Git working directory.
+            return Ok((exit_code, build_state, summary));
+        }
+
+        check_dry_run! {
+            message: "git status",
+            summary.changed = xmlhub_checkout
+                .git_working_dir()
+                .git_status()?
+                .iter()
+                .any(|item| written_files.contains(&item.path.as_str()))
+        }
+
+        if diff_output {
+            check_dry_run! {
+                message: "git diff --numstat HEAD",
+                eprint!(
+                    "{}",
+                    output_file_changes_summary(&output_file_changes(
+                        &xmlhub_checkout.git_working_dir(),
+                        &written_files
+                    )?)
+                )
+            }
+        }
 
-        // Commit files if not prevented by --no-commit, and any
-        // were written, and --no-commit-errors was not given or
-        // there were no errors. I.e. reasons not to commit:
-        let no_commit_files =
-            no_commit || written_files.is_empty() || (have_errors && no_commit_errors);
-        let do_commit_files = !no_commit_files;
-        if do_commit_files {
+        if commit_files {
             if !no_branch_check {
                 // Are we on the expected branch? NOTE: unlike most
                 // checks on the repository, this one occurs late, but
@@ -1488,27 +3805,11 @@ fn build_index(
             }
 
             // Check that there are no uncommitted changes
-            let mut items: Vec<GitStatusItem> = vec![];
+            let mut changed_items: Vec<String> = vec![];
             check_dry_run! {
                 message: "git status",
-                items = xmlhub_checkout.git_working_dir().git_status()?
+                changed_items = uncommitted_changes(xmlhub_checkout, &written_files, batch)?
             }
-            let daemon_folder_name_with_slash = format!("{}/", *DAEMON_FOLDER_NAME);
-            let ignore_path = |path: &str| -> bool {
-                written_files.contains(&path) || path == daemon_folder_name_with_slash
-            };
-            let changed_items: Vec<String> = items
-                .iter()
-                .filter(|item| {
-                    // Ignore untracked files in batch mode (they will
-                    // be killed by reset --hard if in the way, and if
-                    // not, then they could not have been created (XX
-                    // unless there is a bug in the app, though,
-                    // actually))
-                    !(ignore_path(item.path.as_str()) || (batch && item.is_untracked(false)))
-                })
-                .map(|item| item.to_string())
-                .collect();
             if !changed_items.is_empty() {
                 // Avoid making this message look like a failure?
                 // Hence do not use `bail!`, but just `eprintln!` with
@@ -1522,7 +3823,7 @@ fn build_index(
                     "  ",
                     changed_items.join("\n  "),
                 );
-                return Ok(1);
+                return Ok((1, build_state, summary));
             }
 
             check_dry_run! {
@@ -1535,38 +3836,117 @@ fn build_index(
             }
 
             let mut did_commit = true;
+            let files = if written_files.len() > 1 {
+                "files"
+            } else {
+                "file"
+            };
+            // With `version_notes`, the version goes into a note
+            // instead of the commit message
+            let commit_message = if version_notes {
+                format!("{INDEX_COMMIT_SUBJECT_PREFIX}{files}")
+            } else {
+                format!(
+                    "{INDEX_COMMIT_SUBJECT_PREFIX}{files} via {}",
+                    git_log_version_checker.program_name_and_version()
+                )
+            };
+            let commit_message = commit_message_with_trailers(commit_message, &commit_trailers);
+            let mut commit_args = vec!["commit", "-m", &commit_message];
+            if signoff {
+                commit_args.push("--signoff");
+            }
+            if let Some(gpg_sign_arg) = &gpg_sign_arg {
+                commit_args.push(gpg_sign_arg);
+            }
+            commit_args.push("--");
+            commit_args.extend(written_files.iter().copied());
             check_dry_run! {
                 message: format!("git commit -m .. -- {written_files:?}"),
                 did_commit = xmlhub_checkout.git_working_dir().git(
-
-                    &append(
-                        &[
-                            "commit",
-                            "-m",
-                            &format!(
-                                "regenerate index file{} via {}",
-                                if written_files.len() > 1 { "s" } else { "" },
-                                git_log_version_checker.program_name_and_version()
-                            ),
-                            "--",
-                        ],
-                        &written_files,
-                    ),
+                    &commit_args,
                     quietness.quiet()
-                )?
+                ).map_err(|e| {
+                    // (Git exits with code 128 if signing fails,
+                    // which is reported as an error, unlike "nothing
+                    // to commit")
+                    if gpg_sign_arg.is_some() {
+                        e.context(
+                            "git commit failed to create the signed commit (--sign-commits); \
+                             please check that a signing key is available (configured via \
+                             `git config user.signingkey` or given via --signing-key) and \
+                             that gpg (or the gpg agent) works, e.g. by running \
+                             `echo test | gpg --clearsign`"
+                        )
+                    } else {
+                        e
+                    }
+                })?
+            }
+            summary.committed = did_commit && !dry_run;
+
+            if version_notes && did_commit {
+                check_dry_run! {
+                    message: format!("git notes --ref={VERSION_NOTES_REF} add -f -m .. HEAD"),
+                    git_log_version_checker.add_version_note(
+                        VERSION_NOTES_REF,
+                        quietness.quiet()
+                    )?
+                }
             }
 
             if let Some(checked_xmlhub_checkout) = maybe_checked_xmlhub_checkout {
                 let default_remote_for_push = &checked_xmlhub_checkout.default_remote;
-                if did_commit {
+                // Pushing is decided separately from committing: with
+                // --no-push-on-error, errors are committed but not
+                // pushed.
+                if !push {
+                    if !quietness.quiet() {
+                        let later = if batch {
+                            "the index files will be regenerated and pushed by the first \
+                             build without errors (this commit is discarded by the next \
+                             --batch run)"
+                        } else {
+                            "the commit will be pushed with the next successful build"
+                        };
+                        println!(
+                            "There were errors, thus not pushing (--no-push-on-error); {later}."
+                        )
+                    }
+                } else if did_commit {
                     check_dry_run! {
                         message: format!("git push {default_remote_for_push:?}"),
-                        xmlhub_checkout.git_working_dir().git_push::<&str>(
-
-                            default_remote_for_push,
-                            &[],
-                            quietness.quiet()
-                        )?
+                        {
+                            xmlhub_checkout.git_working_dir().git_push::<&str>(
+                                default_remote_for_push,
+                                &[],
+                                quietness.quiet()
+                            )?;
+                            summary.pushed = true;
+                        }
+                    }
+                    if version_notes {
+                        // Merge notes pushed by other clones in the
+                        // meantime first, or the push is rejected
+                        check_dry_run! {
+                            message: format!(
+                                "git fetch {default_remote_for_push:?} {VERSION_NOTES_REF:?} \
+                                 (and merge); git push {default_remote_for_push:?} \
+                                 {VERSION_NOTES_REF:?}"
+                            ),
+                            {
+                                git_log_version_checker.fetch_version_notes(
+                                    VERSION_NOTES_REF,
+                                    default_remote_for_push,
+                                    quietness.quiet()
+                                )?;
+                                xmlhub_checkout.git_working_dir().git_push(
+                                    default_remote_for_push,
+                                    &[VERSION_NOTES_REF],
+                                    quietness.quiet()
+                                )?
+                            }
+                        }
                     }
                 } else {
                     if !quietness.quiet() {
@@ -1591,7 +3971,9 @@ fn build_index(
             // path.canonicalize().as_os_str()
             spawn_browser(
                 xmlhub_checkout.working_dir_path(),
-                &[HTML_FILE.path_from_repo_top.as_ref()],
+                &[std::ffi::OsStr::new(
+                    &*output_files.html_file.path_from_repo_top,
+                )],
             )?;
         } else {
             eprintln!(
@@ -1602,7 +3984,27 @@ fn build_index(
         }
     }
 
-    Ok(exit_code)
+    Ok((exit_code, build_state, summary))
+}
+
+/// Open the HTML index file in `working_dir_path` via
+/// `open_in_browser` (which gets the directory and the path relative
+/// to it, as `spawn_browser`), without building it first. Fails if
+/// the file doesn't exist.
+fn open_existing_index(
+    working_dir_path: &Path,
+    output_files: &OutputFiles,
+    open_in_browser: impl FnOnce(&Path, &std::ffi::OsStr) -> Result<()>,
+) -> Result<()> {
+    let rel_path = &*output_files.html_file.path_from_repo_top;
+    let path = working_dir_path.join(rel_path);
+    if !path.is_file() {
+        bail!(
+            "the index file {path:?} does not exist yet, run `xmlhub build` (without \
+             `--open-existing`) to create it"
+        )
+    }
+    open_in_browser(working_dir_path, std::ffi::OsStr::new(rel_path))
 }
 
 fn typed_from_no_repo_check(no_repo_check: bool) -> CheckExpectedSubpathsExist {
@@ -1638,17 +4040,42 @@ fn upgrade_command(
 }
 
 /// Execute a `changelog` command
-fn changelog_command(command_opts: ChangelogOpts) -> Result<()> {
+fn changelog_command(
+    program_version: GitVersion<SemVersion>,
+    command_opts: ChangelogOpts,
+) -> Result<()> {
     let ChangelogOpts {
         from,
         to,
         allow_downgrades,
         open_or_print,
+        grep,
+        json,
     } = command_opts;
 
     let changelog = Changelog::new_builtin()?;
+    let resolve = |version: Option<ChangelogVersion>| {
+        version
+            .map(|version| version.resolve(&changelog, &program_version))
+            .transpose()
+    };
+    let (from, to) = (resolve(from)?, resolve(to)?);
     let part =
         changelog.get_between_versions(allow_downgrades, false, from.as_ref(), to.as_ref())?;
+    let part = if let Some(grep) = &grep {
+        let grep = grep.to_lowercase();
+        part.retain_point_entries(|entry| entry.to_lowercase().contains(&grep))
+    } else {
+        part
+    };
+
+    if json {
+        let mut output = BufWriter::new(stdout().lock());
+        serde_json::to_writer_pretty(&mut output, &part.release_entries())?;
+        writeln!(&mut output)?;
+        output.flush()?;
+        return Ok(());
+    }
 
     let print_markdown_to = |out: &mut dyn Write| write!(out, "{}", changelog_display(&part));
 
@@ -1686,6 +4113,58 @@ fn changelog_command(command_opts: ChangelogOpts) -> Result<()> {
     Ok(())
 }
 
+/// Describe how the output files in `second` differ from those in
+/// `first` (both by path), one line per differing file. Empty if
+/// they are identical.
+fn output_differences(
+    first: &BTreeMap<String, Vec<u8>>,
+    second: &BTreeMap<String, Vec<u8>>,
+) -> Vec<String> {
+    let paths: BTreeSet<&String> = first.keys().chain(second.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| match (first.get(path), second.get(path)) {
+            (Some(a), Some(b)) => {
+                if a == b {
+                    return None;
+                }
+                let common = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+                let line = a[..common].iter().filter(|b| **b == b'\n').count() + 1;
+                Some(format!(
+                    "{path}: first difference on line {line} (at byte {common}), \
+                     sizes {} vs. {} bytes",
+                    a.len(),
+                    b.len()
+                ))
+            }
+            (Some(_), None) => Some(format!("{path}: only written by the first build")),
+            (None, Some(_)) => Some(format!("{path}: only written by the second build")),
+            (None, None) => unreachable!("path comes from one of the maps"),
+        })
+        .collect()
+}
+
+/// Run `build` twice, each time writing into a new `MemorySink`, and
+/// check that both runs wrote identical files (`build
+/// --self-test`). Returns the number of files.
+fn self_test_build(build: impl Fn(Arc<dyn OutputSink>) -> Result<()>) -> Result<usize> {
+    let run = || -> Result<BTreeMap<String, Vec<u8>>> {
+        let sink = Arc::new(MemorySink::default());
+        build(sink.clone())?;
+        Ok(sink.files())
+    };
+    let first = run().context("self-test: first build")?;
+    let second = run().context("self-test: second build")?;
+    let differences = output_differences(&first, &second);
+    if !differences.is_empty() {
+        bail!(
+            "self-test failed: building the index twice gave different output:\n  {}",
+            differences.join("\n  ")
+        )
+    }
+    Ok(first.len())
+}
+
 /// Execute a `build` command: prepare and run `build_index` in the
 /// requested mode (interactive, batch, daemon). (Never returns `Ok`
 /// but exits directly in the non-`Err` case. `!` is not stable yet.)
@@ -1700,34 +4179,119 @@ fn build_command(
         quietness,
         write_errors,
         no_commit_errors,
+        no_push_on_error,
+        summary_line,
+        since_last_build,
         ok_on_written_errors,
         silent_on_written_errors,
         open,
         open_if_changed,
+        open_existing,
         pull,
         no_commit,
         push,
         batch,
         daemon,
         daemon_sleep_time,
+        daemon_max_sleep_time,
+        max_consecutive_failures,
         no_branch_check,
         no_repo_check,
         ignore_untracked,
+        follow_symlinks,
+        print_paths,
+        count_only,
+        count_by,
+        scan_max_depth,
+        refuse_on_divergence,
+        strict_duplicate_paths,
+        standalone,
+        explain,
+        output_dir,
+        link_base,
+        from_ref,
+        require_attributes,
+        lenient_unknown_attributes,
+        warn_duplicate_list_items,
+        show_file_comments,
+        strict_version_range,
         base_path,
         daemon_opts,
         limit_as,
+        explain_index,
+        short_paths,
+        output_files,
+        extensions,
+        sample,
+        version_notes,
+        signoff,
+        sign_commits,
+        signing_key,
+        co_author,
+        strict_version_check,
+        pretty_html,
+        compact_html: _,
+        self_test,
+        no_index_section,
+        diff_output,
+        group_by,
+        max_output_size,
+        no_toc,
+        mini_toc,
+        toc_fragment,
+        index_layout,
     } = build_opts;
 
+    let output_files = output_files.output_files()?;
+    let extensions = extensions.xml_extensions()?;
+    let standalone = standalone.as_deref().map(expand_path).transpose()?;
+    let output_dir = output_dir.as_deref().map(expand_path).transpose()?;
+    let sample = sample.as_deref().map(Sample::from_args).transpose()?;
+    if scan_max_depth == Some(0) {
+        bail!("--scan-max-depth N must be at least 1")
+    }
+    let count_by = count_by.as_deref().map(count_by_attribute).transpose()?;
+    let group_by = group_by
+        .as_deref()
+        .map(BoxGrouping::from_arg)
+        .transpose()?
+        .unwrap_or(BoxGrouping::Folder);
+    let max_output_size_limit = match max_output_size.unwrap_or(MAX_OUTPUT_SIZE_DEFAULT) {
+        0 => None,
+        n => Some(n),
+    };
+    let attribute_rules = AttributeRules {
+        lenient_unknown_attributes,
+        warn_duplicate_list_items,
+        show_file_comments,
+        strict_version_range,
+        ..AttributeRules::from_names(&require_attributes)?
+    };
+    let commit_trailers: Vec<String> = co_author
+        .iter()
+        .map(|co_author| co_author_trailer(co_author))
+        .collect::<Result<_>>()?;
+    let gpg_sign_arg = gpg_sign_arg(sign_commits, signing_key.as_deref());
+
     let no_repo_check = typed_from_no_repo_check(no_repo_check);
 
-    let xmlhub_checkout: CheckedCheckoutContext1<Cow<Path>> = if let Some(base_path) = base_path {
-        XMLHUB_CHECKOUT
-            .replace_working_dir_path(base_path.into())
-            .check1(no_repo_check)?
-    } else {
-        XMLHUB_CHECKOUT.checked_from_subpath(*CURRENT_DIRECTORY, no_repo_check, false)?
+    let xmlhub_checkout: CheckedCheckoutContext1<Cow<Path>> = match base_path.as_slice() {
+        [] => XMLHUB_CHECKOUT.checked_from_subpath(*CURRENT_DIRECTORY, no_repo_check, false)?,
+        [base_path] => XMLHUB_CHECKOUT
+            .replace_working_dir_path(expand_path(base_path)?.into())
+            .check1(no_repo_check)?,
+        _ => bail!("bug: build_command called with multiple base paths, {base_path:?}"),
     };
 
+    if open_existing {
+        open_existing_index(
+            xmlhub_checkout.working_dir_path(),
+            &output_files,
+            |in_directory, path| spawn_browser(in_directory, &[path]),
+        )?;
+        std::process::exit(0);
+    }
+
     // For pushing, need the `CheckedCheckoutContext` (which has the
     // `default_remote`). Retrieve this early to avoid committing and
     // then erroring out on pushing
@@ -1737,15 +4301,33 @@ fn build_command(
         None
     };
 
-    let git_log_version_checker = git_log_version_checker(
-        program_version,
-        no_version_check,
-        xmlhub_checkout.git_working_dir().into(),
-    );
+    let git_log_version_checker = XmlhubCheckVersion {
+        // Interactive users see the warning, thus only apply in
+        // batch mode (which includes daemon mode)
+        require_version_statements: strict_version_check && batch,
+        ..git_log_version_checker(
+            program_version,
+            no_version_check,
+            xmlhub_checkout.git_working_dir().into(),
+            (&output_files).into(),
+        )
+    };
 
     let min_sleep_seconds = daemon_sleep_time.unwrap_or(MIN_SLEEP_SECONDS_DEFAULT);
+    let max_sleep_seconds = daemon_max_sleep_time.unwrap_or(MAX_SLEEP_SECONDS_DEFAULT);
+    if max_sleep_seconds < min_sleep_seconds {
+        bail!(
+            "the maximum daemon sleep time ({max_sleep_seconds} seconds) must not be less \
+             than the minimum ({min_sleep_seconds} seconds)"
+        )
+    }
 
-    let build_index_once = || {
+    // The self-test compares two builds of the same state of the
+    // repository, thus it does not update it via `git pull` or `git
+    // remote update` and `git reset --hard`.
+    let (pull, batch) = (pull && !self_test, batch && !self_test);
+
+    let build_index_once = |output_sink: Option<Arc<dyn OutputSink>>| {
         build_index(
             BuildIndexOpts {
                 dryness: dryness.clone(),
@@ -1754,21 +4336,81 @@ fn build_command(
                 pull,
                 batch,
                 ignore_untracked,
+                follow_symlinks,
+                print_paths,
+                count_only,
+                count_by,
+                scan_max_depth,
+                refuse_on_divergence,
+                strict_duplicate_paths,
+                standalone: standalone.clone(),
+                explain,
+                output_dir: output_dir.clone(),
+                link_base: link_base.clone(),
+                from_ref: from_ref.clone(),
+                attribute_rules: attribute_rules.clone(),
                 write_errors,
                 silent_on_written_errors,
                 ok_on_written_errors,
                 open_if_changed,
                 no_commit,
                 no_commit_errors,
+                no_push_on_error,
                 no_branch_check,
                 open,
+                explain_index,
+                short_paths,
+                output_files: output_files.clone(),
+                extensions: extensions.clone(),
+                sample: sample.clone(),
+                version_notes,
+                signoff,
+                gpg_sign_arg: gpg_sign_arg.clone(),
+                commit_trailers: commit_trailers.clone(),
+                pretty_html,
+                no_index_section,
+                diff_output,
+                group_by,
+                max_output_size: max_output_size_limit,
+                no_toc,
+                mini_toc,
+                toc_fragment,
+                index_layout,
+                since_last_build_state: since_last_build.then(|| {
+                    xmlhub_checkout
+                        .working_dir_path()
+                        .append(&*DAEMON_FOLDER_NAME)
+                        .append(SINCE_LAST_BUILD_STATE_FILENAME)
+                }),
+                output_sink,
+                progress: Arc::new(NoProgress),
             },
             &git_log_version_checker,
             &xmlhub_checkout,
             &maybe_checked_xmlhub_checkout,
         )
+        .map(|(exit_code, summary)| {
+            if summary_line {
+                println!("{summary}");
+            }
+            if let Some(line) = &summary.since_last_build {
+                println!("{line}");
+            }
+            exit_code
+        })
     };
 
+    if self_test {
+        let num_files = self_test_build(|sink| build_index_once(Some(sink)).map(|_exit_code| ()))?;
+        if !quietness.quiet() {
+            println!(
+                "Self-test passed: two builds produced identical output \
+                 ({num_files} files)"
+            );
+        }
+        std::process::exit(0);
+    }
+
     let daemon_base_dir = xmlhub_checkout
         .working_dir_path()
         .append(&*DAEMON_FOLDER_NAME);
@@ -1816,10 +4458,11 @@ fn build_command(
                     // child process to do it (so that the child crashing or being
                     // killed due to out of memory conditions does not stop the
                     // daemon).
-                    forking_loop(
+                    forking_loop_with_stop_exit_codes(
                         LoopWithBackoff {
                             min_sleep_seconds,
-                            max_sleep_seconds: MAX_SLEEP_SECONDS,
+                            max_sleep_seconds,
+                            max_consecutive_errors: max_consecutive_failures,
                             verbosity: if quietness.quiet() {
                                 LoopVerbosity::LogActivityInterval {
                                     every_n_seconds: DAEMON_ACTIVITY_LOG_INTERVAL_SECONDS,
@@ -1876,12 +4519,37 @@ fn build_command(
                             // child with exit code 0 whenever the action
                             // returned Ok, and that's OK for us, thus we
                             // can and need to drop the code from
-                            // `build_index`).
-                            build_index_once().map(|_exit_code| ())
+                            // `build_index`). SIGINT, as sent by `--daemon
+                            // stop`, is deferred until the build is
+                            // finished, so that it (or its `git commit` or
+                            // `git push`) is not interrupted half-way.
+                            // A missing version statement with
+                            // --strict-version-check is not fixed by
+                            // retrying, thus it gets its own exit code,
+                            // which stops the daemon.
+                            let result = with_signals_deferred(&[Signal::SIGINT], || {
+                                build_index_once(None).map(|_exit_code| ())
+                            })?;
+                            if let Err(e) = &result {
+                                if e.downcast_ref::<VersionStatementsNotFound>().is_some() {
+                                    eprintln!("Error: {e:#}");
+                                    std::process::exit(EXIT_CODE_VERSION_STATEMENTS_NOT_FOUND);
+                                }
+                            }
+                            result
                         },
                         // When to exit
                         || daemon_check_exit.want_exit(),
-                    );
+                        &[EXIT_CODE_VERSION_STATEMENTS_NOT_FOUND],
+                    )
+                    .map_err(|e| match e {
+                        ForkingLoopError::StopExitCode { .. } => anyhow!(
+                            "daemon: stopping because no version statements were found in the \
+                             Git log of the index files (--strict-version-check): {e}"
+                        ),
+                        ForkingLoopError::TooManyErrors(e) => anyhow!(e)
+                            .context("daemon: stopping because the conversion keeps failing"),
+                    })?;
                     Ok(())
                 }
             },
@@ -1891,8 +4559,149 @@ fn build_command(
         Ok(daemon.execute(daemon_mode, true)?)
     } else {
         let _main_lock = get_main_lock()?;
-        std::process::exit(build_index_once()?);
+        std::process::exit(build_index_once(None)?);
+    }
+}
+
+/// Execute a `build` command, for each repository if multiple
+/// `--base-path` options were given (only allowed in daemon mode).
+fn build_command_per_repository(
+    program_version: GitVersion<SemVersion>,
+    build_opts: BuildOpts,
+) -> Result<Option<ExecutionResult>> {
+    if build_opts.base_path.len() <= 1 {
+        return Ok(Some(build_command(program_version, build_opts)?));
+    }
+    let Some(daemon_mode) = build_opts.daemon else {
+        bail!("multiple `--base-path` options are only supported in `--daemon` mode")
+    };
+
+    let build_repository = |base_path: &Path| -> Result<()> {
+        let execution_result = build_command(
+            program_version.clone(),
+            BuildOpts {
+                base_path: vec![base_path.to_owned()],
+                ..build_opts.clone()
+            },
+        )?;
+        if execution_result.is_daemon() {
+            // We are in the background daemon for this repository
+            // (which has finished now), do not continue with the
+            // other repositories. A soft restart re-executes just the
+            // daemon for this repository.
+            execution_result.daemon_cleanup_with_args(command_line_for_repository(
+                std::env::args_os(),
+                base_path,
+            ));
+            std::process::exit(0);
+        }
+        execution_result.daemon_cleanup();
+        Ok(())
+    };
+
+    // In `run` mode, the build loops run in the foreground and never
+    // return, thus each needs its own process; the other modes
+    // return after starting, stopping or querying the daemon of the
+    // repository, and `start` puts each daemon into its own
+    // background process already.
+    let in_child_processes = matches!(daemon_mode, DaemonMode::Run);
+    let failed = run_per_repository(&build_opts.base_path, in_child_processes, build_repository)?;
+    if !failed.is_empty() {
+        bail!(
+            "failed for {} of {} repositories: {}",
+            failed.len(),
+            build_opts.base_path.len(),
+            failed.iter().map(|path| format!("{path:?}")).join(", ")
+        )
+    }
+    Ok(None)
+}
+
+/// The command line `args` (including the program name) with all
+/// `--base-path` options replaced by a single one for `base_path`, at
+/// the place of the first one.
+fn command_line_for_repository(
+    args: impl IntoIterator<Item = OsString>,
+    base_path: &Path,
+) -> Vec<OsString> {
+    let mut base_path_option = OsString::from("--base-path=");
+    base_path_option.push(base_path);
+    let mut base_path_option = Some(base_path_option);
+    let mut result = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let is_base_path_option = if arg == "--" {
+            // No options after this
+            result.push(arg);
+            result.extend(args.by_ref());
+            break;
+        } else if arg == "--base-path" {
+            // Skip the value, too
+            args.next();
+            true
+        } else {
+            arg.as_bytes().starts_with(b"--base-path=")
+        };
+        if is_base_path_option {
+            result.extend(base_path_option.take());
+        } else {
+            result.push(arg);
+        }
+    }
+    result
+}
+
+/// Run `job` for each repository in `base_paths`, continuing with
+/// the others if it fails for one (the error is reported on
+/// stderr). If `in_child_processes` is true, the jobs run
+/// concurrently, each in its own child process (thus a crash only
+/// affects that repository; this must be called while no other
+/// threads are running), otherwise sequentially in the current
+/// process. Returns the base paths for which the job failed.
+fn run_per_repository(
+    base_paths: &[PathBuf],
+    in_child_processes: bool,
+    job: impl Fn(&Path) -> Result<()>,
+) -> Result<Vec<&Path>> {
+    let mut failed = Vec::new();
+    if in_child_processes {
+        let mut children = Vec::new();
+        for base_path in base_paths {
+            if let Some(pid) = easy_fork()? {
+                children.push((base_path, pid));
+            } else {
+                let exit_code = match job(base_path) {
+                    Ok(()) => 0,
+                    Err(e) => {
+                        eprintln!("Error for repository {base_path:?}: {e:#}");
+                        1
+                    }
+                };
+                std::process::exit(exit_code);
+            }
+        }
+        for (base_path, pid) in children {
+            match waitpid_until_gone(pid)? {
+                Status::Normalexit(0) => (),
+                Status::Normalexit(_) => failed.push(base_path.as_path()),
+                Status::Signalexit(signal) => {
+                    eprintln!(
+                        "Error for repository {base_path:?}: process {pid} terminated by \
+                         signal {signal}"
+                    );
+                    failed.push(base_path.as_path());
+                }
+            }
+        }
+    } else {
+        for base_path in base_paths {
+            if let Err(e) = job(base_path) {
+                eprintln!("Error for repository {base_path:?}: {e:#}");
+                failed.push(base_path.as_path());
+            }
+        }
     }
+    Ok(failed)
 }
 
 /// Execute a `check` command: prepare and run `build_index` in
@@ -1908,7 +4717,30 @@ fn check_command(program_version: GitVersion<SemVersion>, check_opts: CheckOpts)
         open,
         open_if_changed,
         no_repo_check,
+        headers_only,
+        count_only,
+        only_errors,
+        only_warnings,
+        require_attributes,
+        lenient_unknown_attributes,
+        warn_duplicate_list_items,
+        show_file_comments,
+        strict_version_range,
+        explain_index,
+        short_paths,
+        output_files,
+        extensions,
     } = check_opts;
+
+    let output_files = output_files.output_files()?;
+    let extensions = extensions.xml_extensions()?;
+    let attribute_rules = AttributeRules {
+        lenient_unknown_attributes,
+        warn_duplicate_list_items,
+        show_file_comments,
+        strict_version_range,
+        ..AttributeRules::from_names(&require_attributes)?
+    };
     // What about these?:
     // no_branch_check, -- just use true?
     // ignore_untracked, -- just use true?
@@ -1943,9 +4775,18 @@ fn check_command(program_version: GitVersion<SemVersion>, check_opts: CheckOpts)
             .ok_or_else(|| anyhow!("`check` needs at least one FILE_PATHS argument"))?;
         GitWorkingDir::from(base_path.to_owned())
     };
+    let mut config = XmlhubConfig::read(git_working_dir.working_dir_path_ref())?;
+    if let Some(extensions) = &extensions {
+        config.extensions = extensions.clone();
+    }
+    let attribute_rules = attribute_rules.with_config(&config);
 
-    let git_log_version_checker =
-        git_log_version_checker(program_version, no_version_check, (&git_working_dir).into());
+    let git_log_version_checker = git_log_version_checker(
+        program_version,
+        no_version_check,
+        (&git_working_dir).into(),
+        (&output_files).into(),
+    );
 
     let maybe_checked_xmlhub_checkout = None;
 
@@ -1974,21 +4815,41 @@ fn check_command(program_version: GitVersion<SemVersion>, check_opts: CheckOpts)
                 let barp = BaseAndRelPath::new(
                     Some(Arc::clone(&shared_base_path)),
                     relative_path.to_owned(),
-                );
-                let is_xml = if let Some(ext) = barp.extension() {
-                    ext.eq_ignore_ascii_case("xml")
-                } else {
-                    false
-                };
-                if is_xml {
+                );
+                let extensions = &config.extensions;
+                if extensions.matches(&barp.rel_path) {
                     Ok(barp)
                 } else {
-                    bail!("not an XML file path, it does not have a .xml suffix: {file_path:?}")
+                    bail!(
+                        "not an XML file path, it does not have a {extensions} suffix \
+                         (see the `--extensions` option and the `extensions` setting \
+                         in {CONFIG_FILE_PATH}): {file_path:?}"
+                    )
                 }
             })
             .collect::<Result<_>>()?
     };
 
+    if headers_only {
+        let mut exit_code = 0;
+        let mut err = stderr().lock();
+        for path in &paths {
+            let status = headers_status(&path.full_path());
+            if !matches!(status, HeadersStatus::Present) {
+                exit_code = 1;
+            }
+            writeln!(&mut err, "    For {:?}: {status}", path.rel_path())?;
+        }
+        std::process::exit(exit_code);
+    }
+
+    if count_only {
+        let (file_infos, file_errorss) = read_and_derive_file_infos(paths, &attribute_rules);
+        let counts = FileCounts::new(&file_infos, &file_errorss);
+        println!("{counts}");
+        std::process::exit(counts.exit_code());
+    }
+
     // Then run build_index first, because of the run of
     // `git_log_version_checker`, we want that to be done "early", uh,
     // not early anyway. XXX look into when that is called
@@ -2002,14 +4863,49 @@ fn check_command(program_version: GitVersion<SemVersion>, check_opts: CheckOpts)
             pull: false,
             batch: false,
             ignore_untracked: false,
+            follow_symlinks: false,
+            print_paths: false,
+            count_only: false,
+            count_by: None,
+            scan_max_depth: None,
+            refuse_on_divergence: false,
+            strict_duplicate_paths: false,
+            standalone: None,
+            explain: false,
+            output_dir: None,
+            link_base: None,
+            from_ref: None,
+            attribute_rules: attribute_rules.clone(),
             write_errors: true,
             silent_on_written_errors: true,
             ok_on_written_errors: true,
             open_if_changed,
             no_commit: true,
             no_commit_errors: true, // but not committing anyway
-            no_branch_check: true,  // ?
+            no_push_on_error: true,
+            no_branch_check: true, // ?
             open,
+            explain_index,
+            short_paths,
+            output_files: output_files.clone(),
+            extensions: extensions.clone(),
+            sample: None,
+            version_notes: false,
+            signoff: false,
+            gpg_sign_arg: None,
+            commit_trailers: Vec::new(),
+            pretty_html: false,
+            no_index_section: false,
+            diff_output: false,
+            group_by: BoxGrouping::Folder,
+            max_output_size: Some(MAX_OUTPUT_SIZE_DEFAULT),
+            no_toc: false,
+            mini_toc: false,
+            toc_fragment: false,
+            index_layout: IndexLayout::Dl,
+            since_last_build_state: None,
+            output_sink: None,
+            progress: Arc::new(NoProgress),
         },
         &git_log_version_checker,
         &xmlhub_checkouts[0],
@@ -2018,34 +4914,339 @@ fn check_command(program_version: GitVersion<SemVersion>, check_opts: CheckOpts)
 
     // Now check the given paths explicitly.
     let fileinfo_or_errors: Vec<Result<FileInfo<WithExtractedValues>, FileErrors>> =
-        read_file_infos(paths);
-    let mut exit_code = 0;
-    let mut err = stderr().lock();
-    let mut hints = Hints::new("checkerror");
-    for fileinfo_or_error in fileinfo_or_errors {
-        match fileinfo_or_error {
-            Ok(fileinfo) => {
-                writeln!(
-                    &mut err,
-                    "    For {:?}: no errors",
-                    fileinfo.path.rel_path()
-                )?;
+        read_file_infos(paths, &attribute_rules, &NoProgress);
+    let issue_classes = if only_errors {
+        IssueClasses::ErrorsOnly
+    } else if only_warnings {
+        IssueClasses::WarningsOnly
+    } else {
+        IssueClasses::Both
+    };
+    let exit_code = print_check_report(&fileinfo_or_errors, issue_classes, &mut stderr().lock())?;
+    std::process::exit(exit_code);
+}
+
+/// Which kinds of issues `check` prints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IssueClasses {
+    Both,
+    ErrorsOnly,
+    WarningsOnly,
+}
+
+/// Print the errors and/or warnings (as selected by `issue_classes`)
+/// for the files checked by `check`. Returns the exit code, which is
+/// 1 if there were errors, whether printed or not.
+fn print_check_report(
+    fileinfo_or_errors: &[Result<FileInfo<WithExtractedValues>, FileErrors>],
+    issue_classes: IssueClasses,
+    out: &mut impl Write,
+) -> Result<i32> {
+    let have_errors = fileinfo_or_errors.iter().any(|r| r.is_err());
+
+    if issue_classes != IssueClasses::WarningsOnly {
+        let mut hints = Hints::with_messages(
+            "checkerror",
+            issues_hints(fileinfo_or_errors.iter().filter_map(|r| r.as_ref().err())),
+        );
+        for fileinfo_or_error in fileinfo_or_errors {
+            match fileinfo_or_error {
+                Ok(fileinfo) => {
+                    writeln!(out, "    For {:?}: no errors", fileinfo.path.rel_path())?;
+                }
+                Err(e) => {
+                    e.print_plain(&mut hints, out)?;
+                }
             }
-            Err(e) => {
-                exit_code = 1;
-                e.print_plain(&mut hints, &mut err)?;
+        }
+        hints.print_plain(&mut *out)?;
+    }
+
+    if issue_classes != IssueClasses::ErrorsOnly {
+        let warningss: Vec<FileWarnings> = fileinfo_or_errors
+            .iter()
+            .filter_map(|r| r.as_ref().ok()?.opt_warnings())
+            .collect();
+        if !warningss.is_empty() {
+            writeln!(out, "\nWarnings:")?;
+            let mut hints = Hints::with_messages("checkwarning", issues_hints(&warningss));
+            for warnings in &warningss {
+                warnings.print_plain(&mut hints, out)?;
             }
+            hints.print_plain(&mut *out)?;
         }
     }
-    hints.print_plain(&mut err)?;
-    std::process::exit(exit_code);
+
+    Ok(if have_errors { 1 } else { 0 })
+}
+
+/// The version of the format of the JSON output of `validate
+/// --json`. Only increase it for changes that are incompatible for
+/// consumers (removed or changed fields, not added ones).
+const VALIDATION_REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// The result of a `validate` run.
+#[derive(Debug, serde::Serialize)]
+struct ValidationReport {
+    /// Always `VALIDATION_REPORT_SCHEMA_VERSION`
+    schema_version: u32,
+    number_of_files: usize,
+    errors: Vec<OwnedFileIssues>,
+    warnings: Vec<OwnedFileIssues>,
+}
+
+impl ValidationReport {
+    fn exit_code(&self) -> i32 {
+        if self.errors.is_empty() {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// The report as JSON, with the keys of all objects sorted, so
+    /// that the output is deterministic.
+    fn to_json(&self) -> Result<String> {
+        // Going via `Value` sorts the keys, since its maps are
+        // `BTreeMap`s (serde_json's `preserve_order` feature is not
+        // used)
+        let value = serde_json::to_value(self)?;
+        Ok(serde_json::to_string_pretty(&value)?)
+    }
+
+    fn print_plain<O: Write>(&self, out: &mut O) -> Result<()> {
+        let Self {
+            schema_version: _,
+            number_of_files,
+            errors,
+            warnings,
+        } = self;
+        if !errors.is_empty() {
+            writeln!(out, "\nValidation errors:")?;
+            let mut hints = Hints::with_messages("validationerrors", issues_hints(errors));
+            for file_errors in errors {
+                file_errors.print_plain(&mut hints, out)?
+            }
+            hints.print_plain(&mut *out)?;
+        }
+        if !warnings.is_empty() {
+            writeln!(out, "\nValidation warnings:")?;
+            let mut hints = Hints::with_messages("validationwarnings", issues_hints(warnings));
+            for file_warnings in warnings {
+                file_warnings.print_plain(&mut hints, out)?
+            }
+            hints.print_plain(&mut *out)?;
+        }
+        pluralized! { *number_of_files => files }
+        writeln!(
+            out,
+            "\nValidated {number_of_files} {files}: {} with errors, {} with warnings.",
+            errors.len(),
+            warnings.len()
+        )?;
+        Ok(())
+    }
+}
+
+/// Read and check all XML files in `git_working_dir` (those with the
+/// configured extensions, or `extensions` if given), and against
+/// `schema` if given, without writing anything.
+fn validate(
+    git_working_dir: &GitWorkingDir,
+    ignore_untracked: bool,
+    extensions: Option<XmlExtensions>,
+    schema: Option<&Schema>,
+) -> Result<ValidationReport> {
+    let mut config = XmlhubConfig::read(git_working_dir.working_dir_path_ref())?;
+    if let Some(extensions) = extensions {
+        config.extensions = extensions;
+    }
+    let XmlFilePaths { paths, .. } = xml_file_paths_with_max_depth(
+        git_working_dir,
+        ignore_untracked,
+        false,
+        None,
+        &config.extensions,
+    )?;
+    let number_of_files = paths.len();
+    let attribute_rules = AttributeRules::default().with_config(&config);
+    let (file_infos, file_errorss) = read_and_derive_file_infos(paths, &attribute_rules);
+    let mut errors: Vec<OwnedFileIssues> = file_errorss
+        .into_iter()
+        .map(OwnedFileIssues::from)
+        .collect();
+    if let Some(schema) = schema {
+        for file_info in &file_infos {
+            let issue = match read_xml_file(&file_info.path.full_path()) {
+                Ok(xmldocument) => match schema.validate(xmldocument.document()) {
+                    Ok(()) => continue,
+                    Err(SchemaError { message, location }) => Issue {
+                        message: format!("does not conform to the schema: {message}"),
+                        hint: None,
+                        location: Some(location),
+                    },
+                },
+                Err(e) => Issue {
+                    message: format!("could not be read for checking against the schema: {e:#}"),
+                    hint: None,
+                    location: None,
+                },
+            };
+            errors.push(OwnedFileIssues {
+                path: file_info.path.rel_path().into(),
+                issues: vec![issue],
+            });
+        }
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    let warnings = file_infos
+        .into_iter()
+        .filter(|file_info| !file_info.warnings.is_empty())
+        .map(|file_info| OwnedFileIssues {
+            path: file_info.path.rel_path().into(),
+            issues: file_info.warnings,
+        })
+        .collect();
+    Ok(ValidationReport {
+        schema_version: VALIDATION_REPORT_SCHEMA_VERSION,
+        number_of_files,
+        errors,
+        warnings,
+    })
+}
+
+/// Execute a `validate` command.
+fn validate_command(command_opts: ValidateOpts) -> Result<()> {
+    let ValidateOpts {
+        quietness,
+        extensions,
+        no_repo_check,
+        ignore_untracked,
+        json,
+        schema,
+        base_path,
+    } = command_opts;
+
+    let schema = schema.as_deref().map(Schema::from_file).transpose()?;
+
+    let no_repo_check = typed_from_no_repo_check(no_repo_check);
+
+    let xmlhub_checkout: CheckedCheckoutContext1<Cow<Path>> = if let Some(base_path) = base_path {
+        XMLHUB_CHECKOUT
+            .replace_working_dir_path(expand_path(&base_path)?.into())
+            .check1(no_repo_check)?
+    } else {
+        XMLHUB_CHECKOUT.checked_from_subpath(*CURRENT_DIRECTORY, no_repo_check, false)?
+    };
+
+    let report = validate(
+        &xmlhub_checkout.git_working_dir(),
+        ignore_untracked,
+        extensions.xml_extensions()?,
+        schema.as_ref(),
+    )?;
+
+    if json {
+        let mut out = stdout().lock();
+        writeln!(&mut out, "{}", report.to_json()?)?;
+    } else if !(quietness.quiet() && report.errors.is_empty() && report.warnings.is_empty()) {
+        report
+            .print_plain(&mut stderr().lock())
+            .context("writing to stderr")?;
+    }
+    std::process::exit(report.exit_code());
+}
+
+/// Write the attributes file (as `build` does) into the working
+/// directory at `working_dir_path`, returning its path.
+fn write_attributes_file(working_dir_path: &Path, output_files: &OutputFiles) -> Result<PathBuf> {
+    let path = working_dir_path.join(&*output_files.attributes_file.path_from_repo_top);
+    std::fs::write(&path, make_attributes_md(true)?.to_string())
+        .with_context(|| anyhow!("writing to file {path:?}"))?;
+    Ok(path)
+}
+
+/// Execute a `gen-attributes` command
+fn gen_attributes_command(command_opts: GenAttributesOpts) -> Result<()> {
+    let GenAttributesOpts {
+        quietness,
+        output_files,
+        no_repo_check,
+        base_path,
+    } = command_opts;
+
+    let output_files = output_files.output_files()?;
+    let no_repo_check = typed_from_no_repo_check(no_repo_check);
+
+    let xmlhub_checkout: CheckedCheckoutContext1<Cow<Path>> = if let Some(base_path) = base_path {
+        XMLHUB_CHECKOUT
+            .replace_working_dir_path(expand_path(&base_path)?.into())
+            .check1(no_repo_check)?
+    } else {
+        XMLHUB_CHECKOUT.checked_from_subpath(*CURRENT_DIRECTORY, no_repo_check, false)?
+    };
+
+    let path = write_attributes_file(xmlhub_checkout.working_dir_path(), &output_files)?;
+    if !quietness.quiet() {
+        println!("Wrote {path:?}");
+    }
+    Ok(())
 }
 
 struct PreparedFile {
     content: String,
     content_has_changed: bool,
-    #[allow(unused)]
     data_was_removed: bool,
+    /// The number of `<sequence>` elements blinded or removed.
+    sequences_removed: usize,
+}
+
+/// Totals over the files processed by a `prepare` or `add-to`
+/// command.
+#[derive(Debug, Default, PartialEq)]
+struct PrepareSummary {
+    files: usize,
+    blinded: usize,
+    sequences_removed: usize,
+    unchanged: usize,
+}
+
+impl PrepareSummary {
+    fn new<'p>(prepared_files: impl IntoIterator<Item = &'p PreparedFile>) -> Self {
+        let mut summary = Self::default();
+        for prepared_file in prepared_files {
+            summary.files += 1;
+            if prepared_file.data_was_removed {
+                summary.blinded += 1;
+            }
+            summary.sequences_removed += prepared_file.sequences_removed;
+            if !prepared_file.content_has_changed {
+                summary.unchanged += 1;
+            }
+        }
+        summary
+    }
+
+    /// The summary line shown to the user.
+    fn message(&self) -> Result<String> {
+        let Self {
+            files: n_files,
+            blinded: n_blinded,
+            sequences_removed: n_sequences,
+            unchanged: n_unchanged,
+        } = *self;
+        pluralized! { n_files => files }
+        pluralized! { n_sequences => sequences }
+        tr!(
+            "prepare.summary",
+            n_files = n_files,
+            files = files,
+            n_blinded = n_blinded,
+            n_sequences = n_sequences,
+            sequences = sequences,
+            n_unchanged = n_unchanged,
+        )
+    }
 }
 
 /// Subset of `PrepareOpts`
@@ -2054,6 +5255,89 @@ struct PrepareFileOpts<'t> {
     blinding: &'t BlindingOpts,
     ignore_version: bool,
     quiet: bool,
+    /// Print informational messages to stderr instead of stdout
+    /// (used when stdout is taken by the prepared content).
+    messages_to_stderr: bool,
+}
+
+/// Blind (delete) the sequence data in `modified_document` (made
+/// from `xmldocument`): clear the `value` attributes of the
+/// `<sequence>` elements, or with `blind_all` the whole contents of
+/// the `<data>` elements, and put `blind_comment` (or the default
+/// comment) above the `<data>` elements. Returns whether data was
+/// removed, and the number of sequences removed.
+fn blind_document<'d>(
+    xmldocument: &XMLDocument,
+    modified_document: &mut ModifiedXMLDocument<'d>,
+    blind_all: bool,
+    blind_comment: Option<&'d str>,
+) -> (bool, usize) {
+    let data_was_removed;
+    // Clear the sub-elements, without adding a comment
+    let n_sequences_blinded = if blind_all {
+        // Do blinding below by removing all of "data" element's
+        // contents, instead.
+        0
+    } else {
+        let actions = &[ClearAction::Attribute {
+            name: "value",
+            replacement: "-",
+        }];
+        // XX should the code check that the `beast > data > sequence`
+        // nesting is upheld? This doesn't.
+        modified_document.clear_elements_named(
+            SEQUENCES_ELEMENT_NAME,
+            &ClearElementsOpts {
+                comment_and_indent: None,
+                always_add_comment: false,
+                actions,
+            },
+        )
+    };
+
+    // Add comment or also clear the element if --blind-all given
+    if n_sequences_blinded > 0 || blind_all {
+        let comment = blind_comment.unwrap_or(DEFAULT_COMMENT_FOR_BLINDED_DATA);
+        // No actions by default, to just add the comments
+        let mut actions = Vec::new();
+        if blind_all {
+            actions.push(ClearAction::Element {
+                treat_whitespace_as_empty: true,
+            });
+        }
+        let n = modified_document.clear_elements_named(
+            "data",
+            &ClearElementsOpts {
+                comment_and_indent: Some((comment, "    ")),
+                // If we're actually blinding here, don't always
+                // add the comment. BTW note: there's a small bug
+                // in that blinding normally first then with
+                // --blind-all adds the comment twice to the
+                // file. I guess that's fitting :)
+                always_add_comment: !blind_all,
+                actions: &actions,
+            },
+        );
+        if blind_all {
+            data_was_removed = n > 0;
+        } else {
+            data_was_removed = n_sequences_blinded > 0;
+        }
+    } else {
+        data_was_removed = n_sequences_blinded > 0;
+    }
+    let sequences_removed = if !data_was_removed {
+        0
+    } else if blind_all {
+        // The sequences went with the contents of the data
+        // elements
+        xmldocument
+            .elements_named(SEQUENCES_ELEMENT_NAME, usize::MAX)
+            .len()
+    } else {
+        n_sequences_blinded
+    };
+    (data_was_removed, sequences_removed)
 }
 
 /// Returns the converted file contents, and what changed. Errors
@@ -2070,24 +5354,41 @@ fn prepare_file(opts: PrepareFileOpts) -> Result<PreparedFile> {
             },
         ignore_version,
         quiet,
+        messages_to_stderr,
     } = opts;
 
+    let message = |msg: String| {
+        if !quiet {
+            if messages_to_stderr {
+                eprintln!("{msg}");
+            } else {
+                println!("{msg}");
+            }
+        }
+    };
+
     let xmldocument = read_xml_file(source_path)
         .with_context(|| anyhow!("loading the XML file {source_path:?}"))?;
+    if let Some(encoding) = xmldocument.transcoded_from() {
+        // The output would be written as UTF-8 but still declare
+        // the original encoding
+        bail!(
+            "the XML file {source_path:?} is encoded in {encoding}, please convert it \
+             to UTF-8 first"
+        )
+    }
 
     let beast_version = check_beast_version(xmldocument.document(), source_path, ignore_version)
         .with_context(|| anyhow!("preparing the file from {source_path:?}"))?;
 
     let mut modified_document = ModifiedXMLDocument::new(&xmldocument);
 
-    let document_has_headers = match parse_comments(xmldocument.header_comments(), true) {
-        Ok(_) => true,
-        Err(_) => false,
-    };
+    let document_has_headers = parse_header(&xmldocument, true, &[], None, None).is_ok();
     if document_has_headers {
-        if !quiet {
-            println!("This document already has header comments: {source_path:?}");
-        }
+        message(tr!(
+            "prepare.already-has-headers",
+            path = format!("{source_path:?}")
+        )?);
     } else {
         // Add header template
         let the_top = modified_document
@@ -2115,8 +5416,10 @@ fn prepare_file(opts: PrepareFileOpts) -> Result<PreparedFile> {
 
     // Optionally, delete (blind) data
     let data_was_removed;
+    let sequences_removed;
     if *no_blind {
         data_was_removed = false;
+        sequences_removed = 0;
     } else {
         if beast_version.product != BeastProductVersion::Two {
             bail!(
@@ -2128,79 +5431,27 @@ fn prepare_file(opts: PrepareFileOpts) -> Result<PreparedFile> {
             )
         }
 
-        let n_sequences_blinded;
-        // Clear the sub-elements, without adding a comment
-        if *blind_all {
-            // Do blinding below by removing all of "data" element's
-            // contents, instead.
-            n_sequences_blinded = 0;
-        } else {
-            let actions = &[ClearAction::Attribute {
-                name: "value",
-                replacement: "-",
-            }];
-            // XX should the code check that the `beast > data > sequence`
-            // nesting is upheld? This doesn't.
-            n_sequences_blinded = modified_document.clear_elements_named(
-                SEQUENCES_ELEMENT_NAME,
-                &ClearElementsOpts {
-                    comment_and_indent: None,
-                    always_add_comment: false,
-                    actions,
-                },
-            );
-        }
-
-        // Add comment or also clear the element if --blind-all given
-        if n_sequences_blinded > 0 || *blind_all {
-            let comment = blind_comment
-                .as_ref()
-                .map(|s| s.as_str())
-                .unwrap_or(DEFAULT_COMMENT_FOR_BLINDED_DATA);
-            // No actions by default, to just add the comments
-            let mut actions = Vec::new();
-            if *blind_all {
-                actions.push(ClearAction::Element {
-                    treat_whitespace_as_empty: true,
-                });
-            }
-            let n = modified_document.clear_elements_named(
-                "data",
-                &ClearElementsOpts {
-                    comment_and_indent: Some((comment, "    ")),
-                    // If we're actually blinding here, don't always
-                    // add the comment. BTW note: there's a small bug
-                    // in that blinding normally first then with
-                    // --blind-all adds the comment twice to the
-                    // file. I guess that's fitting :)
-                    always_add_comment: !*blind_all,
-                    actions: &actions,
-                },
-            );
-            if *blind_all {
-                data_was_removed = n > 0;
-            } else {
-                data_was_removed = n_sequences_blinded > 0;
-            }
-        } else {
-            data_was_removed = n_sequences_blinded > 0;
-        }
+        (data_was_removed, sequences_removed) = blind_document(
+            &xmldocument,
+            &mut modified_document,
+            *blind_all,
+            blind_comment.as_deref(),
+        );
     }
 
     let (content, content_has_changed) = modified_document.to_string_and_modified()?;
 
-    if data_was_removed && !quiet {
+    if data_was_removed {
         let more = if *blind_all {
-            ""
+            String::new()
         } else {
-            " Note that sequence metadata has been retained and is assumed \
-             to not be privacy sensitive; use `--blind-all` if you want to \
-             remove that, too!"
+            tr!("prepare.sequences-removed-more")?
         };
-        println!(
-            "NOTE: sequences from this document have been removed \
-             (use `--no-blind` to keep them!{more}): {source_path:?}"
-        );
+        message(tr!(
+            "prepare.sequences-removed",
+            more = more,
+            path = format!("{source_path:?}")
+        )?);
     }
 
     let len = modified_document.len()?;
@@ -2219,17 +5470,116 @@ fn prepare_file(opts: PrepareFileOpts) -> Result<PreparedFile> {
         content,
         content_has_changed,
         data_was_removed,
+        sequences_removed,
     })
 }
 
-/// Execute a `prepare` command.
-fn prepare_command(command_opts: PrepareOpts) -> Result<()> {
+/// If `--no-trash` was given and any of the `target_paths` exist,
+/// ask the user whether they really want to overwrite them (unless
+/// `--yes` was given, or `force_implies_yes` is true). Returns
+/// whether the trash should be used.
+fn confirm_no_trash<'p>(
+    trash: &TrashOpts,
+    force_implies_yes: bool,
+    target_paths: impl IntoIterator<Item = &'p Path>,
+) -> Result<bool> {
+    let TrashOpts { no_trash, yes } = trash;
+    if !no_trash {
+        return Ok(true);
+    }
+    if *yes || force_implies_yes {
+        return Ok(false);
+    }
+    let existing_target_paths: Vec<&Path> = target_paths
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect();
+    if existing_target_paths.is_empty() {
+        return Ok(false);
+    }
+    pluralized! { existing_target_paths.len() => these, files }
+    if !ask_yn(&format!(
+        "Overwrite {these} {files} without moving the originals to the trash? \n   {}\n",
+        existing_target_paths
+            .iter()
+            .map(|s| format!("{s:?}"))
+            .join("\n   ")
+    ))? {
+        bail!("cancelled by user")
+    }
+    Ok(false)
+}
+
+/// Refuse the files at `paths` if they don't have one of the
+/// `extensions`, since they would not be indexed.
+fn check_xml_extensions(paths: &[PathBuf], extensions: &XmlExtensions) -> Result<()> {
+    for path in paths {
+        if !extensions.matches(path) {
+            bail!(
+                "the file {path:?} does not have a {extensions} suffix, thus would not be \
+                 indexed (see the `--extensions` option and the `extensions` setting in \
+                 {CONFIG_FILE_PATH})"
+            )
+        }
+    }
+    Ok(())
+}
+
+/// The XML file extensions configured in the Git working directory
+/// that contains `path`, or the default ones if `path` is not inside
+/// one.
+fn xml_extensions_for_file(path: &Path) -> Result<XmlExtensions> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match nearest_git_root(dir) {
+        Some(root) => Ok(XmlhubConfig::read(&root)?.extensions),
+        None => Ok(XmlExtensions::default()),
+    }
+}
+
+/// Execute a `prepare` command. In `--stdout` mode, the prepared
+/// content is written to `out`.
+fn prepare_command(command_opts: PrepareOpts, out: &mut dyn Write) -> Result<()> {
     let PrepareOpts {
         quietness,
         files_to_prepare,
         blinding,
+        trash,
+        extensions,
         ignore_version,
+        stdout: to_stdout,
     } = command_opts;
+    match extensions.xml_extensions()? {
+        Some(extensions) => check_xml_extensions(&files_to_prepare, &extensions)?,
+        None => {
+            for path in &files_to_prepare {
+                check_xml_extensions(std::slice::from_ref(path), &xml_extensions_for_file(path)?)?;
+            }
+        }
+    }
+
+    if to_stdout {
+        let source_path = match files_to_prepare.as_slice() {
+            [source_path] => source_path,
+            _ => bail!(
+                "the `--stdout` option requires exactly one file to be given, \
+                 got {}",
+                files_to_prepare.len()
+            ),
+        };
+        let prepared_file = prepare_file(PrepareFileOpts {
+            source_path,
+            blinding: &blinding,
+            ignore_version,
+            quiet: quietness.quiet(),
+            messages_to_stderr: true,
+        })?;
+        out.write_all(prepared_file.content.as_bytes())?;
+        out.flush()?;
+        return Ok(());
+    }
 
     // First, convert them all without writing them out, to avoid
     // writing only some of them (which would then exist when
@@ -2245,23 +5595,120 @@ fn prepare_command(command_opts: PrepareOpts) -> Result<()> {
                     blinding: &blinding,
                     ignore_version,
                     quiet: quietness.quiet(),
+                    messages_to_stderr: false,
                 })?,
             ))
         })
         .collect::<Result<_>>()?;
 
+    let use_trash = confirm_no_trash(
+        &trash,
+        false,
+        converted
+            .iter()
+            .filter(|(_, prepared_file)| prepared_file.content_has_changed)
+            .map(|(path, _)| path.as_path()),
+    )?;
+
     // Now that all files were read and converted successfully, write
     // them out. With regards to IO, only writing happens here.
-    for (target_path, prepared_file) in converted {
+    for (target_path, prepared_file) in &converted {
         if prepared_file.content_has_changed {
-            write_file_moving_to_trash_if_exists(
+            write_file_optionally_moving_to_trash(
                 &target_path,
                 &prepared_file.content,
+                use_trash,
+                quietness.quiet(),
+            )?;
+        } else {
+            if !quietness.quiet() {
+                println!(
+                    "{}",
+                    tr!("prepare.unchanged", path = format!("{target_path:?}"))?
+                );
+            }
+        }
+    }
+    if !quietness.quiet() {
+        let summary = PrepareSummary::new(converted.iter().map(|(_, prepared_file)| prepared_file));
+        println!("{}", summary.message()?);
+    }
+    Ok(())
+}
+
+/// Returns the file contents with the metadata (comments and
+/// `<?xmlhub ...?>` processing instructions) rewritten into canonical
+/// comments, and whether that differs from the
+/// original. Errors already mention the `source_path`.
+fn normalize_file(source_path: &Path) -> Result<(String, bool)> {
+    let xmldocument = read_xml_file(source_path)
+        .with_context(|| anyhow!("loading the XML file {source_path:?}"))?;
+    if let Some(encoding) = xmldocument.transcoded_from() {
+        bail!(
+            "the XML file {source_path:?} is encoded in {encoding}, please convert it \
+             to UTF-8 first"
+        )
+    }
+    let metadata = parse_header(&xmldocument, false, &[], None, None).map_err(|issues| {
+        anyhow!(
+            "the metadata of the XML file {source_path:?} has errors, please fix them \
+             first (see `xmlhub check`):\n  {}",
+            issues.iter().map(|issue| &issue.message).join("\n  ")
+        )
+    })?;
+    let comments: Vec<String> = METADATA_SPECIFICATION
+        .iter()
+        .filter_map(|spec| {
+            let value = metadata.get(spec.key)?;
+            Some(format!(
+                "{}: {}",
+                spec.key.as_ref(),
+                value.to_comment_value()
+            ))
+        })
+        .collect();
+    let mut modified_document = ModifiedXMLDocument::new(&xmldocument);
+    modified_document.replace_header_metadata(&comments, "", METADATA_PI_TARGET);
+    modified_document.to_string_and_modified()
+}
+
+/// Execute a `normalize` command.
+fn normalize_command(command_opts: NormalizeOpts) -> Result<()> {
+    let NormalizeOpts {
+        quietness,
+        trash,
+        files_to_normalize,
+    } = command_opts;
+
+    // As in `prepare_command`, first convert all files, then write.
+    let converted: Vec<(&PathBuf, (String, bool))> = files_to_normalize
+        .iter()
+        .map(|source_path| Ok((source_path, normalize_file(source_path)?)))
+        .collect::<Result<_>>()?;
+
+    let use_trash = confirm_no_trash(
+        &trash,
+        false,
+        converted
+            .iter()
+            .filter(|(_, (_, content_has_changed))| *content_has_changed)
+            .map(|(path, _)| path.as_path()),
+    )?;
+
+    for (target_path, (content, content_has_changed)) in converted {
+        if content_has_changed {
+            write_file_optionally_moving_to_trash(
+                target_path,
+                &content,
+                use_trash,
                 quietness.quiet(),
             )?;
         } else {
             if !quietness.quiet() {
-                println!("File is unchanged (already prepared): {target_path:?}");
+                println!(
+                    "{}",
+                    tr!("normalize.unchanged", path = format!("{target_path:?}"))?
+                );
             }
         }
     }
@@ -2274,6 +5721,8 @@ fn add_to_command(program_version: GitVersion<SemVersion>, command_opts: AddToOp
         versioncheck: VersionCheckOpt { no_version_check },
         quietness,
         blinding,
+        trash,
+        extensions,
         target_directory,
         files_to_add,
         mkdir,
@@ -2286,9 +5735,11 @@ fn add_to_command(program_version: GitVersion<SemVersion>, command_opts: AddToOp
     // boolen is never used directly.)
     let no_repo_check = typed_from_no_repo_check(no_repo_check);
 
-    let target_directory = target_directory
-        .as_ref()
-        .ok_or_else(|| anyhow!("missing TARGET_DIRECTORY argument. Run --help for help."))?;
+    let target_directory = &expand_path(
+        target_directory
+            .as_ref()
+            .ok_or_else(|| anyhow!("missing TARGET_DIRECTORY argument. Run --help for help."))?,
+    )?;
 
     if !target_directory.is_dir() {
         if mkdir {
@@ -2307,6 +5758,11 @@ fn add_to_command(program_version: GitVersion<SemVersion>, command_opts: AddToOp
     let xmlhub_checkout = XMLHUB_CHECKOUT
         .checked_from_subpath(&target_directory, no_repo_check, false)
         .with_context(|| anyhow!("checking target directory {target_directory:?}"))?;
+    let extensions = match extensions.xml_extensions()? {
+        Some(extensions) => extensions,
+        None => XmlhubConfig::read(xmlhub_checkout.working_dir_path())?.extensions,
+    };
+    check_xml_extensions(&files_to_add, &extensions)?;
 
     // Check that this program is up to date, which matters because
     // otherwise it might add the wrong fields.
@@ -2314,17 +5770,18 @@ fn add_to_command(program_version: GitVersion<SemVersion>, command_opts: AddToOp
         program_version,
         no_version_check,
         xmlhub_checkout.git_working_dir().into(),
+        (&DEFAULT_OUTPUT_FILES).into(),
     );
     git_log_version_checker.check_git_log()?;
 
     if files_to_add.is_empty() {
         if !quietness.quiet() {
-            println!("No files given, thus nothing to do.");
+            println!("{}", tr!("add-to.no-files")?);
         }
     } else {
         pluralized! { files_to_add.len() => files }
         if !quietness.quiet() {
-            println!("Reading the {files}...");
+            println!("{}", tr!("add-to.reading", files = files)?);
         }
 
         // First, convert them all without writing them out, to avoid
@@ -2341,6 +5798,7 @@ fn add_to_command(program_version: GitVersion<SemVersion>, command_opts: AddToOp
                         blinding: &blinding,
                         ignore_version,
                         quiet: quietness.quiet(),
+                        messages_to_stderr: false,
                     })?,
                 ))
             })
@@ -2378,8 +5836,14 @@ fn add_to_command(program_version: GitVersion<SemVersion>, command_opts: AddToOp
             }
         }
 
+        let use_trash = confirm_no_trash(
+            &trash,
+            force,
+            outputs.iter().map(|(path, _)| path.as_path()),
+        )?;
+
         if !quietness.quiet() {
-            println!("Writing the {files}...");
+            println!("{}", tr!("add-to.writing", files = files)?);
         }
 
         // Now that all files were read, converted and target-checked
@@ -2391,26 +5855,31 @@ fn add_to_command(program_version: GitVersion<SemVersion>, command_opts: AddToOp
             // file even if no modification is carried out at the same
             // time!
 
-            // Keep existing files in trash, even with --force?
-            write_file_moving_to_trash_if_exists(
+            // Keep existing files in trash, even with --force
+            // (unless --no-trash is given).
+            write_file_optionally_moving_to_trash(
                 target_path,
                 &prepared_file.content,
+                use_trash,
                 quietness.quiet(),
             )?;
         }
 
         if !quietness.quiet() {
+            let summary =
+                PrepareSummary::new(outputs.iter().map(|(_, prepared_file)| prepared_file));
+            println!("{}", summary.message()?);
             println!(
-                "Done.\n\
-                 Now edit the new {files} in {target_directory:?} to complete \
-                 the metadata:\n   \
-                 {}\n\
-                 Run `xmlhub help-attributes` to learn about what to enter into \
-                 the individual fields.",
-                outputs
-                    .iter()
-                    .map(|(target_path, _prepared_file)| format!("{target_path:?}"))
-                    .join("\n   ")
+                "{}",
+                tr!(
+                    "add-to.done",
+                    files = files,
+                    directory = format!("{target_directory:?}"),
+                    paths = outputs
+                        .iter()
+                        .map(|(target_path, _prepared_file)| format!("{target_path:?}"))
+                        .join("\n   ")
+                )?
             );
         }
     }
@@ -2450,30 +5919,76 @@ fn run() -> Result<Option<ExecutionResult>> {
         }
 
         match command {
-            Command::Build(BuildOpts {
-                dryness,
-                verbosity,
-                versioncheck: VersionCheckOpt { no_version_check },
-                quietness: quietness_,
-                write_errors: write_errors_,
-                no_commit_errors: no_commit_errors_,
-                ok_on_written_errors,
-                silent_on_written_errors: silent_on_written_errors_,
-                open,
-                open_if_changed,
-                pull: pull_,
-                no_commit: no_commit_,
-                push: push_,
-                batch: batch_,
-                no_branch_check,
-                daemon,
-                daemon_sleep_time,
-                base_path,
-                ignore_untracked,
-                no_repo_check,
-                daemon_opts,
-                limit_as,
-            }) => {
+            Command::Build(build_opts) => {
+                let BuildOpts {
+                    dryness,
+                    verbosity,
+                    versioncheck: VersionCheckOpt { no_version_check },
+                    quietness: quietness_,
+                    write_errors: write_errors_,
+                    no_commit_errors: no_commit_errors_,
+                    no_push_on_error,
+                    summary_line,
+                    since_last_build,
+                    ok_on_written_errors,
+                    silent_on_written_errors: silent_on_written_errors_,
+                    open,
+                    open_if_changed,
+                    open_existing,
+                    pull: pull_,
+                    no_commit: no_commit_,
+                    push: push_,
+                    batch: batch_,
+                    no_branch_check,
+                    daemon,
+                    daemon_sleep_time,
+                    daemon_max_sleep_time,
+                    max_consecutive_failures,
+                    base_path,
+                    ignore_untracked,
+                    follow_symlinks,
+                    print_paths,
+                    count_only,
+                    count_by,
+                    scan_max_depth,
+                    refuse_on_divergence,
+                    strict_duplicate_paths,
+                    standalone,
+                    explain,
+                    output_dir,
+                    link_base,
+                    from_ref,
+                    require_attributes,
+                    lenient_unknown_attributes,
+                    warn_duplicate_list_items,
+                    show_file_comments,
+                    strict_version_range,
+                    no_repo_check,
+                    daemon_opts,
+                    limit_as,
+                    explain_index,
+                    short_paths,
+                    output_files,
+                    extensions,
+                    sample,
+                    version_notes,
+                    signoff,
+                    sign_commits,
+                    signing_key,
+                    co_author,
+                    strict_version_check,
+                    pretty_html,
+                    compact_html,
+                    self_test,
+                    no_index_section,
+                    diff_output,
+                    group_by,
+                    max_output_size,
+                    no_toc,
+                    mini_toc,
+                    toc_fragment,
+                    index_layout,
+                } = *build_opts;
                 // Create uninitialized variables without the underscores,
                 // then initialize them differently depending on some of the
                 // options (--batch, --daemon).
@@ -2522,30 +6037,75 @@ fn run() -> Result<Option<ExecutionResult>> {
                 Opts {
                     v,
                     version_only,
-                    command: Command::Build(BuildOpts {
+                    command: Command::Build(Box::new(BuildOpts {
                         dryness,
                         verbosity,
                         versioncheck: VersionCheckOpt { no_version_check },
                         quietness,
                         write_errors,
                         no_commit_errors,
+                        no_push_on_error,
+                        summary_line,
+                        since_last_build,
                         ok_on_written_errors,
                         silent_on_written_errors,
                         open,
                         open_if_changed,
+                        open_existing,
                         pull,
                         no_commit,
                         push,
                         batch,
                         daemon,
                         daemon_sleep_time,
+                        daemon_max_sleep_time,
+                        max_consecutive_failures,
                         no_branch_check,
                         ignore_untracked,
+                        follow_symlinks,
+                        print_paths,
+                        count_only,
+                        count_by,
+                        scan_max_depth,
+                        refuse_on_divergence,
+                        strict_duplicate_paths,
+                        standalone,
+                        explain,
+                        output_dir,
+                        link_base,
+                        from_ref,
+                        require_attributes,
+                        lenient_unknown_attributes,
+                        warn_duplicate_list_items,
+                        show_file_comments,
+                        strict_version_range,
                         base_path,
                         no_repo_check,
                         daemon_opts,
                         limit_as,
-                    }),
+                        explain_index,
+                        short_paths,
+                        output_files,
+                        extensions,
+                        sample,
+                        version_notes,
+                        signoff,
+                        sign_commits,
+                        signing_key,
+                        co_author,
+                        strict_version_check,
+                        pretty_html,
+                        compact_html,
+                        self_test,
+                        no_index_section,
+                        diff_output,
+                        group_by,
+                        max_output_size,
+                        no_toc,
+                        mini_toc,
+                        toc_fragment,
+                        index_layout,
+                    })),
                 }
             }
             Command::Install(_)
@@ -2553,10 +6113,13 @@ fn run() -> Result<Option<ExecutionResult>> {
             | Command::CloneTo(_)
             | Command::Prepare(_)
             | Command::AddTo(_)
+            | Command::Normalize(_)
+            | Command::GenAttributes(_)
             | Command::Docs
             | Command::HelpContributing
             | Command::HelpAttributes(_)
             | Command::Check(_)
+            | Command::Validate(_)
             | Command::Changelog(_)
             | Command::Completions { shell: _ } => Opts {
                 v,
@@ -2581,18 +6144,23 @@ fn run() -> Result<Option<ExecutionResult>> {
         Command::HelpAttributes(command_opts) => {
             ur(help_attributes_command(command_opts, program_version))
         }
-        Command::Changelog(command_opts) => ur(changelog_command(command_opts)),
+        Command::Changelog(command_opts) => ur(changelog_command(program_version, command_opts)),
         Command::Install(command_opts) => ur(install_command(command_opts)),
         Command::Upgrade(command_opts) => ur(upgrade_command(program_version, command_opts)),
         Command::CloneTo(command_opts) => ur(clone_to_command(program_version, command_opts)),
         Command::Prepare(command_opts) => {
             // `prepare` can't check `program_version` as it is not
             // given the path to the repository
-            ur(prepare_command(command_opts))
+            ur(prepare_command(command_opts, &mut stdout()))
         }
         Command::AddTo(command_opts) => ur(add_to_command(program_version, command_opts)),
+        Command::Normalize(command_opts) => ur(normalize_command(command_opts)),
+        Command::GenAttributes(command_opts) => ur(gen_attributes_command(command_opts)),
         Command::Check(command_opts) => ur(check_command(program_version, command_opts)),
-        Command::Build(command_opts) => Ok(Some(build_command(program_version, command_opts)?)),
+        Command::Validate(command_opts) => ur(validate_command(command_opts)),
+        Command::Build(command_opts) => {
+            build_command_per_repository(program_version, *command_opts)
+        }
         Command::Completions { shell } => {
             shell.generate(&mut Opts::command(), &mut std::io::stdout());
             Ok(None)
@@ -2601,8 +6169,22 @@ fn run() -> Result<Option<ExecutionResult>> {
 }
 
 fn main() -> Result<()> {
-    if let Some(er) = run()? {
-        er.daemon_cleanup();
+    match run() {
+        Ok(Some(er)) => {
+            er.daemon_cleanup();
+        }
+        Ok(None) => (),
+        Err(e) => {
+            if e.downcast_ref::<VersionStatementsNotFound>().is_some() {
+                eprintln!("Error: {e:?}");
+                std::process::exit(EXIT_CODE_VERSION_STATEMENTS_NOT_FOUND);
+            }
+            return Err(e);
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+#[path = "xmlhub/tests.rs"]
+mod tests;
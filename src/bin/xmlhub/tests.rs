@@ -0,0 +1,4372 @@
+//! Tests for the `xmlhub` program.
+
+use super::*;
+
+use xmlhub_indexer::{
+    contacts::CONTACTS_FILE_PATH,
+    utillib::temp_dir::TempDir,
+    xml_document::LineAndColumn,
+    xmlhub_config::CONFIG_FILE_PATH,
+    xmlhub_fileinfo::{AttributeValue, FileId},
+    xmlhub_read_file::missing_attachments_warnings,
+};
+
+/// Builder for the contents of XML Hub files, for tests. Starts out
+/// as a valid file; the header comments are kept in order, so that
+/// changing a value keeps its position.
+#[derive(Debug, Clone)]
+struct TestXml {
+    /// The text inside the header comments, e.g. `Version: 2.7`
+    comments: Vec<String>,
+    body: String,
+}
+
+impl TestXml {
+    /// A valid file with the given user-specified `Version` value.
+    fn new(version: &str) -> Self {
+        Self {
+            comments: vec![
+                "Keywords: foo, bar".into(),
+                format!("Version: {version}"),
+                "Packages: BEAST 2.7.0".into(),
+                "Contact: Some One".into(),
+            ],
+            body: "<beast version=\"2.7\"></beast>".into(),
+        }
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        let prefix = format!("{key}:");
+        self.comments
+            .iter()
+            .position(|comment| comment.starts_with(&prefix))
+    }
+
+    /// Set the value of the attribute `key`, replacing the existing
+    /// value or adding it after the existing header comments.
+    fn header(mut self, key: &str, value: &str) -> Self {
+        let comment = format!("{key}: {value}");
+        if let Some(i) = self.position(key) {
+            self.comments[i] = comment;
+        } else {
+            self.comments.push(comment);
+        }
+        self
+    }
+
+    /// Remove the attribute `key`.
+    fn without(mut self, key: &str) -> Self {
+        if let Some(i) = self.position(key) {
+            self.comments.remove(i);
+        }
+        self
+    }
+
+    /// Add a comment that is not an attribute after the header
+    /// comments.
+    fn comment(mut self, text: &str) -> Self {
+        self.comments.push(text.into());
+        self
+    }
+
+    /// Replace the `<beast>` element.
+    fn body(mut self, body: &str) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+impl Display for TestXml {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>"
+        )?;
+        for comment in &self.comments {
+            writeln!(f, "<!-- {comment} -->")?;
+        }
+        writeln!(f, "{}", self.body)
+    }
+}
+
+/// The contents of a valid XML Hub file with the given user-specified
+/// `Version` value, for tests.
+fn test_xml_file_content(version: &str) -> String {
+    TestXml::new(version).to_string()
+}
+
+/// `xml_file_paths_with_max_depth` with the default extensions, no
+/// maximum depth, and just the paths.
+fn xml_file_paths(
+    git_working_dir: &GitWorkingDir,
+    ignore_untracked: bool,
+    follow_symlinks: bool,
+) -> Result<Vec<BaseAndRelPath>> {
+    let XmlFilePaths { paths, .. } = xml_file_paths_with_max_depth(
+        git_working_dir,
+        ignore_untracked,
+        follow_symlinks,
+        None,
+        &XmlExtensions::default(),
+    )?;
+    Ok(paths)
+}
+
+/// Create a fresh Git repository in a temporary directory with the
+/// given files committed, for tests. The directory is deleted when
+/// the returned value is dropped.
+fn test_repository(name: &str, files: &[(&str, &str)]) -> Result<TempDir> {
+    let dir = TempDir::new(&format!("xmlhub-{name}"))?;
+    for (path, content) in files {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+    }
+    let git = |args: &[&str]| -> Result<()> {
+        let status = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir.path())
+            .stdout(std::process::Stdio::null())
+            .status()?;
+        if !status.success() {
+            bail!("git {args:?} failed in {dir:?}")
+        }
+        Ok(())
+    };
+    git(&["init", "-q", "-b", "master"])?;
+    git(&["config", "user.email", "test@example.com"])?;
+    git(&["config", "user.name", "Test"])?;
+    git(&["config", "commit.gpgsign", "false"])?;
+    git(&["add", "-A"])?;
+    git(&["commit", "-q", "-m", "initial"])?;
+    Ok(dir)
+}
+
+/// Options for `build_index` for tests: writing the given output
+/// files and committing them, without branch check, pulling or
+/// pushing.
+fn test_build_index_opts(output_files: OutputFiles) -> BuildIndexOpts {
+    BuildIndexOpts {
+        dryness: DrynessOpt { dry_run: false },
+        verbosity: VerbosityOpt { verbose: false },
+        quietness: QuietOpt::default(),
+        pull: false,
+        batch: false,
+        ignore_untracked: false,
+        follow_symlinks: false,
+        print_paths: false,
+        count_only: false,
+        count_by: None,
+        scan_max_depth: None,
+        refuse_on_divergence: false,
+        strict_duplicate_paths: false,
+        standalone: None,
+        explain: false,
+        output_dir: None,
+        link_base: None,
+        from_ref: None,
+        attribute_rules: AttributeRules::default(),
+        write_errors: false,
+        silent_on_written_errors: false,
+        ok_on_written_errors: false,
+        open_if_changed: false,
+        no_commit: false,
+        no_commit_errors: false,
+        no_push_on_error: false,
+        no_branch_check: true,
+        open: false,
+        explain_index: false,
+        short_paths: false,
+        output_files,
+        extensions: None,
+        sample: None,
+        version_notes: false,
+        signoff: false,
+        gpg_sign_arg: None,
+        commit_trailers: Vec::new(),
+        pretty_html: false,
+        no_index_section: false,
+        diff_output: false,
+        group_by: BoxGrouping::Folder,
+        max_output_size: Some(MAX_OUTPUT_SIZE_DEFAULT),
+        no_toc: false,
+        mini_toc: false,
+        toc_fragment: false,
+        index_layout: IndexLayout::Dl,
+        since_last_build_state: None,
+        output_sink: None,
+        progress: Arc::new(NoProgress),
+    }
+}
+
+/// Run `build_index` on the repository at `dir` (without version
+/// check), for tests.
+fn test_build_index(dir: &Path, build_index_opts: BuildIndexOpts) -> Result<i32> {
+    test_build_index_maybe_pushing(dir, build_index_opts, false).map(|(exit_code, _)| exit_code)
+}
+
+/// Like `test_build_index`, but if `push` is true, pushes to the
+/// default remote (as with `--push`), and also returns the summary.
+fn test_build_index_maybe_pushing(
+    dir: &Path,
+    build_index_opts: BuildIndexOpts,
+    push: bool,
+) -> Result<(i32, BuildSummary)> {
+    let xmlhub_checkout = XMLHUB_CHECKOUT
+        .replace_working_dir_path(Cow::from(dir))
+        .check1(CheckExpectedSubpathsExist::No)?;
+    let maybe_checked_xmlhub_checkout = if push {
+        Some(xmlhub_checkout.clone().check2()?)
+    } else {
+        None
+    };
+    let git_log_version_checker = git_log_version_checker(
+        "v12".parse()?,
+        true,
+        xmlhub_checkout.git_working_dir().into(),
+        build_index_opts.output_files.clone().into(),
+    );
+    build_index(
+        build_index_opts,
+        &git_log_version_checker,
+        &xmlhub_checkout,
+        &maybe_checked_xmlhub_checkout,
+    )
+}
+
+/// Create a bare repository in a temporary directory and set it up
+/// as the default remote of the repository at `dir`, for tests. The
+/// remote is deleted when the returned value is dropped.
+fn test_add_remote(dir: &Path) -> Result<TempDir> {
+    let remote = TempDir::new("xmlhub-test-remote")?;
+    let remote_str = remote.to_string_lossy();
+    test_git_stdout(dir, &["init", "-q", "--bare", &remote_str])?;
+    test_git_stdout(dir, &["remote", "add", "origin", &remote_str])?;
+    test_git_stdout(dir, &["push", "-q", "-u", "origin", "master"])?;
+    Ok(remote)
+}
+
+/// Run `git` with `args` in `dir` and return its stdout, for tests.
+fn test_git_stdout(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()?;
+    if !output.status.success() {
+        bail!("git {args:?} failed in {dir:?}")
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[test]
+fn t_prepare_file_without_writing() -> Result<()> {
+    let dir = TempDir::new("xmlhub-t_prepare_file_without_writing")?;
+    let source_path = dir.join("test.xml");
+    let original = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\
+                    <beast version=\"2.7\">\n\
+                    <data id=\"alignment\">\n\
+                    <sequence taxon=\"a\" value=\"ACGT\"/>\n\
+                    </data>\n\
+                    </beast>\n";
+    std::fs::write(&source_path, original)?;
+
+    let prepared_file = prepare_file(PrepareFileOpts {
+        source_path: &source_path,
+        blinding: &BlindingOpts {
+            no_blind: false,
+            blind_all: false,
+            blind_comment: None,
+            recommended_max_file_size_bytes: 5000000,
+        },
+        ignore_version: false,
+        quiet: true,
+        messages_to_stderr: true,
+    })?;
+    assert!(prepared_file.content_has_changed);
+    assert!(prepared_file.content.contains("Keywords:"));
+    assert!(!prepared_file.content.contains("ACGT"));
+    assert_eq!(std::fs::read_to_string(&source_path)?, original);
+
+    // Metadata given as processing instruction counts as headers
+    let with_pi = original.replace(
+        "<beast ",
+        "<?xmlhub Keywords=\"foo\" Version=\"2.7\" Packages=\"BEAST 2.7.0\" \
+         Contact=\"Some One\"?>\n<beast ",
+    );
+    std::fs::write(&source_path, &with_pi)?;
+    let prepared_file = prepare_file(PrepareFileOpts {
+        source_path: &source_path,
+        blinding: &BlindingOpts {
+            no_blind: true,
+            blind_all: false,
+            blind_comment: None,
+            recommended_max_file_size_bytes: 5000000,
+        },
+        ignore_version: false,
+        quiet: true,
+        messages_to_stderr: true,
+    })?;
+    assert!(!prepared_file.content.contains("Keywords:"));
+    assert_eq!(prepared_file.content, with_pi);
+
+    Ok(())
+}
+
+#[test]
+fn t_prepare_stdout() -> Result<()> {
+    let dir = TempDir::new("xmlhub-t_prepare_stdout")?;
+    let original = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\
+                    <beast version=\"2.7\">\n\
+                    <data id=\"alignment\">\n\
+                    <sequence taxon=\"a\" value=\"ACGT\"/>\n\
+                    </data>\n\
+                    </beast>\n";
+    let a = dir.join("a.xml");
+    let b = dir.join("b.xml");
+    std::fs::write(&a, original)?;
+    std::fs::write(&b, original)?;
+    let prepare = |args: &[&Path]| -> Result<String> {
+        let opts = PrepareOpts::try_parse_from(
+            ["prepare", "--stdout"]
+                .iter()
+                .map(std::ffi::OsStr::new)
+                .chain(args.iter().map(|path| path.as_os_str())),
+        )?;
+        let mut out = Vec::new();
+        prepare_command(opts, &mut out)?;
+        Ok(String::from_utf8(out)?)
+    };
+
+    let printed = prepare(&[&a])?;
+    assert!(printed.starts_with("<?xml "), "{printed:?}");
+    for header in ["Keywords:", "Version:", "Packages:", "Contact:"] {
+        assert!(printed.contains(header), "{header:?} in {printed:?}");
+    }
+    assert!(!printed.contains("ACGT"));
+    assert_eq!(std::fs::read_to_string(&a)?, original);
+
+    // Only a single file is allowed
+    let e = prepare(&[&a, &b]).expect_err("two files");
+    assert!(e.to_string().contains("requires exactly one file"), "{e:#}");
+    assert_eq!(std::fs::read_to_string(&a)?, original);
+    assert_eq!(std::fs::read_to_string(&b)?, original);
+    Ok(())
+}
+
+#[test]
+fn t_prepare_summary() -> Result<()> {
+    let dir = TempDir::new("xmlhub-t_prepare_summary")?;
+    let with_sequences = |n: usize| {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\
+             <beast version=\"2.7\">\n\
+             <data id=\"alignment\">\n\
+             {}\
+             </data>\n\
+             </beast>\n",
+            (0..n)
+                .map(|i| format!("<sequence taxon=\"t{i}\" value=\"ACGT\"/>\n"))
+                .join("")
+        )
+    };
+    let files = [
+        ("two.xml", with_sequences(2)),
+        ("one.xml", with_sequences(1)),
+        ("none.xml", with_sequences(0)),
+        ("prepared.xml", test_xml_file_content("2.7")),
+    ];
+    let prepare_all = |blind_all: bool| -> Result<Vec<PreparedFile>> {
+        files
+            .iter()
+            .map(|(name, content)| {
+                let source_path = dir.join(name);
+                std::fs::write(&source_path, content)?;
+                prepare_file(PrepareFileOpts {
+                    source_path: &source_path,
+                    blinding: &BlindingOpts {
+                        no_blind: false,
+                        blind_all,
+                        blind_comment: None,
+                        recommended_max_file_size_bytes: 5000000,
+                    },
+                    ignore_version: false,
+                    quiet: true,
+                    messages_to_stderr: true,
+                })
+            })
+            .collect()
+    };
+    let expected = PrepareSummary {
+        files: 4,
+        blinded: 2,
+        sequences_removed: 3,
+        unchanged: 1,
+    };
+    assert_eq!(PrepareSummary::new(&prepare_all(false)?), expected);
+    assert_eq!(PrepareSummary::new(&prepare_all(true)?), expected);
+    assert_eq!(
+        expected.message()?,
+        "Processed 4 files: 2 blinded (3 sequences removed), 1 unchanged."
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_build_index_output_files() -> Result<()> {
+    use xmlhub_indexer::xmlhub_types::OutputFile;
+
+    let dir = test_repository(
+        "t_build_index_output_files",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let output_files = OutputFiles {
+        html_file: OutputFile {
+            path_from_repo_top: "index.html".into(),
+        },
+        md_file: OutputFile {
+            path_from_repo_top: "index.md".into(),
+        },
+        attributes_file: OutputFile {
+            path_from_repo_top: "attrs.md".into(),
+        },
+    };
+    assert_eq!(
+        test_build_index(&dir, test_build_index_opts(output_files))?,
+        0
+    );
+
+    for path in ["index.html", "index.md", "attrs.md"] {
+        assert!(dir.join(path).exists());
+    }
+    for path in ["README.html", "README.md", "attributes.md"] {
+        assert!(!dir.join(path).exists());
+    }
+    let committed = test_git_stdout(&dir, &["show", "--name-only", "--format=", "HEAD"])?;
+    let mut committed: Vec<&str> = committed.lines().collect();
+    committed.sort();
+    assert_eq!(committed, ["attrs.md", "index.html", "index.md"]);
+    let md = std::fs::read_to_string(dir.join("index.md"))?;
+    assert!(md.contains("open the file &quot;index.html&quot; instead"));
+
+    Ok(())
+}
+
+#[test]
+fn t_build_index_sample() -> Result<()> {
+    let content = test_xml_file_content("2.7");
+    let dir = test_repository(
+        "t_build_index_sample",
+        &[
+            ("a.xml", &content),
+            ("b.xml", &content),
+            ("sub/c.xml", &content),
+        ],
+    )?;
+    let head = test_git_stdout(&dir, &["rev-parse", "HEAD"])?;
+    let sample = Sample::from_args(&["first".into(), "2".into()])?;
+    assert_eq!(
+        sample
+            .select(
+                xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+                &GitWorkingDir::from(dir.to_path_buf())
+            )?
+            .iter()
+            .map(|path| path.rel_path())
+            .collect::<Vec<_>>(),
+        ["a.xml", "b.xml"]
+    );
+    assert_eq!(
+        test_build_index(
+            &dir,
+            BuildIndexOpts {
+                sample: Some(sample),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES)
+            }
+        )?,
+        0
+    );
+
+    let md = std::fs::read_to_string(dir.join("README.md"))?;
+    assert!(md.contains("# XML Hub file index (sample: the first 2 of 3 files)"));
+    let html = std::fs::read_to_string(dir.join("README.html"))?;
+    assert!(html.contains("sample: the first 2 of 3 files"));
+    assert!(html.contains(">a.xml<"));
+    assert!(html.contains(">b.xml<"));
+    assert!(!html.contains("c.xml"));
+
+    // The partial index is not committed
+    assert_eq!(test_git_stdout(&dir, &["rev-parse", "HEAD"])?, head);
+
+    Ok(())
+}
+
+#[test]
+fn t_last_commit_times() -> Result<()> {
+    let dir = test_repository("t_last_commit_times", &[("a.xml", "a"), ("b c.xml", "b")])?;
+    let commit_times = last_commit_times(&GitWorkingDir::from(dir.to_path_buf()))?;
+    assert_eq!(commit_times.len(), 2);
+    assert!(commit_times.contains_key("b c.xml"));
+    Ok(())
+}
+
+#[test]
+fn t_issue_locations() -> Result<()> {
+    let dir = test_repository(
+        "t_issue_locations",
+        &[
+            (
+                "comment.xml",
+                &test_xml_file_content("2.7").replace("<!-- Contact:", "  <!-- Contakt:"),
+            ),
+            ("markup.xml", "<?xml version=\"1.0\"?>\n<beast>\n  </bad>\n"),
+        ],
+    )?;
+    let report = validate(&GitWorkingDir::from(dir.to_path_buf()), false, None, None)?;
+    let mut out = Vec::new();
+    report.print_plain(&mut out)?;
+    let out = String::from_utf8(out)?;
+    assert!(out.contains("* comment.xml:5:3: XML comment: unknown attribute name \"contakt\""));
+    assert!(out.contains("* markup.xml:3:3: "));
+    Ok(())
+}
+
+#[test]
+fn t_build_index_section_short_paths() -> Result<()> {
+    let dir = test_repository(
+        "t_build_index_section_short_paths",
+        &[("deeply/nested/a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    assert!(file_errorss.is_empty());
+    let spec = attribute_specification_by_name("Keywords").expect("present");
+    let index_html = |short_paths| -> Result<String> {
+        let section = build_index_section(
+            spec.key,
+            spec.key_string_preparation().expect("indexed"),
+            spec.autolink,
+            &file_infos,
+            &index_anchor_names(&file_infos),
+            IndexSectionOpts {
+                short_paths,
+                ..Default::default()
+            },
+        )?;
+        Ok(section.intro.expect("has intro").as_str().to_string())
+    };
+
+    let long = index_html(false)?;
+    assert!(long.contains(">deeply/nested/a.xml</a>"));
+    assert!(long.contains("title=\"Jump to info box\""));
+
+    let short = index_html(true)?;
+    assert!(short.contains(">a.xml</a>"));
+    assert!(!short.contains(">deeply/nested/a.xml</a>"));
+    assert!(short.contains("title=\"deeply/nested/a.xml (jump to info box)\""));
+    assert!(short.contains("href=\"deeply/nested/a.xml\""));
+
+    Ok(())
+}
+
+#[test]
+fn t_version_stamping() -> Result<()> {
+    let check = |dir: &Path, program_version: &str| -> Result<()> {
+        git_log_version_checker(
+            program_version.parse()?,
+            false,
+            GitWorkingDir::from(dir.to_owned()).into(),
+            DEFAULT_OUTPUT_FILES.into(),
+        )
+        .check_git_log()
+    };
+    for version_notes in [false, true] {
+        let dir = test_repository(
+            &format!("t_version_stamping-{version_notes}"),
+            &[("a.xml", &test_xml_file_content("2.7"))],
+        )?;
+        assert_eq!(
+            test_build_index(
+                &dir,
+                BuildIndexOpts {
+                    version_notes,
+                    ..test_build_index_opts(DEFAULT_OUTPUT_FILES)
+                }
+            )?,
+            0
+        );
+        let message = test_git_stdout(&dir, &["log", "-1", "--format=%B"])?;
+        let note = test_git_stdout(&dir, &["notes", "--ref", VERSION_NOTES_REF, "list"])?;
+        if version_notes {
+            assert_eq!(message.trim(), "regenerate index files");
+            let note =
+                test_git_stdout(&dir, &["notes", "--ref", VERSION_NOTES_REF, "show", "HEAD"])?;
+            assert!(note.contains("version: 12"));
+        } else {
+            assert!(message.contains("version: 12"));
+            assert!(note.is_empty());
+        }
+        // The stamp is found either way: an older program is refused
+        assert!(check(&dir, "v11").is_err());
+        check(&dir, "v12")?;
+        check(&dir, "v13")?;
+    }
+    Ok(())
+}
+
+#[test]
+fn t_version_notes_shared_between_clones() -> Result<()> {
+    let content = test_xml_file_content("2.7");
+    let a = test_repository(
+        "t_version_notes_shared_between_clones",
+        &[("a.xml", &content)],
+    )?;
+    let remote = test_add_remote(&a)?;
+    let b = TempDir::new("xmlhub-t_version_notes_shared_between_clones-b")?;
+    test_git_stdout(&b, &["clone", "-q", &remote.to_string_lossy(), "."])?;
+    for (key, value) in [
+        ("user.email", "test@example.com"),
+        ("user.name", "Test"),
+        ("commit.gpgsign", "false"),
+    ] {
+        test_git_stdout(&b, &["config", key, value])?;
+    }
+    let opts = |pull: bool| BuildIndexOpts {
+        version_notes: true,
+        pull,
+        ..test_build_index_opts(DEFAULT_OUTPUT_FILES)
+    };
+    let notes = |dir: &Path| -> Result<usize> {
+        Ok(
+            test_git_stdout(dir, &["notes", "--ref", VERSION_NOTES_REF, "list"])?
+                .lines()
+                .count(),
+        )
+    };
+
+    // a builds and pushes, b pulls (getting the notes, too)
+    assert_eq!(test_build_index_maybe_pushing(&a, opts(false), true)?.0, 0);
+    assert_eq!(test_build_index_maybe_pushing(&b, opts(true), true)?.0, 0);
+    assert_eq!(notes(&b)?, 1);
+
+    // Meanwhile, a pushes another note, while b adds one locally by
+    // building after a change
+    test_git_stdout(
+        &a,
+        &[
+            "notes",
+            "--ref",
+            VERSION_NOTES_REF,
+            "add",
+            "-m",
+            "other",
+            "HEAD~",
+        ],
+    )?;
+    test_git_stdout(&a, &["push", "-q", "origin", VERSION_NOTES_REF])?;
+    std::fs::write(b.join("b.xml"), &content)?;
+    test_git_stdout(&b, &["add", "b.xml"])?;
+    test_git_stdout(&b, &["commit", "-q", "-m", "add b.xml"])?;
+    // The notes are merged before pushing them
+    assert_eq!(test_build_index_maybe_pushing(&b, opts(false), true)?.0, 0);
+    assert_eq!(notes(&b)?, 3);
+
+    // a gets all of them when pulling
+    assert_eq!(test_build_index_maybe_pushing(&a, opts(true), true)?.0, 0);
+    assert_eq!(notes(&a)?, 3);
+    assert_eq!(
+        test_git_stdout(&a, &["rev-parse", VERSION_NOTES_REF])?,
+        test_git_stdout(&b, &["rev-parse", VERSION_NOTES_REF])?
+    );
+    Ok(())
+}
+
+#[test]
+fn t_files_needing_attention_section() -> Result<()> {
+    let dir = test_repository(
+        "t_files_needing_attention_section",
+        &[
+            ("clean.xml", &test_xml_file_content("2.7")),
+            ("warned.xml", &test_xml_file_content("2.6")),
+        ],
+    )?;
+    let (file_infos, _) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    let warningss: Vec<FileWarnings> = file_infos
+        .iter()
+        .filter_map(|info| info.opt_warnings())
+        .collect();
+    let section = files_needing_attention_section(&warningss)?;
+    let list = section.intro.expect("has intro");
+    let list = list.as_str();
+    let warned_id = file_infos
+        .iter()
+        .find(|info| info.path.rel_path() == "warned.xml")
+        .expect("present")
+        .id;
+    assert!(list.contains(&format!("href=\"#box-{warned_id}\"")));
+    assert!(list.contains(">warned.xml</a>"));
+    assert!(!list.contains("clean.xml"));
+    Ok(())
+}
+
+#[test]
+fn t_validate() -> Result<()> {
+    let dir = TempDir::new("xmlhub-t_validate")?;
+    std::fs::create_dir_all(dir.join("sub"))?;
+    std::fs::write(dir.join("good.xml"), test_xml_file_content("2.7"))?;
+    std::fs::write(dir.join("sub/warn.xml"), test_xml_file_content("2.6"))?;
+    std::fs::write(
+        dir.join("sub/bad.xml"),
+        "<?xml version=\"1.0\"?>\n<!-- Keywords: foo -->\n<beast version=\"2.7\"></beast>\n",
+    )?;
+    std::fs::write(dir.join("sub/notes.txt"), "not an XML file")?;
+
+    let report = validate(&GitWorkingDir::from(dir.to_path_buf()), false, None, None)?;
+    assert_eq!(report.number_of_files, 3);
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|e| e.path.as_str())
+            .collect::<Vec<_>>(),
+        ["sub/bad.xml"]
+    );
+    assert!(report.errors[0].issues[0].message.contains("missing"));
+    assert_eq!(
+        report
+            .warnings
+            .iter()
+            .map(|e| e.path.as_str())
+            .collect::<Vec<_>>(),
+        ["sub/warn.xml"]
+    );
+    assert_eq!(report.exit_code(), 1);
+
+    std::fs::remove_file(dir.join("sub/bad.xml"))?;
+    let report = validate(&GitWorkingDir::from(dir.to_path_buf()), false, None, None)?;
+    assert_eq!(report.number_of_files, 2);
+    assert_eq!(report.errors.len(), 0);
+    assert_eq!(report.exit_code(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn t_index_exclusions() -> Result<()> {
+    use xmlhub_indexer::xmlhub_fileinfo::Metadata;
+
+    let file_info = |rel_path: &str, attributes: &[(&'static str, &str)]| -> Result<_> {
+        let mut values = BTreeMap::new();
+        for (name, value) in attributes {
+            let spec = attribute_specification_by_name(name).expect("present");
+            values.insert(spec.key, AttributeValue::from_str_and_spec(value, spec)?);
+        }
+        Ok(FileInfo::<WithDerivedValues> {
+            id: FileId::from_rel_path(rel_path, 0),
+            path: BaseAndRelPath::new(None, rel_path.into()),
+            metadata: Metadata::new(values),
+            warnings: vec![],
+            notes: vec![],
+        })
+    };
+    let file_infos = [
+        file_info("a.xml", &[("Keywords", "foo, bar"), ("Published", "yes")])?,
+        file_info("b.xml", &[])?,
+    ];
+    let exclusions = index_exclusions(&file_infos);
+    let keywords = exclusions
+        .iter()
+        .find(|(key, _)| key.as_ref() == "Keywords")
+        .expect("Keywords exclusions present");
+    assert_eq!(keywords.1, ["b.xml"]);
+    // All indexed attributes are missing in b.xml, but only Keywords
+    // and Published are present in a.xml
+    assert!(exclusions.iter().all(|(_, paths)| paths.contains(&"b.xml")));
+    // b.xml is listed under "not given" in the index for Published
+    assert!(!exclusions
+        .iter()
+        .any(|(key, _)| key.as_ref() == "Published"));
+
+    let mut out = Vec::new();
+    print_index_exclusions(&exclusions, &mut out)?;
+    let out = String::from_utf8(out)?;
+    assert!(out.contains(
+        "Note: 1 file is not listed in the index for \"Keywords\" (no value given):\n    \"b.xml\"\n"
+    ));
+    Ok(())
+}
+
+#[test]
+fn t_print_htmldocument_streaming() -> Result<()> {
+    let section = |title: &str, intro: Option<&str>, subsections| -> Result<Section> {
+        let html = HTML_ALLOCATOR_POOL.get();
+        Ok(Section {
+            highlight: Highlight::None,
+            title: Some(title.into()),
+            intro: intro
+                .map(|text| html.preserialize(html.p([], html.text(text)?)?))
+                .transpose()?,
+            markdown_intro: None,
+            subsections,
+        })
+    };
+    let toplevel_section = Section {
+        highlight: Highlight::None,
+        title: None,
+        intro: None,
+        markdown_intro: None,
+        subsections: vec![
+            section("Index", Some("some <intro>"), vec![])?,
+            section(
+                "Files",
+                None,
+                vec![
+                    section("a", Some("file a"), vec![])?,
+                    section("b", Some("file b"), vec![section("c", None, vec![])?])?,
+                ],
+            )?,
+        ],
+    };
+    let title = "Test index";
+    let html = HTML_ALLOCATOR_POOL.get();
+    let toc_html = html.preserialize(toplevel_section.to_toc_html(
+        NumberPath::empty(),
+        &HighlightColors::default(),
+        &html,
+    )?)?;
+
+    let mut streamed = Vec::new();
+    print_htmldocument_streaming(
+        title,
+        Some(&toc_html),
+        &DEFAULT_OUTPUT_FILES,
+        &toplevel_section,
+        &HighlightColors::default(),
+        &DEFAULT_SYMBOLS,
+        &mut streamed,
+    )?;
+
+    let mut non_streamed = Vec::new();
+    let content = html.div(
+        [],
+        toplevel_section.to_html(NumberPath::empty(), &HighlightColors::default(), &html)?,
+    )?;
+    html.print_html_document(
+        make_htmldocument(
+            title,
+            Some(&toc_html),
+            &DEFAULT_OUTPUT_FILES,
+            &DEFAULT_SYMBOLS,
+            content,
+            &html,
+        )?,
+        &mut non_streamed,
+    )?;
+
+    assert_eq!(
+        String::from_utf8(streamed)?,
+        String::from_utf8(non_streamed)?
+    );
+    Ok(())
+}
+
+#[test]
+fn t_build_index_push_on_error() -> Result<()> {
+    // (no_commit_errors, no_push_on_error) => (committed, pushed)
+    for (no_commit_errors, no_push_on_error, committed, pushed) in [
+        (false, false, true, true),
+        (false, true, true, false),
+        (true, false, false, false),
+        (true, true, false, false),
+    ] {
+        let name = format!("t_build_index_push_on_error-{no_commit_errors}-{no_push_on_error}");
+        let dir = test_repository(
+            &name,
+            &[
+                ("a.xml", &test_xml_file_content("2.7")),
+                ("broken.xml", "<beast>"),
+            ],
+        )?;
+        let _remote = test_add_remote(&dir)?;
+
+        let mut opts = test_build_index_opts(DEFAULT_OUTPUT_FILES.clone());
+        opts.write_errors = true;
+        opts.silent_on_written_errors = true;
+        opts.no_commit_errors = no_commit_errors;
+        opts.no_push_on_error = no_push_on_error;
+        assert_eq!(test_build_index_maybe_pushing(&dir, opts, true)?.0, 0);
+
+        let count = |rev: &str| -> Result<String> {
+            Ok(test_git_stdout(&dir, &["rev-list", "--count", rev])?
+                .trim()
+                .to_string())
+        };
+        let expected_local = if committed { "2" } else { "1" };
+        let expected_remote = if pushed { "2" } else { "1" };
+        assert_eq!(count("HEAD")?, expected_local, "{name}");
+        assert_eq!(count("origin/master")?, expected_remote, "{name}");
+    }
+    Ok(())
+}
+
+#[test]
+fn t_build_index_push_on_error_batch() -> Result<()> {
+    let dir = test_repository(
+        "t_build_index_push_on_error_batch",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("broken.xml", "<beast>"),
+        ],
+    )?;
+    let _remote = test_add_remote(&dir)?;
+    let opts = || BuildIndexOpts {
+        batch: true,
+        write_errors: true,
+        silent_on_written_errors: true,
+        no_push_on_error: true,
+        ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+    };
+    let count = |rev: &str| -> Result<String> {
+        Ok(test_git_stdout(&dir, &["rev-list", "--count", rev])?
+            .trim()
+            .to_string())
+    };
+
+    // Committed but not pushed
+    assert_eq!(test_build_index_maybe_pushing(&dir, opts(), true)?.0, 0);
+    assert_eq!(
+        (count("HEAD")?, count("origin/master")?),
+        ("2".into(), "1".into())
+    );
+
+    // Someone fixes the file upstream
+    test_git_stdout(&dir, &["reset", "-q", "--hard", "origin/master"])?;
+    std::fs::write(dir.join("broken.xml"), test_xml_file_content("2.7"))?;
+    test_git_stdout(&dir, &["commit", "-q", "-a", "-m", "fix broken.xml"])?;
+    test_git_stdout(&dir, &["push", "-q", "origin", "master"])?;
+
+    // The next run regenerates the index files and pushes them
+    assert_eq!(test_build_index_maybe_pushing(&dir, opts(), true)?.0, 0);
+    assert_eq!(
+        (count("HEAD")?, count("origin/master")?),
+        ("3".into(), "3".into())
+    );
+    let pushed = test_git_stdout(
+        &dir,
+        &["show", "--name-only", "--format=%s", "origin/master"],
+    )?;
+    assert!(pushed.starts_with(INDEX_COMMIT_SUBJECT_PREFIX), "{pushed}");
+    assert!(pushed.contains("README.md"), "{pushed}");
+    Ok(())
+}
+
+#[test]
+fn t_build_summary() -> Result<()> {
+    let dir = test_repository(
+        "t_build_summary",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("broken.xml", "<beast>"),
+        ],
+    )?;
+    let _remote = test_add_remote(&dir)?;
+
+    let opts = || {
+        let mut opts = test_build_index_opts(DEFAULT_OUTPUT_FILES.clone());
+        opts.write_errors = true;
+        opts.silent_on_written_errors = true;
+        opts
+    };
+    let count = |rev: &str| -> Result<String> {
+        Ok(test_git_stdout(&dir, &["rev-list", "--count", rev])?
+            .trim()
+            .to_string())
+    };
+
+    let (exit_code, summary) = test_build_index_maybe_pushing(&dir, opts(), true)?;
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        summary.to_string(),
+        format!(
+            "files=2 errors=1 warnings={} committed=1 pushed=1 changed=1",
+            summary.warnings
+        )
+    );
+    assert_eq!(count("HEAD")?, "2");
+    assert_eq!(count("origin/master")?, "2");
+
+    // Nothing changes when running again
+    let (_, summary) = test_build_index_maybe_pushing(&dir, opts(), true)?;
+    assert_eq!(
+        (summary.committed, summary.pushed, summary.changed),
+        (false, false, false)
+    );
+    assert_eq!(count("HEAD")?, "2");
+    Ok(())
+}
+
+#[test]
+fn t_build_extracted_index_section() -> Result<()> {
+    let with_description = |description: &str| {
+        TestXml::new("2.7")
+            .header("Description", description)
+            .to_string()
+    };
+    let dir = test_repository(
+        "t_build_extracted_index_section",
+        &[
+            (
+                "a.xml",
+                &with_description("Sequences from GISAID EPI_ISL_12345,\nreference MN908947.3."),
+            ),
+            ("b.xml", &with_description("Nothing to see here.")),
+        ],
+    )?;
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    assert!(file_errorss.is_empty());
+    let spec = attribute_specification_by_name("Description").expect("present");
+    let regex = spec.extract_index_regex().expect("has extract_index_regex");
+    let section =
+        build_extracted_index_section(spec, &regex, &file_infos, IndexSectionOpts::default())?;
+    assert_eq!(section.title.as_deref(), Some("Identifiers in Description"));
+    let html = section.intro.expect("has intro").as_str().to_string();
+    assert_eq!(html.matches("<dt").count(), 2);
+    assert!(html.contains(">EPI_ISL_12345<"));
+    assert!(html.contains(">MN908947.3<"));
+    assert!(html.contains("id=\"Description-id_EPI_ISL_12345\""));
+    assert!(html.contains(">a.xml</a>"));
+    assert!(!html.contains(">b.xml</a>"));
+
+    // The info box still shows the unchanged description
+    let a = file_infos
+        .iter()
+        .find(|info| info.path.rel_path() == "a.xml")
+        .expect("present");
+    assert_eq!(
+        a.metadata.get(spec.key).expect("present").as_string_list()[0],
+        "Sequences from GISAID EPI_ISL_12345,\nreference MN908947.3."
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_headers_status() -> Result<()> {
+    let body = "<beast version=\"2.7\"></beast>\n";
+    let dir = test_repository(
+        "t_headers_status",
+        &[
+            (
+                "templated.xml",
+                &format!(
+                    "<!-- Keywords:  -->\n\
+                     <!-- Version:  -->\n\
+                     <!-- Packages:  -->\n\
+                     <!-- Description: NA -->\n\
+                     <!-- Contact:  -->\n\
+                     {body}"
+                ),
+            ),
+            ("plain.xml", body),
+            ("partial.xml", &format!("<!-- Keywords: foo -->\n{body}")),
+            (
+                "pi.xml",
+                &format!(
+                    "<?xmlhub Keywords=\"foo\" Version=\"2.7\" Packages=\"BEAST 2.7.0\" \
+                     Contact=\"Some One\"?>\n\
+                     {body}"
+                ),
+            ),
+            ("broken.xml", "<beast>"),
+        ],
+    )?;
+    assert!(matches!(
+        headers_status(&dir.join("templated.xml")),
+        HeadersStatus::Present
+    ));
+    assert!(matches!(
+        headers_status(&dir.join("plain.xml")),
+        HeadersStatus::Missing
+    ));
+    assert!(matches!(
+        headers_status(&dir.join("pi.xml")),
+        HeadersStatus::Present
+    ));
+    match headers_status(&dir.join("partial.xml")) {
+        HeadersStatus::Incomplete(issues) => {
+            assert_eq!(issues.len(), 1);
+            assert!(issues[0].message.contains("are missing: \"Version\""));
+        }
+        status => panic!("expected Incomplete, got {status:?}"),
+    }
+    assert!(matches!(
+        headers_status(&dir.join("broken.xml")),
+        HeadersStatus::Unreadable(_)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn t_build_index_streams_html() -> Result<()> {
+    use std::sync::Mutex;
+    use xmlhub_indexer::output_sink::MemorySink;
+
+    /// Records how the files are passed to it.
+    #[derive(Default)]
+    struct RecordingSink {
+        memory: MemorySink,
+        /// The paths passed via `write_file_streaming`, with the size
+        /// of the biggest single write
+        streamed: Mutex<Vec<(String, usize)>>,
+    }
+
+    impl OutputSink for RecordingSink {
+        fn write_file(&self, rel_path: &str, bytes: &[u8]) -> Result<()> {
+            self.memory.write_file(rel_path, bytes)
+        }
+
+        fn write_file_streaming(
+            &self,
+            rel_path: &str,
+            write: &mut dyn FnMut(&mut dyn Write) -> Result<()>,
+        ) -> Result<()> {
+            struct ChunkRecorder {
+                bytes: Vec<u8>,
+                max_chunk: usize,
+            }
+            impl Write for ChunkRecorder {
+                fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                    self.max_chunk = self.max_chunk.max(buf.len());
+                    self.bytes.extend_from_slice(buf);
+                    Ok(buf.len())
+                }
+                fn flush(&mut self) -> std::io::Result<()> {
+                    Ok(())
+                }
+            }
+            let mut out = ChunkRecorder {
+                bytes: Vec::new(),
+                max_chunk: 0,
+            };
+            write(&mut out)?;
+            self.streamed
+                .lock()
+                .unwrap()
+                .push((rel_path.into(), out.max_chunk));
+            self.memory.write_file(rel_path, &out.bytes)
+        }
+    }
+
+    let dir = test_repository(
+        "t_build_index_streams_html",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("b.xml", &test_xml_file_content("2.7")),
+        ],
+    )?;
+    let sink = Arc::new(RecordingSink::default());
+    let (exit_code, _summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+
+    // With the default size limit, the HTML file is still passed on
+    // in pieces, not as one buffer
+    let html_path = &DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top;
+    let html_len = sink.memory.files()[&**html_path].len();
+    assert!(html_len > 8192, "{html_len}");
+    let streamed = sink.streamed.lock().unwrap().clone();
+    match streamed.as_slice() {
+        [(path, max_chunk)] => {
+            assert_eq!(path, &**html_path);
+            assert!(*max_chunk <= 8192, "{max_chunk} of {html_len}");
+        }
+        _ => panic!("expecting just the HTML file to be streamed, got {streamed:?}"),
+    }
+    Ok(())
+}
+
+#[test]
+fn t_build_index_output_sink() -> Result<()> {
+    use xmlhub_indexer::output_sink::MemorySink;
+
+    let dir = test_repository(
+        "t_build_index_output_sink",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let sink = Arc::new(MemorySink::default());
+    let (exit_code, summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+
+    let files = sink.files();
+    let paths: Vec<&str> = files.keys().map(|path| path.as_str()).collect();
+    let mut expected_paths = DEFAULT_OUTPUT_FILES.paths_from_repo_top();
+    expected_paths.sort();
+    assert_eq!(paths, expected_paths);
+    for (path, contents) in &files {
+        let contents = std::str::from_utf8(contents)?;
+        assert!(!contents.is_empty(), "{path:?} is empty");
+        if path.ends_with(".html") {
+            assert!(contents.contains("a.xml"), "{path:?} lists a.xml");
+        }
+    }
+
+    // Nothing was written to, or committed in, the working directory
+    assert!(!summary.committed);
+    for path in DEFAULT_OUTPUT_FILES.paths_from_repo_top() {
+        assert!(!dir.join(path).exists(), "{path:?} was not written");
+    }
+    assert_eq!(
+        test_git_stdout(&dir, &["rev-list", "--count", "HEAD"])?.trim(),
+        "1"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_xml_file_paths_follow_symlinks() -> Result<()> {
+    let dir = test_repository(
+        "t_xml_file_paths_follow_symlinks",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    std::os::unix::fs::symlink("a.xml", dir.join("link.xml"))?;
+    std::fs::create_dir(dir.join("sub"))?;
+    std::os::unix::fs::symlink("..", dir.join("sub/up"))?;
+
+    let rel_paths = |follow_symlinks| -> Result<Vec<String>> {
+        Ok(xml_file_paths(
+            &GitWorkingDir::from(dir.to_path_buf()),
+            false,
+            follow_symlinks,
+        )?
+        .iter()
+        .map(|path| path.rel_path().to_string())
+        .collect())
+    };
+    assert_eq!(rel_paths(false)?, ["a.xml"]);
+    // The link to the file and the cycle via sub/up are reached, but
+    // the file is only listed once
+    assert_eq!(rel_paths(true)?, ["a.xml"]);
+
+    // A link to a file outside the repository is listed under its
+    // link path when following
+    let outside_dir = TempDir::new("xmlhub-t_xml_file_paths_follow_symlinks-outside")?;
+    let outside = outside_dir.join("outside.xml");
+    std::fs::write(&outside, test_xml_file_content("2.7"))?;
+    std::os::unix::fs::symlink(&outside, dir.join("shared.xml"))?;
+    assert_eq!(rel_paths(false)?, ["a.xml"]);
+    assert_eq!(rel_paths(true)?, ["a.xml", "shared.xml"]);
+
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, true)?,
+        &AttributeRules::default(),
+    );
+    assert_eq!(file_infos.len(), 2);
+    assert!(file_errorss.is_empty());
+
+    // Tracked symlinks are treated the same way in `git ls-files`
+    // mode, and are reported when skipped
+    test_git_stdout(&dir, &["add", "link.xml", "shared.xml"])?;
+    let listing = |follow_symlinks| -> Result<(Vec<String>, Vec<PathBuf>)> {
+        let XmlFilePaths {
+            paths,
+            skipped_symlinks,
+            ..
+        } = xml_file_paths_with_max_depth(
+            &GitWorkingDir::from(dir.to_path_buf()),
+            true,
+            follow_symlinks,
+            None,
+            &XmlExtensions::default(),
+        )?;
+        let rel_paths = paths
+            .iter()
+            .map(|path| path.rel_path().to_string())
+            .collect();
+        Ok((rel_paths, skipped_symlinks))
+    };
+    let (rel_paths, skipped_symlinks) = listing(false)?;
+    assert_eq!(rel_paths, ["a.xml"]);
+    assert_eq!(
+        skipped_symlinks,
+        [PathBuf::from("link.xml"), PathBuf::from("shared.xml")]
+    );
+    assert_eq!(
+        skipped_symlinks_note(&skipped_symlinks),
+        "left out 2 XML files that are symbolic links (use --follow-symlinks to index \
+         them): \"link.xml\", \"shared.xml\""
+    );
+    let (rel_paths, skipped_symlinks) = listing(true)?;
+    assert_eq!(rel_paths, ["a.xml", "shared.xml"]);
+    assert!(skipped_symlinks.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn t_print_paths() -> Result<()> {
+    let xml = test_xml_file_content("2.7");
+    let dir = test_repository(
+        "t_print_paths",
+        &[
+            ("b.xml", &xml),
+            ("a/c.XML", &xml),
+            ("notes.txt", "not XML"),
+            ("a.xml.bak", &xml),
+        ],
+    )?;
+    std::fs::write(dir.join("untracked.xml"), &xml)?;
+    std::fs::create_dir(dir.join(&*DAEMON_FOLDER_NAME))?;
+    std::fs::write(dir.join(&*DAEMON_FOLDER_NAME).join("ignored.xml"), &xml)?;
+
+    let printed = |ignore_untracked| -> Result<String> {
+        let paths = xml_file_paths(
+            &GitWorkingDir::from(dir.to_path_buf()),
+            ignore_untracked,
+            false,
+        )?;
+        let mut out = Vec::new();
+        print_paths_plain(&paths, &mut out)?;
+        Ok(String::from_utf8(out)?)
+    };
+    assert_eq!(printed(false)?, "a/c.XML\nb.xml\nuntracked.xml\n");
+    assert_eq!(printed(true)?, "a/c.XML\nb.xml\n");
+
+    // Nothing is built with the option
+    let (exit_code, summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            print_paths: true,
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+    assert_eq!(summary.files, 3);
+    for path in DEFAULT_OUTPUT_FILES.paths_from_repo_top() {
+        assert!(!dir.join(path).exists(), "{path:?} was not written");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn t_read_latin1_file() -> Result<()> {
+    let dir = test_repository("t_read_latin1_file", &[("notes.txt", "")])?;
+    let content = TestXml::new("2.7")
+        .header("Contact", "Jürg Müller")
+        .to_string()
+        .replace("encoding=\"UTF-8\"", "encoding=\"ISO-8859-1\"");
+    let latin1: Vec<u8> = content
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).expect("Latin-1 character"))
+        .collect();
+    std::fs::write(dir.join("latin1.xml"), latin1)?;
+
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    assert!(file_errorss.is_empty());
+    let [file_info] = &file_infos[..] else {
+        panic!("expecting one file")
+    };
+    let contact = attribute_specification_by_name("Contact")
+        .expect("present")
+        .key;
+    assert_eq!(
+        file_info
+            .metadata
+            .get(contact)
+            .expect("given")
+            .as_string_list()[0],
+        "Jürg Müller"
+    );
+    assert!(file_info
+        .warnings
+        .iter()
+        .any(|issue| issue.message.contains("encoded in ISO-8859-1")));
+
+    Ok(())
+}
+
+#[test]
+fn t_count_only() -> Result<()> {
+    let no_version = TestXml::new("2.7").without("Version").to_string();
+    let dir = test_repository(
+        "t_count_only",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            // Version conflict with the <beast> element: a warning
+            ("b.xml", &test_xml_file_content("2.6")),
+            ("broken.xml", "<beast>"),
+            ("no-version.xml", &no_version),
+        ],
+    )?;
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    let counts = FileCounts::new(&file_infos, &file_errorss);
+    assert_eq!(counts.to_string(), "files=4 errors=2 warnings=1");
+    assert_eq!(counts.exit_code(), 1);
+
+    let (exit_code, summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            count_only: true,
+            write_errors: true,
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 1);
+    assert_eq!((summary.files, summary.errors, summary.warnings), (4, 2, 1));
+    assert!(!summary.committed);
+    for path in DEFAULT_OUTPUT_FILES.paths_from_repo_top() {
+        assert!(!dir.join(path).exists(), "{path:?} was not written");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn t_count_by() -> Result<()> {
+    let dir = test_repository(
+        "t_count_by",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("b.xml", &test_xml_file_content("2.7")),
+            (
+                "c.xml",
+                &TestXml::new("2.7")
+                    .header("Contact", "Other Person")
+                    .header("Keywords", "Foo, qux, baz")
+                    .to_string(),
+            ),
+        ],
+    )?;
+    let (file_infos, _) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    let tally = |name: &str| -> Result<String> {
+        let mut out = Vec::new();
+        print_value_tally(
+            &value_tally(count_by_attribute(name)?, &file_infos),
+            &mut out,
+        )?;
+        Ok(String::from_utf8(out)?)
+    };
+    assert_eq!(tally("Contact")?, "2\tSome One\n1\tOther Person\n");
+    // Values are normalized as in the index, ties sorted by value
+    assert_eq!(tally("keywords")?, "3\tfoo\n2\tbar\n1\tbaz\n1\tqux\n");
+
+    let err = |name: &str| count_by_attribute(name).unwrap_err().to_string();
+    assert_eq!(
+        err("Contacts"),
+        "invalid --count-by: unknown attribute name \"Contacts\""
+    );
+    assert!(err("Description").starts_with(
+        "invalid --count-by: attribute \"Description\" is not indexed, expecting one of Keywords, "
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn t_validation_report_json() -> Result<()> {
+    let dir = test_repository(
+        "t_validation_report_json",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("b.xml", &test_xml_file_content("2.6")),
+            ("broken.xml", "<beast>"),
+        ],
+    )?;
+    let json = || -> Result<String> {
+        validate(&GitWorkingDir::from(dir.to_path_buf()), false, None, None)?.to_json()
+    };
+    let first = json()?;
+    assert_eq!(first, json()?);
+
+    // Keys are sorted, also in nested objects
+    assert_eq!(
+        first,
+        r#"{
+  "errors": [
+    {
+      "issues": [
+        {
+          "hint": null,
+          "location": {
+            "column": 1,
+            "line": 1
+          },
+          "message": "parsing the XML markup: the root node was opened but never closed"
+        }
+      ],
+      "path": "broken.xml"
+    }
+  ],
+  "number_of_files": 3,
+  "schema_version": 1,
+  "warnings": [
+    {
+      "issues": [
+        {
+          "hint": "The BEAST2 major version numbers differ. Please edit the file to make both versions match the BEAST version you're actually using.",
+          "location": null,
+          "message": "conflicting values for attribute \"Version\": the value given in the file is \"2.6\", but the <beast> element in the document gives \"2.7\""
+        }
+      ],
+      "path": "b.xml"
+    }
+  ]
+}"#
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_batch_divergence() -> Result<()> {
+    let dir = test_repository(
+        "t_batch_divergence",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let _remote = test_add_remote(&dir)?;
+
+    // An unpushed commit of the index files is not a divergence
+    assert_eq!(
+        test_build_index(&dir, test_build_index_opts(DEFAULT_OUTPUT_FILES))?,
+        0
+    );
+    let local_commits =
+        || local_commits_not_in(&GitWorkingDir::from(dir.to_path_buf()), "origin/master");
+    assert_eq!(local_commits()?, Vec::<String>::new());
+
+    std::fs::write(dir.join("local.txt"), "local")?;
+    test_git_stdout(&dir, &["add", "local.txt"])?;
+    test_git_stdout(&dir, &["commit", "-q", "-m", "local change"])?;
+    let local_commits = local_commits()?;
+    assert_eq!(local_commits.len(), 1);
+    assert!(local_commits[0].ends_with(" local change"));
+
+    let opts = |refuse_on_divergence| BuildIndexOpts {
+        batch: true,
+        refuse_on_divergence,
+        ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+    };
+    let log = || test_git_stdout(&dir, &["log", "--oneline"]);
+
+    let error = test_build_index_maybe_pushing(&dir, opts(true), true).expect_err("refused");
+    let message = format!("{error:#}");
+    assert!(message.contains("refusing to discard"), "{message}");
+    assert!(message.contains("local change"), "{message}");
+    assert!(log()?.contains("local change"));
+
+    // Without the option, the commit is discarded (after a warning)
+    let (exit_code, _) = test_build_index_maybe_pushing(&dir, opts(false), true)?;
+    assert_eq!(exit_code, 0);
+    assert!(!log()?.contains("local change"));
+    assert!(!dir.join("local.txt").exists());
+
+    Ok(())
+}
+
+#[test]
+fn t_attachments() -> Result<()> {
+    use xmlhub_indexer::xmlhub_attributes::attachment_file_names;
+
+    let content = TestXml::new("2.7")
+        .body(
+            "<beast version=\"2.7\">\n\
+         <data id=\"a\" fileName=\"data/seqs.fasta\"/>\n\
+         <data id=\"b\" fileName=\"missing.nex\"/>\n\
+             <data id=\"c\" fileName=\"data/seqs.fasta\"/>\n\
+             </beast>",
+        )
+        .to_string();
+    let dir = test_repository(
+        "t_attachments",
+        &[
+            ("sub/a.xml", &content),
+            ("sub/data/seqs.fasta", ">x\nACGT\n"),
+        ],
+    )?;
+    let paths = xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?;
+    let [path] = &paths[..] else {
+        panic!("expecting one file")
+    };
+
+    let file_names = attachment_file_names(&read_xml_file(&path.full_path())?);
+    assert_eq!(file_names, ["data/seqs.fasta", "missing.nex"]);
+    let warnings = missing_attachments_warnings(path, &file_names);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].message,
+        "the attached file \"missing.nex\" referenced by the document is missing"
+    );
+
+    // Paths leading outside of the folder are not looked at
+    let warnings = missing_attachments_warnings(
+        path,
+        &["/etc/passwd".into(), "../sub/data/seqs.fasta".into()],
+    );
+    assert_eq!(
+        warnings
+            .iter()
+            .map(|issue| issue.message.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "the attached file \"/etc/passwd\" referenced by the document \
+             is not inside the folder of the document",
+            "the attached file \"../sub/data/seqs.fasta\" referenced by the document \
+             is not inside the folder of the document",
+        ]
+    );
+
+    // Only extracted when enabled in the repository configuration
+    let attachments = |config: &str| -> Result<Option<Vec<String>>> {
+        let config = XmlhubConfig::from_file_contents(config)?;
+        let (file_infos, _) = read_and_derive_file_infos(
+            paths.clone(),
+            &AttributeRules::default().with_config(&config),
+        );
+        let key = attribute_specification_by_name("Attachments")
+            .expect("present")
+            .key;
+        Ok(file_infos[0]
+            .metadata
+            .get(key)
+            .map(|value| value.as_string_list().into_owned()))
+    };
+    assert_eq!(attachments("")?, None);
+    assert_eq!(
+        attachments("attachments = true")?.as_deref(),
+        Some(&["data/seqs.fasta".to_string(), "missing.nex".into()][..])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_duplicate_paths() -> Result<()> {
+    let dir = test_repository(
+        "t_duplicate_paths",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let mut paths = xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?;
+    // Simulate two files mapping to the same path
+    paths.push(paths[0].clone());
+    let (file_infos, file_errorss) = read_and_derive_file_infos(paths, &AttributeRules::default());
+    assert!(file_errorss.is_empty());
+    assert_eq!(file_infos.len(), 2);
+
+    let (file_infos, duplicate_errorss) = split_off_duplicate_paths(file_infos);
+    assert_eq!(file_infos.len(), 1);
+    let [file_errors] = &duplicate_errorss[..] else {
+        panic!("expecting one error report")
+    };
+    assert_eq!(file_errors.path.rel_path(), "a.xml");
+    assert!(file_errors.errors[0].message.contains("same path"));
+
+    // Building the folder hierarchy doesn't fail now
+    let mut folder = Folder::new();
+    for file_info in &file_infos {
+        folder.add(file_info)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn t_standalone() -> Result<()> {
+    let content = TestXml::new("2.7")
+        .body(
+            "<beast version=\"2.7\">\n\
+             <data id=\"alignment\">\n\
+             <sequence taxon=\"a\" value=\"ACGT\"/>\n\
+             </data>\n\
+             </beast>",
+        )
+        .to_string();
+    let dir = test_repository("t_standalone", &[("sub/a.xml", &content)])?;
+    let standalone_dir = TempDir::new("xmlhub-t_standalone-output")?;
+    let standalone_path = standalone_dir.join("standalone.html");
+
+    let (exit_code, summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            standalone: Some(standalone_path.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+    assert!(!summary.committed);
+    for path in DEFAULT_OUTPUT_FILES.paths_from_repo_top() {
+        assert!(!dir.join(path).exists(), "{path}");
+    }
+
+    let output = std::fs::read_to_string(&standalone_path)?;
+    assert!(output.contains("File contents"));
+    assert!(output.contains("<details id=\"content-"));
+    assert!(output.contains("<summary>sub/a.xml"));
+    // The embedded XML source, escaped
+    assert!(output.contains("&lt;!-- Contact: Some One --&gt;"));
+    // with the sequences blinded
+    assert!(output.contains("taxon=&quot;a&quot;"));
+    assert!(!output.contains("ACGT"));
+    assert!(!std::fs::read_to_string(dir.join("sub/a.xml"))?.contains("value=\"-\""));
+
+    Ok(())
+}
+
+#[test]
+fn t_build_progress() -> Result<()> {
+    use xmlhub_indexer::{
+        build_progress::{ProgressEvent, RecordingProgress},
+        output_sink::MemorySink,
+    };
+
+    let dir = test_repository(
+        "t_build_progress",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("broken.xml", "<beast>"),
+        ],
+    )?;
+    let progress = Arc::new(RecordingProgress::default());
+    test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            write_errors: true,
+            output_sink: Some(Arc::new(MemorySink::default())),
+            progress: progress.clone(),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+
+    let events = progress.events();
+    let mut parsed: Vec<(&str, usize)> = events
+        .iter()
+        .filter_map(|event| match event {
+            ProgressEvent::FileParsed {
+                rel_path, errors, ..
+            } => Some((rel_path.as_str(), *errors)),
+            _ => None,
+        })
+        .collect();
+    // Files are read in parallel, thus reported in any order
+    parsed.sort();
+    assert_eq!(parsed, [("a.xml", 0), ("broken.xml", 1)]);
+    let sections: Vec<&str> = events
+        .iter()
+        .filter_map(|event| match event {
+            ProgressEvent::SectionBuilt { title } => Some(title.as_str()),
+            _ => None,
+        })
+        .collect();
+    for title in ["Errors", "Index by attribute", "File info by folder"] {
+        assert_eq!(
+            sections.iter().filter(|t| **t == title).count(),
+            1,
+            "{title:?} in {sections:?}"
+        );
+    }
+    // All files are reported before the sections
+    assert!(matches!(events[0], ProgressEvent::FileParsed { .. }));
+    assert!(matches!(events[1], ProgressEvent::FileParsed { .. }));
+
+    Ok(())
+}
+
+#[test]
+fn t_validate_schema() -> Result<()> {
+    let nonconforming = TestXml::new("2.7")
+        .body("<beast version=\"2.7\"><run/></beast>")
+        .to_string();
+    let dir = test_repository(
+        "t_validate_schema",
+        &[
+            ("good.xml", &test_xml_file_content("2.7")),
+            ("sub/bad.xml", &nonconforming),
+        ],
+    )?;
+    let schema = Schema::from_str(
+        r#"<element name="beast" xmlns="http://relaxng.org/ns/structure/1.0">
+             <attribute name="version"/>
+             <empty/>
+           </element>"#,
+    )?;
+
+    let report = validate(
+        &GitWorkingDir::from(dir.to_path_buf()),
+        false,
+        None,
+        Some(&schema),
+    )?;
+    assert_eq!(report.number_of_files, 2);
+    let [file_errors] = &report.errors[..] else {
+        panic!("expecting one file with errors, got {:?}", report.errors)
+    };
+    assert_eq!(file_errors.path, "sub/bad.xml");
+    let issue = &file_errors.issues[0];
+    assert_eq!(
+        issue.message,
+        "does not conform to the schema: element <run> is not allowed here"
+    );
+    assert_eq!(
+        issue.location,
+        Some(LineAndColumn {
+            line: 6,
+            column: 22
+        })
+    );
+    assert_eq!(report.exit_code(), 1);
+
+    // Without the schema, both files are fine
+    let report = validate(&GitWorkingDir::from(dir.to_path_buf()), false, None, None)?;
+    assert!(report.errors.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn t_normalize_file() -> Result<()> {
+    let dir = TempDir::new("xmlhub-t_normalize")?;
+    let messy = dir.join("messy.xml");
+    std::fs::write(
+        &messy,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <!--   contact :  Some One -->\n\
+         <!--Keywords:foo ,bar,,  baz-->   <!-- Version: 2.7-->\n\
+         <!-- Packages:   BEAST 2.7.0 -->\n\
+         <!-- description: two\n   lines -->\n\
+         <beast version=\"2.7\"></beast>\n",
+    )?;
+    let (content, content_has_changed) = normalize_file(&messy)?;
+    assert!(content_has_changed);
+    assert_eq!(
+        content,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <!-- Keywords: foo, bar, baz -->\n\
+         <!-- Version: 2.7 -->\n\
+         <!-- Packages: BEAST 2.7.0 -->\n\
+         <!--\n\
+         Description: two\n   lines\n\
+         -->\n\
+         <!-- Contact: Some One -->\n\
+         <beast version=\"2.7\"></beast>\n"
+    );
+
+    // Canonical files are left unchanged
+    std::fs::write(&messy, &content)?;
+    let (content2, content_has_changed) = normalize_file(&messy)?;
+    assert!(!content_has_changed);
+    assert_eq!(content2, content);
+    let clean = dir.join("clean.xml");
+    std::fs::write(&clean, test_xml_file_content("2.7"))?;
+    assert!(!normalize_file(&clean)?.1);
+
+    // Metadata given as processing instruction is turned into
+    // comments, comments taking precedence
+    let pi = dir.join("pi.xml");
+    std::fs::write(
+        &pi,
+        "<?xmlhub Keywords=\"foo\" Version=\"2.7\" Packages=\"BEAST 2.7.0\" \
+         Contact=\"PI Person\"?>\n\
+         <!-- Contact: Some One -->\n\
+         <beast version=\"2.7\"></beast>\n",
+    )?;
+    let (content, content_has_changed) = normalize_file(&pi)?;
+    assert!(content_has_changed);
+    assert_eq!(
+        content,
+        "<!-- Keywords: foo -->\n\
+         <!-- Version: 2.7 -->\n\
+         <!-- Packages: BEAST 2.7.0 -->\n\
+         <!-- Contact: Some One -->\n\
+         <beast version=\"2.7\"></beast>\n"
+    );
+
+    // Files with metadata errors are refused
+    std::fs::write(&clean, "<!-- Keywords: foo -->\n<beast/>\n")?;
+    assert!(normalize_file(&clean).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn t_build_decision() {
+    for bits in 0..(1u32 << 11) {
+        let bit = |i: u32| bits & (1 << i) != 0;
+        let inputs = BuildDecisionInputs {
+            have_errors: bit(0),
+            write_errors: bit(1),
+            silent_on_written_errors: bit(2),
+            ok_on_written_errors: bit(3),
+            not_in_working_dir: bit(4),
+            have_written_files: bit(5),
+            no_commit: bit(6),
+            no_commit_errors: bit(7),
+            push: bit(8),
+            no_push_on_error: bit(9),
+            sample: bit(10),
+        };
+        let decision = build_decision(inputs);
+        let BuildDecisionInputs {
+            have_errors,
+            write_errors,
+            silent_on_written_errors,
+            ok_on_written_errors,
+            not_in_working_dir,
+            have_written_files,
+            no_commit,
+            sample,
+            no_commit_errors,
+            push,
+            no_push_on_error,
+        } = inputs;
+
+        let write_files = !have_errors || write_errors;
+        let commit_files = write_files
+            && !not_in_working_dir
+            && !(no_commit || sample || !have_written_files || (have_errors && no_commit_errors));
+        let exit_code = if have_errors
+            && !(write_errors && (silent_on_written_errors || ok_on_written_errors))
+        {
+            1
+        } else {
+            0
+        };
+        assert_eq!(decision.write_files, write_files, "{inputs:?}");
+        assert_eq!(decision.commit_files, commit_files, "{inputs:?}");
+        assert_eq!(
+            decision.push,
+            commit_files && push && !(have_errors && no_push_on_error),
+            "{inputs:?}"
+        );
+        assert_eq!(decision.exit_code, exit_code, "{inputs:?}");
+        assert_eq!(
+            decision.write_errors_to_stderr,
+            have_errors && !(write_errors && silent_on_written_errors),
+            "{inputs:?}"
+        );
+
+        // The explanation ends with the decisions, and gives a reason
+        // for each of them
+        let lines = decision.explanation_lines();
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        assert_eq!(
+            lines[lines.len() - 4..],
+            [
+                format!("=> write files: {}", yes_no(write_files)),
+                format!("=> commit: {}", yes_no(commit_files)),
+                format!("=> push: {}", yes_no(decision.push)),
+                format!("=> exit code: {exit_code}"),
+            ],
+            "{inputs:?}"
+        );
+        let reasons = lines[..lines.len() - 4].join("\n");
+        if !write_files {
+            assert!(
+                reasons.contains("--write-errors was not given"),
+                "{inputs:?}"
+            );
+        } else if not_in_working_dir {
+            assert!(reasons.contains("not written to the Git working directory"));
+        } else if no_commit {
+            assert!(reasons.contains("--no-commit was given"), "{inputs:?}");
+        } else if sample {
+            assert!(reasons.contains("--sample was given"), "{inputs:?}");
+        } else if commit_files && !decision.push {
+            assert!(
+                reasons.contains("--push was not given")
+                    || reasons.contains("--no-push-on-error was given"),
+                "{inputs:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn t_build_explain() -> Result<()> {
+    let dir = test_repository(
+        "t_build_explain",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("b.xml", "<beast></beast>"),
+        ],
+    )?;
+    let num_commits =
+        || -> Result<String> { test_git_stdout(&dir, &["rev-list", "--count", "HEAD"]) };
+    let commits_before = num_commits()?;
+
+    // With errors, the files would not be written
+    let (exit_code, summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            explain: true,
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 1);
+    assert!(!summary.committed);
+
+    // With --write-errors they would be, and committed; explaining
+    // still doesn't do it
+    let (exit_code, summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            explain: true,
+            write_errors: true,
+            ok_on_written_errors: true,
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+    assert!(!summary.committed);
+    for path in DEFAULT_OUTPUT_FILES.paths_from_repo_top() {
+        assert!(!dir.join(path).exists(), "{path}");
+    }
+    assert_eq!(num_commits()?, commits_before);
+
+    // The actual build does what was explained
+    let (exit_code, summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            write_errors: true,
+            ok_on_written_errors: true,
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+    assert!(summary.committed);
+    assert_ne!(num_commits()?, commits_before);
+
+    Ok(())
+}
+
+#[test]
+fn t_stable_file_ids() -> Result<()> {
+    let dir = test_repository(
+        "t_stable_file_ids",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("c.xml", &test_xml_file_content("2.7")),
+        ],
+    )?;
+    let box_anchors = || -> Result<BTreeSet<String>> {
+        let html =
+            std::fs::read_to_string(dir.join(&*DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top))?;
+        let regex = regex::Regex::new(r#"id="box-([0-9a-f]+)""#)?;
+        Ok(regex
+            .captures_iter(&html)
+            .map(|c| c[1].to_string())
+            .collect())
+    };
+
+    let (exit_code, _) = test_build_index_maybe_pushing(
+        &dir,
+        test_build_index_opts(DEFAULT_OUTPUT_FILES.clone()),
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+    let before = box_anchors()?;
+    assert_eq!(before.len(), 2);
+
+    // Insert a file that sorts between the existing ones
+    std::fs::write(dir.join("b.xml"), test_xml_file_content("2.7"))?;
+    test_git_stdout(&dir, &["add", "b.xml"])?;
+    test_git_stdout(&dir, &["commit", "-m", "add b.xml"])?;
+    let (exit_code, _) = test_build_index_maybe_pushing(
+        &dir,
+        test_build_index_opts(DEFAULT_OUTPUT_FILES.clone()),
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+    let after = box_anchors()?;
+    let added: Vec<_> = after.difference(&before).collect();
+    assert_eq!(
+        added,
+        [&FileId::from_rel_path("b.xml", 0).to_string()],
+        "only the new file's anchor is new"
+    );
+    assert!(before.is_subset(&after));
+
+    Ok(())
+}
+
+#[test]
+fn t_output_dir() -> Result<()> {
+    let dir = test_repository(
+        "t_output_dir",
+        &[("sub/a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let file_hrefs = |html_path: &Path| -> Result<BTreeSet<String>> {
+        let html = std::fs::read_to_string(html_path)?;
+        let regex = regex::Regex::new(r##"href="([^"#]*a\.xml)""##)?;
+        Ok(regex
+            .captures_iter(&html)
+            .map(|c| c[1].to_string())
+            .collect())
+    };
+    let html_file = &*DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top;
+
+    // Outside of the repository: not committed
+    let output_dir = TempDir::new("xmlhub-t_output_dir-published")?;
+    let (exit_code, summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            output_dir: Some(output_dir.to_path_buf()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+    assert!(!summary.committed);
+    for path in DEFAULT_OUTPUT_FILES.paths_from_repo_top() {
+        assert!(output_dir.join(path).exists(), "{path}");
+        assert!(!dir.join(path).exists(), "{path}");
+    }
+    let hrefs = file_hrefs(&output_dir.join(html_file))?;
+    assert!(!hrefs.is_empty());
+    for href in &hrefs {
+        assert!(href.starts_with("../"), "{href}");
+        assert!(output_dir.join(href).exists(), "{href}");
+    }
+
+    // With an explicit link base
+    let (exit_code, _) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            output_dir: Some(output_dir.to_path_buf()),
+            link_base: Some("https://example.com/hub/".into()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+    assert_eq!(
+        file_hrefs(&output_dir.join(html_file))?,
+        ["https://example.com/hub/sub/a.xml".to_string()].into()
+    );
+
+    // Inside of the repository: committed
+    let (exit_code, summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            output_dir: Some(dir.join("public")),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+    assert!(summary.committed);
+    let tracked = test_git_stdout(&dir, &["ls-files", "public"])?;
+    assert!(
+        tracked.contains(&format!("public/{html_file}")),
+        "{tracked}"
+    );
+    let hrefs = file_hrefs(&dir.join("public").join(html_file))?;
+    assert_eq!(hrefs, ["../sub/a.xml".to_string()].into());
+
+    Ok(())
+}
+
+#[test]
+fn t_location_index() -> Result<()> {
+    let with_location =
+        |location: &str| TestXml::new("2.7").header("Location", location).to_string();
+    let dir = test_repository(
+        "t_location_index",
+        &[
+            ("a.xml", &with_location("USA")),
+            ("b.xml", &with_location("United States")),
+            ("c.xml", &with_location("us")),
+            ("d.xml", &with_location("Atlantis")),
+        ],
+    )?;
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    assert!(file_errorss.is_empty());
+
+    let spec = attribute_specification_by_name("Location").expect("present");
+    let section = build_index_section(
+        spec.key,
+        spec.key_string_preparation().expect("indexed"),
+        spec.autolink,
+        &file_infos,
+        &index_anchor_names(&file_infos),
+        IndexSectionOpts::default(),
+    )?;
+    let index = section.intro.expect("has intro").as_str().to_string();
+    // One key for the 3 variants, one for the unknown location
+    assert_eq!(index.matches("<dt").count(), 2, "{index}");
+    assert!(index.contains("United States"));
+    assert!(index.contains("Atlantis"));
+    assert!(!index.contains("USA"));
+
+    // The boxes show the original text, and the unknown location
+    // gets a warning with a hint
+    let html = HTML_ALLOCATOR_POOL.get();
+    for (file_info, original) in file_infos.iter().zip(["USA", "United States", "us"]) {
+        let box_html = html
+            .preserialize(file_info.to_info_box_html(&html, "box", "", "", Default::default())?)?
+            .as_str()
+            .to_string();
+        assert!(box_html.contains(&format!(">{original}<")), "{box_html}");
+        assert!(file_info.warnings.is_empty());
+    }
+    let [warning] = &file_infos[3].warnings[..] else {
+        panic!("expecting one warning")
+    };
+    assert!(warning
+        .message
+        .contains("\"Atlantis\" is not known as a country"));
+    assert!(warning.hint.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn t_check_issue_classes() -> Result<()> {
+    // (A file with errors has no warnings reported, thus one file for
+    // each class.)
+    let dir = test_repository(
+        "t_check_issue_classes",
+        &[
+            ("broken.xml", "<beast></beast>"),
+            (
+                "warned.xml",
+                &TestXml::new("2.7")
+                    .header("Location", "Atlantis")
+                    .to_string(),
+            ),
+        ],
+    )?;
+    let fileinfo_or_errors = read_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+        &NoProgress,
+    );
+    let report = |issue_classes| -> Result<(i32, String)> {
+        let mut out = Vec::new();
+        let exit_code = print_check_report(&fileinfo_or_errors, issue_classes, &mut out)?;
+        Ok((exit_code, String::from_utf8(out)?))
+    };
+
+    let (exit_code, both) = report(IssueClasses::Both)?;
+    assert_eq!(exit_code, 1);
+    assert!(both.contains("For \"broken.xml\":\n"));
+    assert!(both.contains("Atlantis"));
+
+    let (exit_code, errors) = report(IssueClasses::ErrorsOnly)?;
+    assert_eq!(exit_code, 1);
+    assert!(errors.contains("For \"broken.xml\":\n"));
+    assert!(!errors.contains("Warnings"));
+    assert!(!errors.contains("Atlantis"));
+
+    let (exit_code, warnings) = report(IssueClasses::WarningsOnly)?;
+    assert_eq!(exit_code, 1, "still reflects the errors");
+    assert!(!warnings.contains("broken.xml"));
+    assert!(warnings.contains("For \"warned.xml\":\n"));
+    assert!(warnings.contains("Atlantis"));
+
+    Ok(())
+}
+
+#[test]
+fn t_build_from_ref() -> Result<()> {
+    use xmlhub_indexer::output_sink::MemorySink;
+
+    let dir = test_repository(
+        "t_build_from_ref",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("sub/b.xml", &test_xml_file_content("2.6")),
+        ],
+    )?;
+    let build = |from_ref: Option<&str>| -> Result<BTreeMap<String, Vec<u8>>> {
+        let sink = Arc::new(MemorySink::default());
+        let (exit_code, summary) = test_build_index_maybe_pushing(
+            &dir,
+            BuildIndexOpts {
+                from_ref: from_ref.map(String::from),
+                output_sink: Some(sink.clone()),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+            false,
+        )?;
+        assert_eq!(exit_code, 0);
+        assert!(!summary.committed);
+        Ok(sink.files())
+    };
+
+    let working_tree_files = build(None)?;
+    test_git_stdout(&dir, &["tag", "fixture"])?;
+
+    // Change the working tree and the branch
+    std::fs::write(dir.join("sub/b.xml"), test_xml_file_content("2.5"))?;
+    test_git_stdout(&dir, &["commit", "-a", "-m", "change b.xml"])?;
+    std::fs::write(dir.join("c.xml"), test_xml_file_content("2.7"))?;
+    let head = test_git_stdout(&dir, &["rev-parse", "HEAD"])?;
+
+    assert_eq!(build(Some("fixture"))?, working_tree_files);
+    assert_ne!(build(None)?, working_tree_files);
+    assert_eq!(test_git_stdout(&dir, &["rev-parse", "HEAD"])?, head);
+
+    assert!(build(Some("no-such-ref")).is_err());
+
+    // The exported files are deleted again
+    let prefixes = ["xmlhub-from-ref", "xmlhub-from-ref-index"]
+        .map(|name| format!("{name}-{}-", std::process::id()));
+    for entry in std::fs::read_dir(std::env::temp_dir())? {
+        let file_name = entry?.file_name();
+        let file_name = file_name.to_string_lossy();
+        assert!(
+            !prefixes.iter().any(|prefix| file_name.starts_with(prefix)),
+            "{file_name:?} left behind"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn t_require_attributes() -> Result<()> {
+    let with_doi = TestXml::new("2.7").header("DOI", "10.1000/182").to_string();
+    let with_na_doi = TestXml::new("2.7").header("DOI", "NA").to_string();
+    let dir = test_repository(
+        "t_require_attributes",
+        &[
+            (
+                CONFIG_FILE_PATH,
+                "# Published files only\n[require_attributes]\nproject = [\"DOI\"]\n",
+            ),
+            ("project/sub/missing.xml", &test_xml_file_content("2.7")),
+            ("project/na.xml", &with_na_doi),
+            ("project/given.xml", &with_doi),
+            ("other/missing.xml", &test_xml_file_content("2.7")),
+        ],
+    )?;
+    let config = XmlhubConfig::read(&dir)?;
+    let errors = |attribute_rules: &AttributeRules| -> Result<Vec<String>> {
+        let (_, file_errorss) = read_and_derive_file_infos(
+            xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+            &attribute_rules.clone().with_config(&config),
+        );
+        Ok(file_errorss
+            .iter()
+            .map(|file_errors| {
+                assert!(
+                    file_errors
+                        .errors
+                        .iter()
+                        .any(|issue| issue.message.contains("DOI")),
+                    "{file_errors:?}"
+                );
+                file_errors.path.rel_path().to_string()
+            })
+            .sorted()
+            .collect())
+    };
+    assert_eq!(
+        errors(&AttributeRules::default())?,
+        ["project/na.xml", "project/sub/missing.xml"]
+    );
+    assert_eq!(
+        errors(&AttributeRules::from_names(&["doi".into()])?)?,
+        [
+            "other/missing.xml",
+            "project/na.xml",
+            "project/sub/missing.xml"
+        ]
+    );
+    assert!(AttributeRules::from_names(&["Nonexisting".into()]).is_err());
+    // Calculated attributes can't be required
+    assert!(AttributeRules::from_names(&["Citation via DOI".into()]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn t_commit_trailers() -> Result<()> {
+    assert_eq!(
+        co_author_trailer(" Some One <some@example.com> ")?,
+        "Co-authored-by: Some One <some@example.com>"
+    );
+    for invalid in [
+        "Some One",
+        "<some@example.com>",
+        "Some One <some>",
+        "Some <a@b> c",
+    ] {
+        assert!(co_author_trailer(invalid).is_err(), "{invalid:?}");
+    }
+
+    let dir = test_repository(
+        "t_commit_trailers",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    assert_eq!(
+        test_build_index(
+            &dir,
+            BuildIndexOpts {
+                signoff: true,
+                commit_trailers: vec![
+                    co_author_trailer("Some One <some@example.com>")?,
+                    co_author_trailer("Other One <other@example.com>")?,
+                ],
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            }
+        )?,
+        0
+    );
+    let message = test_git_stdout(&dir, &["log", "-1", "--format=%B"])?;
+    assert!(message.starts_with("regenerate index files via "));
+    // The trailers are the last paragraph
+    let (_, trailers) = message.trim().rsplit_once("\n\n").expect("has trailers");
+    let lines: Vec<&str> = trailers.lines().collect();
+    assert_eq!(
+        &lines[0..2],
+        [
+            "Co-authored-by: Some One <some@example.com>",
+            "Co-authored-by: Other One <other@example.com>"
+        ]
+    );
+    assert!(lines[2].starts_with("Signed-off-by: "));
+    assert!(lines[2].ends_with(" <test@example.com>"));
+    assert_eq!(lines.len(), 3);
+    // The version stamp is still found
+    git_log_version_checker(
+        "v12".parse()?,
+        false,
+        GitWorkingDir::from(dir.to_path_buf()).into(),
+        DEFAULT_OUTPUT_FILES.into(),
+    )
+    .check_git_log()?;
+
+    Ok(())
+}
+
+#[test]
+fn t_strict_version_check() -> Result<()> {
+    // Index files committed without version statements (e.g. by
+    // hand)
+    let dir = test_repository(
+        "t_strict_version_check",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            (&DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top, ""),
+            (&DEFAULT_OUTPUT_FILES.md_file.path_from_repo_top, ""),
+        ],
+    )?;
+    let check = |require_version_statements: bool| -> Result<()> {
+        XmlhubCheckVersion {
+            require_version_statements,
+            ..git_log_version_checker(
+                "v12".parse()?,
+                false,
+                GitWorkingDir::from(dir.to_path_buf()).into(),
+                DEFAULT_OUTPUT_FILES.into(),
+            )
+        }
+        .check_git_log()
+    };
+    check(false)?;
+    let e = check(true).unwrap_err();
+    assert!(e.downcast_ref::<VersionStatementsNotFound>().is_some());
+
+    assert_eq!(
+        test_build_index(&dir, test_build_index_opts(DEFAULT_OUTPUT_FILES.clone()))?,
+        0
+    );
+    check(false)?;
+    check(true)?;
+
+    Ok(())
+}
+
+#[test]
+fn t_flag_index() -> Result<()> {
+    let with_published = |published: &str| {
+        TestXml::new("2.7")
+            .header("Published", published)
+            .to_string()
+    };
+    let dir = test_repository(
+        "t_flag_index",
+        &[
+            ("a.xml", &with_published("yes")),
+            ("b.xml", &with_published("No")),
+            ("c.xml", &with_published("true")),
+            ("d.xml", &test_xml_file_content("2.7")),
+            ("e.xml", &with_published("NA")),
+            ("f.xml", &with_published("maybe")),
+        ],
+    )?;
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    let [file_errors] = &file_errorss[..] else {
+        panic!("expecting errors for f.xml only: {file_errorss:?}")
+    };
+    assert_eq!(file_errors.path.rel_path(), "f.xml");
+    assert!(file_errors.errors[0].message.contains("\"maybe\""));
+
+    let spec = attribute_specification_by_name("Published").expect("present");
+    let values: Vec<Vec<String>> = file_infos
+        .iter()
+        .map(|file_info| {
+            file_info
+                .metadata
+                .get(spec.key)
+                .map(|value| value.as_string_list().to_vec())
+                .unwrap_or_default()
+        })
+        .collect();
+    assert_eq!(
+        values,
+        [vec!["yes"], vec!["no"], vec!["yes"], vec![], vec![]]
+    );
+
+    let section = build_index_section(
+        spec.key,
+        spec.key_string_preparation().expect("indexed"),
+        spec.autolink,
+        &file_infos,
+        &index_anchor_names(&file_infos),
+        IndexSectionOpts::default(),
+    )?;
+    let index = section.intro.expect("has intro").as_str().to_string();
+    let entries: Vec<&str> = index.split("<dt").skip(1).collect();
+    let files_in = |key_string: &str| -> Vec<&str> {
+        let entry = entries
+            .iter()
+            .find(|entry| entry.contains(&format!(">{key_string}<")))
+            .unwrap_or_else(|| panic!("no entry for {key_string:?} in {index}"));
+        ["a.xml", "b.xml", "c.xml", "d.xml", "e.xml"]
+            .into_iter()
+            .filter(|file| entry.contains(*file))
+            .collect()
+    };
+    assert_eq!(entries.len(), 3, "{index}");
+    assert_eq!(files_in("yes"), ["a.xml", "c.xml"]);
+    assert_eq!(files_in("no"), ["b.xml"]);
+    assert_eq!(
+        files_in(xmlhub_indexer::xmlhub_attributes::FLAG_MISSING_KEY_STRING),
+        ["d.xml", "e.xml"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_no_xml_files() -> Result<()> {
+    use xmlhub_indexer::output_sink::MemorySink;
+
+    let dir = test_repository("t_no_xml_files", &[("notes.txt", "not XML")])?;
+    let sink = Arc::new(MemorySink::default());
+    let (exit_code, summary) = test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    assert_eq!(exit_code, 0);
+    assert_eq!(summary.files, 0);
+    let files = sink.files();
+    for path in [
+        &DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top,
+        &DEFAULT_OUTPUT_FILES.md_file.path_from_repo_top,
+    ] {
+        let contents = std::str::from_utf8(&files[path.as_ref()])?;
+        assert!(contents.contains("No XML files found"), "{path}");
+        assert!(contents.contains("no XML files matched"), "{path}");
+    }
+
+    // The note is not shown when there are files
+    std::fs::write(dir.join("a.xml"), test_xml_file_content("2.7"))?;
+    let sink = Arc::new(MemorySink::default());
+    test_build_index_maybe_pushing(
+        &dir,
+        BuildIndexOpts {
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+        false,
+    )?;
+    for contents in sink.files().values() {
+        assert!(!std::str::from_utf8(contents)?.contains("No XML files found"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn t_run_per_repository() -> Result<()> {
+    let good = test_repository(
+        "t_run_per_repository",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let bad = good.with_file_name(format!(
+        "{}-missing",
+        good.file_name().unwrap().to_string_lossy()
+    ));
+    let built = |dir: &Path| -> Result<bool> {
+        Ok(test_git_stdout(dir, &["log", "-1", "--format=%s"])?
+            .starts_with("regenerate index files"))
+    };
+    assert!(!built(&good)?);
+    // The failing repository comes first, the other one is still built
+    let base_paths = vec![bad.clone(), good.to_path_buf()];
+    let failed = run_per_repository(&base_paths, false, |base_path| {
+        let exit_code = test_build_index(
+            base_path,
+            test_build_index_opts(DEFAULT_OUTPUT_FILES.clone()),
+        )?;
+        if exit_code != 0 {
+            bail!("exit code {exit_code}")
+        }
+        Ok(())
+    })?;
+    assert_eq!(failed, [bad.as_path()]);
+    assert!(built(&good)?);
+    Ok(())
+}
+
+#[test]
+fn t_run_per_repository_in_child_processes() -> Result<()> {
+    use chj_unix_util::unix::{waitpid_until_gone, Status};
+    use nix::unistd::{fork, ForkResult};
+
+    let dir = TempDir::new("xmlhub-t_run_per_repository_in_child_processes")?;
+    let a = dir.join("a");
+    let b = dir.join("b");
+    std::fs::create_dir(&a)?;
+    std::fs::create_dir(&b)?;
+    let missing = dir.join("missing");
+    let base_paths = vec![a.clone(), missing.clone(), b.clone()];
+
+    // `run_per_repository` refuses to fork while other threads are
+    // running, thus run it in a child, which only has the current
+    // thread of the test runner
+    match unsafe {
+        // The child only forks and writes files, which is safe enough
+        // despite the threads of the test runner
+        fork()
+    }? {
+        ForkResult::Child => {
+            let failed = run_per_repository(&base_paths, true, |base_path| {
+                std::fs::write(base_path.join("done"), std::process::id().to_string())
+                    .with_context(|| anyhow!("writing to {base_path:?}"))
+            });
+            let exit_code = match failed {
+                Ok(failed) if failed == [missing.as_path()] => 0,
+                _ => 1,
+            };
+            unsafe { nix::libc::_exit(exit_code) }
+        }
+        ForkResult::Parent { child } => {
+            assert!(matches!(waitpid_until_gone(child)?, Status::Normalexit(0)));
+        }
+    }
+    let pid_a = std::fs::read_to_string(a.join("done"))?;
+    let pid_b = std::fs::read_to_string(b.join("done"))?;
+    // Each job ran in its own process
+    assert_ne!(pid_a, pid_b);
+    assert_ne!(pid_a, std::process::id().to_string());
+    Ok(())
+}
+
+#[test]
+fn t_command_line_for_repository() {
+    let t = |args: &[&str]| -> Vec<String> {
+        command_line_for_repository(args.iter().map(OsString::from), Path::new("/x/r2"))
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    };
+    assert_eq!(
+        t(&[
+            "xmlhub",
+            "build",
+            "--base-path",
+            "r1",
+            "--daemon",
+            "run",
+            "--base-path=/x/r2",
+            "--base-path",
+            "r3",
+            "--quiet",
+        ]),
+        [
+            "xmlhub",
+            "build",
+            "--base-path=/x/r2",
+            "--daemon",
+            "run",
+            "--quiet"
+        ]
+    );
+    assert_eq!(
+        t(&["xmlhub", "build", "--daemon=start", "--", "--base-path=r1"]),
+        ["xmlhub", "build", "--daemon=start", "--", "--base-path=r1"]
+    );
+}
+
+#[test]
+fn t_pretty_html() -> Result<()> {
+    use xmlhub_indexer::{
+        html_util::{html_tokens, HtmlToken},
+        output_sink::MemorySink,
+    };
+
+    let dir = test_repository(
+        "t_pretty_html",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("sub/b.xml", &test_xml_file_content("2.7")),
+        ],
+    )?;
+    let html_for = |pretty_html: bool| -> Result<String> {
+        let sink = Arc::new(MemorySink::default());
+        test_build_index(
+            &dir,
+            BuildIndexOpts {
+                pretty_html,
+                output_sink: Some(sink.clone()),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )?;
+        let files = sink.files();
+        let bytes = &files[DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top.as_ref()];
+        Ok(String::from_utf8(bytes.clone())?)
+    };
+    let compact = html_for(false)?;
+    let pretty = html_for(true)?;
+    assert_eq!(html_for(true)?, pretty);
+    assert!(compact.lines().count() < pretty.lines().count());
+
+    // Only whitespace differs
+    let without_whitespace =
+        |s: &str| -> String { s.chars().filter(|c| !c.is_ascii_whitespace()).collect() };
+    assert_eq!(without_whitespace(&compact), without_whitespace(&pretty));
+
+    // The same tree, ignoring whitespace-only text
+    let dom = |s: &str| -> Vec<String> {
+        html_tokens(s.as_bytes())
+            .into_iter()
+            .filter_map(|token| match token {
+                HtmlToken::Tag(tag) => Some(String::from_utf8_lossy(tag).into_owned()),
+                HtmlToken::Text(text) => {
+                    let text = String::from_utf8_lossy(text).trim().to_owned();
+                    (!text.is_empty()).then_some(text)
+                }
+            })
+            .collect()
+    };
+    assert_eq!(dom(&compact), dom(&pretty));
+    Ok(())
+}
+
+#[test]
+fn t_lenient_unknown_attributes() -> Result<()> {
+    let with_unknown = TestXml::new("2.7").header("Colour", "red").to_string();
+    let dir = test_repository("t_lenient_unknown_attributes", &[("a.xml", &with_unknown)])?;
+    let read = |lenient_unknown_attributes: bool| -> Result<_> {
+        Ok(read_and_derive_file_infos(
+            xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+            &AttributeRules {
+                lenient_unknown_attributes,
+                ..Default::default()
+            },
+        ))
+    };
+
+    let (file_infos, file_errorss) = read(false)?;
+    assert!(file_infos.is_empty());
+    let [file_errors] = &file_errorss[..] else {
+        panic!("expecting one file with errors")
+    };
+    assert_eq!(file_errors.errors.len(), 1);
+    assert!(file_errors.errors[0]
+        .message
+        .contains("unknown attribute name \"colour\" given"));
+
+    let (file_infos, file_errorss) = read(true)?;
+    assert!(file_errorss.is_empty());
+    let [file_info] = &file_infos[..] else {
+        panic!("expecting one file")
+    };
+    let [warning] = &file_info.warnings[..] else {
+        panic!("expecting one warning, got {:?}", file_info.warnings)
+    };
+    assert_eq!(
+        warning.message,
+        "XML comment: unknown attribute name \"colour\" given"
+    );
+    assert_eq!(warning.location.map(|location| location.line), Some(6));
+
+    Ok(())
+}
+
+#[test]
+fn t_self_test_build() -> Result<()> {
+    let dir = test_repository(
+        "t_self_test_build",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            ("b/c.xml", &test_xml_file_content("2.7")),
+        ],
+    )?;
+    let num_files = self_test_build(|sink| {
+        test_build_index(
+            &dir,
+            BuildIndexOpts {
+                output_sink: Some(sink),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )
+        .map(|_exit_code| ())
+    })?;
+    assert_eq!(num_files, DEFAULT_OUTPUT_FILES.paths_from_repo_top().len());
+
+    // Injected nondeterminism is caught
+    let counter = std::sync::atomic::AtomicUsize::new(0);
+    let e = self_test_build(|sink| {
+        let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        sink.write_file("same.txt", b"a\nb\n")?;
+        sink.write_file("index.html", format!("<p>\n{n}</p>").as_bytes())?;
+        if n == 0 {
+            sink.write_file("first.txt", b"")?;
+        }
+        Ok(())
+    })
+    .expect_err("differences are detected");
+    let message = e.to_string();
+    assert!(message.contains("index.html: first difference on line 2 (at byte 4)"));
+    assert!(message.contains("first.txt: only written by the first build"));
+    assert!(!message.contains("same.txt"));
+    Ok(())
+}
+
+#[test]
+fn t_box_truncation() -> Result<()> {
+    use xmlhub_indexer::xmlhub_indexer_defaults::BOX_TRUNCATE_LENGTH_DEFAULT;
+
+    let long = "word ".repeat(BOX_TRUNCATE_LENGTH_DEFAULT / 5 + 10) + "THE_END";
+    let content = TestXml::new("2.7")
+        .header("Description", &long)
+        .header("Comments", "short comment")
+        .to_string();
+    let dir = test_repository("t_box_truncation", &[("a.xml", &content)])?;
+    let sink = Arc::new(MemorySink::default());
+    let exit_code = test_build_index(
+        &dir,
+        BuildIndexOpts {
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+    )?;
+    assert_eq!(exit_code, 0);
+    let files = sink.files();
+    let file = |path: &str| String::from_utf8(files[path].clone());
+
+    let html = file(DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top.as_ref())?;
+    let (_, details) = html
+        .split_once("<details class=\"truncated\"><summary>")
+        .expect("long value is truncated");
+    let (summary, rest) = details.split_once("</summary>").expect("present");
+    assert!(summary.contains("word word "));
+    assert!(summary.contains("show more"));
+    assert!(!summary.contains("THE_END"));
+    assert!(rest.contains("THE_END"));
+    assert_eq!(html.matches("<details").count(), 1);
+    assert!(html.contains("short comment"));
+
+    let md = file(DEFAULT_OUTPUT_FILES.md_file.path_from_repo_top.as_ref())?;
+    assert!(!md.contains("<details"));
+    assert!(md.contains(&format!("{}THE_END", "word ".repeat(3))));
+
+    // Truncation can be disabled in the repository configuration
+    let dir = test_repository(
+        "t_box_truncation_disabled",
+        &[
+            ("a.xml", &content),
+            (CONFIG_FILE_PATH, "box_truncate_length = 0\n"),
+        ],
+    )?;
+    let sink = Arc::new(MemorySink::default());
+    let exit_code = test_build_index(
+        &dir,
+        BuildIndexOpts {
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+    )?;
+    assert_eq!(exit_code, 0);
+    let html = String::from_utf8(
+        sink.files()[DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top.as_ref()].clone(),
+    )?;
+    assert!(!html.contains("<details"));
+    assert!(html.contains(&format!("{}THE_END", "word ".repeat(3))));
+    Ok(())
+}
+
+#[test]
+fn t_box_order() -> Result<()> {
+    let dir = test_repository(
+        "t_box_order",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            (CONFIG_FILE_PATH, "box_order = [\"Contact\"]\n"),
+        ],
+    )?;
+    let sink = Arc::new(MemorySink::default());
+    let exit_code = test_build_index(
+        &dir,
+        BuildIndexOpts {
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+    )?;
+    assert_eq!(exit_code, 0);
+    let html = String::from_utf8(
+        sink.files()[DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top.as_ref()].clone(),
+    )?;
+    let position = |key: &str| html.find(key).expect("present");
+    assert!(position("Contact:") < position("Keywords:"));
+    Ok(())
+}
+
+#[test]
+fn t_highlight_colors_config() -> Result<()> {
+    let dir = test_repository(
+        "t_highlight_colors_config",
+        &[
+            (
+                "a.xml",
+                &TestXml::new("2.7").without("Keywords").to_string(),
+            ),
+            (CONFIG_FILE_PATH, "[highlight_colors]\nred = \"#c00000\"\n"),
+        ],
+    )?;
+    let sink = Arc::new(MemorySink::default());
+    test_build_index(
+        &dir,
+        BuildIndexOpts {
+            write_errors: true,
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+    )?;
+    let html = String::from_utf8(
+        sink.files()[DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top.as_ref()].clone(),
+    )?;
+    assert!(html.contains("style=\"color: #c00000;\""));
+    assert!(!html.contains("color: red;"));
+    Ok(())
+}
+
+#[test]
+fn t_symbols_config() -> Result<()> {
+    let dir = test_repository(
+        "t_symbols_config",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            (
+                CONFIG_FILE_PATH,
+                "back_to_index_symbol = \"^\"\ndocument_symbol = \"[xml]\"\n",
+            ),
+        ],
+    )?;
+    let sink = Arc::new(MemorySink::default());
+    test_build_index(
+        &dir,
+        BuildIndexOpts {
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+    )?;
+    let html = String::from_utf8(
+        sink.files()[DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top.as_ref()].clone(),
+    )?;
+    assert!(html.contains("title=\"jump to index entry\">^</a>"));
+    assert!(html.contains("[xml]</a>"));
+    assert!(!html.contains(DEFAULT_SYMBOLS.back_to_index.as_str()));
+    assert!(!html.contains("document.svg"));
+    Ok(())
+}
+
+#[test]
+fn t_no_index_section() -> Result<()> {
+    let dir = test_repository(
+        "t_no_index_section",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let build = |no_index_section| -> Result<(String, String)> {
+        let sink = Arc::new(MemorySink::default());
+        let exit_code = test_build_index(
+            &dir,
+            BuildIndexOpts {
+                no_index_section,
+                output_sink: Some(sink.clone()),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )?;
+        assert_eq!(exit_code, 0);
+        let files = sink.files();
+        let file = |path: &str| String::from_utf8(files[path].clone());
+        Ok((
+            file(DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top.as_ref())?,
+            file(DEFAULT_OUTPUT_FILES.md_file.path_from_repo_top.as_ref())?,
+        ))
+    };
+
+    let (html, md) = build(false)?;
+    // Once in the table of contents, once as the title
+    assert_eq!(html.matches("Index by attribute").count(), 2);
+    assert!(md.contains("Index by attribute"));
+    assert!(html.contains("File info by folder"));
+
+    let (html, md) = build(true)?;
+    assert!(!html.contains("Index by attribute"));
+    assert!(!md.contains("Index by attribute"));
+    // No links to the missing index entries
+    assert!(!html.contains("jump to index entry"));
+    assert!(html.contains("File info by folder"));
+    assert!(html.contains("a.xml"));
+    Ok(())
+}
+
+#[test]
+fn t_output_file_changes() -> Result<()> {
+    let dir = test_repository(
+        "t_output_file_changes",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let git_working_dir = GitWorkingDir::from(dir.to_path_buf());
+    let written_files = DEFAULT_OUTPUT_FILES.paths_from_repo_top();
+    let build = |no_commit| {
+        test_build_index(
+            &dir,
+            BuildIndexOpts {
+                no_commit,
+                diff_output: true,
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )
+    };
+
+    // Not committed yet
+    assert_eq!(build(true)?, 0);
+    let changes = output_file_changes(&git_working_dir, &written_files)?;
+    assert_eq!(changes.len(), written_files.len());
+    assert!(changes.iter().all(|change| change.new));
+    assert!(output_file_changes_summary(&changes).starts_with("Output files: 3 files changed, +"));
+
+    // Committed, unchanged
+    assert_eq!(build(false)?, 0);
+    let changes = output_file_changes(&git_working_dir, &written_files)?;
+    assert_eq!(changes, []);
+    assert_eq!(
+        output_file_changes_summary(&changes),
+        "Output files: no changes\n"
+    );
+
+    // Changed by a new file
+    std::fs::write(dir.join("b.xml"), test_xml_file_content("2.7"))?;
+    test_git_stdout(&dir, &["add", "b.xml"])?;
+    test_git_stdout(&dir, &["commit", "-m", "add b.xml"])?;
+    assert_eq!(build(true)?, 0);
+    let changes = output_file_changes(&git_working_dir, &written_files)?;
+    let html_path: &str = DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top.as_ref();
+    let html_change = changes
+        .iter()
+        .find(|change| change.path == html_path)
+        .expect("the HTML file changed");
+    assert!(!html_change.new);
+    let (added, _removed) = html_change.lines.expect("not binary");
+    assert!(added > 0);
+    let summary = output_file_changes_summary(&changes);
+    assert!(summary.contains(&format!("  {html_path}: +{added} -")));
+    Ok(())
+}
+
+#[test]
+fn t_keywords_singularized_for_index() -> Result<()> {
+    let with_keywords =
+        |keywords: &str| TestXml::new("2.7").header("Keywords", keywords).to_string();
+    let dir = test_repository(
+        "t_keywords_singularized_for_index",
+        &[
+            ("a.xml", &with_keywords("trees, data")),
+            ("b.xml", &with_keywords("Tree, phylogenies")),
+            ("c.xml", &with_keywords("phylogenetic trees")),
+        ],
+    )?;
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    assert!(file_errorss.is_empty());
+
+    let spec = attribute_specification_by_name("Keywords").expect("present");
+    let section = build_index_section(
+        spec.key,
+        spec.key_string_preparation().expect("indexed"),
+        spec.autolink,
+        &file_infos,
+        &index_anchor_names(&file_infos),
+        IndexSectionOpts::default(),
+    )?;
+    let index = section.intro.expect("has intro").as_str().to_string();
+    // "trees" and "Tree" share one key, the plurals that can't be
+    // singularized are kept as they are
+    assert_eq!(index.matches("<dt").count(), 4, "{index}");
+    assert!(index.contains(">tree<"), "{index}");
+    assert!(!index.contains(">trees<"), "{index}");
+    assert!(index.contains(">phylogenetic tree<"), "{index}");
+    assert!(index.contains(">phylogenies<"), "{index}");
+    assert!(index.contains(">data<"), "{index}");
+
+    // The boxes show the original items
+    let html = HTML_ALLOCATOR_POOL.get();
+    let box_html = html
+        .preserialize(file_infos[0].to_info_box_html(&html, "box", "", "", Default::default())?)?
+        .as_str()
+        .to_string();
+    assert!(box_html.contains(">trees<"), "{box_html}");
+
+    Ok(())
+}
+
+#[test]
+fn t_group_by_attribute() -> Result<()> {
+    let with_contact = |contact: &str| TestXml::new("2.7").header("Contact", contact).to_string();
+    let dir = test_repository(
+        "t_group_by_attribute",
+        &[
+            ("a.xml", &with_contact("Some One")),
+            ("sub/b.xml", &with_contact("Other Person")),
+            ("sub/c.xml", &with_contact("Some One")),
+        ],
+    )?;
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    assert!(file_errorss.is_empty());
+
+    let BoxGrouping::Attribute(spec) = BoxGrouping::from_arg("attribute:contact")? else {
+        panic!("expecting attribute grouping")
+    };
+    assert_eq!(spec.key.as_ref(), "Contact");
+    assert!(matches!(
+        BoxGrouping::from_arg("folder")?,
+        BoxGrouping::Folder
+    ));
+    assert!(BoxGrouping::from_arg("attribute:Nonexisting").is_err());
+    assert!(BoxGrouping::from_arg("folders").is_err());
+
+    let mut groups = AttributeGroups::new(spec);
+    for file_info in &file_infos {
+        groups.add(file_info)?;
+    }
+    let section = groups.to_section(
+        Some("File info by Contact".into()),
+        "",
+        InfoBoxOpts::default(),
+    )?;
+    let groups: Vec<(String, String)> = section
+        .subsections
+        .iter()
+        .map(|subsection| {
+            (
+                subsection.title.clone().expect("has title"),
+                subsection
+                    .intro
+                    .as_ref()
+                    .expect("has boxes")
+                    .as_str()
+                    .to_string(),
+            )
+        })
+        .collect();
+    let [(other_title, other_boxes), (some_title, some_boxes)] = &groups[..] else {
+        panic!("expecting 2 groups, got {groups:?}")
+    };
+    assert_eq!(other_title, "Other Person");
+    assert_eq!(some_title, "Some One");
+    assert!(other_boxes.contains(">sub/b.xml"), "{other_boxes}");
+    assert_eq!(other_boxes.matches("class=\"fileinfo\"").count(), 1);
+    assert!(some_boxes.contains(">a.xml"), "{some_boxes}");
+    assert!(some_boxes.contains(">sub/c.xml"), "{some_boxes}");
+    assert!(!some_boxes.contains(">sub/b.xml"), "{some_boxes}");
+
+    Ok(())
+}
+
+#[test]
+fn t_max_output_size() -> Result<()> {
+    let dir = test_repository(
+        "t_max_output_size",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let html_path = dir.join(&*DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top);
+
+    let error = test_build_index(
+        &dir,
+        BuildIndexOpts {
+            max_output_size: Some(12000),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+    )
+    .expect_err("the HTML file is bigger than 12000 bytes");
+    assert!(
+        format!("{error:#}").contains("refusing to write the output file"),
+        "{error:#}"
+    );
+    assert!(!html_path.exists());
+    // The other files fit within the limit but are not written either
+    for output_file in [
+        &DEFAULT_OUTPUT_FILES.md_file,
+        &DEFAULT_OUTPUT_FILES.attributes_file,
+    ] {
+        assert!(!dir.join(&*output_file.path_from_repo_top).exists());
+    }
+    assert_eq!(
+        test_git_stdout(&dir, &["rev-list", "--count", "HEAD"])?,
+        "1\n"
+    );
+
+    assert_eq!(
+        test_build_index(&dir, test_build_index_opts(DEFAULT_OUTPUT_FILES.clone()))?,
+        0
+    );
+    assert!(html_path.exists());
+
+    Ok(())
+}
+
+#[test]
+fn t_no_toc_and_mini_toc() -> Result<()> {
+    let dir = test_repository(
+        "t_no_toc_and_mini_toc",
+        &[("sub/a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let build = |no_toc, mini_toc| -> Result<(String, String)> {
+        let sink = Arc::new(MemorySink::default());
+        let exit_code = test_build_index(
+            &dir,
+            BuildIndexOpts {
+                no_toc,
+                mini_toc,
+                output_sink: Some(sink.clone()),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )?;
+        assert_eq!(exit_code, 0);
+        let files = sink.files();
+        let file = |path: &str| String::from_utf8(files[path].clone());
+        Ok((
+            file(DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top.as_ref())?,
+            file(DEFAULT_OUTPUT_FILES.md_file.path_from_repo_top.as_ref())?,
+        ))
+    };
+
+    let (html, md) = build(false, false)?;
+    assert!(html.contains(">Contents</h2>"));
+    assert!(md.contains("## Contents"));
+    assert!(html.contains("class=\"toc_entry\""));
+    assert!(!html.contains("mini_toc"));
+
+    let (html, md) = build(true, false)?;
+    assert!(!html.contains("Contents"));
+    assert!(!md.contains("Contents"));
+    assert!(!html.contains("class=\"toc_entry\""));
+    // The sections themselves are still there
+    assert!(html.contains("File info by folder"));
+
+    let (html, md) = build(true, true)?;
+    assert!(!html.contains("Contents"));
+    for doc in [&html, &md] {
+        let (_, mini_tocs) = doc.split_once("class=\"mini_toc\"").expect("has mini toc");
+        // The first mini TOC is for the index, the second for the
+        // folders
+        let (index_mini_toc, folder_mini_toc) = mini_tocs
+            .split_once("class=\"mini_toc\"")
+            .expect("2 mini tocs");
+        let index_mini_toc = index_mini_toc.split("</div>").next().expect("some");
+        assert!(
+            index_mini_toc.contains("href=\"#section-1.1\">1.1 Keywords<"),
+            "{index_mini_toc}"
+        );
+        let folder_mini_toc = folder_mini_toc.split("</div>").next().expect("some");
+        assert!(
+            folder_mini_toc.contains("href=\"#section-2.1\">2.1 sub/<"),
+            "{folder_mini_toc}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn t_contacts_allow_list_warnings() -> Result<()> {
+    let with_contact = |contact: &str| TestXml::new("2.7").header("Contact", contact).to_string();
+    let files = [
+        ("a.xml", with_contact("Some One")),
+        ("b.xml", with_contact("Some Oen")),
+        ("c.xml", with_contact("Maria Garcia")),
+    ];
+    let warnings = |dir: &Path| -> Result<Vec<Vec<Issue>>> {
+        let (file_infos, file_errorss) = read_and_derive_file_infos(
+            xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+            &AttributeRules::default(),
+        );
+        assert!(file_errorss.is_empty());
+        Ok(file_infos
+            .into_iter()
+            .map(|file_info| file_info.warnings)
+            .collect())
+    };
+
+    // Without an allow-list, no validation occurs
+    let dir = test_repository(
+        "t_contacts_allow_list_warnings",
+        &files
+            .iter()
+            .map(|(path, content)| (*path, content.as_str()))
+            .collect::<Vec<_>>(),
+    )?;
+    assert!(warnings(&dir)?.iter().all(Vec::is_empty));
+
+    std::fs::write(
+        dir.join(CONTACTS_FILE_PATH),
+        "# The known contacts\nSome One\nOther Person\n",
+    )?;
+    let [a, b, c] = &warnings(&dir)?[..] else {
+        panic!("expecting 3 files")
+    };
+    assert!(a.is_empty(), "{a:?}");
+    let [b] = &b[..] else {
+        panic!("expecting one warning, got {b:?}")
+    };
+    assert!(b.message.contains("\"Some Oen\" is not in the list"));
+    assert!(b
+        .hint
+        .as_deref()
+        .expect("has hint")
+        .starts_with("Did you mean \"Some One\"?"));
+    let [c] = &c[..] else {
+        panic!("expecting one warning, got {c:?}")
+    };
+    assert!(c.message.contains("\"Maria Garcia\" is not in the list"));
+    assert!(!c
+        .hint
+        .as_deref()
+        .expect("has hint")
+        .contains("Did you mean"));
+
+    Ok(())
+}
+
+#[test]
+fn t_write_attributes_file() -> Result<()> {
+    let dir = test_repository(
+        "t_write_attributes_file",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let path = write_attributes_file(&dir, &DEFAULT_OUTPUT_FILES)?;
+    assert_eq!(path, dir.join("attributes.md"));
+    assert_eq!(
+        std::fs::read_to_string(&path)?,
+        make_attributes_md(true)?.to_string()
+    );
+    // Nothing else is written, nor committed
+    assert!(!dir.join("README.html").exists());
+    assert_eq!(
+        test_git_stdout(&dir, &["status", "--porcelain"])?,
+        "?? attributes.md\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_inconsistent_contact_emails() -> Result<()> {
+    let with_contact = |contact: &str| TestXml::new("2.7").header("Contact", contact).to_string();
+    let dir = test_repository(
+        "t_inconsistent_contact_emails",
+        &[
+            ("a.xml", &with_contact("Jane Doe <jane@example.com>")),
+            ("b.xml", &with_contact("jane  doe <jane.doe@example.org>")),
+            ("c.xml", &with_contact("John Smith <john@example.com>")),
+            ("d.xml", &with_contact("John Smith <John@Example.com>")),
+            ("e.xml", &with_contact("Jane Doe <jane@example.com>")),
+        ],
+    )?;
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    assert!(file_errorss.is_empty());
+    let warnings: Vec<&[Issue]> = file_infos
+        .iter()
+        .map(|file_info| file_info.warnings.as_slice())
+        .collect();
+    let [a, b, c, d, e] = &warnings[..] else {
+        panic!("expecting 5 files")
+    };
+    // Consistent usage (emails compared case-insensitively)
+    assert!(c.is_empty(), "{c:?}");
+    assert!(d.is_empty(), "{d:?}");
+    for warnings in [a, b, e] {
+        let [warning] = warnings else {
+            panic!("expecting one warning, got {warnings:?}")
+        };
+        assert_eq!(
+            warning.message,
+            "\"Contact\": \"Jane Doe\" is given with different email addresses across \
+             files: <jane.doe@example.org> in \"b.xml\"; <jane@example.com> in \"a.xml\", \
+             \"e.xml\""
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn t_contacts_allow_list_with_emails() -> Result<()> {
+    let with_contact = |contact: &str| TestXml::new("2.7").header("Contact", contact).to_string();
+    let dir = test_repository(
+        "t_contacts_allow_list_with_emails",
+        &[
+            (
+                CONTACTS_FILE_PATH,
+                "Jane Doe\nJohn Smith <john@example.com>\n",
+            ),
+            ("a.xml", &with_contact("Jane Doe <jane@example.com>")),
+            ("b.xml", &with_contact("Jane Doe <jane.doe@example.org>")),
+            ("c.xml", &with_contact("John Smith <john@example.com>")),
+            ("d.xml", &with_contact("Jane Deo <jane@example.com>")),
+        ],
+    )?;
+    let (file_infos, file_errorss) = read_and_derive_file_infos(
+        xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+        &AttributeRules::default(),
+    );
+    assert!(file_errorss.is_empty());
+    let messages: Vec<Vec<&str>> = file_infos
+        .iter()
+        .map(|file_info| {
+            file_info
+                .warnings
+                .iter()
+                .map(|warning| warning.message.as_str())
+                .collect()
+        })
+        .collect();
+    let different_emails = "\"Contact\": \"Jane Doe\" is given with different email \
+                            addresses across files: <jane.doe@example.org> in \"b.xml\"; \
+                            <jane@example.com> in \"a.xml\"";
+    assert_eq!(
+        messages,
+        [
+            vec![different_emails],
+            vec![different_emails],
+            vec![],
+            vec!["\"Contact\": \"Jane Deo\" is not in the list of known contacts"],
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_scan_max_depth() -> Result<()> {
+    let content = test_xml_file_content("2.7");
+    let dir = test_repository(
+        "t_scan_max_depth",
+        &[
+            ("a.xml", &content),
+            ("sub/b.xml", &content),
+            ("sub/deeper/c.xml", &content),
+            ("other/deeper/d.xml", &content),
+        ],
+    )?;
+    let git_working_dir = GitWorkingDir::from(dir.to_path_buf());
+    let rel_paths = |paths: &[BaseAndRelPath]| -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.rel_path().to_string())
+            .collect()
+    };
+
+    let XmlFilePaths {
+        paths,
+        skipped_directories: skipped,
+        ..
+    } = xml_file_paths_with_max_depth(
+        &git_working_dir,
+        false,
+        false,
+        None,
+        &XmlExtensions::default(),
+    )?;
+    assert_eq!(paths.len(), 4);
+    assert!(skipped.is_empty());
+
+    let XmlFilePaths {
+        paths,
+        skipped_directories: skipped,
+        ..
+    } = xml_file_paths_with_max_depth(
+        &git_working_dir,
+        false,
+        false,
+        Some(2),
+        &XmlExtensions::default(),
+    )?;
+    assert_eq!(rel_paths(&paths), ["a.xml", "sub/b.xml"]);
+    let mut skipped = skipped;
+    skipped.sort();
+    assert_eq!(
+        skipped,
+        [PathBuf::from("other/deeper"), PathBuf::from("sub/deeper")]
+    );
+    assert_eq!(
+        skipped_directories_note(2, &skipped),
+        "did not look for XML files in 2 directories deeper than --scan-max-depth 2: \
+         \"other/deeper\", \"sub/deeper\""
+    );
+
+    let XmlFilePaths {
+        paths,
+        skipped_directories: skipped,
+        ..
+    } = xml_file_paths_with_max_depth(
+        &git_working_dir,
+        false,
+        false,
+        Some(1),
+        &XmlExtensions::default(),
+    )?;
+    assert_eq!(rel_paths(&paths), ["a.xml"]);
+    assert_eq!(skipped.len(), 2);
+
+    // The `git ls-files` listing is not limited
+    let XmlFilePaths {
+        paths,
+        skipped_directories: skipped,
+        ..
+    } = xml_file_paths_with_max_depth(
+        &git_working_dir,
+        true,
+        false,
+        Some(1),
+        &XmlExtensions::default(),
+    )?;
+    assert_eq!(paths.len(), 4);
+    assert!(skipped.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn t_index_order() -> Result<()> {
+    let content = test_xml_file_content("2.7");
+    let build = |config: Option<&str>| -> Result<String> {
+        let mut files = vec![("a.xml", content.as_str())];
+        if let Some(config) = config {
+            files.push((CONFIG_FILE_PATH, config));
+        }
+        let dir = test_repository("t_index_order", &files)?;
+        let sink = Arc::new(MemorySink::default());
+        let exit_code = test_build_index(
+            &dir,
+            BuildIndexOpts {
+                output_sink: Some(sink.clone()),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )?;
+        assert_eq!(exit_code, 0);
+        Ok(String::from_utf8(
+            sink.files()[DEFAULT_OUTPUT_FILES.md_file.path_from_repo_top.as_ref()].clone(),
+        )?)
+    };
+    // The titles of the subsections of "Index by attribute"
+    let index_titles = |md: &str| -> Vec<String> {
+        md.lines()
+            .filter_map(|line| line.strip_prefix("### "))
+            .filter_map(|line| line.split_once("</a>1."))
+            .map(|(_, title)| title.split_once(' ').expect("number and title").1.into())
+            .collect()
+    };
+
+    let default_titles = index_titles(&build(None)?);
+    assert_eq!(default_titles[..3], ["Keywords", "Version", "Packages"]);
+
+    let titles = index_titles(&build(Some("index_order = [\"Contact\", \"Location\"]\n"))?);
+    assert_eq!(titles[..4], ["Contact", "Location", "Keywords", "Version"]);
+    assert_eq!(
+        titles[2..],
+        default_titles
+            .iter()
+            .filter(|title| !["Contact", "Location"].contains(&title.as_str()))
+            .cloned()
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+#[test]
+fn t_config_file_committed() -> Result<()> {
+    let dir = test_repository(
+        "t_config_file_committed",
+        &[
+            (CONFIG_FILE_PATH, "index_order = [\"Contact\"]\n"),
+            ("a.xml", &test_xml_file_content("2.7")),
+            // Anything tracked in the daemon folder makes Git list
+            // the untracked files in it individually
+            (".xmlhub/tracked.txt", "tracked"),
+        ],
+    )?;
+    // The lock and log files of a running daemon
+    std::fs::write(dir.join(".xmlhub/main.lock"), "")?;
+    std::fs::create_dir_all(dir.join(".xmlhub/logs"))?;
+    std::fs::write(dir.join(".xmlhub/logs/000001.log"), "")?;
+
+    assert_eq!(
+        test_build_index(&dir, test_build_index_opts(DEFAULT_OUTPUT_FILES.clone()))?,
+        0
+    );
+    let subject = test_git_stdout(&dir, &["log", "-1", "--format=%s"])?;
+    assert!(subject.starts_with("regenerate index files"), "{subject}");
+    // The configuration was used: the Contact index comes first
+    let md = std::fs::read_to_string(dir.join("README.md"))?;
+    assert!(
+        md.lines()
+            .any(|line| line.starts_with("### ") && line.ends_with("</a>1.1 Contact")),
+        "{md}"
+    );
+    Ok(())
+}
+
+#[test]
+fn t_since_last_build() -> Result<()> {
+    let content = test_xml_file_content("2.7");
+    let dir = test_repository(
+        "t_since_last_build",
+        &[("a.xml", &content), ("b.xml", &content)],
+    )?;
+    let state_path = dir
+        .join(&*DAEMON_FOLDER_NAME)
+        .join(SINCE_LAST_BUILD_STATE_FILENAME);
+    let build = || -> Result<Option<String>> {
+        let (_exit_code, summary) = test_build_index_maybe_pushing(
+            &dir,
+            BuildIndexOpts {
+                since_last_build_state: Some(state_path.clone()),
+                output_sink: Some(Arc::new(MemorySink::default())),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+            false,
+        )?;
+        Ok(summary.since_last_build)
+    };
+
+    // The first build has nothing to compare with
+    assert_eq!(
+        build()?.as_deref(),
+        Some(
+            "since last build: changed=0 added=2 removed=0 errors_appeared=0 \
+             errors_cleared=0 committed=0 pushed=0"
+        )
+    );
+
+    // The next iteration, after one file was changed and another one
+    // broken
+    std::fs::write(
+        dir.join("a.xml"),
+        content.replace("Keywords: foo, bar", "Keywords: foo, baz"),
+    )?;
+    std::fs::write(
+        dir.join("b.xml"),
+        content.replace("Contact: Some One", "Contact: "),
+    )?;
+    assert_eq!(
+        build()?.as_deref(),
+        Some(
+            "since last build: changed=2 added=0 removed=0 errors_appeared=1 \
+             errors_cleared=0 committed=0 pushed=0"
+        )
+    );
+
+    // Nothing changed
+    assert_eq!(
+        build()?.as_deref(),
+        Some(
+            "since last build: changed=0 added=0 removed=0 errors_appeared=0 \
+             errors_cleared=0 committed=0 pushed=0"
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_sign_commits() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    assert_eq!(gpg_sign_arg(false, None), None);
+    assert_eq!(gpg_sign_arg(true, None).as_deref(), Some("-S"));
+    assert_eq!(
+        gpg_sign_arg(true, Some("ABCD1234")).as_deref(),
+        Some("-SABCD1234")
+    );
+
+    // A fake gpg program that records its arguments and pretends to
+    // sign the data
+    let gpg_dir = TempDir::new("xmlhub-t_sign_commits-gpg")?;
+    let fake_gpg = gpg_dir.join("fake-gpg");
+    let args_file = gpg_dir.join("args");
+    std::fs::write(
+        &fake_gpg,
+        format!(
+            "#!/bin/sh\n\
+             echo \"$@\" > {args_file:?}\n\
+             cat > /dev/null\n\
+             echo '[GNUPG:] SIG_CREATED D 1 8 00 0 0' >&2\n\
+             echo '-----BEGIN PGP SIGNATURE-----'\n\
+             echo 'fake'\n\
+             echo '-----END PGP SIGNATURE-----'\n"
+        ),
+    )?;
+    std::fs::set_permissions(&fake_gpg, std::fs::Permissions::from_mode(0o755))?;
+
+    let build = |dir: &Path, gpg_program: &str| -> Result<i32> {
+        test_git_stdout(dir, &["config", "gpg.program", gpg_program])?;
+        test_build_index(
+            dir,
+            BuildIndexOpts {
+                gpg_sign_arg: gpg_sign_arg(true, Some("ABCD1234")),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )
+    };
+
+    let dir = test_repository(
+        "t_sign_commits",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    assert_eq!(build(&dir, fake_gpg.to_str().expect("UTF-8"))?, 0);
+    assert!(std::fs::read_to_string(&args_file)?.contains("ABCD1234"));
+    let commit = test_git_stdout(&dir, &["cat-file", "commit", "HEAD"])?;
+    assert!(commit.contains("gpgsig -----BEGIN PGP SIGNATURE-----"));
+    assert!(commit.contains("regenerate index files via "));
+
+    // Signing fails
+    let dir = test_repository(
+        "t_sign_commits",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let err = build(&dir, "false").expect_err("signing fails");
+    assert!(
+        err.to_string()
+            .starts_with("git commit failed to create the signed commit (--sign-commits)"),
+        "{err:#}"
+    );
+    // Nothing was committed
+    assert_eq!(
+        test_git_stdout(&dir, &["log", "--format=%s"])?.trim(),
+        "initial"
+    );
+    Ok(())
+}
+
+#[test]
+fn t_toc_fragment() -> Result<()> {
+    assert_eq!(
+        DEFAULT_OUTPUT_FILES.toc_fragment_path_from_repo_top(),
+        "toc.html"
+    );
+    assert_eq!(
+        DEFAULT_OUTPUT_FILES
+            .in_subdir("docs/index")
+            .toc_fragment_path_from_repo_top(),
+        "docs/index/toc.html"
+    );
+
+    let dir = test_repository(
+        "t_toc_fragment",
+        &[("sub/a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let sink = Arc::new(MemorySink::default());
+    let exit_code = test_build_index(
+        &dir,
+        BuildIndexOpts {
+            toc_fragment: true,
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+    )?;
+    assert_eq!(exit_code, 0);
+    let files = sink.files();
+    let fragment = String::from_utf8(files["toc.html"].clone())?;
+    for link in [
+        "href=\"#section-1\">1 Index by attribute</a>",
+        "href=\"#section-1.1\">1.1 Keywords</a>",
+        "href=\"#section-2\">2 File info by folder</a>",
+    ] {
+        assert!(fragment.contains(link), "{link:?} in {fragment:?}");
+    }
+    assert!(!fragment.contains("<html"));
+    assert!(!fragment.contains("<head"));
+    assert!(!fragment.contains("<body"));
+    // The same as in the index page
+    let index =
+        String::from_utf8(files[&*DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top].clone())?;
+    assert!(index.contains(&fragment));
+
+    // Committed along with the other files
+    assert_eq!(
+        test_build_index(
+            &dir,
+            BuildIndexOpts {
+                toc_fragment: true,
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )?,
+        0
+    );
+    let committed = test_git_stdout(&dir, &["show", "--name-only", "--format=", "HEAD"])?;
+    assert!(
+        committed.lines().any(|line| line == "toc.html"),
+        "{committed}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_warn_duplicate_list_items() -> Result<()> {
+    let dir = test_repository(
+        "t_warn_duplicate_list_items",
+        &[(
+            "a.xml",
+            &TestXml::new("2.7")
+                .header("Keywords", "phylo, foo, phylo")
+                .to_string(),
+        )],
+    )?;
+    let read = |warn_duplicate_list_items| -> Result<FileInfo<WithDerivedValues>> {
+        let (mut file_infos, file_errorss) = read_and_derive_file_infos(
+            xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+            &AttributeRules {
+                warn_duplicate_list_items,
+                ..Default::default()
+            },
+        );
+        assert!(file_errorss.is_empty());
+        Ok(file_infos.pop().expect("one file"))
+    };
+    let keywords_key = attribute_specification_by_name("Keywords")
+        .expect("present")
+        .key;
+
+    // By default, the repetition is dropped silently
+    let file_info = read(false)?;
+    assert_eq!(
+        file_info
+            .metadata
+            .get(keywords_key)
+            .expect("given")
+            .as_string_list()
+            .as_ref(),
+        ["phylo", "foo"]
+    );
+    assert!(file_info.warnings.is_empty());
+
+    let file_info = read(true)?;
+    let [warning] = &file_info.warnings[..] else {
+        panic!("expecting one warning, got {:?}", file_info.warnings)
+    };
+    assert_eq!(
+        warning.message,
+        "attribute \"Keywords\": the item \"phylo\" is given more than once"
+    );
+    Ok(())
+}
+
+#[test]
+fn t_show_file_comments() -> Result<()> {
+    let dir = test_repository(
+        "t_show_file_comments",
+        &[(
+            "a.xml",
+            &TestXml::new("2.7")
+                .comment("Run with <4> threads & a fixed seed")
+                .to_string(),
+        )],
+    )?;
+    let build = |show_file_comments| -> Result<(i32, Option<String>)> {
+        let sink = Arc::new(MemorySink::default());
+        let exit_code = test_build_index(
+            &dir,
+            BuildIndexOpts {
+                attribute_rules: AttributeRules {
+                    show_file_comments,
+                    ..Default::default()
+                },
+                output_sink: Some(sink.clone()),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )?;
+        let index = sink
+            .files()
+            .remove(&*DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top)
+            .map(String::from_utf8)
+            .transpose()?;
+        Ok((exit_code, index))
+    };
+
+    // By default, a comment that is not metadata is an error
+    let (exit_code, _) = build(false)?;
+    assert_eq!(exit_code, 1);
+
+    let (exit_code, index) = build(true)?;
+    assert_eq!(exit_code, 0);
+    let index = index.expect("written");
+    let notes_start = index.find("fileinfo_notes").expect("has notes");
+    let notes = &index[notes_start..];
+    let notes = &notes[..notes.find("</tr>").expect("end of row")];
+    assert!(notes.contains("Notes:"));
+    assert!(
+        notes.contains("Run with &lt;4&gt; threads &amp; a fixed seed"),
+        "{notes}"
+    );
+    // Recognized metadata is not part of the notes
+    for metadata in ["Keywords", "Some One", "BEAST 2.7.0"] {
+        assert!(!notes.contains(metadata), "{metadata:?} in {notes:?}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn t_stop_requested_during_build() -> Result<()> {
+    use chj_unix_util::{
+        signal::send_signal_to_all_processes_of_session,
+        unix::{waitpid_until_gone, Status},
+    };
+    use nix::unistd::{fork, setsid, ForkResult};
+    use std::{os::unix::fs::PermissionsExt, time::Instant};
+
+    let dir = test_repository(
+        "t_stop_requested_during_build",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    // Make `git commit` take a while, and tell when it has started.
+    // (Not via `/bin/sh`, which may be dash, which unblocks all
+    // signals.)
+    let commit_started = dir.join(".git/commit-started");
+    let hook = dir.join(".git/hooks/pre-commit");
+    std::fs::write(
+        &hook,
+        format!("#!/bin/bash\ntouch {commit_started:?}\nsleep 0.5\n"),
+    )?;
+    std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755))?;
+    std::fs::write(dir.join("b.xml"), test_xml_file_content("2.7"))?;
+    test_git_stdout(&dir, &["add", "b.xml"])?;
+    let head_before = test_git_stdout(&dir, &["rev-parse", "HEAD"])?;
+
+    // The child stands in for the daemon (the leader of its own
+    // session) and its worker doing the commit
+    match unsafe {
+        // The child only forks and execs git, which is safe enough
+        // despite the threads of the test runner
+        fork()
+    }? {
+        ForkResult::Child => {
+            let _ = setsid();
+            let _ = with_signals_deferred(&[Signal::SIGINT], || {
+                GitWorkingDir::from(dir.to_path_buf()).git(&["commit", "-q", "-m", "b"], true)
+            });
+            unsafe { nix::libc::_exit(0) }
+        }
+        ForkResult::Parent { child } => {
+            let start = Instant::now();
+            while !commit_started.exists() {
+                assert!(
+                    start.elapsed() < Duration::from_secs(10),
+                    "commit not started"
+                );
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            // What `--daemon stop` does
+            assert!(send_signal_to_all_processes_of_session(
+                child,
+                Some(Signal::SIGINT)
+            )?);
+            // The worker ends because of the signal, but only after
+            // the commit (including the hook) completed
+            assert!(matches!(
+                waitpid_until_gone(child)?,
+                Status::Signalexit(Signal::SIGINT)
+            ));
+        }
+    }
+    assert_ne!(test_git_stdout(&dir, &["rev-parse", "HEAD"])?, head_before);
+    assert_eq!(test_git_stdout(&dir, &["status", "--porcelain"])?, "");
+
+    Ok(())
+}
+
+#[test]
+fn t_index_layout() -> Result<()> {
+    let dir = test_repository(
+        "t_index_layout",
+        &[
+            ("a.xml", &test_xml_file_content("2.7")),
+            (
+                "b.xml",
+                &TestXml::new("2.7")
+                    .header("Keywords", "bar, baz")
+                    .to_string(),
+            ),
+        ],
+    )?;
+    let build = |index_layout| -> Result<(String, String)> {
+        let sink = Arc::new(MemorySink::default());
+        let exit_code = test_build_index(
+            &dir,
+            BuildIndexOpts {
+                index_layout,
+                output_sink: Some(sink.clone()),
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )?;
+        assert_eq!(exit_code, 0);
+        let files = sink.files();
+        let file = |path: &str| String::from_utf8(files[path].clone());
+        Ok((
+            file(&DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top)?,
+            file(&DEFAULT_OUTPUT_FILES.md_file.path_from_repo_top)?,
+        ))
+    };
+
+    let (dl_html, dl_md) = build(IndexLayout::Dl)?;
+    assert!(dl_html.contains("<dl class=\"key_dl\">"));
+    assert!(!dl_html.contains("<table class=\"key_table\">"));
+
+    let (table_html, table_md) = build(IndexLayout::Table)?;
+    assert!(!table_html.contains("<dl class=\"key_dl\">"));
+    let (_, keywords_table) = table_html
+        .split_once("<th>Keywords</th>")
+        .expect("has a table for the keywords");
+    let keywords_table = &keywords_table[..keywords_table.find("</table>").expect("end")];
+    // One row per value: bar, baz, foo
+    assert_eq!(keywords_table.matches("<tr class=\"key_tr\">").count(), 3);
+    for value in ["bar", "baz", "foo"] {
+        assert!(
+            keywords_table.contains(&format!("{value}</a></q></i></strong></td>")),
+            "{value:?} in {keywords_table}"
+        );
+    }
+    assert!(keywords_table.contains("<td class=\"key_files_td\">"));
+    // The Markdown file is the same for both layouts
+    assert_eq!(table_md, dl_md);
+
+    Ok(())
+}
+
+#[test]
+fn t_allowed_version_range() -> Result<()> {
+    let dir = test_repository(
+        "t_allowed_version_range",
+        &[
+            (
+                CONFIG_FILE_PATH,
+                "allowed_version_range = \">=2.6, <3.0\"\n",
+            ),
+            ("in_range.xml", &test_xml_file_content("2.7")),
+            ("out_of_range.xml", &test_xml_file_content("2.5")),
+            ("unparseable.xml", &test_xml_file_content("two-point-seven")),
+        ],
+    )?;
+    // (path, errors, warnings)
+    type PathIssues = (String, Vec<String>, Vec<String>);
+    let config = XmlhubConfig::read(&dir)?;
+    let read = |strict_version_range| -> Result<Vec<PathIssues>> {
+        let fileinfo_or_errors = read_file_infos(
+            xml_file_paths(&GitWorkingDir::from(dir.to_path_buf()), false, false)?,
+            &AttributeRules {
+                strict_version_range,
+                ..Default::default()
+            }
+            .with_config(&config),
+            &NoProgress,
+        );
+        let messages = |issues: &[Issue]| issues.iter().map(|i| i.message.clone()).collect();
+        Ok(fileinfo_or_errors
+            .iter()
+            .map(|result| match result {
+                Ok(file_info) => (
+                    file_info.path.rel_path().to_string(),
+                    vec![],
+                    messages(&file_info.warnings),
+                ),
+                Err(file_errors) => (
+                    file_errors.path.rel_path().to_string(),
+                    messages(&file_errors.errors),
+                    vec![],
+                ),
+            })
+            .collect())
+    };
+    let out_of_range = "\"Version\": \"2.5\" is outside of the allowed range \">=2.6, <3.0\"";
+
+    let results = read(false)?;
+    assert_eq!(results.len(), 3);
+    let (path, errors, warnings) = &results[0];
+    assert_eq!(path, "in_range.xml");
+    assert!(errors.is_empty() && warnings.is_empty(), "{warnings:?}");
+    let (path, errors, warnings) = &results[1];
+    assert_eq!(path, "out_of_range.xml");
+    assert!(errors.is_empty());
+    assert!(warnings.iter().any(|w| w == out_of_range), "{warnings:?}");
+    // Only the existing warning about the unparseable version
+    let (path, errors, warnings) = &results[2];
+    assert_eq!(path, "unparseable.xml");
+    assert!(errors.is_empty());
+    assert!(!warnings.is_empty());
+    assert!(
+        warnings.iter().all(|w| !w.contains("allowed range")),
+        "{warnings:?}"
+    );
+
+    let results = read(true)?;
+    assert!(results[0].1.is_empty());
+    assert_eq!(results[1].1, [out_of_range]);
+    assert!(results[2].1.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn t_extensions() -> Result<()> {
+    let content = test_xml_file_content("2.7");
+    let files = [
+        ("a.xml", content.as_str()),
+        ("b.xml.beast", &content),
+        ("c.beast", &content),
+    ];
+    let build = |dir: &Path, extensions: Option<XmlExtensions>| -> Result<String> {
+        let sink = Arc::new(MemorySink::default());
+        let exit_code = test_build_index(
+            dir,
+            BuildIndexOpts {
+                output_sink: Some(sink.clone()),
+                extensions,
+                ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+            },
+        )?;
+        assert_eq!(exit_code, 0);
+        let index = sink
+            .files()
+            .remove(&*DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top)
+            .expect("written");
+        Ok(String::from_utf8(index)?)
+    };
+
+    let dir = test_repository("t_extensions-default", &files)?;
+    let index = build(&dir, None)?;
+    assert!(index.contains(">a.xml<"));
+    assert!(!index.contains("b.xml.beast"));
+    assert!(!index.contains("c.beast"));
+    let report = validate(&GitWorkingDir::from(dir.to_path_buf()), false, None, None)?;
+    assert_eq!(report.number_of_files, 1);
+
+    // `--extensions` overrides the default
+    let xml_beast = XmlExtensions::from_list("xml,beast")?;
+    let index = build(&dir, Some(xml_beast.clone()))?;
+    for path in ["a.xml", "b.xml.beast", "c.beast"] {
+        assert!(index.contains(&format!(">{path}<")), "{path:?}");
+    }
+    let report = validate(
+        &GitWorkingDir::from(dir.to_path_buf()),
+        false,
+        Some(xml_beast.clone()),
+        None,
+    )?;
+    assert_eq!(report.number_of_files, 3);
+
+    let config = "extensions = [\"xml\", \"beast\"]\n";
+    let dir = test_repository(
+        "t_extensions-configured",
+        &[files[0], files[1], files[2], (CONFIG_FILE_PATH, config)],
+    )?;
+    let index = build(&dir, None)?;
+    for path in ["a.xml", "b.xml.beast", "c.beast"] {
+        assert!(index.contains(&format!(">{path}<")), "{path:?}");
+    }
+    let report = validate(&GitWorkingDir::from(dir.to_path_buf()), false, None, None)?;
+    assert_eq!(report.number_of_files, 3);
+    // and `--extensions` narrows it again
+    let report = validate(
+        &GitWorkingDir::from(dir.to_path_buf()),
+        false,
+        Some(XmlExtensions::default()),
+        None,
+    )?;
+    assert_eq!(report.number_of_files, 1);
+
+    // `prepare` and `add-to` use the same set, from the configuration
+    // of the clone that contains the files
+    let beast_path = dir.join("c.beast");
+    assert_eq!(
+        xml_extensions_for_file(&beast_path)?,
+        XmlhubConfig::from_file_contents(config)?.extensions
+    );
+    check_xml_extensions(
+        std::slice::from_ref(&beast_path),
+        &xml_extensions_for_file(&beast_path)?,
+    )?;
+    assert!(
+        check_xml_extensions(&[beast_path], &XmlExtensions::default())
+            .unwrap_err()
+            .to_string()
+            .contains("does not have a .xml suffix")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_index_anchors_unique() -> Result<()> {
+    let dir = test_repository(
+        "t_index_anchors_unique",
+        &[
+            (
+                "a.xml",
+                &TestXml::new("2.7")
+                    .header("Keywords", "birth-death, skyline")
+                    .to_string(),
+            ),
+            (
+                "b.xml",
+                &TestXml::new("2.7")
+                    .header("Keywords", "birth death")
+                    .to_string(),
+            ),
+        ],
+    )?;
+    let sink = Arc::new(MemorySink::default());
+    let exit_code = test_build_index(
+        &dir,
+        BuildIndexOpts {
+            output_sink: Some(sink.clone()),
+            ..test_build_index_opts(DEFAULT_OUTPUT_FILES.clone())
+        },
+    )?;
+    assert_eq!(exit_code, 0);
+    let html = String::from_utf8(
+        sink.files()
+            .remove(&*DEFAULT_OUTPUT_FILES.html_file.path_from_repo_top)
+            .expect("written"),
+    )?;
+    let captures = |regex: &str| -> Result<BTreeSet<String>> {
+        Ok(regex::Regex::new(regex)?
+            .captures_iter(&html)
+            .map(|c| c[1].to_string())
+            .collect())
+    };
+    let ids = captures(r#"id="(Keywords-[^"]*)""#)?;
+    let hrefs = captures(r##"href="#(Keywords-[^"]*)""##)?;
+    // "birth death" sorts first and keeps the plain anchor name,
+    // "birth-death" gets a hash suffix
+    assert!(ids.contains("Keywords-birth_death"), "{ids:?}");
+    assert_eq!(
+        ids.iter()
+            .filter(|id| id.starts_with("Keywords-birth_death_"))
+            .count(),
+        1,
+        "{ids:?}"
+    );
+    assert_eq!(ids.len(), 3, "{ids:?}");
+    assert!(ids.contains("Keywords-skyline"), "{ids:?}");
+    // The links from the info boxes lead to the index entries
+    assert_eq!(hrefs, ids);
+
+    Ok(())
+}
+
+#[test]
+fn t_open_existing_index() -> Result<()> {
+    let dir = test_repository(
+        "t_open_existing_index",
+        &[("a.xml", &test_xml_file_content("2.7"))],
+    )?;
+    let output_files = DEFAULT_OUTPUT_FILES.clone();
+    let html_path = dir.join(&*output_files.html_file.path_from_repo_top);
+
+    let err = open_existing_index(&dir, &output_files, |_, _| panic!("not opened"))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("does not exist yet"), "{err}");
+
+    // Not an index as `build` would write it, to see that it is not
+    // rebuilt
+    std::fs::write(&html_path, "<p>existing</p>")?;
+    let head_before = test_git_stdout(&dir, &["rev-parse", "HEAD"])?;
+    let mut opened = Vec::new();
+    open_existing_index(&dir, &output_files, |in_directory, path| {
+        opened.push((in_directory.to_owned(), path.to_owned()));
+        Ok(())
+    })?;
+    assert_eq!(opened, [(dir.to_path_buf(), "README.html".into())]);
+    assert_eq!(std::fs::read_to_string(&html_path)?, "<p>existing</p>");
+    assert!(!dir.join(&*output_files.md_file.path_from_repo_top).exists());
+    assert_eq!(test_git_stdout(&dir, &["rev-parse", "HEAD"])?, head_before);
+
+    Ok(())
+}
@@ -1,13 +1,16 @@
 use std::{
     env,
     ffi::{OsStr, OsString},
+    io::Read,
     ops::Deref,
     path::Path,
-    process::Child,
+    process::{Child, ExitStatus},
+    thread::sleep,
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, bail, Context, Result};
-use run_git::command::{run_outputs, spawn, Capturing};
+use run_git::command::{spawn, Capturing};
 
 use crate::{
     fixup_path::CURRENT_DIRECTORY,
@@ -61,12 +64,189 @@ fn get_browsers() -> Result<(BrowsersSource, Vec<String>)> {
     }
 }
 
-pub fn spawn_browser_linux(in_directory: &Path, arguments: &[&OsStr]) -> Result<Child> {
-    let (browsers_source, browsers) = get_browsers()?;
+/// How long to watch a started browser for failing (exiting with an
+/// error status) before considering it successfully started.
+const BROWSER_STARTUP_CHECK_TIME: Duration = Duration::from_millis(500);
+
+/// How long starting a browser candidate (including the startup
+/// check, or waiting for `open` on macOS) may take before it is
+/// abandoned.
+const BROWSER_LAUNCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long to wait before trying a failed candidate again.
+const BROWSER_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The limits for starting each browser candidate.
+#[derive(Debug, Clone, Copy)]
+struct LaunchLimits {
+    /// See `spawn_checked`
+    check_time: Duration,
+    /// After this time, a launcher (see `run_launcher`) is killed
+    timeout: Duration,
+    /// How many times to try (a momentarily busy browser may work
+    /// on the second attempt)
+    attempts: u32,
+    retry_delay: Duration,
+}
+
+const LAUNCH_LIMITS: LaunchLimits = LaunchLimits {
+    check_time: BROWSER_STARTUP_CHECK_TIME,
+    timeout: BROWSER_LAUNCH_TIMEOUT,
+    attempts: 2,
+    retry_delay: BROWSER_RETRY_DELAY,
+};
+
+/// Why an attempt in `with_retry` failed.
+enum AttemptError {
+    /// The program could not be started or failed; trying again may
+    /// help
+    Failed(anyhow::Error),
+    /// The program did not finish in time and was killed; not tried
+    /// again, since it may already have been opening the document
+    TimedOut(anyhow::Error),
+}
+
+impl From<anyhow::Error> for AttemptError {
+    fn from(e: anyhow::Error) -> Self {
+        AttemptError::Failed(e)
+    }
+}
+
+/// Run `action` until it succeeds, up to `limits.attempts` times in
+/// total, but not again after a timeout. The error of the last
+/// attempt is returned.
+fn with_retry<T>(
+    limits: LaunchLimits,
+    mut action: impl FnMut() -> Result<T, AttemptError>,
+) -> Result<T> {
+    let mut attempt = 1;
+    loop {
+        let error = match action() {
+            Ok(value) => return Ok(value),
+            Err(AttemptError::TimedOut(e)) => return Err(e),
+            Err(AttemptError::Failed(e)) => e,
+        };
+        if attempt >= limits.attempts {
+            if attempt > 1 {
+                return Err(error.context(format!("failed {attempt} attempts")));
+            }
+            return Err(error);
+        }
+        sleep(limits.retry_delay);
+        attempt += 1;
+    }
+}
+
+/// Wait for `child` to exit, but at most `timeout`; if it is still
+/// running then, kill it and return `None`.
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>> {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() >= timeout {
+            // (Fails if it has exited in the meantime, which is fine)
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        sleep(Duration::from_millis(20));
+    }
+}
+
+/// `spawn_checked` with the `limits`.
+fn spawn_checked_with_limits(
+    in_directory: &Path,
+    browser: &str,
+    arguments: &[&OsStr],
+    limits: LaunchLimits,
+) -> Result<Child> {
+    with_retry(limits, || {
+        Ok(spawn_checked(
+            in_directory,
+            browser,
+            arguments,
+            limits.check_time,
+        )?)
+    })
+    .with_context(|| anyhow!("starting {browser:?}"))
+}
 
+/// Run `launcher` (like `open` on macOS), which is expected to exit
+/// after handing the document to the browser, with the `limits`. An
+/// exit code other than 0 counts as failure, too. If it doesn't exit
+/// within `limits.timeout`, it is killed.
+fn run_launcher(
+    in_directory: &Path,
+    launcher: &str,
+    arguments: Vec<OsString>,
+    limits: LaunchLimits,
+) -> Result<()> {
+    with_retry(limits, || {
+        let mut child = spawn(in_directory, launcher, &arguments, &[], Capturing::stderr())?;
+        let Some(status) = wait_with_timeout(&mut child, limits.timeout)? else {
+            return Err(AttemptError::TimedOut(anyhow!(
+                "did not finish within {:?}, killed",
+                limits.timeout
+            )));
+        };
+        if !status.success() {
+            let mut stderr = String::new();
+            if let Some(mut output) = child.stderr.take() {
+                output
+                    .read_to_string(&mut stderr)
+                    .context("reading its stderr")?;
+            }
+            return Err(anyhow!(
+                "failed with {status}:\n\t\t{}",
+                stderr.trim_end().replace('\n', "\n\t\t")
+            )
+            .into());
+        }
+        Ok(())
+    })
+    .with_context(|| anyhow!("running {launcher:?}"))
+}
+
+/// Start `browser` and watch it for `check_time`: if it exits with
+/// an error status during that time (e.g. because it is not actually
+/// a browser, or can't open a display), that is reported as an
+/// error. Exiting successfully is fine (some programs just hand the
+/// document to an already running browser).
+fn spawn_checked(
+    in_directory: &Path,
+    browser: &str,
+    arguments: &[&OsStr],
+    check_time: Duration,
+) -> Result<Child> {
+    let mut child = spawn(in_directory, browser, arguments, &[], Capturing::none())?;
+    let start = Instant::now();
+    while start.elapsed() < check_time {
+        if let Some(status) = child.try_wait()? {
+            if !status.success() {
+                bail!("{browser:?} exited immediately with {status}")
+            }
+            break;
+        }
+        sleep(Duration::from_millis(20));
+    }
+    Ok(child)
+}
+
+/// Try the `browsers` in turn until one starts (see
+/// `spawn_checked_with_limits`). On failure, the error lists the errors from
+/// all of them.
+fn spawn_first_working(
+    in_directory: &Path,
+    browsers_source: BrowsersSource,
+    browsers: &[String],
+    arguments: &[&OsStr],
+    limits: LaunchLimits,
+) -> Result<Child> {
     let mut errors = Vec::new();
-    for browser in &browsers {
-        match spawn(in_directory, browser, arguments, &[], Capturing::none()) {
+    for browser in browsers {
+        match spawn_checked_with_limits(in_directory, browser, arguments, limits) {
             Ok(handle) => return Ok(handle),
             // I wish I could split the anyhow into separate parts,
             // increasingly indented, but "{e:#}" is the best we can
@@ -75,7 +255,7 @@ pub fn spawn_browser_linux(in_directory: &Path, arguments: &[&OsStr]) -> Result<
         }
     }
     bail!(
-        "could not find a web browser, tried {browsers:?} from {}:\n{}",
+        "could not start a web browser, tried {browsers:?} from {}:\n{}",
         browsers_source.to_str(),
         errors
             .iter()
@@ -85,6 +265,17 @@ pub fn spawn_browser_linux(in_directory: &Path, arguments: &[&OsStr]) -> Result<
     )
 }
 
+pub fn spawn_browser_linux(in_directory: &Path, arguments: &[&OsStr]) -> Result<Child> {
+    let (browsers_source, browsers) = get_browsers()?;
+    spawn_first_working(
+        in_directory,
+        browsers_source,
+        &browsers,
+        arguments,
+        LAUNCH_LIMITS,
+    )
+}
+
 fn spawn_browser_macos(in_directory: &Path, arguments: &[&OsStr]) -> Result<()> {
     let (browsers_source, mut browsers) = get_browsers()?;
     match browsers_source {
@@ -113,21 +304,14 @@ fn spawn_browser_macos(in_directory: &Path, arguments: &[&OsStr]) -> Result<()>
         };
 
         if may_be_gui_program_name {
-            let mut outputs = run_outputs(in_directory, "open", &all_arguments, &[], &[0, 1])
-                .with_context(|| {
-                    anyhow!("starting a browser, trying 'open' with argument {all_arguments:?}")
-                })?;
-            if outputs.truthy {
-                return Ok(());
+            match run_launcher(in_directory, "open", all_arguments, LAUNCH_LIMITS) {
+                Ok(()) => return Ok(()),
+                Err(e) => errors.push(format!("* {browser:?} failed executed via open -a: {e:#}")),
             }
-            outputs.indent = "\t\t";
-            errors.push(format!(
-                "* {browser:?} failed executed via open -a:\n\t\t{outputs}",
-            ));
         }
 
         // Try as path or program name via $PATH instead
-        match spawn(in_directory, browser, arguments, &[], Capturing::none()) {
+        match spawn_checked_with_limits(in_directory, browser, arguments, LAUNCH_LIMITS) {
             Ok(_handle) => return Ok(()),
             Err(e) => errors.push(format!("* {browser:?} failed executed directly: {e:#}")),
         }
@@ -147,10 +331,13 @@ fn spawn_browser_macos(in_directory: &Path, arguments: &[&OsStr]) -> Result<()>
 /// `BROWSER` environment variable is set, splits it on ':' into
 /// browser names or paths (when containing at least one '/') and
 /// tries executing those. Otherwise tries "sensible-browser",
-/// "firefox", "chromium", "chrome" in turn.  Fails if none could be
-/// started or an env variable could not be decoded as UTF-8. On
-/// macOS, browser names are opened via `open -a`, paths directly (but
-/// note that passing a path to an executable in
+/// "firefox", "chromium", "chrome" in turn. A browser that exits
+/// with an error status right after starting counts as failed; it
+/// is tried once more, then the next one is tried. Starting one
+/// that hangs is abandoned after `BROWSER_LAUNCH_TIMEOUT`. Fails if
+/// none could be started or an env variable could not be decoded as
+/// UTF-8. On macOS, browser names are opened via `open -a`, paths
+/// directly (but note that passing a path to an executable in
 /// `/Applications/$appname.app/..somewhere..` may ignore arguments,
 /// instead use just $appname).
 pub fn spawn_browser(in_directory: &Path, arguments: &[&OsStr]) -> Result<()> {
@@ -173,3 +360,135 @@ pub fn spawn_browser_on_path(document_path: &Path) -> Result<()> {
     spawn_browser(*CURRENT_DIRECTORY, &[&OsString::try_from(document_path)?])?;
     Ok(())
 }
+
+/// Create an executable shell script `name` in `dir` running
+/// `body`, for tests. Returns its path.
+#[cfg(test)]
+fn test_script(dir: &Path, name: &str, body: &str) -> Result<String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{body}\n"))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(path.to_string_lossy().into())
+}
+
+#[test]
+fn t_spawn_first_working() -> Result<()> {
+    use crate::utillib::temp_dir::TempDir;
+
+    let dir = TempDir::new("xmlhub-t_spawn_first_working")?;
+    let failing = test_script(&dir, "failing", "exit 3")?;
+    let working = test_script(&dir, "working", "touch \"$1\"")?;
+    let limits = LaunchLimits {
+        check_time: Duration::from_secs(5),
+        timeout: Duration::from_secs(30),
+        attempts: 1,
+        retry_delay: Duration::ZERO,
+    };
+    let arguments = [OsStr::new("opened")];
+
+    let mut child = spawn_first_working(
+        &dir,
+        BrowsersSource::Env,
+        &[failing.clone(), working],
+        &arguments,
+        limits,
+    )?;
+    child.wait()?;
+    assert!(dir.join("opened").exists());
+
+    let missing = dir.join("missing").to_string_lossy().to_string();
+    let error = spawn_first_working(
+        &dir,
+        BrowsersSource::Env,
+        &[failing.clone(), missing.clone()],
+        &arguments,
+        limits,
+    )
+    .expect_err("all fail");
+    let message = error.to_string();
+    assert!(message.starts_with("could not start a web browser"));
+    assert!(message.contains(&format!(
+        "{failing:?} exited immediately with exit status: 3"
+    )));
+    assert!(message.contains(&missing));
+
+    Ok(())
+}
+
+#[test]
+fn t_with_retry_and_timeout() -> Result<()> {
+    use crate::utillib::temp_dir::TempDir;
+
+    let dir = TempDir::new("xmlhub-t_with_retry_and_timeout")?;
+    let limits = LaunchLimits {
+        check_time: Duration::from_millis(200),
+        timeout: Duration::from_millis(500),
+        attempts: 2,
+        retry_delay: Duration::from_millis(10),
+    };
+
+    // A launcher that hangs is killed, and not run again
+    let hanging = test_script(
+        &dir,
+        "hanging",
+        "echo run >> runs; echo $$ > pid; exec sleep 30",
+    )?;
+    let start = Instant::now();
+    let error = run_launcher(&dir, &hanging, vec![], limits).expect_err("hangs");
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(
+        format!("{error:#}"),
+        format!("running {hanging:?}: did not finish within 500ms, killed")
+    );
+    assert_eq!(std::fs::read_to_string(dir.join("runs"))?, "run\n");
+    let pid: i32 = std::fs::read_to_string(dir.join("pid"))?.trim().parse()?;
+    assert_eq!(
+        nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), None),
+        Err(nix::errno::Errno::ESRCH)
+    );
+
+    // A failing launcher is run again, its error output is reported
+    std::fs::remove_file(dir.join("runs"))?;
+    let failing = test_script(&dir, "failing", "echo run >> runs; echo oops >&2; exit 2")?;
+    let error = run_launcher(&dir, &failing, vec![], limits).expect_err("fails");
+    assert_eq!(
+        format!("{error:#}"),
+        format!("running {failing:?}: failed 2 attempts: failed with exit status: 2:\n\t\toops")
+    );
+    assert_eq!(std::fs::read_to_string(dir.join("runs"))?, "run\nrun\n");
+
+    // A browser that fails once, then succeeds, gets a second chance
+    let flaky = test_script(
+        &dir,
+        "flaky",
+        "if [ -e failed-once ]; then touch \"$1\"; else touch failed-once; exit 1; fi",
+    )?;
+    let mut child = spawn_first_working(
+        &dir,
+        BrowsersSource::Env,
+        std::slice::from_ref(&flaky),
+        &[OsStr::new("opened")],
+        limits,
+    )?;
+    child.wait()?;
+    assert!(dir.join("failed-once").exists());
+    assert!(dir.join("opened").exists());
+
+    // Without the retry, it fails
+    std::fs::remove_file(dir.join("failed-once"))?;
+    assert!(spawn_first_working(
+        &dir,
+        BrowsersSource::Env,
+        &[flaky],
+        &[OsStr::new("opened")],
+        LaunchLimits {
+            attempts: 1,
+            ..limits
+        },
+    )
+    .is_err());
+
+    Ok(())
+}
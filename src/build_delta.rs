@@ -0,0 +1,179 @@
+//! What changed between two builds, for `build --since-last-build`:
+//! the state of a build (the Git blob hashes of the XML files and
+//! which of them had errors) is stored in a file after each build,
+//! and compared with the state of the next build. Meant for the daemon
+//! logs, where it gives a concise activity trail per iteration.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use run_git::git::{BaseAndRelPath, GitWorkingDir};
+use serde::{Deserialize, Serialize};
+
+/// How many paths to pass to one `git hash-object` call.
+const HASH_OBJECT_CHUNK_SIZE: usize = 500;
+
+/// The Git blob hashes of the files at `paths` (which must all have
+/// the same base path), by relative path. (`git hash-object` does
+/// not need a repository, thus this also works for exported trees.)
+pub fn blob_hashes(paths: &[BaseAndRelPath]) -> Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    for chunk in paths.chunks(HASH_OBJECT_CHUNK_SIZE) {
+        let git_working_dir = GitWorkingDir {
+            working_dir_path: chunk[0]
+                .base_path
+                .clone()
+                .unwrap_or_else(|| Arc::new(".".into())),
+        };
+        let mut arguments = vec!["hash-object", "--"];
+        arguments.extend(chunk.iter().map(|path| path.rel_path()));
+        let stdout = git_working_dir.git_stdout(&arguments)?;
+        let stdout = String::from_utf8(stdout).context("output of git hash-object")?;
+        let lines: Vec<&str> = stdout.lines().collect();
+        if lines.len() != chunk.len() {
+            bail!(
+                "git hash-object returned {} hashes for {} paths",
+                lines.len(),
+                chunk.len()
+            )
+        }
+        for (path, hash) in chunk.iter().zip(lines) {
+            hashes.insert(path.rel_path().to_string(), hash.to_string());
+        }
+    }
+    Ok(hashes)
+}
+
+/// The state of a build, as stored between builds.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildState {
+    /// Git blob hash by relative path, for all XML files
+    pub blob_hashes: BTreeMap<String, String>,
+    /// The relative paths of the files that had errors
+    pub error_paths: BTreeSet<String>,
+}
+
+impl BuildState {
+    /// Read the state stored at `path`; returns `None` if there is
+    /// none (i.e. for the first build).
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(contents) => Ok(Some(
+                serde_json::from_slice(&contents).with_context(|| anyhow!("parsing {path:?}"))?,
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| anyhow!("reading {path:?}")),
+        }
+    }
+
+    /// Store the state at `path`, creating the directory if needed.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| anyhow!("creating directory {dir:?}"))?;
+        }
+        let contents = serde_json::to_vec(self)?;
+        std::fs::write(path, contents).with_context(|| anyhow!("writing {path:?}"))
+    }
+}
+
+/// The differences between the states of two builds.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BuildDelta {
+    /// Files present in both builds, with a different blob hash
+    pub changed: usize,
+    pub added: usize,
+    pub removed: usize,
+    /// Files with errors that didn't have errors (or didn't exist)
+    /// in the previous build
+    pub errors_appeared: usize,
+    /// Files that had errors in the previous build but don't (or
+    /// don't exist any more) now
+    pub errors_cleared: usize,
+}
+
+impl BuildDelta {
+    pub fn between(previous: &BuildState, current: &BuildState) -> Self {
+        let mut delta = Self::default();
+        for (path, hash) in &current.blob_hashes {
+            match previous.blob_hashes.get(path) {
+                None => delta.added += 1,
+                Some(previous_hash) if previous_hash != hash => delta.changed += 1,
+                Some(_) => (),
+            }
+        }
+        delta.removed = previous
+            .blob_hashes
+            .keys()
+            .filter(|path| !current.blob_hashes.contains_key(*path))
+            .count();
+        delta.errors_appeared = current
+            .error_paths
+            .difference(&previous.error_paths)
+            .count();
+        delta.errors_cleared = previous
+            .error_paths
+            .difference(&current.error_paths)
+            .count();
+        delta
+    }
+}
+
+impl Display for BuildDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            changed,
+            added,
+            removed,
+            errors_appeared,
+            errors_cleared,
+        } = self;
+        write!(
+            f,
+            "changed={changed} added={added} removed={removed} \
+             errors_appeared={errors_appeared} errors_cleared={errors_cleared}"
+        )
+    }
+}
+
+#[test]
+fn t_build_delta() {
+    let state = |files: &[(&str, &str)], errors: &[&str]| BuildState {
+        blob_hashes: files
+            .iter()
+            .map(|(path, hash)| (path.to_string(), hash.to_string()))
+            .collect(),
+        error_paths: errors.iter().map(|path| path.to_string()).collect(),
+    };
+    let previous = state(
+        &[("a.xml", "1"), ("b.xml", "2"), ("c.xml", "3")],
+        &["b.xml"],
+    );
+    let current = state(
+        &[("a.xml", "1"), ("b.xml", "4"), ("d.xml", "5")],
+        &["d.xml"],
+    );
+    let delta = BuildDelta::between(&previous, &current);
+    assert_eq!(
+        delta,
+        BuildDelta {
+            changed: 1,
+            added: 1,
+            removed: 1,
+            errors_appeared: 1,
+            errors_cleared: 1,
+        }
+    );
+    assert_eq!(
+        delta.to_string(),
+        "changed=1 added=1 removed=1 errors_appeared=1 errors_cleared=1"
+    );
+    assert_eq!(
+        BuildDelta::between(&current, &current),
+        BuildDelta::default()
+    );
+}
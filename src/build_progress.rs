@@ -0,0 +1,77 @@
+//! Callbacks through which `build` (and
+//! `xmlhub_index_documents::build_index_documents_with_progress`)
+//! report their progress, so that applications embedding them can
+//! show progress or collect metrics without depending on the
+//! program's output to stderr.
+
+use std::sync::Mutex;
+
+/// Receives progress events. All methods default to doing nothing;
+/// they may be called from multiple threads.
+pub trait BuildProgress: Send + Sync {
+    /// Called once for every XML file as soon as it has been read
+    /// and parsed, with the number of errors (a file with errors is
+    /// left out of the index) and warnings (not counting those from
+    /// the checks across files, which happen after all files are
+    /// read).
+    fn on_file_parsed(&self, _rel_path: &str, _errors: usize, _warnings: usize) {}
+
+    /// Called once for every top-level section of the index as soon
+    /// as it has been built.
+    fn on_section_built(&self, _title: &str) {}
+}
+
+/// Ignores all events.
+#[derive(Debug)]
+pub struct NoProgress;
+
+impl BuildProgress for NoProgress {}
+
+/// An event as recorded by `RecordingProgress`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProgressEvent {
+    FileParsed {
+        rel_path: String,
+        errors: usize,
+        warnings: usize,
+    },
+    SectionBuilt {
+        title: String,
+    },
+}
+
+/// Keeps all events in memory.
+#[derive(Debug, Default)]
+pub struct RecordingProgress {
+    events: Mutex<Vec<ProgressEvent>>,
+}
+
+impl RecordingProgress {
+    /// The events received so far, in order.
+    pub fn events(&self) -> Vec<ProgressEvent> {
+        self.events.lock().expect("no panics while locked").clone()
+    }
+
+    fn push(&self, event: ProgressEvent) {
+        self.events
+            .lock()
+            .expect("no panics while locked")
+            .push(event);
+    }
+}
+
+impl BuildProgress for RecordingProgress {
+    fn on_file_parsed(&self, rel_path: &str, errors: usize, warnings: usize) {
+        self.push(ProgressEvent::FileParsed {
+            rel_path: rel_path.into(),
+            errors,
+            warnings,
+        });
+    }
+
+    fn on_section_built(&self, title: &str) {
+        self.push(ProgressEvent::SectionBuilt {
+            title: title.into(),
+        });
+    }
+}
@@ -2,77 +2,280 @@ use std::{
     ffi::OsStr,
     fmt::{Debug, Display},
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
 use anyhow::{anyhow, bail, Context, Result};
+use itertools::Itertools;
 use run_git::command::{run, Capturing};
 use toml::Value;
 
 use crate::installation::binaries_repo;
 
-pub fn check_cargo_toml_no_path<P: AsRef<Path> + Debug>(cargo_toml_path: P) -> Result<()> {
-    (|| -> Result<()> {
-        let string =
-            std::fs::read_to_string(&cargo_toml_path).with_context(|| anyhow!("reading file"))?;
-        let val: Value = string.parse()?;
-        let top = val
-            .as_table()
-            .ok_or_else(|| anyhow!("expecting table at the top level"))?;
-
-        let mut bad = Vec::new();
-        // Hmm, is `dependencies` actually optional?
-        // XX todo: also check [patch.crates-io] but that is nested.
-        for (section_name, required) in [("dependencies", false), ("build-dependencies", false)] {
-            let section = match top.get(section_name) {
-                Some(val) => val,
-                None => {
-                    if required {
-                        bail!("missing {section_name:?} section")
+/// A problem in a Cargo.toml file that would prevent other people
+/// from building the same program from the published source.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CargoTomlIssue {
+    /// A `path =` dependency outside of `libs/`; those would not
+    /// build for other people who do not have the right source
+    /// checked out in the right places.
+    PathDependency {
+        section: String,
+        package: String,
+        path: String,
+    },
+    /// A `git =` dependency; the referenced repository may change or
+    /// disappear.
+    GitDependency {
+        section: String,
+        package: String,
+        git: String,
+    },
+    /// A `[patch]` section, e.g. `[patch.crates-io]`, overriding
+    /// published packages.
+    PatchSection { registry: String },
+}
+
+impl Display for CargoTomlIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CargoTomlIssue::PathDependency {
+                section,
+                package,
+                path,
+            } => write!(
+                f,
+                "[{section}] entry for {package:?} has `path = {path:?}`, \
+                 which is not below `libs/`"
+            ),
+            CargoTomlIssue::GitDependency {
+                section,
+                package,
+                git,
+            } => write!(f, "[{section}] entry for {package:?} has `git = {git:?}`"),
+            CargoTomlIssue::PatchSection { registry } => {
+                write!(f, "there is a [patch.{registry}] section")
+            }
+        }
+    }
+}
+
+/// Check the dependency entries in the given section table.
+fn dependency_section_issues(
+    section_name: &str,
+    section: &Value,
+    issues: &mut Vec<CargoTomlIssue>,
+) -> Result<()> {
+    let entries = section
+        .as_table()
+        .ok_or_else(|| anyhow!("expecting section {section_name:?} to be a table"))?;
+    for (package_name, val) in entries {
+        match val {
+            Value::Table(table) => {
+                if let Some(path) = table.get("path") {
+                    let ok = if let Some(s) = path.as_str() {
+                        s.starts_with("libs/")
                     } else {
-                        continue;
+                        false
+                    };
+                    if !ok {
+                        issues.push(CargoTomlIssue::PathDependency {
+                            section: section_name.into(),
+                            package: package_name.into(),
+                            path: path.as_str().map(String::from).unwrap_or(path.to_string()),
+                        });
                     }
                 }
-            };
-
-            let entries = section
-                .as_table()
-                .ok_or_else(|| anyhow!("expecting section {section_name:?} to be a table"))?;
-            for (package_name, val) in entries {
-                match val {
-                    Value::Table(table) => {
-                        if let Some(path) = table.get("path") {
-                            let ok = if let Some(s) = path.as_str() {
-                                s.starts_with("libs/")
-                            } else {
-                                false
-                            };
-                            if !ok {
-                                bad.push((section_name, package_name, path));
-                            }
-                        }
-                    }
-                    Value::String(_) => (),
-                    _ => bail!(
-                        "expecting package entry for dependencies to be a table or string, \
-                         but for {package_name:?} got: {val:?}"
-                    ),
+                if let Some(git) = table.get("git") {
+                    issues.push(CargoTomlIssue::GitDependency {
+                        section: section_name.into(),
+                        package: package_name.into(),
+                        git: git.as_str().map(String::from).unwrap_or(git.to_string()),
+                    });
+                }
+            }
+            Value::String(_) => (),
+            _ => bail!(
+                "expecting package entry for dependencies to be a table or string, \
+                 but for {package_name:?} got: {val:?}"
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// The dependency sections checked by `cargo_toml_issues` (at the top
+/// level and per target). `dev-dependencies` are needed for running
+/// the test suite from the published source, too.
+const DEPENDENCY_SECTIONS: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// Find all entries in the given Cargo.toml file contents that would
+/// break building from the published source: `path =` dependencies
+/// outside `libs/`, `git =` dependencies, and `[patch]` sections.
+pub fn cargo_toml_issues(cargo_toml_contents: &str) -> Result<Vec<CargoTomlIssue>> {
+    let val: Value = cargo_toml_contents.parse()?;
+    let top = val
+        .as_table()
+        .ok_or_else(|| anyhow!("expecting table at the top level"))?;
+
+    let mut issues = Vec::new();
+    // Hmm, is `dependencies` actually optional?
+    for section_name in DEPENDENCY_SECTIONS {
+        if let Some(section) = top.get(section_name) {
+            dependency_section_issues(section_name, section, &mut issues)?;
+        }
+    }
+    // Platform specific dependencies, `[target.'cfg(..)'.dependencies]`
+    if let Some(targets) = top.get("target") {
+        let targets = targets
+            .as_table()
+            .ok_or_else(|| anyhow!("expecting section \"target\" to be a table"))?;
+        for (target_name, target) in targets {
+            for section_name in DEPENDENCY_SECTIONS {
+                if let Some(section) = target.get(section_name) {
+                    dependency_section_issues(
+                        &format!("target.{target_name}.{section_name}"),
+                        section,
+                        &mut issues,
+                    )?;
                 }
             }
         }
-        if !bad.is_empty() {
+    }
+    if let Some(patch) = top.get("patch") {
+        let patch = patch
+            .as_table()
+            .ok_or_else(|| anyhow!("expecting section \"patch\" to be a table"))?;
+        for registry in patch.keys() {
+            issues.push(CargoTomlIssue::PatchSection {
+                registry: registry.into(),
+            });
+        }
+    }
+    Ok(issues)
+}
+
+/// Check that the Cargo.toml file at the given path has none of the
+/// issues listed by `cargo_toml_issues`, reporting all of them if
+/// there are any.
+pub fn check_cargo_toml<P: AsRef<Path> + Debug>(cargo_toml_path: P) -> Result<()> {
+    (|| -> Result<()> {
+        let string =
+            std::fs::read_to_string(&cargo_toml_path).with_context(|| anyhow!("reading file"))?;
+        let issues = cargo_toml_issues(&string)?;
+        if !issues.is_empty() {
             bail!(
-                "the file has the following package entries with `path` entries, \
-                 (section_name, package_name, path)--those \
-                 would not build for other people who do not have the right source \
-                 checked out in the right places: {bad:?}"
+                "the file has the following entries that would prevent other people \
+                 from building the same program from the published source:\n  - {}",
+                issues.iter().map(|issue| issue.to_string()).join("\n  - ")
             )
         }
         Ok(())
     })()
-    .with_context(|| anyhow!("checking Cargo toml file {cargo_toml_path:?} for `path =` entries"))
+    .with_context(|| {
+        anyhow!(
+            "checking Cargo toml file {cargo_toml_path:?} for `path =`, \
+             `git =` and `[patch]` entries"
+        )
+    })
+}
+
+#[test]
+fn t_cargo_toml_issues() -> Result<()> {
+    let t = |s: &str| cargo_toml_issues(s).unwrap();
+    assert_eq!(
+        t(r#"
+[package]
+name = "foo"
+
+[dependencies]
+anyhow = "1.0.65"
+run-git = { path = "libs/run-git" }
+serde = { version = "1", features = ["derive"] }
+
+[dev-dependencies]
+tempfile = "3"
+test-util = { path = "libs/test-util" }
+
+[build-dependencies]
+cc = "1"
+"#),
+        []
+    );
+    assert_eq!(
+        t(r#"
+[dependencies]
+anyhow = "1.0.65"
+ahtml = { path = "../ahtml" }
+chj-util = { git = "https://example.com/chj-util.git", branch = "master" }
+
+[dev-dependencies]
+quickcheck = { git = "https://example.com/quickcheck.git" }
+test-util = { path = "../test-util" }
+
+[build-dependencies]
+bar = { path = "/home/me/bar" }
+
+[target.'cfg(unix)'.dependencies]
+nix = { git = "https://example.com/nix.git" }
+
+[target.'cfg(unix)'.dev-dependencies]
+rustix = { path = "../rustix" }
+
+[patch.crates-io]
+roxmltree = { path = "../roxmltree" }
+"#),
+        [
+            CargoTomlIssue::PathDependency {
+                section: "dependencies".into(),
+                package: "ahtml".into(),
+                path: "../ahtml".into()
+            },
+            CargoTomlIssue::GitDependency {
+                section: "dependencies".into(),
+                package: "chj-util".into(),
+                git: "https://example.com/chj-util.git".into()
+            },
+            CargoTomlIssue::GitDependency {
+                section: "dev-dependencies".into(),
+                package: "quickcheck".into(),
+                git: "https://example.com/quickcheck.git".into()
+            },
+            CargoTomlIssue::PathDependency {
+                section: "dev-dependencies".into(),
+                package: "test-util".into(),
+                path: "../test-util".into()
+            },
+            CargoTomlIssue::PathDependency {
+                section: "build-dependencies".into(),
+                package: "bar".into(),
+                path: "/home/me/bar".into()
+            },
+            CargoTomlIssue::GitDependency {
+                section: "target.cfg(unix).dependencies".into(),
+                package: "nix".into(),
+                git: "https://example.com/nix.git".into()
+            },
+            CargoTomlIssue::PathDependency {
+                section: "target.cfg(unix).dev-dependencies".into(),
+                package: "rustix".into(),
+                path: "../rustix".into()
+            },
+            CargoTomlIssue::PatchSection {
+                registry: "crates-io".into()
+            },
+        ]
+    );
+    assert!(cargo_toml_issues("[dependencies]\nfoo = 1\n").is_err());
+    Ok(())
+}
+
+#[test]
+fn t_cargo_toml_of_this_repository() -> Result<()> {
+    check_cargo_toml(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompilationProfile {
     Debug,
     Release,
@@ -97,7 +300,7 @@ impl CompilationProfile {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Env {
     None,
     Gnu,
@@ -117,7 +320,7 @@ impl Env {
 }
 
 /// Representation of e.g. "aarch64-apple-darwin"
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TargetTriple {
     pub arch: binaries_repo::Arch,
     pub os: binaries_repo::Os,
@@ -136,6 +339,69 @@ impl Display for TargetTriple {
     }
 }
 
+/// Parses the same format as generated by `Display`, i.e. only the
+/// combinations of architecture, OS and env that we know about.
+impl FromStr for TargetTriple {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        (|| -> Result<Self> {
+            let (arch_str, rest) = s
+                .split_once('-')
+                .ok_or_else(|| anyhow!("missing '-' after the architecture"))?;
+            let arch = arch_str.parse()?;
+            for os in [binaries_repo::Os::MacOS, binaries_repo::Os::Linux] {
+                if let Some(env_str) = rest.strip_prefix(os.as_str_for_target_triple()) {
+                    for env in [Env::None, Env::Gnu, Env::Musl] {
+                        if env_str == env.as_str_for_target_triple() {
+                            return Ok(TargetTriple { arch, os, env });
+                        }
+                    }
+                    bail!("unknown env part {env_str:?}")
+                }
+            }
+            bail!("unknown OS part in {rest:?}")
+        })()
+        .with_context(|| anyhow!("unsupported target triple {s:?}"))
+    }
+}
+
+#[test]
+fn t_target_triple_from_str() {
+    use binaries_repo::{Arch, Os};
+    let t = |s: &str| -> TargetTriple {
+        let triple: TargetTriple = s.parse().unwrap();
+        assert_eq!(triple.to_string(), s);
+        triple
+    };
+    assert_eq!(
+        t("aarch64-unknown-linux-gnu"),
+        TargetTriple {
+            arch: Arch::Aarch64,
+            os: Os::Linux,
+            env: Env::Gnu
+        }
+    );
+    assert_eq!(
+        t("x86_64-apple-darwin"),
+        TargetTriple {
+            arch: Arch::X86_64,
+            os: Os::MacOS,
+            env: Env::None
+        }
+    );
+    assert_eq!(t("x86_64-unknown-linux-musl").env, Env::Musl);
+    for bad in [
+        "",
+        "x86_64",
+        "riscv64-unknown-linux-gnu",
+        "x86_64-pc-windows-msvc",
+        "x86_64-apple-darwin-foo",
+    ] {
+        assert!(TargetTriple::from_str(bad).is_err(), "{bad:?}");
+    }
+}
+
 pub fn run_cargo<P: AsRef<Path>, S: AsRef<OsStr> + Debug>(
     working_dir: P,
     args: &[S],
@@ -144,7 +410,7 @@ pub fn run_cargo<P: AsRef<Path>, S: AsRef<OsStr> + Debug>(
     Ok(())
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CompilationTarget {
     pub target_triple: Option<TargetTriple>,
     pub profile: CompilationProfile,
@@ -193,4 +459,24 @@ impl CompilationTarget {
         }
         run_cargo(working_dir, &args)
     }
+
+    /// Remove the build artifacts of the given package for this
+    /// target, so that the next `run_build_in` has to build it from
+    /// scratch.
+    pub fn run_clean_package_in<P: AsRef<Path>>(
+        &self,
+        working_dir: P,
+        package_name: &str,
+    ) -> Result<()> {
+        let mut args: Vec<String> = vec!["clean".into(), "-p".into(), package_name.into()];
+        match self.profile {
+            CompilationProfile::Debug => (),
+            CompilationProfile::Release => args.push("--release".into()),
+        }
+        if let Some(target_triple) = &self.target_triple {
+            args.push("--target".into());
+            args.push(target_triple.to_string());
+        }
+        run_cargo(working_dir, &args)
+    }
 }
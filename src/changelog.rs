@@ -1,6 +1,7 @@
-use std::{borrow::Cow, fmt::Display, mem::take};
+use std::{borrow::Cow, fmt::Display, mem::take, str::FromStr};
 
 use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
 
 use crate::{
     git_version::{GitVersion, SemVersion},
@@ -150,6 +151,19 @@ pub struct ChangelogSection<'s, 't0> {
     pub entries: Vec<&'s str>,
 }
 
+/// A release (or the not yet released changes at the end of the
+/// changelog) with its bullet points, as plain data for machine
+/// consumption (e.g. JSON output for release notes automation).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ChangelogReleaseEntry {
+    /// The release tag name, e.g. "v12"; `None` for unreleased
+    /// changes.
+    pub version: Option<String>,
+    pub date: Option<String>,
+    /// The bullet point texts, without the leading "- ".
+    pub bullet_points: Vec<String>,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ChangelogGetError {
     #[error("given `from` release number is after `to`: {0} > {1}")]
@@ -161,6 +175,50 @@ pub enum ChangelogGetError {
     ChangelogFileHasWronglyOrderedReleases(String, String),
 }
 
+/// A version given for selecting a range from the changelog: either
+/// a concrete version, or a keyword that is resolved via
+/// `resolve`: `latest` (or `HEAD`) for the newest release in the
+/// changelog, `current` for the version of the running program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChangelogVersion {
+    Version(GitVersion<SemVersion>),
+    Latest,
+    Current,
+}
+
+impl FromStr for ChangelogVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "latest" | "HEAD" => Ok(ChangelogVersion::Latest),
+            "current" => Ok(ChangelogVersion::Current),
+            _ => Ok(ChangelogVersion::Version(s.parse().with_context(|| {
+                anyhow!("expecting a version number, `latest`, `HEAD` or `current`, got {s:?}")
+            })?)),
+        }
+    }
+}
+
+impl ChangelogVersion {
+    /// Turn keywords into the concrete version, using the newest
+    /// release in `changelog` and the `current` program version.
+    pub fn resolve(
+        &self,
+        changelog: &Changelog,
+        current: &GitVersion<SemVersion>,
+    ) -> Result<GitVersion<SemVersion>> {
+        match self {
+            ChangelogVersion::Version(version) => Ok(version.clone()),
+            ChangelogVersion::Latest => changelog
+                .latest_release_version()
+                .cloned()
+                .ok_or_else(|| anyhow!("the changelog does not contain any releases")),
+            ChangelogVersion::Current => Ok(current.clone()),
+        }
+    }
+}
+
 impl<'t, 't0> Changelog<'static, 't, 't0> {
     pub fn new_builtin() -> Result<Self> {
         Changelog::from_str(CHANGELOG)
@@ -198,6 +256,55 @@ impl<'s: 't, 't, 't0> Changelog<'s, 't, 't0> {
         sections
     }
 
+    /// The version of the last release line in the changelog, if any.
+    pub fn latest_release_version(&self) -> Option<&GitVersion<SemVersion>> {
+        self.entries.iter().rev().find_map(|entry| match entry {
+            ChangelogEntry::Release(Release { version, date: _ }) => Some(&**version),
+            ChangelogEntry::PointEntry(_) => None,
+        })
+    }
+
+    /// The changes grouped by release, as `ChangelogReleaseEntry`
+    /// values, oldest first.
+    pub fn release_entries(&'t self) -> Vec<ChangelogReleaseEntry> {
+        self.sections()
+            .into_iter()
+            .map(|ChangelogSection { release, entries }| {
+                let (version, date) = if let Some(Release { version, date }) = release {
+                    (Some(format!("v{version}")), Some(date.as_ref().clone()))
+                } else {
+                    (None, None)
+                };
+                ChangelogReleaseEntry {
+                    version,
+                    date,
+                    bullet_points: entries
+                        .iter()
+                        .map(|e| e.strip_prefix('-').unwrap_or(e).trim().to_string())
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Keep only those bullet points for which `keep` returns true;
+    /// release lines are always kept.
+    pub fn retain_point_entries(&self, mut keep: impl FnMut(&str) -> bool) -> Self {
+        let entries: Vec<ChangelogEntry<'s, 't0>> = self
+            .entries
+            .iter()
+            .filter(|entry| match entry {
+                ChangelogEntry::Release(_) => true,
+                ChangelogEntry::PointEntry(e) => keep(e),
+            })
+            .cloned()
+            .collect();
+        Self {
+            entries: entries.into(),
+            ..self.clone()
+        }
+    }
+
     /// Returns "Changes" document title string (with ucfirst
     /// "Changes") and whether it is actually more than just "Changes"
     pub fn display_title(&self, show_downgrade: bool) -> (String, bool) {
@@ -398,3 +505,101 @@ fn t_changelog() -> Result<()> {
     assert!(changelog.entries.len() > sublog.entries.len());
     Ok(())
 }
+
+#[test]
+fn t_release_entries() -> Result<()> {
+    let changelog = Changelog::from_str(
+        "# Changelog
+
+Newest entries at the bottom.
+
+- Initial development
+
+v1 - 2025-01-07
+
+- Fix Cargo.toml
+- Add `--verbose` option
+
+v1.1 - 2025-01-08
+
+- Fix typo in help text
+",
+    )?;
+    let entry =
+        |version: Option<&str>, date: Option<&str>, bullet_points: &[&str]| ChangelogReleaseEntry {
+            version: version.map(String::from),
+            date: date.map(String::from),
+            bullet_points: bullet_points.iter().map(|s| s.to_string()).collect(),
+        };
+    assert_eq!(
+        changelog.release_entries(),
+        [
+            entry(Some("v1"), Some("2025-01-07"), &["Initial development"]),
+            entry(
+                Some("v1.1"),
+                Some("2025-01-08"),
+                &["Fix Cargo.toml", "Add `--verbose` option"]
+            ),
+            entry(None, None, &["Fix typo in help text"]),
+        ]
+    );
+
+    let fixes = changelog.retain_point_entries(|e| e.to_lowercase().contains("fix"));
+    assert_eq!(
+        fixes.release_entries(),
+        [
+            entry(Some("v1"), Some("2025-01-07"), &[]),
+            entry(Some("v1.1"), Some("2025-01-08"), &["Fix Cargo.toml"]),
+            entry(None, None, &["Fix typo in help text"]),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn t_changelog_version_keywords() -> Result<()> {
+    let changelog = Changelog::from_str(
+        "# Changelog
+
+- Initial development
+
+v1 - 2025-01-07
+
+- Fix Cargo.toml
+
+v1.1 - 2025-01-08
+
+- Fix typo in help text
+",
+    )?;
+    let current: GitVersion<SemVersion> = "v1-3-g1234567".parse()?;
+    let resolve = |s: &str| -> Result<String> {
+        Ok(s.parse::<ChangelogVersion>()?
+            .resolve(&changelog, &current)?
+            .to_string())
+    };
+    assert_eq!(resolve("latest")?, "1.1");
+    assert_eq!(resolve("HEAD")?, "1.1");
+    assert_eq!(resolve("current")?, "1-3-g1234567");
+    assert_eq!(resolve("v1")?, "1");
+    assert!(resolve("newest").is_err());
+
+    let current: GitVersion<SemVersion> = "v1".parse()?;
+    let from = ChangelogVersion::Current.resolve(&changelog, &current)?;
+    let to = ChangelogVersion::Latest.resolve(&changelog, &current)?;
+    let part = changelog.get_between_versions(false, false, Some(&from), Some(&to))?;
+    assert_eq!(
+        part.release_entries()
+            .iter()
+            .map(|entry| entry.version.as_deref())
+            .collect::<Vec<_>>(),
+        [Some("v1.1")]
+    );
+    assert_eq!(part.release_entries()[0].bullet_points, ["Fix Cargo.toml"]);
+
+    let no_releases = Changelog::from_str("- Initial development\n")?;
+    assert!(ChangelogVersion::Latest
+        .resolve(&no_releases, &current)
+        .is_err());
+    Ok(())
+}
@@ -113,8 +113,7 @@ impl<'s, P: AsRef<Path>> CheckoutContext<'s, P> {
         let mut current_path: &Path = &absolute;
         let mut first_error = None;
         while !current_path.is_empty() {
-            // XX is_dir()? How do the shared-database things work?
-            if current_path.append(".git").exists() {
+            if git_dir(current_path).is_some() {
                 let repo = self.replace_working_dir_path(Cow::from(current_path.to_owned()));
                 match repo.check1(subpath_check) {
                     Ok(r) => return Ok(r),
@@ -155,10 +154,10 @@ impl<'s, P: AsRef<Path>> CheckoutContext<'s, P> {
         Ok(())
     }
 
-    /// Checks that the working dir exists (and has a .git subdir),
-    /// and if requested via `subpath_check`, checks for the expected
-    /// sub-paths. For further checks, see
-    /// `CheckedCheckoutContext1::check2`.
+    /// Checks that the working dir exists (and has a .git subdir, or
+    /// is a linked worktree, see `git_dir`), and if requested via
+    /// `subpath_check`, checks for the expected sub-paths. For
+    /// further checks, see `CheckedCheckoutContext1::check2`.
     pub fn check1(
         self,
         subpath_check: CheckExpectedSubpathsExist,
@@ -182,12 +181,17 @@ impl<'s, P: AsRef<Path>> CheckoutContext<'s, P> {
                     .unwrap_or_else(|| OsString::from("???")),
             )
         }
-        let git_path = working_dir_path.append(".git");
-        // XX use .is_dir()?
-        if !git_path.exists() {
+        if git_dir(working_dir_path).is_none() {
+            // If the path is inside a Git clone, the user probably
+            // meant to give the top of that one
+            let suggestion = if let Some(root) = nearest_git_root(working_dir_path) {
+                format!("; did you mean {root:?}?")
+            } else {
+                "".into()
+            };
             bail!(
                 "directory {working_dir_path:?} is not a Git clone (it does not contain \
-                 a `.git` subdirectory)"
+                 a `.git` subdirectory){suggestion}"
             )
         }
         match subpath_check {
@@ -300,6 +304,39 @@ impl<'s, P: AsRef<Path>> CheckedCheckoutContext1<'s, P> {
     }
 }
 
+/// The Git directory of the working directory at `working_dir_path`,
+/// if it is the top of one: its `.git` subdirectory, or for linked
+/// worktrees (created via `git worktree add`) and submodules, the
+/// directory that the `.git` file points to via its `gitdir: `
+/// line (relative paths are resolved from `working_dir_path`). Returns
+/// `None` if there is no `.git` entry or the pointer is broken.
+pub fn git_dir(working_dir_path: &Path) -> Option<PathBuf> {
+    let git_path = working_dir_path.append(".git");
+    if git_path.is_dir() {
+        return Some(git_path);
+    }
+    let contents = std::fs::read_to_string(&git_path).ok()?;
+    let pointer = contents.lines().next()?.strip_prefix("gitdir:")?.trim();
+    let git_dir = working_dir_path.append(pointer);
+    if git_dir.is_dir() {
+        Some(git_dir)
+    } else {
+        None
+    }
+}
+
+/// Find the top directory of the Git working directory that `path`
+/// is in, by looking for a `.git` entry in `path` and its parents
+/// (after canonicalizing `path`; see `git_dir`). Returns `None` if
+/// there is none or `path` can't be canonicalized.
+pub fn nearest_git_root(path: &Path) -> Option<PathBuf> {
+    let absolute = path.canonicalize().ok()?;
+    absolute
+        .ancestors()
+        .find(|dir| git_dir(dir).is_some())
+        .map(ToOwned::to_owned)
+}
+
 #[derive(Debug, Clone)]
 pub struct CheckedCheckoutContext2<'s, P: AsRef<Path>> {
     parent: CheckedCheckoutContext1<'s, P>,
@@ -321,3 +358,118 @@ impl<'s, P: AsRef<Path>> CheckedCheckoutContext2<'s, P> {
         format!("remotes/{}/{}", self.default_remote, self.branch_name)
     }
 }
+
+#[test]
+fn t_nearest_git_root() -> Result<()> {
+    use crate::utillib::temp_dir::TempDir;
+
+    let dir = TempDir::new("xmlhub-t_nearest_git_root")?;
+    let subdir = dir.join("sub/dir");
+    std::fs::create_dir_all(&subdir)?;
+    if !std::process::Command::new("git")
+        .args(["init", "-q"])
+        .current_dir(&dir)
+        .status()?
+        .success()
+    {
+        bail!("git init failed in {dir:?}")
+    }
+    let root = dir.canonicalize()?;
+
+    assert_eq!(nearest_git_root(&subdir), Some(root.clone()));
+    assert_eq!(nearest_git_root(&dir), Some(root.clone()));
+
+    let checkout = CheckoutContext {
+        working_dir_path: subdir.as_path(),
+        branch_name: "master",
+        supposed_upstream_git_url: "git@example.com:foo/repo.git",
+        supposed_upstream_web_url: "https://example.com/foo/repo",
+        expected_sub_paths: &[],
+    };
+    let error = checkout
+        .check1(CheckExpectedSubpathsExist::No)
+        .expect_err("subdirectory is not a Git clone");
+    assert!(error
+        .to_string()
+        .ends_with(&format!("a `.git` subdirectory); did you mean {root:?}?")));
+
+    Ok(())
+}
+
+#[test]
+fn t_git_dir_worktree() -> Result<()> {
+    use crate::utillib::temp_dir::TempDir;
+
+    let dir = TempDir::new("xmlhub-t_git_dir_worktree")?;
+    let main = dir.join("main");
+    std::fs::create_dir_all(main.join("sub"))?;
+    std::fs::write(main.join("sub/a.xml"), "<beast/>")?;
+    let git = |args: &[&str]| -> Result<()> {
+        if !std::process::Command::new("git")
+            .args(args)
+            .current_dir(&main)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()?
+            .success()
+        {
+            bail!("git {args:?} failed in {main:?}")
+        }
+        Ok(())
+    };
+    git(&["init", "-q", "-b", "master"])?;
+    git(&[
+        "-c",
+        "user.email=test@example.com",
+        "-c",
+        "user.name=Test",
+        "add",
+        "-A",
+    ])?;
+    git(&[
+        "-c",
+        "user.email=test@example.com",
+        "-c",
+        "user.name=Test",
+        "-c",
+        "commit.gpgsign=false",
+        "commit",
+        "-q",
+        "-m",
+        "initial",
+    ])?;
+    git(&["worktree", "add", "-q", "../worktree", "-b", "other"])?;
+    let worktree = dir.join("worktree").canonicalize()?;
+    assert!(worktree.join(".git").is_file());
+    assert!(git_dir(&worktree).expect("resolved").is_dir());
+
+    let checkout = CheckoutContext {
+        working_dir_path: ".",
+        branch_name: "master",
+        supposed_upstream_git_url: "git@example.com:foo/repo.git",
+        supposed_upstream_web_url: "https://example.com/foo/repo",
+        expected_sub_paths: &["sub"],
+    };
+    let checked = checkout.checked_from_subpath(
+        worktree.join("sub"),
+        CheckExpectedSubpathsExist::Yes,
+        false,
+    )?;
+    assert_eq!(checked.working_dir_path(), worktree);
+    assert_eq!(
+        nearest_git_root(&worktree.join("sub")),
+        Some(worktree.clone())
+    );
+
+    // A `.git` file with a broken pointer is not accepted
+    let broken = dir.join("broken");
+    std::fs::create_dir_all(&broken)?;
+    std::fs::write(broken.join(".git"), "gitdir: ../nowhere\n")?;
+    assert_eq!(git_dir(&broken), None);
+    assert!(checkout
+        .replace_working_dir_path(broken.as_path())
+        .check1(CheckExpectedSubpathsExist::No)
+        .is_err());
+
+    Ok(())
+}
@@ -0,0 +1,270 @@
+//! Validation of the values of the "Contact" attribute against an
+//! optional allow-list of the known contributors, to catch typos. The
+//! list is read from `CONTACTS_FILE_PATH` in the repository; if that
+//! file doesn't exist, no validation takes place. Also, a check
+//! across all files that a person is always given with the same email
+//! address.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+
+use crate::{
+    util::normalize_whitespace,
+    xmlhub_attributes::attribute_specification_by_name,
+    xmlhub_fileinfo::{FileInfo, Issue, WithDerivedValues},
+};
+
+/// The path of the allow-list file, relative to the top of the
+/// repository (not in the untracked daemon folder, `.xmlhub/`, as it
+/// is meant to be committed). One contact per line, empty lines and
+/// lines starting with `#` are ignored. Contacts may be given as
+/// `Name <email>`; only the name is compared.
+pub const CONTACTS_FILE_PATH: &str = "xmlhub-contacts.txt";
+
+/// The known contacts, as read from `CONTACTS_FILE_PATH`.
+#[derive(Debug)]
+pub struct ContactsAllowList {
+    /// The names only, with normalized whitespace
+    contacts: Vec<String>,
+}
+
+/// The name part of a contact: for `Name <email>`, the name,
+/// otherwise the whole contact, with normalized whitespace.
+fn contact_name(contact: &str) -> String {
+    match name_and_email(contact) {
+        Some((name, _email)) => name,
+        None => normalize_whitespace(contact.trim()),
+    }
+}
+
+/// The number of single-character insertions, deletions or
+/// substitutions needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = Vec::with_capacity(b.len() + 1);
+        row.push(i + 1);
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous_row[j] + usize::from(a_char != *b_char);
+            let deletion = previous_row[j + 1] + 1;
+            let insertion = row[j] + 1;
+            row.push(substitution.min(deletion).min(insertion));
+        }
+        previous_row = row;
+    }
+    previous_row[b.len()]
+}
+
+impl ContactsAllowList {
+    /// Parse the contents of a `CONTACTS_FILE_PATH` file.
+    pub fn from_file_contents(contents: &str) -> Self {
+        let contacts = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(contact_name)
+            .collect();
+        Self { contacts }
+    }
+
+    /// Read the allow-list of the repository at `base_path`; returns
+    /// `None` if there is none.
+    pub fn read(base_path: &Path) -> Result<Option<Self>> {
+        let path = base_path.join(CONTACTS_FILE_PATH);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Ok(Some(Self::from_file_contents(&contents))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| anyhow!("reading {path:?}")),
+        }
+    }
+
+    /// The listed contact closest to `contact` (compared
+    /// case-insensitively), if it is close enough to likely be what
+    /// was meant.
+    fn closest_match(&self, contact: &str) -> Option<&str> {
+        let contact = contact.to_lowercase();
+        let max_distance = (contact.chars().count() / 4).max(1);
+        self.contacts
+            .iter()
+            .map(|known| (edit_distance(&contact, &known.to_lowercase()), known))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, known)| known.as_str())
+    }
+
+    /// A warning if the name of `contact` is not on the list, with
+    /// the closest match as a suggestion if there is one. The email
+    /// address of contacts given as `Name <email>` is ignored (see
+    /// `add_inconsistent_email_warnings` for checking those).
+    pub fn check(&self, contact: &str) -> Option<Issue> {
+        let contact = contact_name(contact);
+        if self.contacts.contains(&contact) {
+            return None;
+        }
+        let suggestion = match self.closest_match(&contact) {
+            Some(known) => format!("Did you mean {known:?}? "),
+            None => "".into(),
+        };
+        Some(Issue {
+            message: format!("\"Contact\": {contact:?} is not in the list of known contacts"),
+            hint: Some(
+                format!(
+                    "{suggestion}If this is a new contributor, please add them to the file \
+                     `{CONTACTS_FILE_PATH}` in the repository."
+                )
+                .into(),
+            ),
+            location: None,
+        })
+    }
+}
+
+/// Split a contact given as `Name <email>` into the name (with
+/// normalized whitespace) and the email address. `None` if the
+/// contact is not in that form.
+fn name_and_email(contact: &str) -> Option<(String, &str)> {
+    let (name, rest) = contact.trim().split_once('<')?;
+    let email = rest.strip_suffix('>')?.trim();
+    let name = normalize_whitespace(name.trim());
+    if name.is_empty() || email.is_empty() {
+        None
+    } else {
+        Some((name, email))
+    }
+}
+
+/// For contacts given as `Name <email>`, add a warning to every file
+/// in which a name (compared case-insensitively) is given with an
+/// email address that differs from the one in other files, listing
+/// the addresses and the files using them.
+pub fn add_inconsistent_email_warnings(file_infos: &mut [FileInfo<WithDerivedValues>]) {
+    let contact_key = attribute_specification_by_name("Contact")
+        .expect("'Contact' attribute definition should always be present")
+        .key;
+    // lower-cased name -> (name as first seen, email -> indices
+    // into file_infos)
+    let mut by_name: BTreeMap<String, (String, BTreeMap<String, BTreeSet<usize>>)> =
+        BTreeMap::new();
+    for (i, file_info) in file_infos.iter().enumerate() {
+        let Some(value) = file_info.metadata.get(contact_key) else {
+            continue;
+        };
+        for contact in value.as_string_list().iter() {
+            if let Some((name, email)) = name_and_email(contact) {
+                by_name
+                    .entry(name.to_lowercase())
+                    .or_insert_with(|| (name, BTreeMap::new()))
+                    .1
+                    .entry(email.to_lowercase())
+                    .or_default()
+                    .insert(i);
+            }
+        }
+    }
+    for (name, files_by_email) in by_name.values() {
+        if files_by_email.len() < 2 {
+            continue;
+        }
+        let uses = files_by_email
+            .iter()
+            .map(|(email, indices)| {
+                format!(
+                    "<{email}> in {}",
+                    indices
+                        .iter()
+                        .map(|i| format!("{:?}", file_infos[*i].path.rel_path()))
+                        .join(", ")
+                )
+            })
+            .join("; ");
+        let indices: BTreeSet<usize> = files_by_email.values().flatten().copied().collect();
+        for i in indices {
+            file_infos[i].warnings.push(Issue {
+                message: format!(
+                    "\"Contact\": {name:?} is given with different email addresses \
+                     across files: {uses}"
+                ),
+                hint: Some(
+                    "Please use the same email address for a person in all files \
+                     (or check for a typo)."
+                        .into(),
+                ),
+                location: None,
+            });
+        }
+    }
+}
+
+#[test]
+fn t_edit_distance() {
+    assert_eq!(edit_distance("", ""), 0);
+    assert_eq!(edit_distance("abc", ""), 3);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("Jane Doe", "Jane Doe"), 0);
+    assert_eq!(edit_distance("Jane Deo", "Jane Doe"), 2);
+}
+
+#[test]
+fn t_contacts_allow_list() {
+    let list = ContactsAllowList::from_file_contents(
+        "# Known contributors\n\
+         Jane Doe\n\
+         \n\
+         \x20 John  Smith \n",
+    );
+    assert!(list.check("Jane Doe").is_none());
+    assert!(list.check("John Smith").is_none());
+
+    let issue = list.check("Jane Deo").expect("not on the list");
+    assert_eq!(
+        issue.message,
+        "\"Contact\": \"Jane Deo\" is not in the list of known contacts"
+    );
+    assert!(issue
+        .hint
+        .expect("has hint")
+        .starts_with("Did you mean \"Jane Doe\"? "));
+    assert!(list
+        .check("jane doe")
+        .expect("casing differs")
+        .hint
+        .expect("has hint")
+        .starts_with("Did you mean \"Jane Doe\"? "));
+
+    let issue = list.check("Maria Garcia").expect("not on the list");
+    assert!(issue
+        .hint
+        .expect("has hint")
+        .starts_with("If this is a new"));
+
+    // Only the name is compared
+    assert!(list.check("Jane Doe <jane@example.com>").is_none());
+    assert!(list.check(" John Smith  <js@example.com>").is_none());
+    let issue = list
+        .check("Jane Deo <jane@example.com>")
+        .expect("not on the list");
+    assert_eq!(
+        issue.message,
+        "\"Contact\": \"Jane Deo\" is not in the list of known contacts"
+    );
+    let list = ContactsAllowList::from_file_contents("Jane Doe <jane@example.com>\n");
+    assert!(list.check("Jane Doe").is_none());
+    assert!(list.check("Jane Doe <other@example.com>").is_none());
+}
+
+#[test]
+fn t_name_and_email() {
+    assert_eq!(
+        name_and_email(" Jane  Doe <jane@example.com> "),
+        Some(("Jane Doe".into(), "jane@example.com"))
+    );
+    assert_eq!(name_and_email("Jane Doe"), None);
+    assert_eq!(name_and_email("<jane@example.com>"), None);
+    assert_eq!(name_and_email("Jane Doe <>"), None);
+}
@@ -0,0 +1,91 @@
+//! Honoring a stop request for the daemon only between builds, so
+//! that a build that is in progress (in particular its `git commit`
+//! and `git push`) is not interrupted half-way. `--daemon stop`
+//! sends SIGINT to all processes of the session of the daemon, then
+//! SIGKILL if they are still there after `--timeout-before-sigkill`
+//! seconds; the worker process blocks SIGINT while building, thus
+//! the build can finish within that grace period, and the worker
+//! terminates right afterwards.
+
+use anyhow::{Context, Result};
+use nix::sys::signal::{pthread_sigmask, SigSet, SigmaskHow, Signal};
+
+/// Run `job` with `signals` blocked. The Git processes started by
+/// `job` (via `run_git`, which keeps the signal mask, unlike plain
+/// `std::process::Command`) have them blocked, too.
+/// Signals that arrived in the meantime are delivered after `job`
+/// has finished, i.e. if their action is to terminate the process
+/// (the default for SIGINT), this function does not return. The
+/// signal mask is per thread, thus this must be called while the
+/// process has no other threads (e.g. right after forking); threads
+/// started by `job` inherit the mask.
+pub fn with_signals_deferred<T>(signals: &[Signal], job: impl FnOnce() -> T) -> Result<T> {
+    let mut set = SigSet::empty();
+    for signal in signals {
+        set.add(*signal);
+    }
+    let mut old_set = SigSet::empty();
+    pthread_sigmask(SigmaskHow::SIG_BLOCK, Some(&set), Some(&mut old_set))
+        .with_context(|| format!("blocking the signals {signals:?}"))?;
+    let result = job();
+    pthread_sigmask(SigmaskHow::SIG_SETMASK, Some(&old_set), None)
+        .with_context(|| format!("unblocking the signals {signals:?}"))?;
+    Ok(result)
+}
+
+#[test]
+fn t_with_signals_deferred() -> Result<()> {
+    use std::{thread::sleep, time::Duration};
+
+    use nix::{
+        fcntl::OFlag,
+        sys::{
+            signal::kill,
+            wait::{waitpid, WaitStatus},
+        },
+        unistd::{close, fork, pipe2, read, write, ForkResult},
+    };
+
+    // The child tells about its progress via a pipe: 1 when the job
+    // has started, 2 when it has finished, 3 if
+    // `with_signals_deferred` returned. (Close-on-exec, so that
+    // processes started by other tests don't keep it open.)
+    let (progress_r, progress_w) = pipe2(OFlag::O_CLOEXEC)?;
+    match unsafe {
+        // The child only uses async-signal-safe functions (and
+        // `with_signals_deferred`, which is a thin wrapper around
+        // one), thus this is safe despite the threads of the test
+        // runner.
+        fork()
+    }? {
+        ForkResult::Child => {
+            let _ = with_signals_deferred(&[Signal::SIGINT], || {
+                let _ = write(progress_w, &[1]);
+                sleep(Duration::from_millis(300));
+                let _ = write(progress_w, &[2]);
+            });
+            let _ = write(progress_w, &[3]);
+            unsafe { nix::libc::_exit(0) }
+        }
+        ForkResult::Parent { child } => {
+            close(progress_w)?;
+            let mut progress = Vec::new();
+            let mut buf = [0];
+            while read(progress_r, &mut buf)? == 1 {
+                if buf[0] == 1 {
+                    kill(child, Signal::SIGINT)?;
+                }
+                progress.push(buf[0]);
+            }
+            close(progress_r)?;
+            // The job finished despite the signal, which was
+            // delivered afterwards
+            assert_eq!(progress, [1, 2]);
+            assert_eq!(
+                waitpid(child, None)?,
+                WaitStatus::Signaled(child, Signal::SIGINT, false)
+            );
+        }
+    }
+    Ok(())
+}
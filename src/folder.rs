@@ -1,14 +1,60 @@
 use std::collections::BTreeMap;
 
+use ahtml::SerHtmlFrag;
 use anyhow::{bail, Result};
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 
 use crate::{
     section::{Highlight, Section},
-    xmlhub_fileinfo::{FileInfo, WithDerivedValues},
+    util::normalize_whitespace,
+    xmlhub_attributes::{AttributeSpecification, FLAG_MISSING_KEY_STRING},
+    xmlhub_fileinfo::{FileInfo, InfoBoxOpts, WithDerivedValues},
     xmlhub_indexer_defaults::HTML_ALLOCATOR_POOL,
 };
 
+/// The HTML for the info boxes for `files` (pairs of the name to
+/// show and the file), as the `intro` and `markdown_intro` of a
+/// `Section`. Long values are only truncated in the HTML version of
+/// the boxes (as given in `opts`), the Markdown version gets the
+/// whole values.
+fn info_boxes<'f>(
+    files: impl Iterator<Item = (&'f str, &'f FileInfo<WithDerivedValues>)> + Clone,
+    file_link_base: &str,
+    opts: InfoBoxOpts,
+) -> Result<(Option<SerHtmlFrag>, Option<SerHtmlFrag>)> {
+    let boxes = |truncate_length: Option<usize>| -> Result<SerHtmlFrag> {
+        let html = HTML_ALLOCATOR_POOL.get();
+
+        // Create and then fill in a vector of boxes which we'll use
+        // as the body for a `div` HTML element; this vector is a
+        // custom vector implementation that allocates its storage
+        // space from the `HtmlAllocator` in `html`, that's why we
+        // allocate it via the `new_vec` method and not via
+        // `Vec::new()`.
+        let mut file_info_boxes = html.new_vec();
+        for (file_name, file_info) in files.clone() {
+            file_info_boxes.push(file_info.to_info_box_html(
+                &html,
+                "box",
+                file_name,
+                file_link_base,
+                InfoBoxOpts {
+                    truncate_length,
+                    ..opts
+                },
+            )?)?;
+        }
+        html.preserialize(html.div([], file_info_boxes)?)
+    };
+    let intro = Some(boxes(opts.truncate_length)?);
+    let markdown_intro = if opts.truncate_length.is_some() {
+        Some(boxes(None)?)
+    } else {
+        None
+    };
+    Ok((intro, markdown_intro))
+}
+
 /// An abstraction for folders and files, to collect the paths
 /// reported by Git into, and then to map to nested
 /// `Section`s. Contained files/folders are stored sorted by the name
@@ -66,23 +112,22 @@ impl<'f> Folder<'f> {
         self.add_(&segments, file)
     }
 
-    /// Convert to nested `Section`s.
-    pub fn to_section(&self, title: Option<String>) -> Result<Section> {
-        let intro = {
-            let html = HTML_ALLOCATOR_POOL.get();
-
-            // Create and then fill in a vector of boxes which we'll use
-            // as the body for a `div` HTML element; this vector is a
-            // custom vector implementation that allocates its storage
-            // space from the `HtmlAllocator` in `html`, that's why we
-            // allocate it via the `new_vec` method and not via
-            // `Vec::new()`.
-            let mut file_info_boxes = html.new_vec();
-            for (file_name, file_info) in &self.files {
-                file_info_boxes.push(file_info.to_info_box_html(&html, "box", file_name)?)?;
-            }
-            Some(html.preserialize(html.div([], file_info_boxes)?)?)
-        };
+    /// Convert to nested `Section`s. `file_link_base` and `opts` are
+    /// passed on to `FileInfo::to_info_box_html` (for the Markdown
+    /// version of the boxes without truncation).
+    pub fn to_section(
+        &self,
+        title: Option<String>,
+        file_link_base: &str,
+        opts: InfoBoxOpts,
+    ) -> Result<Section> {
+        let (intro, markdown_intro) = info_boxes(
+            self.files
+                .iter()
+                .map(|(file_name, file_info)| (file_name.as_str(), *file_info)),
+            file_link_base,
+            opts,
+        )?;
 
         let subsections = self
             .folders
@@ -90,7 +135,7 @@ impl<'f> Folder<'f> {
             .map(|(folder_name, folder)| {
                 // Append a '/' to folder_name to indicate that those are
                 // folder names
-                folder.to_section(Some(format!("{folder_name}/")))
+                folder.to_section(Some(format!("{folder_name}/")), file_link_base, opts)
             })
             .collect::<Result<_>>()?;
 
@@ -98,6 +143,87 @@ impl<'f> Folder<'f> {
             highlight: Highlight::None,
             title,
             intro,
+            markdown_intro,
+            subsections,
+        })
+    }
+}
+
+/// Where the file info boxes are placed: the sections of an
+/// `AttributeGroups` are by the (first) value of an attribute instead
+/// of by folder as with `Folder`. Files without a value for the
+/// attribute are collected under `FLAG_MISSING_KEY_STRING`.
+pub struct AttributeGroups<'f> {
+    spec: &'static AttributeSpecification,
+    groups: BTreeMap<String, BTreeMap<&'f str, &'f FileInfo<WithDerivedValues>>>,
+}
+
+impl<'f> AttributeGroups<'f> {
+    pub fn new(spec: &'static AttributeSpecification) -> Self {
+        Self {
+            spec,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    /// The group for `file`: its first value for the attribute,
+    /// prepared as for the index if the attribute is indexed (so
+    /// that the groups match the index entries).
+    fn group_key(&self, file: &FileInfo<WithDerivedValues>) -> String {
+        let value = file
+            .metadata
+            .get(self.spec.key)
+            .and_then(|value| value.as_string_list().first().cloned());
+        match (value, self.spec.key_string_preparation()) {
+            (Some(value), Some(preparation)) => preparation.prepare_key_string(&value),
+            (Some(value), None) => normalize_whitespace(value.trim()),
+            (None, _) => FLAG_MISSING_KEY_STRING.into(),
+        }
+    }
+
+    /// Add a `FileInfo` to the group for its attribute value.
+    pub fn add(&mut self, file: &'f FileInfo<WithDerivedValues>) -> Result<()> {
+        let key = self.group_key(file);
+        let files = self.groups.entry(key).or_default();
+        if let Some(oldfile) = files.insert(file.path.rel_path(), file) {
+            bail!("duplicate file: {file:?} already entered as {oldfile:?}")
+        }
+        Ok(())
+    }
+
+    /// Convert to a `Section` with a subsection per group, showing
+    /// the full paths of the files. See `Folder::to_section` for the
+    /// other arguments.
+    pub fn to_section(
+        &self,
+        title: Option<String>,
+        file_link_base: &str,
+        opts: InfoBoxOpts,
+    ) -> Result<Section> {
+        let subsections = self
+            .groups
+            .par_iter()
+            .map(|(key, files)| -> Result<Section> {
+                let (intro, markdown_intro) = info_boxes(
+                    files.iter().map(|(path, file_info)| (*path, *file_info)),
+                    file_link_base,
+                    opts,
+                )?;
+                Ok(Section {
+                    highlight: Highlight::None,
+                    title: Some(key.clone()),
+                    intro,
+                    markdown_intro,
+                    subsections: vec![],
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Section {
+            highlight: Highlight::None,
+            title,
+            intro: None,
+            markdown_intro: None,
             subsections,
         })
     }
@@ -278,6 +278,33 @@ impl<'t> GitLogVersionChecker<'t> {
         )
     }
 
+    /// Record `program_name_and_version` as a Git note in
+    /// `notes_ref` on `commit`, replacing an existing note there.
+    pub fn add_version_note(
+        &self,
+        git_working_dir: &GitWorkingDir,
+        notes_ref: &str,
+        commit: &str,
+        quiet: bool,
+    ) -> anyhow::Result<()> {
+        let message = self.program_name_and_version();
+        if !git_working_dir.git(
+            &[
+                "notes",
+                &format!("--ref={notes_ref}"),
+                "add",
+                "-f",
+                "-m",
+                &message,
+                commit,
+            ],
+            quiet,
+        )? {
+            anyhow::bail!("git notes add on commit {commit:?} failed")
+        }
+        Ok(())
+    }
+
     pub fn parse_version_from_message(&self, message: &str) -> Option<GitVersion<SemVersion>> {
         let mut lines = message.split('\n');
         while let Some(line) = lines.next() {
@@ -298,7 +325,9 @@ impl<'t> GitLogVersionChecker<'t> {
         None
     }
 
-    /// Check a Git log for written-down version numbers, when found,
+    /// Check a Git log for written-down version numbers (in the commit
+    /// messages, or in notes if `--notes=..` is passed as part of
+    /// `git_log_arguments`), when found,
     /// do a SemVer comparison with the given version, if the
     /// `program_name` is less than the version found, report an
     /// error. Returns the ordering comparison from the program
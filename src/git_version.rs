@@ -481,6 +481,34 @@ impl PartialOrd for GitVersion<SemVersion> {
     }
 }
 
+#[test]
+fn t_git_version_non_default_describe() {
+    // Outputs of `git describe` with arguments given via
+    // `XMLHUB_GIT_DESCRIBE_ARGS` (see build.rs) instead of the
+    // defaults
+    let t = |s: &str| -> GitVersion<SemVersion> { s.parse().unwrap() };
+    // --long
+    assert_eq!(
+        t("v1.2.3-0-g8c847ab"),
+        GitVersion {
+            version: "1.2.3".parse().unwrap(),
+            past_tag: Some((0, "8c847ab".into())),
+            modified: false
+        }
+    );
+    // --abbrev=12 --dirty=-modified, tag without "v"
+    assert_eq!(
+        t("4.5-12-g8c847ab01234-modified"),
+        GitVersion {
+            version: "4.5".parse().unwrap(),
+            past_tag: Some((12, "8c847ab01234".into())),
+            modified: true
+        }
+    );
+    // --dirty (with the default suffix) is not supported
+    assert!(GitVersion::<SemVersion>::from_str("v1.2.3-4-g8c847ab-dirty").is_err());
+}
+
 #[cfg(test)]
 #[test]
 fn t_git_version_version() {
@@ -1,4 +1,9 @@
-use std::{borrow::Cow, collections::HashMap, io::Write, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    io::Write,
+    sync::Arc,
+};
 
 use ahtml::{att, AId, HtmlAllocator, Node};
 use anyhow::Result;
@@ -78,6 +83,23 @@ impl<'id> Hints<'id> {
         }
     }
 
+    /// Like `new`, but registers all the given hint texts up front,
+    /// deduplicated and in sorted order, so that their numbers only
+    /// depend on the set of texts, not on the order in which `intern`
+    /// is called later on (which may vary e.g. when files are
+    /// processed in parallel).
+    pub fn with_messages<'m>(
+        id: &'id str,
+        messages: impl IntoIterator<Item = &'m Cow<'static, str>>,
+    ) -> Self {
+        let mut hints = Self::new(id);
+        let messages: BTreeSet<&Cow<'static, str>> = messages.into_iter().collect();
+        for msg in messages {
+            hints.intern(msg.clone());
+        }
+        hints
+    }
+
     pub fn intern(&mut self, msg: Cow<'static, str>) -> HintId<'_> {
         if let Some(id) = self.index.get(&msg) {
             HintId {
@@ -1,7 +1,7 @@
 //! Should add these to ahtml crate.
 
 use ahtml::{att, flat::Flat, AId, AllocatorType, HtmlAllocator, Node, ToASlice};
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 // Utils for `Flat`
 
@@ -83,3 +83,308 @@ pub fn anchor(
 ) -> Result<AId<Node>> {
     html.a([att("name", anchor_name), att("id", anchor_name)], body)
 }
+
+/// A text to put into an HTML tree where content should be streamed
+/// in later, see `split_at_placeholder`.
+pub const STREAMING_PLACEHOLDER: &str = "XMLHUB-STREAMING-PLACEHOLDER-5c1e7b3a";
+
+/// Split `serialized` HTML into the parts before and after
+/// `STREAMING_PLACEHOLDER`, so that content can be written between
+/// the two parts without ever holding all of it in an
+/// `HtmlAllocator`. Errors if the placeholder doesn't appear exactly
+/// once.
+pub fn split_at_placeholder(serialized: &[u8]) -> Result<(&[u8], &[u8])> {
+    let placeholder = STREAMING_PLACEHOLDER.as_bytes();
+    let mut positions = serialized
+        .windows(placeholder.len())
+        .enumerate()
+        .filter(|(_, window)| *window == placeholder)
+        .map(|(i, _)| i);
+    match (positions.next(), positions.next()) {
+        (Some(pos), None) => Ok((&serialized[..pos], &serialized[pos + placeholder.len()..])),
+        (None, _) => bail!("streaming placeholder not found in serialized HTML"),
+        (Some(_), Some(_)) => {
+            bail!("streaming placeholder appears multiple times in serialized HTML")
+        }
+    }
+}
+
+#[test]
+fn t_split_at_placeholder() {
+    let s = format!("<div>{STREAMING_PLACEHOLDER}</div>");
+    assert_eq!(
+        split_at_placeholder(s.as_bytes()).unwrap(),
+        (&b"<div>"[..], &b"</div>"[..])
+    );
+    assert!(split_at_placeholder(b"<div></div>").is_err());
+    let s = format!("{STREAMING_PLACEHOLDER}{STREAMING_PLACEHOLDER}");
+    assert!(split_at_placeholder(s.as_bytes()).is_err());
+}
+
+/// A piece of serialized HTML, see `html_tokens`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtmlToken<'s> {
+    /// An opening or closing tag, a `<!DOCTYPE ..>` declaration or a
+    /// comment, including the angle brackets
+    Tag(&'s [u8]),
+    /// Everything between tags
+    Text(&'s [u8]),
+}
+
+/// Elements whose contents are not HTML but raw text.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea"];
+
+/// Elements that are put on their own line by `pretty_print_html`.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "blockquote",
+    "body",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "hr",
+    "html",
+    "li",
+    "link",
+    "meta",
+    "ol",
+    "p",
+    "pre",
+    "script",
+    "style",
+    "table",
+    "tbody",
+    "td",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// Elements without a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// The lowercased element name of a tag token, and whether it is a
+/// closing tag. `None` for declarations and comments.
+fn tag_name(tag: &[u8]) -> Option<(String, bool)> {
+    let rest = tag.strip_prefix(b"<")?;
+    let (rest, is_closing) = match rest.strip_prefix(b"/") {
+        Some(rest) => (rest, true),
+        None => (rest, false),
+    };
+    let name: String = rest
+        .iter()
+        .take_while(|b| b.is_ascii_alphanumeric())
+        .map(|b| b.to_ascii_lowercase() as char)
+        .collect();
+    if name.is_empty() {
+        None
+    } else {
+        Some((name, is_closing))
+    }
+}
+
+/// Split serialized HTML into tags and text. Relies on `>` being
+/// escaped in attribute values (as `ahtml` does). The contents of
+/// `script`, `style` and `textarea` elements are returned as a single
+/// text token.
+pub fn html_tokens(serialized: &[u8]) -> Vec<HtmlToken<'_>> {
+    let find = |from: usize, needle: &[u8]| -> Option<usize> {
+        serialized[from..]
+            .windows(needle.len())
+            .position(|window| window.eq_ignore_ascii_case(needle))
+            .map(|pos| from + pos)
+    };
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < serialized.len() {
+        if serialized[pos] != b'<' {
+            let end = find(pos, b"<").unwrap_or(serialized.len());
+            tokens.push(HtmlToken::Text(&serialized[pos..end]));
+            pos = end;
+            continue;
+        }
+        let end = if serialized[pos..].starts_with(b"<!--") {
+            find(pos, b"-->").map(|end| end + 3)
+        } else {
+            find(pos, b">").map(|end| end + 1)
+        }
+        .unwrap_or(serialized.len());
+        let tag = &serialized[pos..end];
+        tokens.push(HtmlToken::Tag(tag));
+        pos = end;
+        if let Some((name, false)) = tag_name(tag) {
+            if RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+                let end = find(pos, format!("</{name}").as_bytes()).unwrap_or(serialized.len());
+                if end > pos {
+                    tokens.push(HtmlToken::Text(&serialized[pos..end]));
+                }
+                pos = end;
+            }
+        }
+    }
+    tokens
+}
+
+/// Re-format compact serialized HTML (as printed by `ahtml`) with
+/// line breaks and indentation, for easier reading and diffing. Only
+/// whitespace is added, and only between the children of block-level
+/// elements that contain nothing but block-level elements (and
+/// comments). Elements with text or inline elements in them, and
+/// `pre` elements, are kept as they are including everything nested
+/// in them, since added whitespace could show up there. The result
+/// only depends on the input.
+pub fn pretty_print_html(serialized: &[u8]) -> Vec<u8> {
+    let tokens = html_tokens(serialized);
+    let is_block = |name: &str| BLOCK_ELEMENTS.contains(&name);
+    let is_void = |name: &str| VOID_ELEMENTS.contains(&name);
+
+    // For each opening tag of a block element (by token index),
+    // whether its children are only block elements
+    let mut block_only = vec![false; tokens.len()];
+    {
+        // The open block elements: their name and token index
+        let mut open: Vec<(String, usize)> = Vec::new();
+        let clear_parent = |open: &[(String, usize)], block_only: &mut [bool]| {
+            if let Some((_, i)) = open.last() {
+                block_only[*i] = false;
+            }
+        };
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                HtmlToken::Text(text) => {
+                    if !text.iter().all(u8::is_ascii_whitespace) {
+                        clear_parent(&open, &mut block_only);
+                    }
+                }
+                HtmlToken::Tag(tag) => match tag_name(tag) {
+                    None => (),
+                    Some((name, true)) if is_block(&name) => {
+                        // (Unbalanced closing tags are ignored)
+                        if let Some(pos) = open.iter().rposition(|(n, _)| *n == name) {
+                            open.truncate(pos);
+                        }
+                    }
+                    Some((name, false)) if is_block(&name) && !is_void(&name) => {
+                        block_only[i] = name != "pre";
+                        open.push((name, i));
+                    }
+                    // Void block elements like `<hr>`
+                    Some((name, _)) if is_block(&name) => (),
+                    Some(_) => clear_parent(&open, &mut block_only),
+                },
+            }
+        }
+    }
+
+    let mut out = Vec::with_capacity(serialized.len() + serialized.len() / 4);
+    // The open block elements: their name, whether whitespace may be
+    // added between their children, and whether they have child
+    // elements
+    let mut open: Vec<(String, bool, bool)> = Vec::new();
+    let mut seen_tag = false;
+    let newline = |out: &mut Vec<u8>, depth: usize| {
+        if !out.ends_with(b"\n") {
+            out.push(b'\n');
+        }
+        out.extend(std::iter::repeat_n(b' ', 2 * depth));
+    };
+    // Whether whitespace may be added before a child of the innermost
+    // open element (the document itself counts as block-only)
+    let may_break = |open: &[(String, bool, bool)]| open.last().is_none_or(|(_, b, _)| *b);
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            HtmlToken::Text(text) => out.extend_from_slice(text),
+            HtmlToken::Tag(tag) => {
+                match tag_name(tag) {
+                    Some((name, true)) if is_block(&name) => {
+                        if let Some(pos) = open.iter().rposition(|(n, _, _)| *n == name) {
+                            let (_, breaks, has_children) = &open[pos];
+                            let add_break = *breaks && *has_children;
+                            open.truncate(pos);
+                            if add_break {
+                                newline(&mut out, open.len());
+                            }
+                        }
+                    }
+                    name => {
+                        let is_element = name.is_some();
+                        let breaks = may_break(&open);
+                        if seen_tag && breaks {
+                            newline(&mut out, open.len());
+                        }
+                        if let Some((_, _, has_children)) = open.last_mut() {
+                            *has_children |= is_element;
+                        }
+                        if let Some((name, false)) = name {
+                            if is_block(&name) && !is_void(&name) {
+                                // Content nested in an element that is
+                                // kept as-is is kept as-is, too
+                                open.push((name, breaks && block_only[i], false));
+                            }
+                        }
+                    }
+                }
+                out.extend_from_slice(tag);
+                seen_tag = true;
+            }
+        }
+    }
+    out.push(b'\n');
+    out
+}
+
+#[test]
+fn t_pretty_print_html() {
+    let compact = "\u{feff}<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>T</title>\
+                   <style>a > b { x: 1 }</style></head><body><h1>A <b>b</b></h1>\
+                   <ul><li>one</li><li>two<ul><li>x</li></ul></li></ul>\
+                   <pre><div>  keep\n</div></pre><p>end</p></body></html>";
+    let pretty = String::from_utf8(pretty_print_html(compact.as_bytes())).unwrap();
+    assert_eq!(
+        pretty,
+        "\u{feff}<!DOCTYPE html>\n\
+         <html>\n  \
+           <head>\n    \
+             <meta charset=\"utf-8\">\n    \
+             <title>T</title>\n    \
+             <style>a > b { x: 1 }</style>\n  \
+           </head>\n  \
+           <body>\n    \
+             <h1>A <b>b</b></h1>\n    \
+             <ul>\n      \
+               <li>one</li>\n      \
+               <li>two<ul><li>x</li></ul></li>\n    \
+             </ul>\n    \
+             <pre><div>  keep\n</div></pre>\n    \
+             <p>end</p>\n  \
+           </body>\n\
+         </html>\n"
+    );
+    assert_eq!(pretty_print_html(compact.as_bytes()), pretty.as_bytes());
+
+    // Blocks mixed with text or inline elements are left alone
+    let mixed = "<div><div>a <span>b</span><div>c</div></div><p><div><p>d</p></div></p></div>";
+    assert_eq!(
+        String::from_utf8(pretty_print_html(mixed.as_bytes())).unwrap(),
+        "<div>\n  \
+           <div>a <span>b</span><div>c</div></div>\n  \
+           <p>\n    \
+             <div>\n      \
+               <p>d</p>\n    \
+             </div>\n  \
+           </p>\n\
+         </div>\n"
+    );
+}
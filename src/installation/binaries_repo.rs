@@ -83,6 +83,7 @@ impl Arch {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BinariesRepoSection {
     pub os: Os,
     pub arch: Arch,
@@ -18,7 +18,8 @@ use crate::{
     installation::shell::AppendToShellFileDone,
     sha256::sha256sum,
     util::ask_yn,
-    xmlhub_indexer_defaults::{BINARIES_CHECKOUT, PROGRAM_NAME, XMLHUB_BINARY_FILE_NAME},
+    xmlhub_checkouts::BINARIES_CHECKOUT,
+    xmlhub_indexer_defaults::{PROGRAM_NAME, XMLHUB_BINARY_FILE_NAME},
 };
 
 use super::{
@@ -1,7 +1,16 @@
+use std::path::Path;
+
 use anyhow::{Context, Result};
 use chrono::Local;
+use run_git::command::run_stdout_string;
+
+pub fn prog_version(in_dir: &Path, prog_name: &str) -> Result<String> {
+    run_stdout_string(in_dir, prog_name, &["--version"], &[], &[0], true)
+}
 
-use crate::util::hostname;
+pub fn hostname() -> Result<String> {
+    run_stdout_string::<_, &str, &str>(".", "hostname", &[], &[], &[0], true)
+}
 
 /// Current time in rfc2822 format.
 pub fn get_timestamp() -> String {
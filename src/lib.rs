@@ -1,10 +1,14 @@
 pub mod beast_version;
 pub mod browser;
+pub mod build_delta;
+pub mod build_progress;
 pub mod cargo;
 pub mod changelog;
 pub mod checkout_context;
 pub mod clap_styles;
 pub mod const_util;
+pub mod contacts;
+pub mod deferred_stop;
 pub mod doi;
 pub mod dry_run;
 pub mod effect;
@@ -17,10 +21,14 @@ pub mod hack09;
 pub mod hints;
 pub mod html_util;
 pub mod installation;
+pub mod location;
+pub mod messages;
 pub mod modified_document;
 pub mod modified_xml_document;
+pub mod output_sink;
 pub mod rayon_util;
 pub mod ref_or_owned;
+pub mod relaxng;
 pub mod section;
 pub mod sha256;
 pub mod string_tree;
@@ -34,12 +42,18 @@ pub mod xml_document;
 pub mod xmlhub_attributes;
 pub mod xmlhub_autolink;
 pub mod xmlhub_check_version;
+pub mod xmlhub_checkouts;
 pub mod xmlhub_clone_to;
+pub mod xmlhub_config;
 pub mod xmlhub_docs;
+pub mod xmlhub_error;
 pub mod xmlhub_file_issues;
 pub mod xmlhub_fileinfo;
 pub mod xmlhub_global_opts;
 pub mod xmlhub_help;
+pub mod xmlhub_index_documents;
 pub mod xmlhub_indexer_defaults;
 pub mod xmlhub_install;
+pub mod xmlhub_metadata_parser;
+pub mod xmlhub_read_file;
 pub mod xmlhub_types;
@@ -0,0 +1,144 @@
+//! Normalization of geographic (sampling) location names, for
+//! attributes of kind `AttributeKind::Location`: common spellings of
+//! a country (names, ISO 3166 two and three letter codes) are mapped
+//! to one canonical country name, which is used as the index key.
+
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+
+use crate::util::normalize_whitespace;
+
+/// Canonical country names with their alternative spellings. The
+/// canonical name and the alternatives are matched
+/// case-insensitively, ignoring dots. This is not meant to be
+/// complete; unknown locations are indexed as given.
+const COUNTRIES: &[(&str, &[&str])] = &[
+    ("Argentina", &["AR", "ARG"]),
+    ("Australia", &["AU", "AUS"]),
+    ("Austria", &["AT", "AUT", "Österreich"]),
+    ("Bangladesh", &["BD", "BGD"]),
+    ("Belgium", &["BE", "BEL"]),
+    ("Bolivia", &["BO", "BOL"]),
+    ("Brazil", &["BR", "BRA", "Brasil"]),
+    ("Cambodia", &["KH", "KHM"]),
+    ("Cameroon", &["CM", "CMR"]),
+    ("Canada", &["CA", "CAN"]),
+    ("Chile", &["CL", "CHL"]),
+    ("China", &["CN", "CHN", "PRC", "People's Republic of China"]),
+    ("Colombia", &["CO", "COL"]),
+    ("Czechia", &["CZ", "CZE", "Czech Republic"]),
+    (
+        "Democratic Republic of the Congo",
+        &["CD", "COD", "DRC", "DR Congo", "Congo-Kinshasa"],
+    ),
+    ("Denmark", &["DK", "DNK"]),
+    ("Ecuador", &["EC", "ECU"]),
+    ("Egypt", &["EG", "EGY"]),
+    ("Ethiopia", &["ET", "ETH"]),
+    ("Finland", &["FI", "FIN"]),
+    ("France", &["FR", "FRA"]),
+    ("Germany", &["DE", "DEU", "Deutschland"]),
+    ("Ghana", &["GH", "GHA"]),
+    ("Greece", &["GR", "GRC"]),
+    ("Guinea", &["GN", "GIN"]),
+    ("Hong Kong", &["HK", "HKG"]),
+    ("India", &["IN", "IND"]),
+    ("Indonesia", &["ID", "IDN"]),
+    ("Iran", &["IR", "IRN", "Islamic Republic of Iran"]),
+    ("Ireland", &["IE", "IRL"]),
+    ("Israel", &["IL", "ISR"]),
+    ("Italy", &["IT", "ITA", "Italia"]),
+    ("Japan", &["JP", "JPN"]),
+    ("Kenya", &["KE", "KEN"]),
+    ("Liberia", &["LR", "LBR"]),
+    ("Madagascar", &["MG", "MDG"]),
+    ("Malaysia", &["MY", "MYS"]),
+    ("Mexico", &["MX", "MEX"]),
+    ("Morocco", &["MA", "MAR"]),
+    ("Netherlands", &["NL", "NLD", "The Netherlands", "Holland"]),
+    ("New Zealand", &["NZ", "NZL"]),
+    ("Nigeria", &["NG", "NGA"]),
+    ("Norway", &["NO", "NOR"]),
+    ("Pakistan", &["PK", "PAK"]),
+    ("Peru", &["PE", "PER"]),
+    ("Philippines", &["PH", "PHL"]),
+    ("Poland", &["PL", "POL"]),
+    ("Portugal", &["PT", "PRT"]),
+    ("Russia", &["RU", "RUS", "Russian Federation"]),
+    ("Saudi Arabia", &["SA", "SAU"]),
+    ("Senegal", &["SN", "SEN"]),
+    ("Sierra Leone", &["SL", "SLE"]),
+    ("Singapore", &["SG", "SGP"]),
+    ("South Africa", &["ZA", "ZAF"]),
+    ("South Korea", &["KR", "KOR", "Republic of Korea"]),
+    ("Spain", &["ES", "ESP", "España"]),
+    ("Sweden", &["SE", "SWE"]),
+    (
+        "Switzerland",
+        &["CH", "CHE", "Schweiz", "Suisse", "Svizzera"],
+    ),
+    ("Taiwan", &["TW", "TWN"]),
+    ("Tanzania", &["TZ", "TZA"]),
+    ("Thailand", &["TH", "THA"]),
+    ("Turkey", &["TR", "TUR", "Türkiye"]),
+    ("Uganda", &["UG", "UGA"]),
+    (
+        "United Kingdom",
+        &["GB", "GBR", "UK", "Great Britain", "Britain"],
+    ),
+    ("United States", &["US", "USA", "United States of America"]),
+    ("Vietnam", &["VN", "VNM", "Viet Nam"]),
+    ("Zambia", &["ZM", "ZMB"]),
+    ("Zimbabwe", &["ZW", "ZWE"]),
+];
+
+/// The form in which names are compared.
+fn comparison_key(name: &str) -> String {
+    normalize_whitespace(name.trim())
+        .replace('.', "")
+        .to_lowercase()
+}
+
+lazy_static! {
+    static ref CANONICAL_COUNTRY_BY_KEY: HashMap<String, &'static str> = {
+        let mut map = HashMap::new();
+        for (canonical, alternatives) in COUNTRIES {
+            map.insert(comparison_key(canonical), *canonical);
+            for alternative in *alternatives {
+                map.insert(comparison_key(alternative), *canonical);
+            }
+        }
+        map
+    };
+}
+
+/// The canonical country name for `location`, if it is a known way
+/// of naming a country.
+pub fn canonical_country(location: &str) -> Option<&'static str> {
+    CANONICAL_COUNTRY_BY_KEY
+        .get(&comparison_key(location))
+        .copied()
+}
+
+#[test]
+fn t_canonical_country() {
+    for variant in ["USA", "United States", "us", "U.S.A.", " united  states "] {
+        assert_eq!(
+            canonical_country(variant),
+            Some("United States"),
+            "{variant}"
+        );
+    }
+    assert_eq!(canonical_country("CH"), Some("Switzerland"));
+    assert_eq!(canonical_country("Atlantis"), None);
+    // No alternative is given for two countries
+    let mut seen = HashMap::new();
+    for (canonical, alternatives) in COUNTRIES {
+        for name in std::iter::once(canonical).chain(alternatives.iter()) {
+            if let Some(other) = seen.insert(comparison_key(name), canonical) {
+                panic!("{name:?} is given for both {other:?} and {canonical:?}");
+            }
+        }
+    }
+}
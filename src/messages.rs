@@ -0,0 +1,220 @@
+//! Message catalog for user-facing messages, so that translations
+//! could be provided. Messages are looked up by key via the `tr!`
+//! macro; the built-in catalog is English. A file with replacement
+//! texts can be given via the `XMLHUB_MESSAGES` environment variable
+//! (`~` and `$VAR` in the path are expanded, see `expand_path`; the
+//! file has lines of the form `key = text`, `#` starting a comment
+//! line, `\n` in a text meaning a newline). Keys missing from the
+//! file fall back to English.
+//!
+//! Texts can contain placeholders like `{path}`, which are replaced
+//! by the values passed to `tr!` under that name.
+
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+use anyhow::{anyhow, bail, Context, Result};
+use lazy_static::lazy_static;
+
+use crate::utillib::expand_path::expand_path;
+
+/// The built-in (English) texts, by key.
+const ENGLISH: &[(&str, &str)] = &[
+    (
+        "prepare.already-has-headers",
+        "This document already has header comments: {path}",
+    ),
+    (
+        "prepare.sequences-removed",
+        "NOTE: sequences from this document have been removed \
+         (use `--no-blind` to keep them!{more}): {path}",
+    ),
+    (
+        "prepare.sequences-removed-more",
+        " Note that sequence metadata has been retained and is assumed \
+         to not be privacy sensitive; use `--blind-all` if you want to \
+         remove that, too!",
+    ),
+    (
+        "prepare.unchanged",
+        "File is unchanged (already prepared): {path}",
+    ),
+    (
+        "prepare.summary",
+        "Processed {n_files} {files}: {n_blinded} blinded \
+         ({n_sequences} {sequences} removed), {n_unchanged} unchanged.",
+    ),
+    (
+        "normalize.unchanged",
+        "File is unchanged (already normalized): {path}",
+    ),
+    ("add-to.no-files", "No files given, thus nothing to do."),
+    ("add-to.reading", "Reading the {files}..."),
+    ("add-to.writing", "Writing the {files}..."),
+    (
+        "add-to.done",
+        "Done.\n\
+         Now edit the new {files} in {directory} to complete the metadata:\n   \
+         {paths}\n\
+         Run `xmlhub help-attributes` to learn about what to enter into \
+         the individual fields.",
+    ),
+];
+
+/// A set of message texts, by key, with fallback to `ENGLISH`.
+#[derive(Debug, Default)]
+pub struct MessageCatalog {
+    overrides: HashMap<String, String>,
+}
+
+impl MessageCatalog {
+    /// The catalog with only the built-in English texts.
+    pub fn english() -> Self {
+        Self::default()
+    }
+
+    /// Parse the contents of a messages file (see module docs).
+    /// Unknown keys are an error, to catch typos.
+    pub fn from_file_contents(contents: &str) -> Result<Self> {
+        let mut overrides = HashMap::new();
+        for (i, line) in contents.lines().enumerate() {
+            let lineno = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, text) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expecting `key = text` on line {lineno}: {line:?}"))?;
+            let key = key.trim();
+            if english(key).is_none() {
+                bail!("unknown message key {key:?} on line {lineno}")
+            }
+            overrides.insert(key.to_string(), text.trim().replace("\\n", "\n"));
+        }
+        Ok(Self { overrides })
+    }
+
+    /// The text for `key`. Panics for keys that are not in the
+    /// built-in catalog (that's a bug in the program).
+    pub fn get(&self, key: &str) -> &str {
+        if let Some(text) = self.overrides.get(key) {
+            text
+        } else {
+            english(key).unwrap_or_else(|| panic!("unknown message key {key:?}"))
+        }
+    }
+
+    /// The text for `key` with the `{name}` placeholders replaced by
+    /// the corresponding values from `args`. Placeholders without a
+    /// value are left as they are.
+    pub fn format(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let mut output = String::new();
+        let mut rest = self.get(key);
+        while let Some(start) = rest.find('{') {
+            output.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let value = after.find('}').and_then(|end| {
+                let name = &after[..end];
+                args.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, value)| (end, value))
+            });
+            if let Some((end, value)) = value {
+                output.push_str(&value.to_string());
+                rest = &after[end + 1..];
+            } else {
+                output.push('{');
+                rest = after;
+            }
+        }
+        output.push_str(rest);
+        output
+    }
+}
+
+fn english(key: &str) -> Option<&'static str> {
+    ENGLISH
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, text)| *text)
+}
+
+lazy_static! {
+    static ref MESSAGES: Result<MessageCatalog, String> = match std::env::var("XMLHUB_MESSAGES") {
+        Ok(path) => expand_path(Path::new(&path))
+            .and_then(|path| {
+                std::fs::read_to_string(&path)
+                    .with_context(|| anyhow!("reading messages file {path:?}"))
+            })
+            .and_then(|contents| {
+                MessageCatalog::from_file_contents(&contents)
+                    .with_context(|| anyhow!("parsing messages file {path:?}"))
+            })
+            .map_err(|e| format!("{e:#}")),
+        Err(std::env::VarError::NotPresent) => Ok(MessageCatalog::english()),
+        Err(e @ std::env::VarError::NotUnicode(_)) =>
+            Err(format!("can't decode XMLHUB_MESSAGES env var: {e}")),
+    };
+}
+
+/// The catalog to use, as given via the `XMLHUB_MESSAGES`
+/// environment variable, or the English one.
+pub fn messages() -> Result<&'static MessageCatalog> {
+    MESSAGES.as_ref().map_err(|e| anyhow!("{e}"))
+}
+
+/// Look up a message by key in the catalog from `messages()`, with
+/// optional `name = value` arguments for the placeholders. Returns a
+/// `Result<String>`.
+#[macro_export]
+macro_rules! tr {
+    ($key:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::messages::messages().map(|catalog| {
+            catalog.format(
+                $key,
+                &[$((stringify!($name), &$value as &dyn std::fmt::Display)),*],
+            )
+        })
+    };
+}
+
+#[test]
+fn t_message_catalog() -> Result<()> {
+    let english = MessageCatalog::english();
+    assert_eq!(
+        english.get("add-to.no-files"),
+        "No files given, thus nothing to do."
+    );
+    assert_eq!(
+        english.format("prepare.unchanged", &[("path", &"\"a.xml\"")]),
+        "File is unchanged (already prepared): \"a.xml\""
+    );
+
+    let translated = MessageCatalog::from_file_contents(
+        "# German\n\
+         prepare.unchanged = Datei ist unverändert: {path}\n\
+         add-to.reading = Lese die {files}...\\nBitte warten.\n",
+    )?;
+    assert_eq!(
+        translated.format("prepare.unchanged", &[("path", &"a.xml")]),
+        "Datei ist unverändert: a.xml"
+    );
+    assert_eq!(
+        translated.format("add-to.reading", &[("files", &"Dateien")]),
+        "Lese die Dateien...\nBitte warten."
+    );
+    // Keys not in the file are still in English
+    assert_eq!(
+        translated.get("add-to.no-files"),
+        "No files given, thus nothing to do."
+    );
+    // Placeholders without a value are kept
+    assert_eq!(
+        translated.format("prepare.unchanged", &[]),
+        "Datei ist unverändert: {path}"
+    );
+
+    assert!(MessageCatalog::from_file_contents("no-such-key = x\n").is_err());
+    assert!(MessageCatalog::from_file_contents("prepare.unchanged\n").is_err());
+    Ok(())
+}
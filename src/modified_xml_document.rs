@@ -11,6 +11,7 @@ use std::{
 };
 
 use anyhow::Result;
+use itertools::Itertools;
 
 use crate::{
     modified_document::{Modification, ModifiedDocument},
@@ -185,6 +186,36 @@ impl<'d> ModifiedXMLDocument<'d> {
         ));
     }
 
+    /// Replace the comments at the top of the document (see
+    /// `XMLDocument::header_comments`), and the processing
+    /// instructions there with the target `pi_target` (see
+    /// `XMLDocument::header_processing_instructions`), with the given
+    /// `comments`, each on its own line. Does nothing if there are
+    /// neither. NOTE: see docs on `escape_comment`.
+    pub fn replace_header_metadata(&mut self, comments: &[String], indent: &str, pi_target: &str) {
+        let ranges: Vec<Range<usize>> = self
+            .xml_document
+            .header_comments()
+            .map(|comment| comment.location.byte_range())
+            .chain(
+                self.xml_document
+                    .header_processing_instructions(pi_target)
+                    .map(|pi| pi.location.byte_range()),
+            )
+            .sorted_by_key(|range| range.start)
+            .collect();
+        if let (Some(first), Some(last)) = (ranges.first(), ranges.last()) {
+            let replacement = comments
+                .iter()
+                .map(|comment| escape_comment(comment, indent))
+                .join("\n");
+            self.document
+                .push(Modification::Delete(first.start..last.end));
+            self.document
+                .push(Modification::Insert(first.start, replacement.into()));
+        }
+    }
+
     /// Insert the given text at the given position. It is properly
     /// escaped. Panics if the given `DocumentPosition` is not for
     /// this document. NOTE: inserting text other than whitespace is
@@ -0,0 +1,330 @@
+//! Where `build` writes the index files to: by default the Git
+//! working directory, but other implementations of `OutputSink` can
+//! send them elsewhere (e.g. a remote store, or into memory for
+//! tests) without changing the build logic.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::utillib::temp_dir::TempDir;
+
+/// A destination for the output files. `write_file` may be called
+/// from multiple threads.
+pub trait OutputSink: Send + Sync {
+    /// Store `bytes` as the file at `rel_path` (relative to the top
+    /// of the repository, as in `OutputFile::path_from_repo_top`).
+    fn write_file(&self, rel_path: &str, bytes: &[u8]) -> Result<()>;
+
+    /// Store what `write` writes as the file at `rel_path`, so that
+    /// big files don't need to be held in memory by sinks that can
+    /// stream. The default implementation collects the output and
+    /// passes it to `write_file`.
+    fn write_file_streaming(
+        &self,
+        rel_path: &str,
+        write: &mut dyn FnMut(&mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        let mut bytes = Vec::new();
+        write(&mut bytes)?;
+        self.write_file(rel_path, &bytes)
+    }
+
+    /// Called once after all files were written successfully (not
+    /// called if writing any of them failed). For sinks that hold
+    /// back the files until then.
+    fn finish(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the files into a directory in the filesystem.
+#[derive(Debug)]
+pub struct FilesystemSink {
+    pub base_path: PathBuf,
+}
+
+impl OutputSink for FilesystemSink {
+    fn write_file(&self, rel_path: &str, bytes: &[u8]) -> Result<()> {
+        let path = self.base_path.join(rel_path);
+        std::fs::write(&path, bytes).with_context(|| anyhow!("writing to file {path:?}"))
+    }
+
+    fn write_file_streaming(
+        &self,
+        rel_path: &str,
+        write: &mut dyn FnMut(&mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        let path = self.base_path.join(rel_path);
+        let mut out =
+            BufWriter::new(File::create(&path).with_context(|| anyhow!("creating file {path:?}"))?);
+        write(&mut out).with_context(|| anyhow!("writing to file {path:?}"))?;
+        out.flush()
+            .with_context(|| anyhow!("writing to file {path:?}"))
+    }
+}
+
+/// Keeps the files in memory.
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    files: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemorySink {
+    /// The files written so far, by path.
+    pub fn files(&self) -> BTreeMap<String, Vec<u8>> {
+        self.files.lock().expect("no panics while locked").clone()
+    }
+}
+
+impl OutputSink for MemorySink {
+    fn write_file(&self, rel_path: &str, bytes: &[u8]) -> Result<()> {
+        self.files
+            .lock()
+            .expect("no panics while locked")
+            .insert(rel_path.into(), bytes.into());
+        Ok(())
+    }
+}
+
+/// Refuses files bigger than `max_size` bytes (with an error, so that
+/// they are not committed, either) and passes the others on to
+/// `inner`. The files are only passed on in `finish`, thus if any
+/// file is too big, none of them is written. Streamed files are
+/// spooled to a temporary file until then instead of being kept in
+/// memory.
+pub struct SizeLimitSink {
+    inner: Arc<dyn OutputSink>,
+    max_size: u64,
+    /// The files written so far, by path
+    pending: Mutex<BTreeMap<String, PendingFile>>,
+    /// The directory holding the spooled files, created on first use
+    spool_dir: Mutex<Option<TempDir>>,
+    /// For naming the spooled files
+    num_spooled: AtomicU64,
+}
+
+/// A file held back by `SizeLimitSink`.
+#[derive(Debug)]
+enum PendingFile {
+    Bytes(Vec<u8>),
+    /// The path of the temporary file holding the contents
+    Spooled(PathBuf),
+}
+
+impl SizeLimitSink {
+    pub fn new(inner: Arc<dyn OutputSink>, max_size: u64) -> Self {
+        Self {
+            inner,
+            max_size,
+            pending: Default::default(),
+            spool_dir: Default::default(),
+            num_spooled: Default::default(),
+        }
+    }
+}
+
+impl SizeLimitSink {
+    /// The error for the file at `rel_path` being too big; `size` is
+    /// not known for streamed files, as writing stops at the limit.
+    fn too_big_error(&self, rel_path: &str, size: Option<u64>) -> anyhow::Error {
+        let max_size = self.max_size;
+        let size = match size {
+            Some(size) => format!("{size} bytes, more than the maximum of {max_size} bytes"),
+            None => format!("more than the maximum of {max_size} bytes"),
+        };
+        anyhow!(
+            "refusing to write the output file {rel_path:?}: it would have {size}; this is \
+             likely caused by very long metadata values (e.g. embedded previews or data \
+             pasted into a comment) in some of the XML files, or by a bug"
+        )
+    }
+
+    /// Keep `file` for `finish`.
+    fn hold_back(&self, rel_path: &str, file: PendingFile) {
+        self.pending
+            .lock()
+            .expect("no panics while locked")
+            .insert(rel_path.into(), file);
+    }
+
+    /// A path for a new file in the spool directory.
+    fn new_spool_path(&self) -> Result<PathBuf> {
+        let mut spool_dir = self.spool_dir.lock().expect("no panics while locked");
+        let spool_dir = match &mut *spool_dir {
+            Some(spool_dir) => spool_dir,
+            None => spool_dir.insert(TempDir::new("xmlhub-output")?),
+        };
+        let num = self.num_spooled.fetch_add(1, Ordering::Relaxed);
+        Ok(spool_dir.join(num.to_string()))
+    }
+}
+
+/// Passes the bytes on to `inner` until more than `max_size` of them
+/// were written, then fails, so that producing the output stops.
+struct LimitedWriter<W: Write> {
+    inner: W,
+    max_size: u64,
+    size: u64,
+    exceeded: bool,
+}
+
+impl<W: Write> Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.size += buf.len() as u64;
+        if self.size > self.max_size {
+            self.exceeded = true;
+            return Err(std::io::Error::other("output size limit exceeded"));
+        }
+        self.inner.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl OutputSink for SizeLimitSink {
+    fn write_file(&self, rel_path: &str, bytes: &[u8]) -> Result<()> {
+        let size = bytes.len() as u64;
+        if size > self.max_size {
+            return Err(self.too_big_error(rel_path, Some(size)));
+        }
+        self.hold_back(rel_path, PendingFile::Bytes(bytes.into()));
+        Ok(())
+    }
+
+    /// Streams into a temporary file, stopping as soon as the output
+    /// gets bigger than `max_size`.
+    fn write_file_streaming(
+        &self,
+        rel_path: &str,
+        write: &mut dyn FnMut(&mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        let spool_path = self.new_spool_path()?;
+        let result = (|| -> Result<()> {
+            let file = File::create(&spool_path)
+                .with_context(|| anyhow!("creating file {spool_path:?}"))?;
+            let mut out = LimitedWriter {
+                inner: BufWriter::new(file),
+                max_size: self.max_size,
+                size: 0,
+                exceeded: false,
+            };
+            let result = write(&mut out);
+            if out.exceeded {
+                return Err(self.too_big_error(rel_path, None));
+            }
+            result?;
+            out.flush()
+                .with_context(|| anyhow!("writing to file {spool_path:?}"))
+        })();
+        match result {
+            Ok(()) => {
+                self.hold_back(rel_path, PendingFile::Spooled(spool_path));
+                Ok(())
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&spool_path);
+                Err(e)
+            }
+        }
+    }
+
+    fn finish(&self) -> Result<()> {
+        let pending = std::mem::take(&mut *self.pending.lock().expect("no panics while locked"));
+        for (rel_path, file) in pending {
+            match file {
+                PendingFile::Bytes(bytes) => self.inner.write_file(&rel_path, &bytes)?,
+                PendingFile::Spooled(spool_path) => {
+                    self.inner.write_file_streaming(&rel_path, &mut |out| {
+                        let mut input = File::open(&spool_path)
+                            .with_context(|| anyhow!("opening file {spool_path:?}"))?;
+                        std::io::copy(&mut input, out)
+                            .with_context(|| anyhow!("copying from file {spool_path:?}"))?;
+                        Ok(())
+                    })?
+                }
+            }
+        }
+        // Delete the spooled files
+        self.spool_dir
+            .lock()
+            .expect("no panics while locked")
+            .take();
+        self.inner.finish()
+    }
+}
+
+#[test]
+fn t_size_limit_sink() -> Result<()> {
+    let memory = Arc::new(MemorySink::default());
+    let sink = SizeLimitSink::new(memory.clone(), 3);
+    sink.write_file("a", b"abc")?;
+    assert!(memory.files().is_empty());
+    sink.finish()?;
+    assert_eq!(memory.files().keys().collect::<Vec<_>>(), ["a"]);
+
+    // A file that's too big stops the others from being written,
+    // too, even those written before it
+    let memory = Arc::new(MemorySink::default());
+    let sink = SizeLimitSink::new(memory.clone(), 3);
+    sink.write_file("a", b"abc")?;
+    let error = sink.write_file("b", b"abcd").expect_err("too big");
+    assert!(error.to_string().contains("\"b\": it would have 4 bytes"));
+    assert!(memory.files().is_empty());
+
+    // Streamed files are checked the same way; writing stops at the
+    // limit
+    let memory = Arc::new(MemorySink::default());
+    let sink = SizeLimitSink::new(memory.clone(), 3);
+    sink.write_file_streaming("a", &mut |out| Ok(out.write_all(b"ab")?))?;
+    let mut writes = 0;
+    let error = sink
+        .write_file_streaming("b", &mut |out| loop {
+            writes += 1;
+            out.write_all(b"ab")?;
+        })
+        .expect_err("too big");
+    assert_eq!(writes, 2);
+    assert!(error
+        .to_string()
+        .contains("\"b\": it would have more than the maximum of 3 bytes"));
+    sink.write_file_streaming("b", &mut |out| Ok(out.write_all(b"abc")?))?;
+
+    // They are held in temporary files, not in memory, until `finish`
+    let spool_dir = sink
+        .spool_dir
+        .lock()
+        .unwrap()
+        .as_ref()
+        .expect("created")
+        .to_path_buf();
+    for (rel_path, contents) in [("a", "ab"), ("b", "abc")] {
+        match &sink.pending.lock().unwrap()[rel_path] {
+            PendingFile::Spooled(path) => {
+                assert!(path.starts_with(&spool_dir));
+                assert_eq!(std::fs::read_to_string(path)?, contents);
+            }
+            PendingFile::Bytes(_) => panic!("{rel_path:?} is held in memory"),
+        }
+    }
+    assert!(memory.files().is_empty());
+    sink.finish()?;
+    assert_eq!(
+        memory.files(),
+        BTreeMap::from([("a".into(), b"ab".to_vec()), ("b".into(), b"abc".to_vec())])
+    );
+    assert!(!spool_dir.exists());
+    Ok(())
+}
@@ -0,0 +1,1008 @@
+//! Validation of XML documents against a RELAX NG schema (in XML
+//! syntax), for `validate --schema`. Implemented via derivatives of
+//! the schema patterns, following James Clark's "An algorithm for
+//! RELAX NG validation". Supported are `grammar`, `start`, `define`
+//! (with `combine`), `ref`, `div`, `element`, `attribute`, `group`,
+//! `interleave`, `choice`, `optional`, `zeroOrMore`, `oneOrMore`,
+//! `mixed`, `text`, `empty`, `notAllowed`, `value`, and `data` (whose
+//! datatype is not checked, any text is accepted), and the name
+//! classes `name`, `anyName`, `nsName` and `choice` (with
+//! `except`). Other constructs (`list`, `include`, `externalRef`,
+//! `parentRef`, nested grammars) are reported as unsupported. The
+//! patterns created during the validation of a document are interned
+//! and the derivatives memoized, so that equal patterns are
+//! pointer-equal and duplicate alternatives are dropped, which keeps
+//! choices from growing exponentially.
+
+use std::{cell::RefCell, collections::HashMap, hash::Hash, path::Path, rc::Rc};
+
+use anyhow::{anyhow, bail, Context, Result};
+use roxmltree::{Document, Node, NodeType};
+
+use crate::xml_document::LineAndColumn;
+
+const RELAXNG_NAMESPACE: &str = "http://relaxng.org/ns/structure/1.0";
+const XSD_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema";
+
+#[derive(Debug)]
+enum NameClass {
+    Name {
+        ns: String,
+        local: String,
+    },
+    AnyName {
+        except: Option<Box<NameClass>>,
+    },
+    NsName {
+        ns: String,
+        except: Option<Box<NameClass>>,
+    },
+    Choice(Box<NameClass>, Box<NameClass>),
+}
+
+impl NameClass {
+    fn contains(&self, ns: &str, local: &str) -> bool {
+        match self {
+            NameClass::Name {
+                ns: ns1,
+                local: local1,
+            } => ns == ns1 && local == local1,
+            NameClass::AnyName { except } => {
+                !except.as_ref().is_some_and(|e| e.contains(ns, local))
+            }
+            NameClass::NsName { ns: ns1, except } => {
+                ns == ns1 && !except.as_ref().is_some_and(|e| e.contains(ns, local))
+            }
+            NameClass::Choice(a, b) => a.contains(ns, local) || b.contains(ns, local),
+        }
+    }
+}
+
+type P = Rc<Pattern>;
+
+#[derive(Debug)]
+enum Pattern {
+    Empty,
+    NotAllowed,
+    Text,
+    Value(String),
+    Choice(P, P),
+    Interleave(P, P),
+    Group(P, P),
+    OneOrMore(P),
+    Attribute(Rc<NameClass>, P),
+    Element(Rc<NameClass>, P),
+    After(P, P),
+    /// Index into `Schema::defines`
+    Ref(usize),
+}
+
+fn not_allowed() -> P {
+    Rc::new(Pattern::NotAllowed)
+}
+
+fn empty() -> P {
+    Rc::new(Pattern::Empty)
+}
+
+fn is_not_allowed(p: &P) -> bool {
+    matches!(**p, Pattern::NotAllowed)
+}
+
+fn choice(a: P, b: P) -> P {
+    if is_not_allowed(&a) || Rc::ptr_eq(&a, &b) {
+        b
+    } else if is_not_allowed(&b) {
+        a
+    } else {
+        Rc::new(Pattern::Choice(a, b))
+    }
+}
+
+fn group(a: P, b: P) -> P {
+    match (&*a, &*b) {
+        (Pattern::NotAllowed, _) | (_, Pattern::NotAllowed) => not_allowed(),
+        (Pattern::Empty, _) => b,
+        (_, Pattern::Empty) => a,
+        _ => Rc::new(Pattern::Group(a, b)),
+    }
+}
+
+fn interleave(a: P, b: P) -> P {
+    match (&*a, &*b) {
+        (Pattern::NotAllowed, _) | (_, Pattern::NotAllowed) => not_allowed(),
+        (Pattern::Empty, _) => b,
+        (_, Pattern::Empty) => a,
+        _ => Rc::new(Pattern::Interleave(a, b)),
+    }
+}
+
+fn after(a: P, b: P) -> P {
+    if is_not_allowed(&a) || is_not_allowed(&b) {
+        not_allowed()
+    } else {
+        Rc::new(Pattern::After(a, b))
+    }
+}
+
+fn one_or_more(a: P) -> P {
+    if is_not_allowed(&a) {
+        a
+    } else {
+        Rc::new(Pattern::OneOrMore(a))
+    }
+}
+
+fn is_whitespace(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_whitespace())
+}
+
+fn normalize_whitespace(s: &str) -> String {
+    s.split_ascii_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A validation failure, with the position of the offending node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    pub message: String,
+    pub location: LineAndColumn,
+}
+
+/// A parsed RELAX NG schema.
+#[derive(Debug)]
+pub struct Schema {
+    start: P,
+    defines: Vec<P>,
+}
+
+impl Schema {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let string = std::fs::read_to_string(path)
+            .with_context(|| anyhow!("reading schema file {path:?}"))?;
+        Self::from_str(&string).with_context(|| anyhow!("parsing schema file {path:?}"))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(string: &str) -> Result<Self> {
+        let document = Document::parse(string)?;
+        let root = document.root_element();
+        if root.tag_name().namespace() == Some(XSD_NAMESPACE) {
+            bail!("XML Schema (XSD) files are not supported, only RELAX NG (XML syntax)")
+        }
+        let mut parser = SchemaParser::default();
+        let start = if is_rng(root, "grammar") {
+            parser.parse_grammar(root)?
+        } else {
+            parser.parse_pattern(root, "")?
+        };
+        let SchemaParser { names, defines } = parser;
+        let defines = defines
+            .into_iter()
+            .enumerate()
+            .map(|(i, define)| {
+                define.ok_or_else(|| {
+                    anyhow!(
+                        "reference to undefined pattern {:?}",
+                        define_name(&names, i)
+                    )
+                })
+            })
+            .collect::<Result<Vec<P>>>()?;
+        check_ref_recursion(&defines, &names)?;
+        Ok(Schema { start, defines })
+    }
+
+    /// Check `document` against the schema, returning the first
+    /// problem found.
+    pub fn validate(&self, document: &Document) -> Result<(), SchemaError> {
+        let validator = Validator {
+            schema: self,
+            interned: Default::default(),
+            caches: Default::default(),
+        };
+        let root = document.root_element();
+        let p = validator.element_deriv(self.start.clone(), root)?;
+        if validator.nullable(&p) {
+            Ok(())
+        } else {
+            Err(error_at(root, "the document is incomplete".into()))
+        }
+    }
+}
+
+/// A cache for a function of a pattern and `K`, keyed by the address
+/// of the pattern. The pattern is kept in the cache so that its
+/// address can't be reused while the cache exists.
+type Cache<K, V> = RefCell<HashMap<(*const Pattern, K), (P, V)>>;
+
+#[derive(Default)]
+struct Caches {
+    nullable: Cache<(), bool>,
+    text_deriv: Cache<String, P>,
+    start_tag_open_deriv: Cache<(String, String), P>,
+    att_deriv: Cache<(String, String, String), P>,
+    start_tag_close_deriv: Cache<(), P>,
+    end_tag_deriv: Cache<(), P>,
+}
+
+fn memoized<K: Eq + Hash, V: Clone>(
+    cache: &Cache<K, V>,
+    p: &P,
+    key: K,
+    compute: impl FnOnce() -> V,
+) -> V {
+    let key = (Rc::as_ptr(p), key);
+    let cached = cache.borrow().get(&key).map(|(_, v)| v.clone());
+    if let Some(v) = cached {
+        return v;
+    }
+    let v = compute();
+    cache.borrow_mut().insert(key, (p.clone(), v.clone()));
+    v
+}
+
+/// The children (by address) of a pattern created during validation,
+/// for `Validator::intern`. Choice and interleave are commutative,
+/// thus their children are ordered.
+#[derive(PartialEq, Eq, Hash)]
+enum InternKey {
+    Choice((*const Pattern, *const Pattern)),
+    Interleave((*const Pattern, *const Pattern)),
+    Group(*const Pattern, *const Pattern),
+    After(*const Pattern, *const Pattern),
+    OneOrMore(*const Pattern),
+}
+
+/// The state for validating one document.
+struct Validator<'s> {
+    schema: &'s Schema,
+    /// The patterns created during validation, so that equal ones
+    /// are the same (pointer-equal), which lets `choice` drop
+    /// duplicates and makes the `caches` effective.
+    interned: RefCell<HashMap<InternKey, P>>,
+    caches: Caches,
+}
+
+impl<'s> Validator<'s> {
+    fn deref(&self, i: usize) -> &'s P {
+        &self.schema.defines[i]
+    }
+
+    /// Returns the pattern equal to `p` that was seen before, if
+    /// any, or `p` (with its children already being interned, this
+    /// is just comparing the addresses of the children).
+    fn intern(&self, p: P) -> P {
+        let ptr = Rc::as_ptr;
+        let unordered = |a: &P, b: &P| {
+            let (a, b) = (ptr(a), ptr(b));
+            if a <= b {
+                (a, b)
+            } else {
+                (b, a)
+            }
+        };
+        let key = match &*p {
+            Pattern::Choice(a, b) => InternKey::Choice(unordered(a, b)),
+            Pattern::Interleave(a, b) => InternKey::Interleave(unordered(a, b)),
+            Pattern::Group(a, b) => InternKey::Group(ptr(a), ptr(b)),
+            Pattern::After(a, b) => InternKey::After(ptr(a), ptr(b)),
+            Pattern::OneOrMore(a) => InternKey::OneOrMore(ptr(a)),
+            _ => return p,
+        };
+        self.interned.borrow_mut().entry(key).or_insert(p).clone()
+    }
+
+    fn choice(&self, a: P, b: P) -> P {
+        self.intern(choice(a, b))
+    }
+
+    fn group(&self, a: P, b: P) -> P {
+        self.intern(group(a, b))
+    }
+
+    fn interleave(&self, a: P, b: P) -> P {
+        self.intern(interleave(a, b))
+    }
+
+    fn after(&self, a: P, b: P) -> P {
+        self.intern(after(a, b))
+    }
+
+    fn one_or_more(&self, a: P) -> P {
+        self.intern(one_or_more(a))
+    }
+
+    fn nullable(&self, p: &P) -> bool {
+        memoized(&self.caches.nullable, p, (), || match &**p {
+            Pattern::Empty | Pattern::Text => true,
+            Pattern::Group(a, b) | Pattern::Interleave(a, b) => {
+                self.nullable(a) && self.nullable(b)
+            }
+            Pattern::Choice(a, b) => self.nullable(a) || self.nullable(b),
+            Pattern::OneOrMore(a) => self.nullable(a),
+            Pattern::Ref(i) => self.nullable(self.deref(*i)),
+            Pattern::NotAllowed
+            | Pattern::Value(_)
+            | Pattern::Attribute(_, _)
+            | Pattern::Element(_, _)
+            | Pattern::After(_, _) => false,
+        })
+    }
+
+    fn text_deriv(&self, p: &P, s: &str) -> P {
+        memoized(&self.caches.text_deriv, p, s.into(), || match &**p {
+            Pattern::Choice(a, b) => self.choice(self.text_deriv(a, s), self.text_deriv(b, s)),
+            Pattern::Interleave(a, b) => self.choice(
+                self.interleave(self.text_deriv(a, s), b.clone()),
+                self.interleave(a.clone(), self.text_deriv(b, s)),
+            ),
+            Pattern::Group(a, b) => {
+                let p1 = self.group(self.text_deriv(a, s), b.clone());
+                if self.nullable(a) {
+                    self.choice(p1, self.text_deriv(b, s))
+                } else {
+                    p1
+                }
+            }
+            Pattern::After(a, b) => self.after(self.text_deriv(a, s), b.clone()),
+            Pattern::OneOrMore(a) => {
+                self.group(self.text_deriv(a, s), self.choice(p.clone(), empty()))
+            }
+            Pattern::Text => p.clone(),
+            Pattern::Value(value) => {
+                if normalize_whitespace(value) == normalize_whitespace(s) {
+                    empty()
+                } else {
+                    not_allowed()
+                }
+            }
+            Pattern::Ref(i) => self.text_deriv(self.deref(*i), s),
+            _ => not_allowed(),
+        })
+    }
+
+    fn value_matches(&self, p: &P, s: &str) -> bool {
+        (self.nullable(p) && is_whitespace(s)) || self.nullable(&self.text_deriv(p, s))
+    }
+
+    fn apply_after(&self, f: &dyn Fn(P) -> P, p: &P) -> P {
+        match &**p {
+            Pattern::After(a, b) => self.after(a.clone(), f(b.clone())),
+            Pattern::Choice(a, b) => self.choice(self.apply_after(f, a), self.apply_after(f, b)),
+            _ => not_allowed(),
+        }
+    }
+
+    fn start_tag_open_deriv(&self, p: &P, ns: &str, local: &str) -> P {
+        memoized(
+            &self.caches.start_tag_open_deriv,
+            p,
+            (ns.into(), local.into()),
+            || match &**p {
+                Pattern::Choice(a, b) => self.choice(
+                    self.start_tag_open_deriv(a, ns, local),
+                    self.start_tag_open_deriv(b, ns, local),
+                ),
+                Pattern::Element(name_class, content) => {
+                    if name_class.contains(ns, local) {
+                        self.after(content.clone(), empty())
+                    } else {
+                        not_allowed()
+                    }
+                }
+                Pattern::Interleave(a, b) => self.choice(
+                    self.apply_after(
+                        &|x| self.interleave(x, b.clone()),
+                        &self.start_tag_open_deriv(a, ns, local),
+                    ),
+                    self.apply_after(
+                        &|x| self.interleave(a.clone(), x),
+                        &self.start_tag_open_deriv(b, ns, local),
+                    ),
+                ),
+                Pattern::OneOrMore(a) => self.apply_after(
+                    &|x| self.group(x, self.choice(p.clone(), empty())),
+                    &self.start_tag_open_deriv(a, ns, local),
+                ),
+                Pattern::Group(a, b) => {
+                    let x = self.apply_after(
+                        &|x| self.group(x, b.clone()),
+                        &self.start_tag_open_deriv(a, ns, local),
+                    );
+                    if self.nullable(a) {
+                        self.choice(x, self.start_tag_open_deriv(b, ns, local))
+                    } else {
+                        x
+                    }
+                }
+                Pattern::After(a, b) => self.apply_after(
+                    &|x| self.after(x, b.clone()),
+                    &self.start_tag_open_deriv(a, ns, local),
+                ),
+                Pattern::Ref(i) => self.start_tag_open_deriv(self.deref(*i), ns, local),
+                _ => not_allowed(),
+            },
+        )
+    }
+
+    fn att_deriv(&self, p: &P, ns: &str, local: &str, value: &str) -> P {
+        memoized(
+            &self.caches.att_deriv,
+            p,
+            (ns.into(), local.into(), value.into()),
+            || match &**p {
+                Pattern::After(a, b) => self.after(self.att_deriv(a, ns, local, value), b.clone()),
+                Pattern::Choice(a, b) => self.choice(
+                    self.att_deriv(a, ns, local, value),
+                    self.att_deriv(b, ns, local, value),
+                ),
+                Pattern::Group(a, b) => self.choice(
+                    self.group(self.att_deriv(a, ns, local, value), b.clone()),
+                    self.group(a.clone(), self.att_deriv(b, ns, local, value)),
+                ),
+                Pattern::Interleave(a, b) => self.choice(
+                    self.interleave(self.att_deriv(a, ns, local, value), b.clone()),
+                    self.interleave(a.clone(), self.att_deriv(b, ns, local, value)),
+                ),
+                Pattern::OneOrMore(a) => self.group(
+                    self.att_deriv(a, ns, local, value),
+                    self.choice(p.clone(), empty()),
+                ),
+                Pattern::Attribute(name_class, content) => {
+                    if name_class.contains(ns, local) && self.value_matches(content, value) {
+                        empty()
+                    } else {
+                        not_allowed()
+                    }
+                }
+                Pattern::Ref(i) => self.att_deriv(self.deref(*i), ns, local, value),
+                _ => not_allowed(),
+            },
+        )
+    }
+
+    fn start_tag_close_deriv(&self, p: &P) -> P {
+        memoized(&self.caches.start_tag_close_deriv, p, (), || match &**p {
+            Pattern::After(a, b) => self.after(self.start_tag_close_deriv(a), b.clone()),
+            Pattern::Choice(a, b) => {
+                self.choice(self.start_tag_close_deriv(a), self.start_tag_close_deriv(b))
+            }
+            Pattern::Group(a, b) => {
+                self.group(self.start_tag_close_deriv(a), self.start_tag_close_deriv(b))
+            }
+            Pattern::Interleave(a, b) => {
+                self.interleave(self.start_tag_close_deriv(a), self.start_tag_close_deriv(b))
+            }
+            Pattern::OneOrMore(a) => self.one_or_more(self.start_tag_close_deriv(a)),
+            Pattern::Attribute(_, _) => not_allowed(),
+            Pattern::Ref(i) => self.start_tag_close_deriv(self.deref(*i)),
+            _ => p.clone(),
+        })
+    }
+
+    fn end_tag_deriv(&self, p: &P) -> P {
+        memoized(&self.caches.end_tag_deriv, p, (), || match &**p {
+            Pattern::Choice(a, b) => self.choice(self.end_tag_deriv(a), self.end_tag_deriv(b)),
+            Pattern::After(a, b) => {
+                if self.nullable(a) {
+                    b.clone()
+                } else {
+                    not_allowed()
+                }
+            }
+            _ => not_allowed(),
+        })
+    }
+
+    fn element_deriv(&self, p: P, node: Node) -> Result<P, SchemaError> {
+        let name = node.tag_name();
+        let ns = name.namespace().unwrap_or("");
+        let local = name.name();
+
+        let mut p = self.start_tag_open_deriv(&p, ns, local);
+        if is_not_allowed(&p) {
+            return Err(error_at(
+                node,
+                format!("element <{local}> is not allowed here"),
+            ));
+        }
+        for attribute in node.attributes() {
+            p = self.att_deriv(
+                &p,
+                attribute.namespace().unwrap_or(""),
+                attribute.name(),
+                attribute.value(),
+            );
+            if is_not_allowed(&p) {
+                return Err(error_at(
+                    node,
+                    format!(
+                        "attribute {:?} of element <{local}> is not allowed or has an \
+                         invalid value",
+                        attribute.name()
+                    ),
+                ));
+            }
+        }
+        p = self.start_tag_close_deriv(&p);
+        if is_not_allowed(&p) {
+            return Err(error_at(
+                node,
+                format!("element <{local}> is missing a required attribute"),
+            ));
+        }
+
+        let children: Vec<Node> = node
+            .children()
+            .filter(|child| matches!(child.node_type(), NodeType::Element | NodeType::Text))
+            .collect();
+        match &children[..] {
+            [] => {
+                p = self.choice(p.clone(), self.text_deriv(&p, ""));
+            }
+            [child] if child.is_text() => {
+                let text = child.text().unwrap_or("");
+                let p1 = self.text_deriv(&p, text);
+                p = if is_whitespace(text) {
+                    self.choice(p, p1)
+                } else {
+                    p1
+                };
+                if is_not_allowed(&p) {
+                    return Err(error_at(
+                        node,
+                        format!("invalid text content in element <{local}>"),
+                    ));
+                }
+            }
+            _ => {
+                for child in children {
+                    if child.is_element() {
+                        p = self.element_deriv(p, child)?;
+                    } else {
+                        let text = child.text().unwrap_or("");
+                        if is_whitespace(text) {
+                            continue;
+                        }
+                        p = self.text_deriv(&p, text);
+                        if is_not_allowed(&p) {
+                            return Err(error_at(
+                                child,
+                                format!("text is not allowed here in element <{local}>"),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        p = self.end_tag_deriv(&p);
+        if is_not_allowed(&p) {
+            return Err(error_at(
+                node,
+                format!("element <{local}> is incomplete, required content is missing"),
+            ));
+        }
+        Ok(p)
+    }
+}
+
+fn error_at(node: Node, message: String) -> SchemaError {
+    let pos = node.document().text_pos_at(node.range().start);
+    SchemaError {
+        message,
+        location: LineAndColumn {
+            line: pos.row as usize,
+            column: pos.col as usize,
+        },
+    }
+}
+
+fn is_rng(node: Node, name: &str) -> bool {
+    node.is_element()
+        && node.tag_name().namespace() == Some(RELAXNG_NAMESPACE)
+        && node.tag_name().name() == name
+}
+
+/// The child elements in the RELAX NG namespace (others are
+/// annotations).
+fn rng_children<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(|child| {
+        child.is_element() && child.tag_name().namespace() == Some(RELAXNG_NAMESPACE)
+    })
+}
+
+#[derive(Default)]
+struct SchemaParser {
+    names: HashMap<String, usize>,
+    defines: Vec<Option<P>>,
+}
+
+impl SchemaParser {
+    fn define_index(&mut self, name: &str) -> usize {
+        if let Some(i) = self.names.get(name) {
+            *i
+        } else {
+            let i = self.defines.len();
+            self.defines.push(None);
+            self.names.insert(name.into(), i);
+            i
+        }
+    }
+
+    /// Returns the start pattern.
+    fn parse_grammar(&mut self, grammar: Node) -> Result<P> {
+        let ns = grammar.attribute("ns").unwrap_or("");
+        let mut start: Option<P> = None;
+        self.parse_grammar_content(grammar, ns, &mut start)?;
+        start.ok_or_else(|| anyhow!("grammar has no <start> element"))
+    }
+
+    fn parse_grammar_content(&mut self, node: Node, ns: &str, start: &mut Option<P>) -> Result<()> {
+        for child in rng_children(node) {
+            let ns = child.attribute("ns").unwrap_or(ns);
+            let combine = child.attribute("combine");
+            match child.tag_name().name() {
+                "start" => {
+                    let p = self.parse_group(child, ns)?;
+                    *start = Some(combine_patterns(start.take(), p, combine));
+                }
+                "define" => {
+                    let name = child
+                        .attribute("name")
+                        .ok_or_else(|| anyhow!("<define> without name attribute"))?;
+                    let p = self.parse_group(child, ns)?;
+                    let i = self.define_index(name);
+                    self.defines[i] = Some(combine_patterns(self.defines[i].take(), p, combine));
+                }
+                "div" => self.parse_grammar_content(child, ns, start)?,
+                other => bail!("unsupported RELAX NG construct <{other}> in grammar"),
+            }
+        }
+        Ok(())
+    }
+
+    /// The child patterns of `node`, as a group.
+    fn parse_group(&mut self, node: Node, ns: &str) -> Result<P> {
+        self.parse_children(rng_children(node), ns, group)
+    }
+
+    fn parse_children<'a, 'input: 'a>(
+        &mut self,
+        children: impl Iterator<Item = Node<'a, 'input>>,
+        ns: &str,
+        combine: fn(P, P) -> P,
+    ) -> Result<P> {
+        let mut result: Option<P> = None;
+        for child in children {
+            let p = self.parse_pattern(child, ns)?;
+            result = Some(match result {
+                Some(result) => combine(result, p),
+                None => p,
+            });
+        }
+        result.ok_or_else(|| anyhow!("missing pattern"))
+    }
+
+    fn parse_pattern(&mut self, node: Node, ns: &str) -> Result<P> {
+        if node.tag_name().namespace() != Some(RELAXNG_NAMESPACE) {
+            bail!(
+                "expecting an element in the RELAX NG namespace, got <{}>",
+                node.tag_name().name()
+            )
+        }
+        let ns = node.attribute("ns").unwrap_or(ns);
+        let p = match node.tag_name().name() {
+            "element" => {
+                if let Some(name) = node.attribute("name") {
+                    let name_class = parse_qname(node, name, ns)?;
+                    let content = self.parse_group(node, ns)?;
+                    Pattern::Element(name_class.into(), content)
+                } else {
+                    let mut children = rng_children(node);
+                    let first = children
+                        .next()
+                        .ok_or_else(|| anyhow!("<element> without name"))?;
+                    let name_class = parse_name_class(first, ns)?;
+                    let content = self.parse_children(children, ns, group)?;
+                    Pattern::Element(name_class.into(), content)
+                }
+            }
+            "attribute" => {
+                // The `ns` attribute is not inherited by attributes
+                let attribute_ns = node.attribute("ns").unwrap_or("");
+                let (name_class, mut children): (_, Box<dyn Iterator<Item = Node>>) =
+                    if let Some(name) = node.attribute("name") {
+                        (
+                            parse_qname(node, name, attribute_ns)?,
+                            Box::new(rng_children(node)),
+                        )
+                    } else {
+                        let mut children = rng_children(node);
+                        let first = children
+                            .next()
+                            .ok_or_else(|| anyhow!("<attribute> without name"))?;
+                        (parse_name_class(first, attribute_ns)?, Box::new(children))
+                    };
+                let content = if let Some(child) = children.next() {
+                    self.parse_pattern(child, ns)?
+                } else {
+                    Rc::new(Pattern::Text)
+                };
+                Pattern::Attribute(name_class.into(), content)
+            }
+            "group" => return self.parse_group(node, ns),
+            "interleave" => return self.parse_children(rng_children(node), ns, interleave),
+            "choice" => return self.parse_children(rng_children(node), ns, choice),
+            "optional" => return Ok(choice(self.parse_group(node, ns)?, empty())),
+            "zeroOrMore" => return Ok(choice(one_or_more(self.parse_group(node, ns)?), empty())),
+            "oneOrMore" => return Ok(one_or_more(self.parse_group(node, ns)?)),
+            "mixed" => {
+                return Ok(interleave(
+                    self.parse_group(node, ns)?,
+                    Rc::new(Pattern::Text),
+                ))
+            }
+            "text" | "data" => Pattern::Text,
+            "empty" => Pattern::Empty,
+            "notAllowed" => Pattern::NotAllowed,
+            "value" => Pattern::Value(node.text().unwrap_or("").into()),
+            "ref" => {
+                let name = node
+                    .attribute("name")
+                    .ok_or_else(|| anyhow!("<ref> without name attribute"))?;
+                Pattern::Ref(self.define_index(name))
+            }
+            other => bail!("unsupported RELAX NG construct <{other}>"),
+        };
+        Ok(Rc::new(p))
+    }
+}
+
+/// The name of the define with index `i`.
+fn define_name(names: &HashMap<String, usize>, i: usize) -> &str {
+    names
+        .iter()
+        .find(|(_, j)| **j == i)
+        .map(|(name, _)| name.as_str())
+        .unwrap_or_default()
+}
+
+/// Check that no define refers to itself other than from within an
+/// `element` (which RELAX NG requires), as the derivatives would
+/// recurse endlessly otherwise.
+fn check_ref_recursion(defines: &[P], names: &HashMap<String, usize>) -> Result<()> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        InProgress,
+        Done,
+    }
+
+    fn walk(
+        p: &P,
+        defines: &[P],
+        names: &HashMap<String, usize>,
+        states: &mut [State],
+    ) -> Result<()> {
+        match &**p {
+            Pattern::Empty
+            | Pattern::NotAllowed
+            | Pattern::Text
+            | Pattern::Value(_)
+            | Pattern::Element(_, _) => Ok(()),
+            Pattern::Choice(a, b)
+            | Pattern::Interleave(a, b)
+            | Pattern::Group(a, b)
+            | Pattern::After(a, b) => {
+                walk(a, defines, names, states)?;
+                walk(b, defines, names, states)
+            }
+            Pattern::OneOrMore(a) | Pattern::Attribute(_, a) => walk(a, defines, names, states),
+            Pattern::Ref(i) => match states[*i] {
+                State::Done => Ok(()),
+                State::InProgress => bail!(
+                    "pattern {:?} refers to itself other than from within an <element>",
+                    define_name(names, *i)
+                ),
+                State::Unvisited => {
+                    states[*i] = State::InProgress;
+                    walk(&defines[*i], defines, names, states)?;
+                    states[*i] = State::Done;
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    let mut states = vec![State::Unvisited; defines.len()];
+    for i in 0..defines.len() {
+        walk(&Rc::new(Pattern::Ref(i)), defines, names, &mut states)?;
+    }
+    Ok(())
+}
+
+fn combine_patterns(old: Option<P>, new: P, combine: Option<&str>) -> P {
+    match old {
+        None => new,
+        Some(old) => match combine {
+            Some("interleave") => interleave(old, new),
+            _ => choice(old, new),
+        },
+    }
+}
+
+fn parse_qname(node: Node, qname: &str, ns: &str) -> Result<NameClass> {
+    let qname = qname.trim();
+    if let Some((prefix, local)) = qname.split_once(':') {
+        let ns = node
+            .lookup_namespace_uri(Some(prefix))
+            .ok_or_else(|| anyhow!("unknown namespace prefix in {qname:?}"))?;
+        Ok(NameClass::Name {
+            ns: ns.into(),
+            local: local.into(),
+        })
+    } else {
+        Ok(NameClass::Name {
+            ns: ns.into(),
+            local: qname.into(),
+        })
+    }
+}
+
+fn parse_name_class(node: Node, ns: &str) -> Result<NameClass> {
+    let ns = node.attribute("ns").unwrap_or(ns);
+    let except = |node: Node| -> Result<Option<Box<NameClass>>> {
+        rng_children(node)
+            .find(|child| is_rng(*child, "except"))
+            .map(|except| {
+                let mut name_classes =
+                    rng_children(except).map(|child| parse_name_class(child, ns));
+                let first = name_classes
+                    .next()
+                    .ok_or_else(|| anyhow!("empty <except>"))??;
+                name_classes.try_fold(first, |a, b| Ok(NameClass::Choice(a.into(), b?.into())))
+            })
+            .transpose()
+            .map(|except| except.map(Box::new))
+    };
+    match node.tag_name().name() {
+        "name" => parse_qname(node, node.text().unwrap_or(""), ns),
+        "anyName" => Ok(NameClass::AnyName {
+            except: except(node)?,
+        }),
+        "nsName" => Ok(NameClass::NsName {
+            ns: ns.into(),
+            except: except(node)?,
+        }),
+        "choice" => {
+            let mut name_classes = rng_children(node).map(|child| parse_name_class(child, ns));
+            let first = name_classes
+                .next()
+                .ok_or_else(|| anyhow!("empty <choice> name class"))??;
+            name_classes.try_fold(first, |a, b| Ok(NameClass::Choice(a.into(), b?.into())))
+        }
+        other => bail!("unsupported RELAX NG name class <{other}>"),
+    }
+}
+
+#[test]
+fn t_relaxng() -> Result<()> {
+    let schema = Schema::from_str(
+        r#"<grammar xmlns="http://relaxng.org/ns/structure/1.0">
+             <start>
+               <element name="beast">
+                 <attribute name="version"/>
+                 <zeroOrMore><ref name="data"/></zeroOrMore>
+                 <element name="run">
+                   <optional><attribute name="chainLength"/></optional>
+                   <empty/>
+                 </element>
+               </element>
+             </start>
+             <define name="data">
+               <element name="data">
+                 <attribute name="id"/>
+                 <choice>
+                   <value>nucleotide</value>
+                   <text/>
+                 </choice>
+               </element>
+             </define>
+           </grammar>"#,
+    )?;
+    let check = |xml: &str| -> Result<Result<(), SchemaError>> {
+        Ok(schema.validate(&Document::parse(xml)?))
+    };
+
+    assert_eq!(
+        check(
+            "<beast version=\"2.7\">\n\
+             <data id=\"a\">ACGT</data><data id=\"b\"/>\n\
+             <run chainLength=\"10\"/></beast>"
+        )?,
+        Ok(())
+    );
+    assert_eq!(
+        check("<beast version=\"2.7\">\n  <foo/><run/></beast>")?,
+        Err(SchemaError {
+            message: "element <foo> is not allowed here".into(),
+            location: LineAndColumn { line: 2, column: 3 }
+        })
+    );
+    assert_eq!(
+        check("<beast><run/></beast>")?.unwrap_err().message,
+        "element <beast> is missing a required attribute"
+    );
+    assert_eq!(
+        check("<beast version=\"2\"><data id=\"a\"/></beast>")?
+            .unwrap_err()
+            .message,
+        "element <beast> is incomplete, required content is missing"
+    );
+    assert_eq!(
+        check("<beast version=\"2\"><run length=\"1\"/></beast>")?
+            .unwrap_err()
+            .message,
+        "attribute \"length\" of element <run> is not allowed or has an invalid value"
+    );
+
+    assert!(
+        Schema::from_str(r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"/>"#).is_err()
+    );
+    assert!(Schema::from_str(
+        r#"<element name="a" xmlns="http://relaxng.org/ns/structure/1.0"><list><text/></list></element>"#
+    )
+    .is_err());
+    Ok(())
+}
+
+#[test]
+fn t_relaxng_ambiguous_and_recursive() -> Result<()> {
+    // Without interning, the alternatives double with every <x/>
+    let schema = Schema::from_str(
+        r#"<element name="a" xmlns="http://relaxng.org/ns/structure/1.0">
+             <oneOrMore>
+               <choice>
+                 <element name="x"><empty/></element>
+                 <element name="x"><empty/></element>
+               </choice>
+             </oneOrMore>
+           </element>"#,
+    )?;
+    let xml = format!("<a>{}</a>", "<x/>".repeat(200));
+    assert_eq!(schema.validate(&Document::parse(&xml)?), Ok(()));
+
+    // Recursion through an element is fine
+    let schema = Schema::from_str(
+        r#"<grammar xmlns="http://relaxng.org/ns/structure/1.0">
+             <start><ref name="node"/></start>
+             <define name="node">
+               <element name="node"><zeroOrMore><ref name="node"/></zeroOrMore></element>
+             </define>
+           </grammar>"#,
+    )?;
+    let xml = "<node><node><node/></node><node/></node>";
+    assert_eq!(schema.validate(&Document::parse(xml)?), Ok(()));
+
+    // Other recursion is refused instead of recursing endlessly
+    for define in [
+        r#"<define name="a"><ref name="a"/></define>"#,
+        r#"<define name="a"><choice><empty/><ref name="b"/></choice></define>
+           <define name="b"><group><text/><ref name="a"/></group></define>"#,
+    ] {
+        let err = Schema::from_str(&format!(
+            r#"<grammar xmlns="http://relaxng.org/ns/structure/1.0">
+                 <start><element name="x"><ref name="a"/></element></start>
+                 {define}
+               </grammar>"#
+        ))
+        .expect_err("recursive");
+        assert!(
+            err.to_string()
+                .contains("refers to itself other than from within"),
+            "{err}"
+        );
+    }
+    Ok(())
+}
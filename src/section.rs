@@ -5,8 +5,10 @@
 //! * that a table of contents can be built from (showing and linking the
 //!   (possibly nested) subsections).
 
-use ahtml::{att, AId, ASlice, HtmlAllocator, Node, Print, SerHtmlFrag};
-use anyhow::Result;
+use std::io::Write;
+
+use ahtml::{att, AId, ASlice, AVec, HtmlAllocator, Node, Print, SerHtmlFrag};
+use anyhow::{bail, Result};
 use kstring::KString;
 use rayon::{
     iter::ParallelIterator,
@@ -14,28 +16,69 @@ use rayon::{
 };
 
 use crate::{
-    html_util::anchor, string_tree::StringTree, xmlhub_indexer_defaults::HTML_ALLOCATOR_POOL,
+    html_util::{anchor, split_at_placeholder, STREAMING_PLACEHOLDER},
+    string_tree::StringTree,
+    xmlhub_indexer_defaults::{HIGHLIGHT_ORANGE_COLOR, HIGHLIGHT_RED_COLOR, HTML_ALLOCATOR_POOL},
 };
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum Highlight {
     None,
+    /// For errors
     Red,
+    /// For warnings
     Orange,
 }
 
+/// The colours used for the highlighted sections, as CSS colour
+/// values; part of the repository configuration (see
+/// `XmlhubConfig::highlight_colors`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighlightColors {
+    pub red: String,
+    pub orange: String,
+}
+
+impl Default for HighlightColors {
+    fn default() -> Self {
+        Self {
+            red: HIGHLIGHT_RED_COLOR.into(),
+            orange: HIGHLIGHT_ORANGE_COLOR.into(),
+        }
+    }
+}
+
+impl HighlightColors {
+    /// Change the colour for the highlight `name` (`red` or
+    /// `orange`) to `color`, e.g. `#c00000`.
+    pub fn set(&mut self, name: &str, color: &str) -> Result<()> {
+        let color = color.trim();
+        // The colour ends up in a `style` attribute
+        if color.is_empty() || color.contains([';', '"', '<', '>']) {
+            bail!("invalid colour {color:?}")
+        }
+        let field = match name.trim().to_lowercase().as_str() {
+            "red" => &mut self.red,
+            "orange" => &mut self.orange,
+            _ => bail!("unknown highlight {name:?}, expecting red or orange"),
+        };
+        *field = color.into();
+        Ok(())
+    }
+}
+
 impl Highlight {
-    pub fn color_string(self) -> Option<&'static str> {
+    pub fn color_string(self, colors: &HighlightColors) -> Option<&str> {
         match self {
             Highlight::None => None,
-            Highlight::Red => Some("red"),
-            Highlight::Orange => Some("orange"),
+            Highlight::Red => Some(&colors.red),
+            Highlight::Orange => Some(&colors.orange),
         }
     }
 
     /// Give attribute key-value pair for html elements
-    pub fn color_att(self) -> Option<(KString, KString)> {
-        self.color_string()
+    pub fn color_att(self, colors: &HighlightColors) -> Option<(KString, KString)> {
+        self.color_string(colors)
             .and_then(|color| att("style", format!("color: {color};")))
     }
 }
@@ -50,6 +93,9 @@ pub struct Section {
     pub highlight: Highlight,
     pub title: Option<String>,
     pub intro: Option<SerHtmlFrag>,
+    /// Used instead of `intro` in the Markdown file, if given (for
+    /// content that is shown differently there)
+    pub markdown_intro: Option<SerHtmlFrag>,
     pub subsections: Vec<Section>,
 }
 
@@ -88,14 +134,19 @@ impl NumberPath {
 
 impl Section {
     /// Build a table of contents
-    pub fn to_toc_html(&self, number_path: NumberPath, html: &HtmlAllocator) -> Result<AId<Node>> {
+    pub fn to_toc_html(
+        &self,
+        number_path: NumberPath,
+        colors: &HighlightColors,
+        html: &HtmlAllocator,
+    ) -> Result<AId<Node>> {
         let title_node = if let Some(title) = &self.title {
             let number_path_string = number_path.to_string();
             let section_id = format!("section-{number_path_string}");
             html.a(
                 [
                     att("class", "toc_entry"),
-                    self.highlight.color_att(),
+                    self.highlight.color_att(colors),
                     att("href", format!("#{section_id}")),
                 ],
                 html.text(format!("{number_path_string} {title}"))?,
@@ -107,14 +158,73 @@ impl Section {
         for (i, section) in self.subsections.iter().enumerate() {
             let id = i + 1;
             let sub_path = number_path.add(id);
-            sub_nodes.push(section.to_toc_html(sub_path, html)?)?;
+            sub_nodes.push(section.to_toc_html(sub_path, colors, html)?)?;
         }
         html.dl([], [html.dt([], title_node)?, html.dd([], sub_nodes)?])
     }
 
-    /// Format the section for the inclusion in an HTML file
-    pub fn to_html(&self, number_path: NumberPath, html: &HtmlAllocator) -> Result<ASlice<Node>> {
-        let mut vec = html.new_vec();
+    /// For each subsection of this (toplevel) section that has
+    /// subsections itself, put a line with links to those (a "mini
+    /// table of contents") in front of its intro. Must be called
+    /// after the subsections are complete, as the links use the
+    /// section numbers.
+    pub fn insert_mini_tocs(&mut self, colors: &HighlightColors) -> Result<()> {
+        let number_path = NumberPath::empty();
+        for (i, section) in self.subsections.iter_mut().enumerate() {
+            let id = i + 1;
+            let sub_path = number_path.add(id);
+            if section.subsections.is_empty() {
+                continue;
+            }
+            let html = HTML_ALLOCATOR_POOL.get();
+            let mini_toc = |html: &HtmlAllocator| -> Result<AId<Node>> {
+                let mut links = html.new_vec();
+                let mut is_first = true;
+                for (j, subsection) in section.subsections.iter().enumerate() {
+                    let Some(title) = &subsection.title else {
+                        continue;
+                    };
+                    let number_path_string = sub_path.add(j + 1).to_string();
+                    if !is_first {
+                        links.push(html.text(" \u{b7} ")?)?;
+                    }
+                    is_first = false;
+                    links.push(html.a(
+                        [
+                            att("class", "toc_entry"),
+                            subsection.highlight.color_att(colors),
+                            att("href", format!("#section-{number_path_string}")),
+                        ],
+                        html.text(format!("{number_path_string} {title}"))?,
+                    )?)?;
+                }
+                html.div([att("class", "mini_toc")], links)
+            };
+            let prepend = |intro: &Option<SerHtmlFrag>| -> Result<SerHtmlFrag> {
+                let mini_toc = mini_toc(&html)?;
+                html.preserialize(if let Some(intro) = intro {
+                    html.div([], [mini_toc, html.preserialized(intro.clone())?])?
+                } else {
+                    mini_toc
+                })
+            };
+            if section.markdown_intro.is_some() {
+                section.markdown_intro = Some(prepend(&section.markdown_intro)?);
+            }
+            section.intro = Some(prepend(&section.intro)?);
+        }
+        Ok(())
+    }
+
+    /// Push the title (if any) and intro (if any) of the section to
+    /// `vec`, for the inclusion in an HTML file.
+    fn push_title_and_intro_html(
+        &self,
+        number_path: &NumberPath,
+        colors: &HighlightColors,
+        vec: &mut AVec<Node>,
+        html: &HtmlAllocator,
+    ) -> Result<()> {
         if let Some(title) = &self.title {
             // Choose the html method for the current nesting level by
             // indexing into the list of them, referring to the
@@ -146,7 +256,7 @@ impl Section {
             vec.push(anchor(&section_id, [], html)?)?;
             vec.push(element(
                 html,
-                [att("id", section_id), self.highlight.color_att()],
+                [att("id", section_id), self.highlight.color_att(colors)],
                 // Prefix the path to the title; don't try to use CSS
                 // as it won't make it through Markdown.
                 html.text(format!("{number_path_string} {title}"))?,
@@ -156,16 +266,67 @@ impl Section {
         if let Some(fragment) = &self.intro {
             vec.push(html.preserialized(fragment.clone())?)?;
         }
+        Ok(())
+    }
+
+    /// Format the section for the inclusion in an HTML file
+    pub fn to_html(
+        &self,
+        number_path: NumberPath,
+        colors: &HighlightColors,
+        html: &HtmlAllocator,
+    ) -> Result<ASlice<Node>> {
+        let mut vec = html.new_vec();
+        self.push_title_and_intro_html(&number_path, colors, &mut vec, html)?;
 
         for (i, section) in self.subsections.iter().enumerate() {
             let id = i + 1;
             let sub_path = number_path.add(id);
-            vec.push(html.div([], section.to_html(sub_path, html)?)?)?;
+            vec.push(html.div([], section.to_html(sub_path, colors, html)?)?)?;
         }
 
         Ok(vec.as_slice())
     }
 
+    /// Print the same HTML as `to_html` gives, but without building
+    /// the whole tree in one `HtmlAllocator`: each (sub)section is
+    /// formatted with a separate allocator and written out right
+    /// away, which keeps the peak memory use low for large documents.
+    pub fn print_html_streaming(
+        &self,
+        number_path: NumberPath,
+        colors: &HighlightColors,
+        out: &mut impl Write,
+    ) -> Result<()> {
+        {
+            let html = HTML_ALLOCATOR_POOL.get();
+            let mut vec = html.new_vec();
+            self.push_title_and_intro_html(&number_path, colors, &mut vec, &html)?;
+            vec.as_slice().print_html_fragment(out, &html)?;
+        }
+
+        if !self.subsections.is_empty() {
+            let mut wrapper = Vec::new();
+            {
+                let html = HTML_ALLOCATOR_POOL.get();
+                html.print_html_fragment(
+                    html.div([], html.text(STREAMING_PLACEHOLDER)?)?,
+                    &mut wrapper,
+                )?;
+            }
+            let (wrapper_start, wrapper_end) = split_at_placeholder(&wrapper)?;
+
+            for (i, section) in self.subsections.iter().enumerate() {
+                let id = i + 1;
+                let sub_path = number_path.add(id);
+                out.write_all(wrapper_start)?;
+                section.print_html_streaming(sub_path, colors, out)?;
+                out.write_all(wrapper_end)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Format the section for the inclusion in a markdown file
     pub fn to_markdown(&self, number_path: NumberPath) -> Result<StringTree<'_>> {
         let mut title_and_intro = String::new();
@@ -192,7 +353,7 @@ impl Section {
             title_and_intro.push_str("\n\n");
         }
 
-        if let Some(fragment) = &self.intro {
+        if let Some(fragment) = self.markdown_intro.as_ref().or(self.intro.as_ref()) {
             title_and_intro.push_str(fragment.as_str());
             title_and_intro.push_str("\n\n");
         }
@@ -214,3 +375,86 @@ impl Section {
         ]))
     }
 }
+
+#[test]
+fn t_highlight_colors() -> Result<()> {
+    let section = Section {
+        highlight: Highlight::None,
+        title: None,
+        intro: None,
+        markdown_intro: None,
+        subsections: vec![
+            Section {
+                highlight: Highlight::Red,
+                title: Some("Errors".into()),
+                intro: None,
+                markdown_intro: None,
+                subsections: vec![],
+            },
+            Section {
+                highlight: Highlight::Orange,
+                title: Some("Warnings".into()),
+                intro: None,
+                markdown_intro: None,
+                subsections: vec![],
+            },
+        ],
+    };
+    let to_html = |colors: &HighlightColors| -> Result<String> {
+        let html = HTML_ALLOCATOR_POOL.get();
+        let mut out = Vec::new();
+        section
+            .to_html(NumberPath::empty(), colors, &html)?
+            .print_html_fragment(&mut out, &html)?;
+        Ok(String::from_utf8(out)?)
+    };
+
+    let default = to_html(&HighlightColors::default())?;
+    assert!(default.contains("style=\"color: red;\">1 Errors<"));
+    assert!(default.contains("style=\"color: orange;\">2 Warnings<"));
+
+    let mut colors = HighlightColors::default();
+    colors.set("red", "#c00000")?;
+    colors.set(" Orange ", " #e65100 ")?;
+    let configured = to_html(&colors)?;
+    assert!(configured.contains("style=\"color: #c00000;\">1 Errors<"));
+    assert!(configured.contains("style=\"color: #e65100;\">2 Warnings<"));
+    assert!(!configured.contains("color: red;"));
+
+    assert!(colors.set("green", "#2e7d32").is_err());
+    assert!(colors.set("red", "").is_err());
+    assert!(colors.set("red", "x; background: y").is_err());
+    Ok(())
+}
+
+#[test]
+fn t_insert_mini_tocs() -> Result<()> {
+    let section = |title: &str, subsections| Section {
+        highlight: Highlight::None,
+        title: Some(title.into()),
+        intro: None,
+        markdown_intro: None,
+        subsections,
+    };
+    let mut toplevel = Section {
+        highlight: Highlight::None,
+        title: None,
+        intro: None,
+        markdown_intro: None,
+        subsections: vec![
+            section("Leaf", vec![]),
+            section("Parent", vec![section("A", vec![]), section("B", vec![])]),
+        ],
+    };
+    toplevel.insert_mini_tocs(&HighlightColors::default())?;
+    assert!(toplevel.subsections[0].intro.is_none());
+    let intro = toplevel.subsections[1]
+        .intro
+        .as_ref()
+        .expect("mini toc added")
+        .as_str();
+    assert!(intro.contains("href=\"#section-2.1\">2.1 A<"), "{intro}");
+    assert!(intro.contains("href=\"#section-2.2\">2.2 B<"), "{intro}");
+    assert!(!intro.contains("Parent"), "{intro}");
+    Ok(())
+}
@@ -1,15 +1,17 @@
 use anyhow::{anyhow, bail, Context, Result};
 use itertools::Itertools;
-use run_git::command::run_stdout_string;
+use sha2::{Digest, Sha256};
 use std::{
     collections::{BTreeMap, BTreeSet},
     fmt::Display,
     fs::{create_dir, File, OpenOptions},
     io::{BufRead, Write},
     io::{BufReader, BufWriter},
-    path::Path,
+    path::{Component, Path, PathBuf},
 };
 
+use crate::utillib::hex::to_hex_string;
+
 pub trait InsertValue<K, V> {
     /// Insert a value into a collection of value that `key` maps to,
     /// creating the collection and the mapping from key if it doesn't
@@ -102,14 +104,6 @@ pub fn remove_file_if_present(path: &str) -> Result<()> {
     }
 }
 
-pub fn prog_version(in_dir: &Path, prog_name: &str) -> Result<String> {
-    run_stdout_string(in_dir, prog_name, &["--version"], &[], &[0], true)
-}
-
-pub fn hostname() -> Result<String> {
-    run_stdout_string::<_, &str, &str>(".", "hostname", &[], &[], &[0], true)
-}
-
 pub fn stringify_error<T: Display, E: Display>(res: Result<T, E>) -> String {
     match res {
         Ok(v) => format!(": {v}"),
@@ -202,16 +196,58 @@ const MAX_ANCHOR_NAME_LEN: usize = 60;
 /// Format a string so that it can be safely used as an anchor name:
 /// only alphanumeric characters are preserved, anything else is
 /// replaced with underscore. Also, limits the length to
-/// MAX_ANCHOR_NAME_LEN (simply cuts off the remainder!). Note that
-/// this function does not guarantee an 1:1 mapping even if `s` is
-/// shorter.
+/// MAX_ANCHOR_NAME_LEN characters (simply cuts off the
+/// remainder!). Note that this function does not guarantee an 1:1
+/// mapping even if `s` is shorter, see `unique_anchor_names` for that.
 pub fn format_anchor_name(s: &str) -> String {
-    let s = &s[..MAX_ANCHOR_NAME_LEN.min(s.len())];
     s.chars()
+        .take(MAX_ANCHOR_NAME_LEN)
         .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
         .collect()
 }
 
+/// The number of hex digits of the SHA-256 hash appended by
+/// `unique_anchor_names` (at least).
+const ANCHOR_NAME_HASH_LEN: usize = 12;
+
+/// Anchor names for all of `strings`, as by `format_anchor_name`,
+/// but different for each string: of the strings that map to the
+/// same anchor name, the first (in sorted order) gets it unchanged,
+/// the others get an underscore and the start of the SHA-256 hash of
+/// the string appended. Thus the result only depends on the set of
+/// strings. The anchor names only contain ASCII letters, digits and
+/// underscores, thus are valid as HTML ids.
+pub fn unique_anchor_names<'s>(
+    strings: impl IntoIterator<Item = &'s str>,
+) -> BTreeMap<&'s str, String> {
+    let strings: BTreeSet<&str> = strings.into_iter().collect();
+    let mut used: BTreeSet<String> = BTreeSet::new();
+    let mut anchor_names = BTreeMap::new();
+    // First give out the unchanged names, so that a name with a hash
+    // can't take away the name of a string that doesn't need one
+    let mut clashing = Vec::new();
+    for s in strings {
+        let anchor_name = format_anchor_name(s);
+        if used.insert(anchor_name.clone()) {
+            anchor_names.insert(s, anchor_name);
+        } else {
+            clashing.push((s, anchor_name));
+        }
+    }
+    for (s, anchor_name) in clashing {
+        let hash = to_hex_string(&Sha256::digest(s.as_bytes()));
+        // (Use more of the hash in the unlikely case that the name
+        // is taken, too)
+        let anchor_name = (ANCHOR_NAME_HASH_LEN..=hash.len())
+            .map(|len| format!("{anchor_name}_{}", &hash[..len]))
+            .find(|anchor_name| !used.contains(anchor_name))
+            .expect("different strings have different hashes");
+        used.insert(anchor_name.clone());
+        anchor_names.insert(s, anchor_name);
+    }
+    anchor_names
+}
+
 #[test]
 fn t_format_anchor_name() {
     let t = format_anchor_name;
@@ -221,6 +257,57 @@ fn t_format_anchor_name() {
         t("Format a string so that it can be safely used as an anchor name"),
         "Format_a_string_so_that_it_can_be_safely_used_as_an_anchor_n"
     );
+    // Cutting off doesn't split characters
+    assert_eq!(t(&"ä".repeat(61)), "_".repeat(60));
+}
+
+#[test]
+fn t_unique_anchor_names() {
+    let t = |strings: &[&str]| -> Vec<String> {
+        let anchor_names = unique_anchor_names(strings.iter().copied());
+        strings.iter().map(|s| anchor_names[s].clone()).collect()
+    };
+    assert_eq!(t(&["foo", "Foo2", ""]), ["foo", "Foo2", ""]);
+    assert_eq!(t(&["birth-death"]), ["birth_death"]);
+    // Would all be "birth_death" with `format_anchor_name`; the first
+    // in sorted order keeps it
+    let anchor_names = t(&["birth-death", "birth death", "birth_death"]);
+    assert_eq!(anchor_names[1], "birth_death");
+    for anchor_name in [&anchor_names[0], &anchor_names[2]] {
+        assert!(anchor_name.starts_with("birth_death_"), "{anchor_name}");
+        assert_eq!(
+            anchor_name.len(),
+            "birth_death_".len() + ANCHOR_NAME_HASH_LEN
+        );
+    }
+    assert_ne!(anchor_names[0], anchor_names[2]);
+    // Independent of the order they are given in
+    assert_eq!(
+        t(&["birth_death", "birth-death", "birth death"]),
+        [
+            anchor_names[2].clone(),
+            anchor_names[0].clone(),
+            anchor_names[1].clone()
+        ]
+    );
+    // Strings that are only different after the cut-off point
+    let long = "a".repeat(MAX_ANCHOR_NAME_LEN);
+    let (long_b, long_c) = (format!("{long}b"), format!("{long}c"));
+    let anchor_names = unique_anchor_names([long.as_str(), &long_b, &long_c]);
+    assert_eq!(anchor_names[long.as_str()], long);
+    assert_eq!(
+        anchor_names.values().collect::<BTreeSet<_>>().len(),
+        3,
+        "{anchor_names:?}"
+    );
+    // A name with a hash doesn't take the name of another string
+    let hashed = t(&["a b", "a-b"])[1].clone();
+    let anchor_names = t(&["a b", "a-b", &hashed]);
+    assert_eq!(anchor_names[2], hashed);
+    assert_ne!(anchor_names[1], hashed);
+    assert!(t(&["ä ö"])[0]
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_'));
 }
 
 pub fn bool_to_yes_no(val: bool) -> &'static str {
@@ -231,6 +318,27 @@ pub fn bool_to_yes_no(val: bool) -> &'static str {
     }
 }
 
+/// Parse "yes"/"no", "true"/"false" or "y"/"n" (case-insensitively)
+/// into a boolean.
+pub fn parse_yes_no(val: &str) -> Option<bool> {
+    match val.trim().to_lowercase().as_str() {
+        "yes" | "true" | "y" => Some(true),
+        "no" | "false" | "n" => Some(false),
+        _ => None,
+    }
+}
+
+#[test]
+fn t_parse_yes_no() {
+    let t = parse_yes_no;
+    assert_eq!(t("yes"), Some(true));
+    assert_eq!(t("No"), Some(false));
+    assert_eq!(t(" true "), Some(true));
+    assert_eq!(t("FALSE"), Some(false));
+    assert_eq!(t("maybe"), None);
+    assert_eq!(t(""), None);
+}
+
 pub fn prefix_lines(lines: &str, prefix: &str) -> String {
     let (lines_no_ending_newline, suffix) = if let Some(s) = lines.strip_suffix("\n") {
         (s, "\n")
@@ -255,6 +363,32 @@ fn t_prefix_lines() {
     assert_eq!(t("\n\n", "  "), "  \n  \n");
 }
 
+/// The relative path leading from the directory `from_dir` to
+/// `to`. Both paths must be absolute and canonical (no symlinks or
+/// `..`); returns the empty path if they are the same.
+pub fn relative_path(from_dir: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from_dir.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    for component in &to[common..] {
+        path.push(component);
+    }
+    path
+}
+
+#[test]
+fn t_relative_path() {
+    let t = |from: &str, to: &str| relative_path(Path::new(from), Path::new(to));
+    assert_eq!(t("/a/b", "/a/b"), Path::new(""));
+    assert_eq!(t("/a/b", "/a/b/c/d"), Path::new("c/d"));
+    assert_eq!(t("/a/b/c", "/a/x"), Path::new("../../x"));
+    assert_eq!(t("/a", "/"), Path::new(".."));
+}
+
 pub fn strip_prefixes<'s>(s: &'s str, prefixes: &[&str]) -> &'s str {
     let mut s = s;
     for prefix in prefixes {
@@ -0,0 +1,121 @@
+//! Expansion of `~` and environment variables in paths given by the
+//! user, for cases where no shell did that already (e.g. when run
+//! via systemd or cron with quoted arguments).
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::utillib::home::home_dir;
+
+/// Expand a leading `~` (alone or followed by `/`) to the home
+/// directory (as given by the `HOME` environment variable), and
+/// `$NAME` or `${NAME}` anywhere in the path to the value of the
+/// environment variable `NAME`, where `NAME` consists of ASCII
+/// letters, digits and underscores and does not start with a
+/// digit. No other forms are expanded (`~user` is left as is, a `$`
+/// not followed by a valid name is kept literally). Referring to an
+/// unset variable is an error. Paths that are not valid UTF-8 are
+/// returned unchanged.
+pub fn expand_path(path: &Path) -> Result<PathBuf> {
+    expand_path_with(
+        path,
+        || home_dir().map(|p| p.to_owned()).map_err(|e| anyhow!("{e}")),
+        |name| std::env::var_os(name),
+    )
+}
+
+/// Same as `expand_path` but taking the home directory and the
+/// variable values from the given functions.
+pub fn expand_path_with(
+    path: &Path,
+    get_home: impl Fn() -> Result<PathBuf>,
+    get_var: impl Fn(&str) -> Option<OsString>,
+) -> Result<PathBuf> {
+    let Some(s) = path.to_str() else {
+        return Ok(path.to_owned());
+    };
+
+    let mut result = OsString::new();
+    let mut rest = s;
+    if rest == "~" || rest.starts_with("~/") {
+        result.push(get_home().map_err(|e| anyhow!("expanding `~` in path {path:?}: {e:#}"))?);
+        rest = &rest[1..];
+    }
+
+    let is_name_start = |c: char| c.is_ascii_alphabetic() || c == '_';
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    while let Some(pos) = rest.find('$') {
+        result.push(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            let Some(end) = braced.find('}') else {
+                bail!("missing closing `}}` in path {path:?}")
+            };
+            let name = &braced[..end];
+            if !(name.starts_with(is_name_start) && name.chars().all(is_name_char)) {
+                bail!("invalid variable name {name:?} in path {path:?}")
+            }
+            (name, &braced[end + 1..])
+        } else if after.starts_with(is_name_start) {
+            let end = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        } else {
+            // Not a variable reference, keep the `$`
+            result.push("$");
+            rest = after;
+            continue;
+        };
+        let value = get_var(name).ok_or_else(|| {
+            anyhow!("environment variable {name:?} used in path {path:?} is not set")
+        })?;
+        result.push(value);
+        rest = remainder;
+    }
+    result.push(rest);
+    Ok(result.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(path: &str) -> Result<PathBuf> {
+        expand_path_with(
+            path.as_ref(),
+            || Ok("/home/me".into()),
+            |name| match name {
+                "FOO" => Some("foo-value".into()),
+                "EMPTY" => Some("".into()),
+                _ => None,
+            },
+        )
+    }
+
+    fn ok(path: &str) -> String {
+        expand(path).unwrap().to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn t_expand_path() {
+        assert_eq!(ok("~"), "/home/me");
+        assert_eq!(ok("~/xmlhub"), "/home/me/xmlhub");
+        assert_eq!(ok("$FOO/bar"), "foo-value/bar");
+        assert_eq!(ok("/a/${FOO}bar/$EMPTY"), "/a/foo-valuebar/");
+        // Literal paths are unaffected
+        assert_eq!(ok("/some/path"), "/some/path");
+        assert_eq!(ok("relative/path.xml"), "relative/path.xml");
+        assert_eq!(ok("a/~/b"), "a/~/b");
+        assert_eq!(ok("~user/b"), "~user/b");
+        assert_eq!(ok("price$5"), "price$5");
+        assert_eq!(ok("a$"), "a$");
+        // Errors
+        assert!(expand("$UNSET/x").is_err());
+        assert!(expand("${FOO").is_err());
+        assert!(expand("${1A}").is_err());
+    }
+}
@@ -9,14 +9,95 @@ pub fn write_file_moving_to_trash_if_exists(
     content: &str,
     quiet: bool,
 ) -> Result<()> {
-    if target_path.exists() {
-        trash::delete(&target_path)
-            .with_context(|| anyhow!("moving existing target file {target_path:?} to trash"))?;
-        if !quiet {
-            println!("Moved existing target file {target_path:?} to trash.");
+    write_file_optionally_moving_to_trash(target_path, content, true, quiet)
+}
+
+/// Where `write_file_moving_to` moves existing files.
+#[derive(Debug, Clone, Copy)]
+pub enum Trash<'p> {
+    /// The trash can of the desktop environment
+    System,
+    /// A plain directory; a file with the same name that is already
+    /// in it is replaced
+    Dir(&'p Path),
+}
+
+impl Trash<'_> {
+    fn move_file(self, path: &Path) -> Result<()> {
+        match self {
+            Trash::System => Ok(trash::delete(path)?),
+            Trash::Dir(dir) => {
+                let file_name = path
+                    .file_name()
+                    .ok_or_else(|| anyhow!("path {path:?} does not have a file name"))?;
+                Ok(std::fs::rename(path, dir.join(file_name))?)
+            }
+        }
+    }
+}
+
+/// Like `write_file_moving_to_trash_if_exists` if `use_trash` is
+/// true, otherwise an existing file at `target_path` is overwritten
+/// directly (it is not backed up anywhere).
+pub fn write_file_optionally_moving_to_trash(
+    target_path: &Path,
+    content: &str,
+    use_trash: bool,
+    quiet: bool,
+) -> Result<()> {
+    write_file_moving_to(
+        target_path,
+        content,
+        use_trash.then_some(Trash::System),
+        quiet,
+    )
+}
+
+/// Write `content` to `target_path`, first moving an existing file
+/// there to `trash` if given.
+pub fn write_file_moving_to(
+    target_path: &Path,
+    content: &str,
+    trash: Option<Trash>,
+    quiet: bool,
+) -> Result<()> {
+    if let Some(trash) = trash {
+        if target_path.exists() {
+            trash
+                .move_file(target_path)
+                .with_context(|| anyhow!("moving existing target file {target_path:?} to trash"))?;
+            if !quiet {
+                println!("Moved existing target file {target_path:?} to trash.");
+            }
         }
     }
-    std::fs::write(&target_path, content)
+    std::fs::write(target_path, content)
         .with_context(|| anyhow!("writing contents to file {target_path:?}"))?;
     Ok(())
 }
+
+#[test]
+fn t_write_file_moving_to() -> Result<()> {
+    use crate::utillib::temp_dir::TempDir;
+
+    let dir = TempDir::new("xmlhub-t_write_file_moving_to")?;
+    let trash_dir = dir.join("trash");
+    std::fs::create_dir(&trash_dir)?;
+    let path = dir.join("file.txt");
+    let trashed_path = trash_dir.join("file.txt");
+
+    // Nothing to move yet
+    write_file_moving_to(&path, "one", Some(Trash::Dir(&trash_dir)), true)?;
+    assert_eq!(std::fs::read_to_string(&path)?, "one");
+    assert!(!trashed_path.exists());
+
+    write_file_moving_to(&path, "two", None, true)?;
+    assert_eq!(std::fs::read_to_string(&path)?, "two");
+    assert!(!trashed_path.exists());
+
+    write_file_moving_to(&path, "three", Some(Trash::Dir(&trash_dir)), true)?;
+    assert_eq!(std::fs::read_to_string(&path)?, "three");
+    assert_eq!(std::fs::read_to_string(&trashed_path)?, "two");
+
+    Ok(())
+}
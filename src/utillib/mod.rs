@@ -1,4 +1,6 @@
+pub mod expand_path;
 pub mod file_util_with_trash;
 pub mod hex;
 pub mod home;
 pub mod setpriority;
+pub mod temp_dir;
@@ -0,0 +1,111 @@
+//! A private temporary directory that is deleted when dropped.
+
+use std::{
+    io::ErrorKind,
+    ops::Deref,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// How many names to try before giving up.
+const MAX_ATTEMPTS: u32 = 100;
+
+/// A directory created with a unique name inside the system's
+/// temporary directory, accessible only by the current user. It is
+/// deleted with all its contents when the `TempDir` is dropped
+/// (errors during deletion are ignored).
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create the directory, with a name starting with `prefix`
+    /// followed by a unique suffix. The directory is guaranteed to be
+    /// new (an existing file or symlink of the same name is never
+    /// used) and has mode 0700.
+    pub fn new(prefix: &str) -> Result<Self> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let base = std::env::temp_dir();
+        for _ in 0..MAX_ATTEMPTS {
+            let nanos = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.subsec_nanos())
+                .unwrap_or(0);
+            let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = base.join(format!(
+                "{prefix}-{}-{count}-{nanos:08x}",
+                std::process::id()
+            ));
+            match std::fs::DirBuilder::new().mode(0o700).create(&path) {
+                Ok(()) => return Ok(Self { path }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| anyhow!("creating temporary directory {path:?}"))
+                }
+            }
+        }
+        bail!(
+            "could not create a new temporary directory in {base:?} after {MAX_ATTEMPTS} attempts"
+        )
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Do not delete the directory; returns its path.
+    pub fn keep(mut self) -> PathBuf {
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // (The path is empty after `keep`)
+        if !self.path.as_os_str().is_empty() {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn t_temp_dir() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new("xmlhub-t_temp_dir")?;
+    let path = dir.to_path_buf();
+    assert_eq!(
+        std::fs::metadata(&path)?.permissions().mode() & 0o777,
+        0o700
+    );
+    std::fs::create_dir(dir.join("sub"))?;
+    std::fs::write(dir.join("sub/file"), "x")?;
+    drop(dir);
+    assert!(!path.exists());
+
+    let dir = TempDir::new("xmlhub-t_temp_dir")?;
+    let path = dir.keep();
+    assert!(path.is_dir());
+    std::fs::remove_dir(&path)?;
+    Ok(())
+}
@@ -5,10 +5,11 @@
 
 use std::{fmt::Display, ops::Range, path::Path};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use ouroboros::self_referencing;
 use pluraless::pluralized;
 use roxmltree::{Document, Node, ParsingOptions};
+use serde::Serialize;
 
 /// Find elements with the given tag name without being in a namespace
 /// (XX: danger?), append them to `output`. Do not recurse into found
@@ -47,6 +48,53 @@ impl<'a> XMLDocumentLocation<'a> {
     pub fn start_col(&self) -> usize {
         str_col(0, &self.xmldocument.as_str()[0..self.byte_range.start])
     }
+
+    /// The start position, 1-based, for reporting to the user.
+    pub fn start(&self) -> LineAndColumn {
+        LineAndColumn::from_0_based(self.start_line_and_col())
+    }
+
+    /// The byte range in the document string.
+    pub fn byte_range(&self) -> Range<usize> {
+        self.byte_range.clone()
+    }
+}
+
+/// A position in a file, with line and column both 1-based (see
+/// `line_col_string`). Shown as `line:col`, so that prefixed with
+/// the path as `path:line:col` it can be parsed by editors and CI
+/// tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LineAndColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LineAndColumn {
+    fn from_0_based(line_and_col: (usize, usize)) -> Self {
+        let (line, col) = line_and_col;
+        Self {
+            line: line + 1,
+            column: col + 1,
+        }
+    }
+
+    /// The position of a parse error, if `error` is (or wraps) one
+    /// from roxmltree.
+    pub fn of_parse_error(error: &anyhow::Error) -> Option<Self> {
+        let pos = error.downcast_ref::<roxmltree::Error>()?.pos();
+        Some(Self {
+            line: pos.row as usize,
+            column: pos.col as usize,
+        })
+    }
+}
+
+impl Display for LineAndColumn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { line, column } = self;
+        write!(f, "{line}:{column}")
+    }
 }
 
 /// Returns (line, column), based on `start`, of the end of `s` with
@@ -132,21 +180,82 @@ pub struct XMLDocumentComment<'a> {
     pub string: &'a str,
 }
 
+/// A processing instruction (`<?target value?>`) in the document.
+pub struct XMLDocumentProcessingInstruction<'a> {
+    pub location: XMLDocumentLocation<'a>,
+    /// The text after the target name, if any
+    pub value: Option<&'a str>,
+}
+
 /// A parsed XML document: bundles the XML string and parsed
 /// `roxmltree::Document`.
 #[self_referencing]
 pub struct XMLDocument {
     string: Box<str>,
+    transcoded_from: Option<&'static str>,
+    content_before_declaration: Option<String>,
     #[borrows(string)]
     #[covariant]
     document: Document<'this>,
 }
 
 impl XMLDocument {
+    /// Parse the given string, without any IO.
+    pub fn from_string(string: Box<str>) -> Result<XMLDocument> {
+        Self::parse(string, None, None)
+    }
+
+    /// Parse the given file contents, without any IO: a leading
+    /// byte order mark and any content before the XML declaration
+    /// are removed (see `strip_before_declaration`), and files in
+    /// other encodings than UTF-8 are converted (see
+    /// `decode_xml_bytes`).
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<XMLDocument> {
+        let (bytes, content_before_declaration) = strip_before_declaration(bytes);
+        let (string, transcoded_from) = decode_xml_bytes(bytes)?;
+        Self::parse(
+            string.into_boxed_str(),
+            transcoded_from,
+            content_before_declaration,
+        )
+    }
+
+    fn parse(
+        string: Box<str>,
+        transcoded_from: Option<&'static str>,
+        content_before_declaration: Option<String>,
+    ) -> Result<XMLDocument> {
+        XMLDocument::try_new(
+            string,
+            transcoded_from,
+            content_before_declaration,
+            |string| {
+                let opt = ParsingOptions {
+                    allow_dtd: true,
+                    // nodes_limit: 1, -- somehow ignored
+                    ..ParsingOptions::default()
+                };
+                Document::parse_with_options(string, opt).context("parsing the XML markup")
+            },
+        )
+    }
+
     pub fn as_str(&self) -> &str {
         self.borrow_string()
     }
 
+    /// The name of the encoding declared in the file if it was not
+    /// UTF-8 (or ASCII) and hence had to be converted.
+    pub fn transcoded_from(&self) -> Option<&'static str> {
+        *self.borrow_transcoded_from()
+    }
+
+    /// The (non-whitespace) content that was found before the XML
+    /// declaration and removed so that the document could be parsed.
+    pub fn content_before_declaration(&self) -> Option<&str> {
+        self.borrow_content_before_declaration().as_deref()
+    }
+
     pub fn document<'a>(&'a self) -> &'a Document<'a> {
         self.borrow_document()
     }
@@ -159,11 +268,13 @@ impl XMLDocument {
         }
     }
 
-    /// The comments above the first element in the document.
+    /// The comments above the first element in the document (also
+    /// those after processing instructions).
     pub fn header_comments<'a>(&'a self) -> impl Iterator<Item = XMLDocumentComment<'a>> {
         let root: Node = self.document().root();
         root.children()
-            .take_while(|item| item.is_comment())
+            .take_while(|item| item.is_comment() || item.is_pi())
+            .filter(|item| item.is_comment())
             .map(|item| XMLDocumentComment {
                 location: XMLDocumentLocation {
                     xmldocument: self,
@@ -173,6 +284,27 @@ impl XMLDocument {
             })
     }
 
+    /// The processing instructions with the given `target` above
+    /// the first element in the document.
+    pub fn header_processing_instructions<'a>(
+        &'a self,
+        target: &'a str,
+    ) -> impl Iterator<Item = XMLDocumentProcessingInstruction<'a>> {
+        let root: Node = self.document().root();
+        root.children()
+            .take_while(|item| item.is_comment() || item.is_pi())
+            .filter_map(move |item| {
+                let pi = item.pi()?;
+                (pi.target == target).then(|| XMLDocumentProcessingInstruction {
+                    location: XMLDocumentLocation {
+                        xmldocument: self,
+                        byte_range: item.range(),
+                    },
+                    value: pi.value,
+                })
+            })
+    }
+
     /// Find elements with the given tag name. `limit` is the maximum
     /// number of nodes found before it stops and returns (it can push
     /// one more if called on an element that matches).
@@ -188,21 +320,190 @@ impl XMLDocument {
     }
 }
 
+/// The value of the `encoding` pseudo-attribute in the XML
+/// declaration at the start of `bytes`, if present.
+pub fn declared_encoding(bytes: &[u8]) -> Option<&str> {
+    let rest = bytes.strip_prefix(b"<?xml")?;
+    let declaration = &rest[..rest.windows(2).position(|w| w == b"?>")?];
+    // The declaration is ASCII in all supported encodings
+    let declaration = std::str::from_utf8(declaration).ok()?;
+    let (_, after) = declaration.split_once("encoding")?;
+    let after = after.trim_start().strip_prefix('=')?.trim_start();
+    let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let (value, _) = after[1..].split_once(quote)?;
+    Some(value)
+}
+
+/// The byte order mark as encoded in UTF-8.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Remove a leading UTF-8 byte order mark, and anything else before
+/// the XML declaration, which must be at the very start of the
+/// document for it to be recognized. Returns the remaining bytes
+/// and the removed content other than the byte order mark, if it is
+/// not just whitespace.
+pub fn strip_before_declaration(mut bytes: Vec<u8>) -> (Vec<u8>, Option<String>) {
+    if bytes.starts_with(UTF8_BOM) {
+        bytes.drain(..UTF8_BOM.len());
+    }
+    let Some(start) = bytes.iter().position(|b| *b == b'<') else {
+        return (bytes, None);
+    };
+    let is_declaration = bytes[start..].starts_with(b"<?xml")
+        && bytes.get(start + 5).is_some_and(u8::is_ascii_whitespace);
+    if start == 0 || !is_declaration {
+        return (bytes, None);
+    }
+    let removed: Vec<u8> = bytes.drain(..start).collect();
+    let removed = String::from_utf8_lossy(&removed);
+    let removed = removed.trim();
+    if removed.is_empty() {
+        (bytes, None)
+    } else {
+        (bytes, Some(removed.into()))
+    }
+}
+
+/// The characters for the bytes 0x80..=0x9F in windows-1252
+/// (undefined ones mapped to the C1 control characters, as browsers
+/// do).
+const WINDOWS_1252_80_9F: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+/// Decode the contents of an XML file according to the encoding
+/// given in its XML declaration (UTF-8 if none). Supports UTF-8,
+/// US-ASCII, ISO-8859-1, ISO-8859-15 and windows-1252. Returns the
+/// string and, if it had to be converted (i.e. it is not ASCII), the
+/// canonical name of the encoding it was converted from.
+pub fn decode_xml_bytes(bytes: Vec<u8>) -> Result<(String, Option<&'static str>)> {
+    let encoding = declared_encoding(&bytes).map(|s| s.to_ascii_lowercase());
+    let (name, single_byte): (&'static str, fn(u8) -> char) = match encoding.as_deref() {
+        None | Some("utf-8" | "utf8" | "us-ascii" | "ascii") => {
+            let string = String::from_utf8(bytes)
+                .context("the file is not valid UTF-8 (and does not declare another encoding)")?;
+            return Ok((string, None));
+        }
+        Some("iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "latin-1" | "l1") => {
+            ("ISO-8859-1", char::from)
+        }
+        Some("iso-8859-15" | "iso8859-15" | "iso_8859-15" | "latin-9" | "latin9") => {
+            ("ISO-8859-15", |b| match b {
+                0xA4 => '\u{20AC}',
+                0xA6 => '\u{160}',
+                0xA8 => '\u{161}',
+                0xB4 => '\u{17D}',
+                0xB8 => '\u{17E}',
+                0xBC => '\u{152}',
+                0xBD => '\u{153}',
+                0xBE => '\u{178}',
+                _ => char::from(b),
+            })
+        }
+        Some("windows-1252" | "cp1252") => ("windows-1252", |b| match b {
+            0x80..=0x9F => WINDOWS_1252_80_9F[usize::from(b - 0x80)],
+            _ => char::from(b),
+        }),
+        Some(_) => bail!(
+            "unsupported encoding {:?} declared in the XML declaration; \
+             please convert the file to UTF-8",
+            declared_encoding(&bytes).expect("present")
+        ),
+    };
+    if bytes.is_ascii() {
+        // Nothing to convert
+        let string = String::from_utf8(bytes).expect("ASCII is valid UTF-8");
+        return Ok((string, None));
+    }
+    Ok((bytes.into_iter().map(single_byte).collect(), Some(name)))
+}
+
 /// Load the given file into memory and parse it into a tree of
-/// elements representation.
+/// elements representation (see `XMLDocument::from_bytes`).
 pub fn read_xml_file(path: &Path) -> Result<XMLDocument> {
     // Back to reading the whole file to memory first since roxmltree
     // requires that.
-    let string = std::fs::read_to_string(path)
-        .context("opening or reading the file contents")?
-        .into_boxed_str();
-
-    XMLDocument::try_new(string, |string| {
-        let opt = ParsingOptions {
-            allow_dtd: true,
-            // nodes_limit: 1, -- somehow ignored
-            ..ParsingOptions::default()
-        };
-        Document::parse_with_options(string, opt).context("parsing the XML markup")
-    })
+    let bytes = std::fs::read(path).context("opening or reading the file contents")?;
+    XMLDocument::from_bytes(bytes)
+}
+
+#[test]
+fn t_decode_xml_bytes() -> Result<()> {
+    assert_eq!(
+        declared_encoding(b"<?xml version='1.0' encoding = 'ISO-8859-1'?><a/>"),
+        Some("ISO-8859-1")
+    );
+    assert_eq!(declared_encoding(b"<?xml version=\"1.0\"?><a/>"), None);
+    assert_eq!(declared_encoding(b"<a/>"), None);
+
+    let mut latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n\
+                       <!-- Contact: J\xFCrg M\xE9ndez -->\n<beast/>"
+        .to_vec();
+    let (string, transcoded_from) = decode_xml_bytes(latin1.clone())?;
+    assert_eq!(transcoded_from, Some("ISO-8859-1"));
+    assert!(string.contains("Jürg Méndez"));
+    let document = XMLDocument::parse(string.into_boxed_str(), transcoded_from, None)?;
+    assert_eq!(
+        document.header_comments().next().expect("present").string,
+        " Contact: Jürg Méndez "
+    );
+    assert_eq!(document.transcoded_from(), Some("ISO-8859-1"));
+
+    let (string, transcoded_from) = decode_xml_bytes(
+        b"<?xml version=\"1.0\" encoding=\"windows-1252\"?><a>\x80 \x93q\x94</a>".to_vec(),
+    )?;
+    assert_eq!(transcoded_from, Some("windows-1252"));
+    assert!(string.ends_with("<a>€ “q”</a>"));
+
+    let (string, transcoded_from) =
+        decode_xml_bytes("<?xml version=\"1.0\" encoding=\"UTF-8\"?><a>ü</a>".into())?;
+    assert_eq!(transcoded_from, None);
+    assert!(string.ends_with("<a>ü</a>"));
+
+    let (_, transcoded_from) =
+        decode_xml_bytes(b"<?xml version=\"1.0\" encoding=\"latin1\"?><a>u</a>".to_vec())?;
+    assert_eq!(transcoded_from, None);
+
+    // Not declared, not UTF-8
+    latin1.drain(..latin1.iter().position(|b| *b == b'\n').expect("present"));
+    assert!(decode_xml_bytes(latin1).is_err());
+
+    let e = decode_xml_bytes(b"<?xml version=\"1.0\" encoding=\"EBCDIC\"?><a/>".to_vec())
+        .expect_err("unsupported");
+    assert!(e.to_string().contains("unsupported encoding \"EBCDIC\""));
+    Ok(())
+}
+
+#[test]
+fn t_strip_before_declaration() -> Result<()> {
+    let file = b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <!-- Keywords: f\xC3\xBCr -->\n<beast/>";
+    let document = XMLDocument::from_bytes(file.to_vec())?;
+    assert_eq!(
+        document.header_comments().next().expect("present").string,
+        " Keywords: für "
+    );
+    assert_eq!(document.content_before_declaration(), None);
+
+    let document = XMLDocument::from_bytes(
+        b"\xEF\xBB\xBF\n  <?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><a>\xFC</a>".to_vec(),
+    )?;
+    assert_eq!(document.content_before_declaration(), None);
+    assert_eq!(document.transcoded_from(), Some("ISO-8859-1"));
+
+    let document = XMLDocument::from_bytes(
+        b"junk\n<?xml version=\"1.0\"?>\n<!-- Version: 2.7 -->\n<a/>".to_vec(),
+    )?;
+    assert_eq!(document.content_before_declaration(), Some("junk"));
+    assert_eq!(document.header_comments().count(), 1);
+
+    // Without a declaration there is nothing to strip
+    let (bytes, removed) = strip_before_declaration(b"junk<a/>".to_vec());
+    assert_eq!((bytes.as_slice(), removed), (&b"junk<a/>"[..], None));
+    let (_, removed) = strip_before_declaration(b"junk<?xml-stylesheet href=\"a\"?><a/>".to_vec());
+    assert_eq!(removed, None);
+    Ok(())
 }
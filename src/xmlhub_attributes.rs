@@ -4,18 +4,22 @@
 //! actual description in `METADATA_SPECIFICATION`.
 
 use std::{
-    collections::BTreeMap,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
     fmt::{Debug, Display},
 };
 
 use ahtml::{att, flat::Flat, util::SoftPre, AId, HtmlAllocator, Node};
 use ahtml_from_markdown::markdown::markdown_to_html;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use lazy_static::lazy_static;
+use pluraless::{english_plural, PluralizedWord};
+use regex::Regex;
 
 use crate::{
     html_util::extract_paragraph_body,
-    util::{self, format_anchor_name},
+    location::canonical_country,
+    util::{self, format_anchor_name, format_string_list, unique_anchor_names},
     xml_document::XMLDocument,
     xmlhub_autolink::Autolink,
     xmlhub_fileinfo::{AttributeValue, AttributeValueKind, Issue},
@@ -42,7 +46,8 @@ impl AsRef<str> for AttributeName {
 
 impl AttributeName {
     /// Generate an anchor name for this attribute with the given
-    /// attribute item string.
+    /// attribute item string. Note that different item strings can
+    /// give the same anchor name, use `anchor_names` to avoid that.
     pub fn anchor_name(self, key_string: &str) -> String {
         format!(
             "{}-{}",
@@ -50,6 +55,46 @@ impl AttributeName {
             format_anchor_name(key_string)
         )
     }
+
+    /// Generate the anchor names for this attribute with all the
+    /// given item strings, different for each item string (see
+    /// `unique_anchor_names`). The first of the item strings
+    /// mapping to the same anchor name (and any that's the only one)
+    /// gets the same name as from `anchor_name`.
+    pub fn anchor_names<'s>(self, key_strings: impl IntoIterator<Item = &'s str>) -> AnchorNames {
+        AnchorNames {
+            attribute: self,
+            by_key_string: unique_anchor_names(key_strings)
+                .into_iter()
+                .map(|(key_string, anchor_name)| {
+                    (
+                        key_string.to_owned(),
+                        format!("{}-{anchor_name}", format_anchor_name(self.as_ref())),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The anchor names of the entries in the index for one attribute,
+/// see `AttributeName::anchor_names`.
+#[derive(Debug, Clone)]
+pub struct AnchorNames {
+    attribute: AttributeName,
+    by_key_string: BTreeMap<String, String>,
+}
+
+impl AnchorNames {
+    /// The anchor name for `key_string`; the same as from
+    /// `AttributeName::anchor_name` if it was not among the item
+    /// strings given to `AttributeName::anchor_names`.
+    pub fn get(&self, key_string: &str) -> String {
+        match self.by_key_string.get(key_string) {
+            Some(anchor_name) => anchor_name.clone(),
+            None => self.attribute.anchor_name(key_string),
+        }
+    }
 }
 
 /// Specifies whether an attribute is required
@@ -90,6 +135,16 @@ pub enum AttributeKind {
         /// AttributeValue.
         input_separator: &'static str,
     },
+    /// Like `StringList`, but the items are geographic (e.g.
+    /// sampling) locations: those naming a known country in one of
+    /// its common variants (see `location::canonical_country`) are
+    /// indexed under the canonical country name, the others as
+    /// given. The info boxes show the values as given.
+    Location { input_separator: &'static str },
+    /// A yes/no value (also accepting true/false), shown and indexed
+    /// as "yes" or "no". The index has an additional entry for the
+    /// files that do not give a value.
+    Flag,
 }
 
 fn text_not(is: bool) -> &'static str {
@@ -107,6 +162,8 @@ impl AttributeKind {
                 normalize_whitespace: _,
             } => false,
             AttributeKind::StringList { input_separator: _ } => true,
+            AttributeKind::Location { input_separator: _ } => true,
+            AttributeKind::Flag => false,
         }
     }
 
@@ -126,6 +183,14 @@ impl AttributeKind {
                 &format!("list with items separated by {input_separator:?}",),
                 html,
             ),
+            AttributeKind::Location { input_separator } => softpre.format(
+                &format!(
+                    "list of locations separated by {input_separator:?},\n\
+                     countries indexed under a canonical name"
+                ),
+                html,
+            ),
+            AttributeKind::Flag => softpre.format("yes or no", html),
         }
     }
 }
@@ -142,6 +207,13 @@ pub enum AttributeIndexing {
         /// Whether to convert the user-given values to lowercase for
         /// the index
         use_lowercase: bool,
+        /// Whether to index plural forms under the singular (the last
+        /// word of the value only, so that "phylogenetic trees" and
+        /// "phylogenetic tree" share a key); words that can't be
+        /// singularized by just removing the "s", or which are usually
+        /// not plurals ("bayes", "genomics", "bias", "SARS"), are left
+        /// unchanged.
+        singularize_for_index: bool,
     },
     NoIndex,
 }
@@ -152,9 +224,13 @@ impl AttributeIndexing {
             AttributeIndexing::Index {
                 first_word_only,
                 use_lowercase,
+                singularize_for_index,
             } => Some(KeyStringPreparation {
                 first_word_only,
                 use_lowercase,
+                singularize_for_index,
+                canonical_location: false,
+                missing_key_string: None,
             }),
             AttributeIndexing::NoIndex => None,
         }
@@ -165,16 +241,22 @@ impl AttributeIndexing {
             AttributeIndexing::Index {
                 first_word_only,
                 use_lowercase,
+                singularize_for_index,
             } => softpre.format(
                 &format!(
-                    "{}{}indexed,\n{}lower-cased",
+                    "{}{}indexed,\n{}lower-cased{}",
                     if *first_word_only {
                         "first word "
                     } else {
                         "full value "
                     },
                     if is_list { "of each item " } else { "" },
-                    text_not(*use_lowercase)
+                    text_not(*use_lowercase),
+                    if *singularize_for_index {
+                        ",\nplurals indexed as singular"
+                    } else {
+                        ""
+                    }
                 ),
                 html,
             ),
@@ -194,7 +276,9 @@ pub struct SourceSpecification {
 
 #[derive(Debug)]
 pub struct ExtractionSpecification {
-    pub extractor: for<'a> fn(&'a XMLDocument, &mut Vec<Issue>) -> AttributeValueKind,
+    /// Returns `None` if the attribute doesn't apply (it is then
+    /// not shown for the file).
+    pub extractor: for<'a> fn(&'a XMLDocument, &mut Vec<Issue>) -> Option<AttributeValueKind>,
 }
 
 #[derive(Debug)]
@@ -230,6 +314,19 @@ pub struct AttributeSpecification {
     pub source: AttributeSource,
     pub autolink: Autolink,
     pub indexing: AttributeIndexing,
+    /// A regular expression whose matches in the value are indexed
+    /// in a separate index ("Identifiers in {key}"), independently
+    /// of `indexing`. Does not change what is shown in the info
+    /// boxes.
+    pub extract_index_regex: Option<&'static str>,
+    /// A valid sample value, shown in the documentation and
+    /// tooltips (only for specified attributes).
+    pub example: Option<&'static str>,
+    /// Whether long values are cut off in the info boxes in the HTML
+    /// index file, with a "show more" expander for the rest (see
+    /// `XmlhubConfig::box_truncate_length`). The Markdown file
+    /// always shows the whole value.
+    pub truncate_in_box: bool,
 }
 
 impl AttributeSpecification {
@@ -242,6 +339,73 @@ impl AttributeSpecification {
         "Indexing",
     ];
 
+    /// How the values are turned into keys for the index, `None` if
+    /// not indexed.
+    pub fn key_string_preparation(&self) -> Option<KeyStringPreparation> {
+        let preparation = self.indexing.key_string_preparation()?;
+        let kind = match &self.source {
+            AttributeSource::Specified(SourceSpecification { kind, .. }) => Some(kind),
+            AttributeSource::Derived(_) | AttributeSource::Extracted(_) => None,
+        };
+        Some(KeyStringPreparation {
+            canonical_location: matches!(kind, Some(AttributeKind::Location { .. })),
+            missing_key_string: if kind == Some(&AttributeKind::Flag) {
+                Some(FLAG_MISSING_KEY_STRING)
+            } else {
+                None
+            },
+            ..preparation
+        })
+    }
+
+    /// The title of the index built via `extract_index_regex`.
+    pub fn extracted_index_title(&self) -> String {
+        format!("Identifiers in {}", self.key.as_ref())
+    }
+
+    /// `extract_index_regex`, compiled.
+    pub fn extract_index_regex(&self) -> Option<Regex> {
+        self.extract_index_regex.map(|regex| {
+            Regex::new(regex)
+                .expect("valid regex in `extract_index_regex` in METADATA_SPECIFICATION")
+        })
+    }
+
+    /// A plain-text description of the attribute, for tooltips. For
+    /// specified attributes this is `desc` with the Markdown markup
+    /// characters removed.
+    pub fn description(&self) -> Cow<'static, str> {
+        match &self.source {
+            AttributeSource::Specified(SourceSpecification {
+                desc,
+                need: _,
+                kind: _,
+            }) => {
+                let desc: Cow<'static, str> = if desc.contains(['*', '`']) {
+                    desc.replace(['*', '`'], "").into()
+                } else {
+                    (*desc).into()
+                };
+                if let Some(example) = self.example {
+                    format!("{desc} Example: {example}").into()
+                } else {
+                    desc
+                }
+            }
+            AttributeSource::Extracted(_) => {
+                "Extracted automatically from the XML document.".into()
+            }
+            AttributeSource::Derived(DerivationSpecification {
+                derived_from,
+                derivation: _,
+            }) => format!(
+                "Calculated automatically from {}.",
+                format_string_list(derived_from.iter())
+            )
+            .into(),
+        }
+    }
+
     /// Show the specification using HTML markup, for writing to
     /// ATTRIBUTE_SPECIFICATION_FILENAME. Derived attributes yield no
     /// output.
@@ -251,18 +415,29 @@ impl AttributeSpecification {
             source: AttributeSource::Specified(SourceSpecification { desc, need, kind }),
             autolink,
             indexing,
+            extract_index_regex,
+            example,
+            truncate_in_box: _,
         } = self
         {
             let desc_html = markdown_to_html(desc, html)?.html();
             // markdown_to_html wraps paragraphs in <p> even if it's just
             // one of them; strip that if possible:
             let desc_stripped = extract_paragraph_body(desc_html, true, html);
+            let mut desc_cell = html.new_vec();
+            desc_cell.push_flat(desc_stripped)?;
+            if let Some(example) = example {
+                desc_cell.push(html.br([], [])?)?;
+                desc_cell.push(html.i([], html.text("Example:")?)?)?;
+                desc_cell.push(html.text(" ")?)?;
+                desc_cell.push(html.code([], html.text(example)?)?)?;
+            }
 
             Ok(Flat::One(html.tr(
                 [],
                 [
                     html.td([], html.i([], html.text(key.as_ref())?)?)?,
-                    html.td([], desc_stripped)?,
+                    html.td([], desc_cell)?,
                     html.td(
                         [],
                         html.text(match need {
@@ -272,7 +447,23 @@ impl AttributeSpecification {
                     )?,
                     html.td([], kind.to_html(html)?)?,
                     html.td([], html.text(autolink.to_text())?)?,
-                    html.td([], indexing.to_html(kind.is_list(), html)?)?,
+                    html.td(
+                        [],
+                        [
+                            indexing.to_html(kind.is_list(), html)?,
+                            if let Some(regex) = extract_index_regex {
+                                SoftPre::default().format(
+                                    &format!(
+                                        ";\nmatches of {regex:?} indexed under {:?}",
+                                        self.extracted_index_title()
+                                    ),
+                                    html,
+                                )?
+                            } else {
+                                html.empty_node()?
+                            },
+                        ],
+                    )?,
                 ],
             )?))
         } else {
@@ -290,10 +481,16 @@ impl Display for AttributeSpecification {
             source: AttributeSource::Specified(SourceSpecification { desc, need, kind }),
             autolink,
             indexing,
+            extract_index_regex,
+            example,
+            truncate_in_box: _,
         } = self
         {
             f.write_fmt(format_args!("  {}:\n", key.as_ref()))?;
             f.write_fmt(format_args!("      {desc}\n"))?;
+            if let Some(example) = example {
+                f.write_fmt(format_args!("    example: {example}\n"))?;
+            }
             f.write_fmt(format_args!("    need: {need:?}\n"))?;
             f.write_fmt(format_args!("    kind: {kind:?}\n"))?;
             f.write_fmt(format_args!(
@@ -301,6 +498,12 @@ impl Display for AttributeSpecification {
                 autolink.to_text() // XX too long?
             ))?;
             f.write_fmt(format_args!("    indexing: {indexing:?}\n"))?;
+            if let Some(regex) = extract_index_regex {
+                f.write_fmt(format_args!(
+                    "    matches of {regex:?} indexed under {:?}\n",
+                    self.extracted_index_title()
+                ))?;
+            }
         }
         Ok(())
     }
@@ -340,7 +543,11 @@ pub const METADATA_SPECIFICATION: &[AttributeSpecification] = {
             indexing: AttributeIndexing::Index {
                 first_word_only: false,
                 use_lowercase: true,
+                singularize_for_index: true,
             },
+            extract_index_regex: None,
+            example: Some("sampling-through-time, birth-death skyline"),
+            truncate_in_box: false,
         },
         AttributeSpecification {
             key: AttributeName("Version"),
@@ -355,7 +562,11 @@ pub const METADATA_SPECIFICATION: &[AttributeSpecification] = {
             indexing: AttributeIndexing::Index {
                 first_word_only: false,
                 use_lowercase: false,
+                singularize_for_index: false,
             },
+            extract_index_regex: None,
+            example: Some("2.7.5"),
+            truncate_in_box: false,
         },
         AttributeSpecification {
             key: AttributeName("Packages"),
@@ -370,7 +581,11 @@ pub const METADATA_SPECIFICATION: &[AttributeSpecification] = {
             indexing: AttributeIndexing::Index {
                 first_word_only: true,
                 use_lowercase: false,
+                singularize_for_index: false,
             },
+            extract_index_regex: None,
+            example: Some("BEAST.base 2.7.5, BDSKY 1.5.0"),
+            truncate_in_box: false,
         },
         AttributeSpecification {
             key: AttributeName("Description"),
@@ -383,6 +598,10 @@ pub const METADATA_SPECIFICATION: &[AttributeSpecification] = {
             }),
             autolink: Autolink::Web,
             indexing: AttributeIndexing::NoIndex,
+            // GISAID (EPI_ISL_12345) and INSDC (MN908947.3) accessions
+            extract_index_regex: Some(r"\bEPI_ISL_[0-9]+\b|\b[A-Z]{1,2}[0-9]{5,6}(?:\.[0-9]+)?\b"),
+            example: None,
+            truncate_in_box: true,
         },
         AttributeSpecification {
             key: AttributeName("Comments"),
@@ -395,6 +614,9 @@ pub const METADATA_SPECIFICATION: &[AttributeSpecification] = {
             }),
             autolink: Autolink::Web,
             indexing: AttributeIndexing::NoIndex,
+            extract_index_regex: None,
+            example: None,
+            truncate_in_box: true,
         },
         AttributeSpecification {
             key: AttributeName("DOI"),
@@ -409,7 +631,11 @@ pub const METADATA_SPECIFICATION: &[AttributeSpecification] = {
             indexing: AttributeIndexing::Index {
                 first_word_only: false,
                 use_lowercase: false,
+                singularize_for_index: false,
             },
+            extract_index_regex: None,
+            example: Some("10.1371/journal.pcbi.1006650"),
+            truncate_in_box: false,
         },
         AttributeSpecification {
             key: AttributeName("Citation via DOI"), // XXX
@@ -444,7 +670,11 @@ pub const METADATA_SPECIFICATION: &[AttributeSpecification] = {
             indexing: AttributeIndexing::Index {
                 first_word_only: false,
                 use_lowercase: false,
+                singularize_for_index: false,
             },
+            extract_index_regex: None,
+            example: None,
+            truncate_in_box: false,
         },
         AttributeSpecification {
             key: AttributeName("Citation"),
@@ -460,7 +690,11 @@ pub const METADATA_SPECIFICATION: &[AttributeSpecification] = {
             indexing: AttributeIndexing::Index {
                 first_word_only: false,
                 use_lowercase: false,
+                singularize_for_index: false,
             },
+            extract_index_regex: None,
+            example: None,
+            truncate_in_box: false,
         },
         AttributeSpecification {
             key: AttributeName("Contact"),
@@ -475,7 +709,11 @@ pub const METADATA_SPECIFICATION: &[AttributeSpecification] = {
             indexing: AttributeIndexing::Index {
                 first_word_only: false,
                 use_lowercase: false,
+                singularize_for_index: false,
             },
+            extract_index_regex: None,
+            example: Some("Jane Doe"),
+            truncate_in_box: false,
         },
         AttributeSpecification {
             key: AttributeName("Repository"),
@@ -490,28 +728,109 @@ pub const METADATA_SPECIFICATION: &[AttributeSpecification] = {
             indexing: AttributeIndexing::Index {
                 first_word_only: false,
                 use_lowercase: false,
+                singularize_for_index: false,
             },
+            extract_index_regex: None,
+            example: None,
+            truncate_in_box: false,
+        },
+        AttributeSpecification {
+            key: AttributeName("Location"),
+            source: AttributeSource::Specified(SourceSpecification {
+                desc: "Where the samples were taken. Countries can be given by name or \
+                       by their two or three letter ISO code (like \"CH\" or \"CHE\"), \
+                       they are indexed under a common name.",
+                need: AttributeNeed::Optional,
+                kind: AttributeKind::Location {
+                    input_separator: ",",
+                },
+            }),
+            autolink: Autolink::None,
+            indexing: AttributeIndexing::Index {
+                first_word_only: false,
+                use_lowercase: false,
+                singularize_for_index: false,
+            },
+            extract_index_regex: None,
+            example: Some("CH, Germany"),
+            truncate_in_box: false,
+        },
+        AttributeSpecification {
+            key: AttributeName("Published"),
+            source: AttributeSource::Specified(SourceSpecification {
+                desc: "Whether the analysis has been published, \"yes\" or \"no\".",
+                need: AttributeNeed::Optional,
+                kind: AttributeKind::Flag,
+            }),
+            autolink: Autolink::None,
+            indexing: AttributeIndexing::Index {
+                first_word_only: false,
+                use_lowercase: false,
+                singularize_for_index: false,
+            },
+            extract_index_regex: None,
+            example: Some("yes"),
+            truncate_in_box: false,
         },
         AttributeSpecification {
             key: AttributeName("Contains sequence data"),
             source: AttributeSource::Extracted(ExtractionSpecification {
-                extractor: |xmldocument, _warnings| -> AttributeValueKind {
-                    AttributeValueKind::Boolean(
+                extractor: |xmldocument, _warnings| -> Option<AttributeValueKind> {
+                    Some(AttributeValueKind::Boolean(
                         !xmldocument
                             .elements_named(SEQUENCES_ELEMENT_NAME, 1)
                             .is_empty(),
-                    )
+                    ))
                 },
             }),
             autolink: Autolink::Web,
             indexing: AttributeIndexing::Index {
                 first_word_only: false,
                 use_lowercase: false,
+                singularize_for_index: false,
             },
+            extract_index_regex: None,
+            example: None,
+            truncate_in_box: false,
+        },
+        AttributeSpecification {
+            key: AttributeName("Attachments"),
+            source: AttributeSource::Extracted(ExtractionSpecification {
+                extractor: |xmldocument, _warnings| -> Option<AttributeValueKind> {
+                    let file_names = attachment_file_names(xmldocument);
+                    if file_names.is_empty() {
+                        None
+                    } else {
+                        Some(AttributeValueKind::StringList(file_names))
+                    }
+                },
+            }),
+            autolink: Autolink::None,
+            indexing: AttributeIndexing::NoIndex,
+            extract_index_regex: None,
+            example: None,
+            truncate_in_box: false,
         },
     ]
 };
 
+/// The name of the XML attribute that refers to external data
+/// files, e.g. in `<data fileName="...">`.
+pub const ATTACHMENT_ATTRIBUTE_NAME: &str = "fileName";
+
+/// The values of all `ATTACHMENT_ATTRIBUTE_NAME` attributes in the
+/// document (on any element), sorted and without duplicates.
+pub fn attachment_file_names(xmldocument: &XMLDocument) -> Vec<String> {
+    let file_names: BTreeSet<&str> = xmldocument
+        .document()
+        .descendants()
+        .filter_map(|node| node.attribute(ATTACHMENT_ATTRIBUTE_NAME))
+        .map(str::trim)
+        .filter(|file_name| !file_name.is_empty())
+        .collect();
+    file_names.into_iter().map(ToOwned::to_owned).collect()
+}
+
 /// Look up an attribute specification by attribute name. Casing must
 /// be identical to the spec or it won't be found!
 pub fn attribute_specification_by_name(
@@ -523,6 +842,19 @@ pub fn attribute_specification_by_name(
         .find(move |spec| spec.key == key)
 }
 
+/// Find the specified (i.e. not calculated) attribute with the
+/// given name, case-insensitively.
+pub fn specified_attribute_name(name: &str) -> Result<AttributeName> {
+    METADATA_SPECIFICATION
+        .iter()
+        .find(|spec| {
+            matches!(spec.source, AttributeSource::Specified(_))
+                && spec.key.as_ref().eq_ignore_ascii_case(name)
+        })
+        .map(|spec| spec.key)
+        .ok_or_else(|| anyhow!("unknown attribute name {name:?}"))
+}
+
 // `lazy_static` sets things up so that the data for the given
 // constant (`METADATA_KEY_POSITION`) is calculated when it is read
 // for the first time.
@@ -553,6 +885,47 @@ pub fn sort_in_definition_order<T>(
     result
 }
 
+/// Like `sort_in_definition_order`, but the attributes listed in
+/// `order` come first, in that order; the other ones follow in
+/// definition order.
+pub fn sort_in_box_order<T>(
+    key_vals: impl IntoIterator<Item = (AttributeName, T)>,
+    order: &[AttributeName],
+) -> Vec<(AttributeName, Option<T>)> {
+    let mut entries: Vec<_> = sort_in_definition_order(key_vals)
+        .into_iter()
+        .map(Some)
+        .collect();
+    let mut result = Vec::with_capacity(entries.len());
+    for key in order {
+        result.push(
+            entries[METADATA_KEY_POSITION[key]]
+                .take()
+                .expect("no duplicates"),
+        );
+    }
+    result.extend(entries.into_iter().flatten());
+    result
+}
+
+/// Parse a list of attribute names as used for
+/// `sort_in_box_order`. The names must be given with the same
+/// casing as in `METADATA_SPECIFICATION`, and only once each.
+pub fn parse_box_order<'s>(names: impl IntoIterator<Item = &'s str>) -> Result<Vec<AttributeName>> {
+    let mut order: Vec<AttributeName> = Vec::new();
+    for name in names {
+        let spec = METADATA_SPECIFICATION
+            .iter()
+            .find(|spec| spec.key.as_ref() == name)
+            .ok_or_else(|| anyhow!("unknown attribute name {name:?}"))?;
+        if order.contains(&spec.key) {
+            bail!("attribute name {name:?} is given more than once")
+        }
+        order.push(spec.key);
+    }
+    Ok(order)
+}
+
 /// Settings and a method for the conversion of a value (string) into
 /// the key string to be used in an index entry (e.g. an item of
 /// `Packages` like "BDSKY 1.2.3" might be converted to "BDSKY", or a
@@ -561,10 +934,34 @@ pub fn sort_in_definition_order<T>(
 pub struct KeyStringPreparation {
     first_word_only: bool,
     use_lowercase: bool,
+    /// Index plurals under the singular, see
+    /// `AttributeIndexing::Index`
+    singularize_for_index: bool,
+    /// Map known countries to their canonical name (for
+    /// `AttributeKind::Location`)
+    canonical_location: bool,
+    /// If given, files without a value are indexed under this key
+    /// string (for `AttributeKind::Flag`)
+    missing_key_string: Option<&'static str>,
 }
 
+/// The index entry for the files not giving a value for an
+/// attribute of kind `AttributeKind::Flag`.
+pub const FLAG_MISSING_KEY_STRING: &str = "not given";
+
 impl KeyStringPreparation {
+    /// The key string for the files without a value, if those are
+    /// to be indexed.
+    pub fn missing_key_string(&self) -> Option<&'static str> {
+        self.missing_key_string
+    }
+
     pub fn prepare_key_string(&self, key_string: &str) -> String {
+        if self.canonical_location {
+            if let Some(country) = canonical_country(key_string) {
+                return country.into();
+            }
+        }
         let normalized = util::normalize_whitespace(key_string.trim());
         // ^ Should we keep newlines instead, and then SoftPre for the
         // display? Probably not.
@@ -576,10 +973,107 @@ impl KeyStringPreparation {
         } else {
             &normalized
         };
-        if self.use_lowercase {
+        let part = if self.use_lowercase {
             part.to_lowercase()
         } else {
             part.into()
+        };
+        if self.singularize_for_index {
+            singularize_last_word(&part)
+        } else {
+            part
         }
     }
 }
+
+/// Word endings for which removing the "s" does not give the
+/// singular ("glass", "virus", "analysis", "phylogenies", "analyses",
+/// "boxes", "bias", ...), or the word is usually not a plural in the
+/// first place ("genomics", "phylodynamics", "always", ...).
+const NON_SINGULARIZABLE_ENDINGS: &[&str] = &[
+    "ss", "us", "is", "ies", "ses", "xes", "ches", "shes", "ics", "as", "ys",
+];
+
+/// Words ending in "s" that are not plurals but don't have one of
+/// the `NON_SINGULARIZABLE_ENDINGS` (lower-case; acronyms given in
+/// upper case are left alone anyway).
+const NON_PLURAL_WORDS: &[&str] = &["sars", "mers", "news"];
+
+/// Whether `word` (lower-case) ends in "es" after a vowel other than
+/// "e" (counting "y" as a vowel), as in "bayes", where removing the
+/// "s" would not give a singular. ("trees" is fine.)
+fn ends_in_es_after_vowel(word: &str) -> bool {
+    word.strip_suffix("es")
+        .and_then(|stem| stem.chars().last())
+        .is_some_and(|c| "aiouy".contains(c))
+}
+
+/// Replace the last word of `key_string` with its singular, if it
+/// is a regular plural (ending in 's'), `pluraless` knows it, and it
+/// is not written in upper case (an acronym like "SARS"), not one of
+/// the `NON_PLURAL_WORDS`, doesn't have one of the
+/// `NON_SINGULARIZABLE_ENDINGS` and doesn't end in "es" after a
+/// vowel; otherwise returns `key_string` unchanged. (Irregular
+/// plurals like "data" are kept, since they are commonly used as
+/// keywords as they are.)
+fn singularize_last_word(key_string: &str) -> String {
+    let (before, word) = match key_string.rfind(' ') {
+        Some(i) => key_string.split_at(i + 1),
+        None => ("", key_string),
+    };
+    let lowercase_word = word.to_lowercase();
+    if word.len() < 3
+        || !lowercase_word.ends_with('s')
+        || !word.chars().any(|c| c.is_lowercase())
+        || NON_PLURAL_WORDS.contains(&lowercase_word.as_str())
+        || NON_SINGULARIZABLE_ENDINGS
+            .iter()
+            .any(|ending| lowercase_word.ends_with(ending))
+        || ends_in_es_after_vowel(&lowercase_word)
+    {
+        return key_string.into();
+    }
+    match english_plural(word) {
+        Some(PluralizedWord { singular, .. }) => format!("{before}{singular}"),
+        None => key_string.into(),
+    }
+}
+
+#[test]
+fn t_singularize_last_word() {
+    let t = singularize_last_word;
+    assert_eq!(t("trees"), "tree");
+    assert_eq!(t("tree"), "tree");
+    assert_eq!(t("phylogenetic trees"), "phylogenetic tree");
+    assert_eq!(t("Skyline Models"), "Skyline Model");
+    assert_eq!(t("data"), "data");
+    assert_eq!(t("people"), "people");
+    assert_eq!(t("analyses"), "analyses");
+    assert_eq!(t("analysis"), "analysis");
+    assert_eq!(t("virus"), "virus");
+    assert_eq!(t("phylogenies"), "phylogenies");
+    assert_eq!(t("bs"), "bs");
+    // Not plurals, or not ones that removing the "s" fixes
+    for word in [
+        "bayes",
+        "Bayes",
+        "phylodynamics",
+        "genomics",
+        "sars",
+        "SARS",
+        "SARS-CoV-2 variants",
+        "bias",
+        "always",
+        "toes",
+    ] {
+        let expected = if word == "SARS-CoV-2 variants" {
+            "SARS-CoV-2 variant"
+        } else {
+            word
+        };
+        assert_eq!(t(word), expected);
+    }
+    assert_eq!(t("approximate bayes"), "approximate bayes");
+    assert_eq!(t("trees"), "tree");
+    assert_eq!(t("clocks"), "clock");
+}
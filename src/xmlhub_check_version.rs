@@ -4,14 +4,15 @@
 
 //! Wrapper around git_check_version.rs for the xmlhub specific parts.
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use run_git::git::GitWorkingDir;
 
 use crate::{
     git_check_version::GitLogVersionChecker,
     git_version::{GitVersion, SemVersion},
     ref_or_owned::RefOrOwned,
-    xmlhub_types::OutputFile,
+    xmlhub_indexer_defaults::VERSION_NOTES_REF,
+    xmlhub_types::OutputFiles,
 };
 
 pub struct XmlhubCheckVersion<'s> {
@@ -19,8 +20,24 @@ pub struct XmlhubCheckVersion<'s> {
     pub program_version: RefOrOwned<'s, GitVersion<SemVersion>>,
     pub no_version_check: bool,
     pub git_working_dir: RefOrOwned<'s, GitWorkingDir>,
-    pub html_file: RefOrOwned<'s, OutputFile>,
-    pub md_file: RefOrOwned<'s, OutputFile>,
+    /// The files whose Git log is checked (the HTML and Markdown
+    /// index files)
+    pub output_files: RefOrOwned<'s, OutputFiles>,
+    /// Whether to return an error (`VersionStatementsNotFound`)
+    /// instead of only printing a warning when there are no version
+    /// statements in the Git log.
+    pub require_version_statements: bool,
+}
+
+/// Returned by `check_git_log` if no version statements were found
+/// and `require_version_statements` is true.
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "could not find or parse {program_name} version statements in the git log on the \
+     output files, refusing to continue because of --strict-version-check"
+)]
+pub struct VersionStatementsNotFound {
+    pub program_name: String,
 }
 
 impl<'s> XmlhubCheckVersion<'s> {
@@ -37,12 +54,24 @@ impl<'s> XmlhubCheckVersion<'s> {
             let git_log_version_checker = self.git_log_version_checker();
             let program_name = self.program_name;
 
+            // Versions may be recorded in the commit messages or in
+            // notes (`build --version-notes`), look at both. Only
+            // pass `--notes` if the notes ref exists, as Git warns
+            // about it otherwise.
+            let (notes_ref_exists, _) = self.git_working_dir.git_stdout_accepting(
+                &["rev-parse", "--verify", "--quiet", VERSION_NOTES_REF],
+                &[0, 1],
+            )?;
+            let notes_arg = format!("--notes={VERSION_NOTES_REF}");
+            let mut arguments: Vec<&str> = Vec::new();
+            if notes_ref_exists {
+                arguments.push(&notes_arg);
+            }
+            arguments.push(&self.output_files.html_file.path_from_repo_top);
+            arguments.push(&self.output_files.md_file.path_from_repo_top);
             let found = git_log_version_checker.check_git_log(
                 self.git_working_dir.as_ref(),
-                &[
-                    self.html_file.path_from_repo_top,
-                    self.md_file.path_from_repo_top,
-                ],
+                &arguments,
                 Some(format!(
                     "please upgrade your copy of the {program_name} program with:\n  \
                      `{program_name} upgrade`\n\
@@ -51,6 +80,12 @@ impl<'s> XmlhubCheckVersion<'s> {
                 )),
             )?;
             if found.is_none() {
+                if self.require_version_statements {
+                    return Err(VersionStatementsNotFound {
+                        program_name: program_name.into(),
+                    }
+                    .into());
+                }
                 println!(
                     "Warning: could not find or parse {program_name} version statements \
                      in the git log on the output files; this may mean that \
@@ -68,4 +103,59 @@ impl<'s> XmlhubCheckVersion<'s> {
     pub fn program_name_and_version(&self) -> String {
         self.git_log_version_checker().program_name_and_version()
     }
+
+    /// Record the program version as a note in `notes_ref` on the
+    /// HEAD commit (delegates to `GitLogVersionChecker`).
+    pub fn add_version_note(&self, notes_ref: &str, quiet: bool) -> Result<()> {
+        self.git_log_version_checker().add_version_note(
+            self.git_working_dir.as_ref(),
+            notes_ref,
+            "HEAD",
+            quiet,
+        )
+    }
+
+    /// Fetch the notes in `notes_ref` from `remote` and merge them
+    /// into the local ones, since other clones may have added notes
+    /// since (and pushing the local ones would fail otherwise). Does
+    /// nothing if the remote has none. Notes on the same commit from
+    /// both sides are concatenated (the version check finds the
+    /// version in either).
+    pub fn fetch_version_notes(&self, notes_ref: &str, remote: &str, quiet: bool) -> Result<()> {
+        let git_working_dir = self.git_working_dir.as_ref();
+        let (remote_has_notes, _) = git_working_dir
+            .git_stdout_accepting(&["ls-remote", "--exit-code", remote, notes_ref], &[0, 2])?;
+        if !remote_has_notes {
+            return Ok(());
+        }
+        let fetched_ref = format!("{notes_ref}-fetched");
+        if !git_working_dir.git(
+            &["fetch", remote, &format!("+{notes_ref}:{fetched_ref}")],
+            quiet,
+        )? {
+            bail!("git fetch of {notes_ref:?} from {remote:?} failed")
+        }
+        let (have_local_notes, _) = git_working_dir
+            .git_stdout_accepting(&["rev-parse", "--verify", "--quiet", notes_ref], &[0, 1])?;
+        let notes_ref_arg = format!("--ref={notes_ref}");
+        let merged = if have_local_notes {
+            git_working_dir.git(
+                &[
+                    "notes",
+                    &notes_ref_arg,
+                    "merge",
+                    "--strategy=cat_sort_uniq",
+                    "--quiet",
+                    &fetched_ref,
+                ],
+                quiet,
+            )?
+        } else {
+            git_working_dir.git(&["update-ref", notes_ref, &fetched_ref], quiet)?
+        };
+        if !merged {
+            bail!("merging the notes fetched from {remote:?} into {notes_ref:?} failed")
+        }
+        Ok(())
+    }
 }
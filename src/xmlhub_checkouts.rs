@@ -0,0 +1,75 @@
+//! The Git checkouts that the programs work with, and the version
+//! checker for the xmlhub repository. Kept apart from
+//! `xmlhub_indexer_defaults`, so that the metadata parser (which uses
+//! the latter) does not depend on the code running Git.
+
+use run_git::git::GitWorkingDir;
+
+use crate::{
+    checkout_context::CheckoutContext,
+    git_version::{GitVersion, SemVersion},
+    ref_or_owned::RefOrOwned,
+    xmlhub_check_version::XmlhubCheckVersion,
+    xmlhub_indexer_defaults::{PROGRAM_NAME, SOURCE_WEB_URL},
+    xmlhub_types::OutputFiles,
+};
+
+/// Information on the Git checkout of the xmlhub repo; used
+/// by xmlhub.rs
+pub const XMLHUB_CHECKOUT: CheckoutContext<&str> = CheckoutContext {
+    // This path is replaced with the BASE_PATH argument
+    working_dir_path: ".",
+    branch_name: "master",
+    supposed_upstream_git_url: "git@cevo-git.ethz.ch:cevo-resources/xmlhub.git",
+    supposed_upstream_web_url: "https://cevo-git.ethz.ch/cevo-resources/xmlhub",
+    expected_sub_paths: &["attributes.md"],
+};
+
+/// Information on the Git checkout of the xmlhub-experiments repo;
+/// used by xmlhub.rs
+pub const XMLHUB_EXPERIMENTS_CHECKOUT: CheckoutContext<&str> = CheckoutContext {
+    // This path is replaced with the BASE_PATH argument
+    working_dir_path: ".",
+    branch_name: "master",
+    supposed_upstream_git_url: "git@cevo-git.ethz.ch:cevo-resources/xmlhub-experiments.git",
+    supposed_upstream_web_url: "https://cevo-git.ethz.ch/cevo-resources/xmlhub-experiments",
+    expected_sub_paths: &["attributes.md"],
+};
+
+/// Information on the Git checkout of the xmlhub-indexer repo; used
+/// by both xmlhub.rs and make-release.rs
+pub const SOURCE_CHECKOUT: CheckoutContext<&str> = CheckoutContext {
+    // This path is only used by make-release.rs and
+    // replaced with the program argument for xmlhub.rs
+    working_dir_path: ".",
+    branch_name: "master",
+    supposed_upstream_git_url: "git@cevo-git.ethz.ch:cevo-resources/xmlhub-indexer.git",
+    supposed_upstream_web_url: SOURCE_WEB_URL,
+    expected_sub_paths: &["Cargo.toml", "src/bin/xmlhub.rs"],
+};
+
+/// Information on the Git checkout of the xmlhub-indexer-binaries repo; currently used
+/// only by make-release.rs
+pub const BINARIES_CHECKOUT: CheckoutContext<&str> = CheckoutContext {
+    working_dir_path: "../xmlhub-indexer-binaries/",
+    branch_name: "master",
+    supposed_upstream_git_url: "git@cevo-git.ethz.ch:cevo-resources/xmlhub-indexer-binaries.git",
+    supposed_upstream_web_url: "https://cevo-git.ethz.ch/cevo-resources/xmlhub-indexer-binaries",
+    expected_sub_paths: &["macOS", "keys"],
+};
+
+pub fn git_log_version_checker<'t>(
+    program_version: GitVersion<SemVersion>,
+    no_version_check: bool,
+    git_working_dir: RefOrOwned<'t, GitWorkingDir>,
+    output_files: RefOrOwned<'t, OutputFiles>,
+) -> XmlhubCheckVersion<'t> {
+    XmlhubCheckVersion {
+        program_name: PROGRAM_NAME,
+        program_version: program_version.into(),
+        no_version_check,
+        git_working_dir,
+        output_files,
+        require_version_statements: false,
+    }
+}
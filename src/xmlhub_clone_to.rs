@@ -8,10 +8,10 @@ use crate::{
     checkout_context::{CheckExpectedSubpathsExist, CheckoutContext},
     fixup_path::FixupPath,
     git_version::{GitVersion, SemVersion},
+    utillib::expand_path::expand_path,
+    xmlhub_checkouts::{git_log_version_checker, XMLHUB_CHECKOUT, XMLHUB_EXPERIMENTS_CHECKOUT},
     xmlhub_global_opts::{DrynessOpt, VersionCheckOpt},
-    xmlhub_indexer_defaults::{
-        git_log_version_checker, XMLHUB_CHECKOUT, XMLHUB_EXPERIMENTS_CHECKOUT,
-    },
+    xmlhub_indexer_defaults::DEFAULT_OUTPUT_FILES,
 };
 
 #[derive(clap::Parser, Debug)]
@@ -37,7 +37,9 @@ pub struct CloneToOpts {
     /// path. Otherwise the path is taken as desired path to the
     /// future directory ("base path") of the Git checkout, i.e. the
     /// repository is renamed to the last segment. (I.e. this works
-    /// similar to how the unix `cp` or `mv` commands work.)
+    /// similar to how the unix `cp` or `mv` commands work.) A leading
+    /// `~` and `$VAR` or `${VAR}` environment variable references
+    /// are expanded.
     pub target_path: Option<PathBuf>,
 }
 
@@ -100,8 +102,9 @@ pub fn clone_to_command(
         experiments,
     } = command_opts;
 
-    let target_path =
-        target_path.ok_or_else(|| anyhow!("missing BASE_PATH argument. Run --help for help."))?;
+    let target_path = expand_path(
+        &target_path.ok_or_else(|| anyhow!("missing BASE_PATH argument. Run --help for help."))?,
+    )?;
 
     let target = {
         let checkout = if experiments {
@@ -207,6 +210,7 @@ pub fn clone_to_command(
         program_version,
         no_version_check,
         target.checkout.git_working_dir().into(),
+        (&DEFAULT_OUTPUT_FILES).into(),
     );
 
     git_log_version_checker.check_git_log()?;
@@ -0,0 +1,432 @@
+//! Optional per-repository configuration of the index and of the
+//! checks on the files, read from
+//! `CONFIG_FILE_PATH` in the repository. If that file doesn't exist,
+//! the defaults are used.
+
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use toml::Value;
+
+use crate::{
+    beast_version::BeastVersionRange,
+    section::HighlightColors,
+    xmlhub_attributes::{
+        parse_box_order, specified_attribute_name, AttributeName, AttributeSpecification,
+        METADATA_SPECIFICATION,
+    },
+    xmlhub_indexer_defaults::{Symbols, BOX_TRUNCATE_LENGTH_DEFAULT},
+    xmlhub_types::XmlExtensions,
+};
+
+/// The path of the configuration file, relative to the top of the
+/// repository. It is meant to be committed, thus must not be in the
+/// daemon folder (`.xmlhub/`), which holds untracked runtime files.
+/// In TOML format, e.g.:
+///
+/// ```toml
+/// index_order = ["Keywords", "Contact"]
+/// allowed_version_range = ">=2.6, <3.0"
+/// box_order = ["Contact", "Keywords"]
+/// box_truncate_length = 500
+/// extensions = ["xml", "beast"]
+/// attachments = true
+/// document_symbol = "📄"
+///
+/// [require_attributes]
+/// "published" = ["DOI"]
+///
+/// [highlight_colors]
+/// red = "#c00000"
+/// ```
+pub const CONFIG_FILE_PATH: &str = "xmlhub.toml";
+
+/// The settings from `CONFIG_FILE_PATH`.
+#[derive(Debug, PartialEq)]
+pub struct XmlhubConfig {
+    /// The names of the indexed attributes whose indexes should
+    /// appear first in the "Index by attribute" section, in this
+    /// order.
+    pub index_order: Vec<String>,
+    /// The BEAST versions that the files may declare in their
+    /// "Version" attribute; files outside of it get a warning (or an
+    /// error with `--strict-version-range`).
+    pub allowed_version_range: Option<BeastVersionRange>,
+    /// Optional attributes that are required for the files in a
+    /// folder (a path relative to the top of the repository, `""`
+    /// for all files) and its subfolders.
+    pub require_attributes: BTreeMap<PathBuf, Vec<AttributeName>>,
+    /// The attributes to show first in the metadata table of the
+    /// file info boxes, in this order; the other attributes follow
+    /// in `METADATA_SPECIFICATION` order. Does not affect parsing or
+    /// the index.
+    pub box_order: Vec<AttributeName>,
+    /// The number of characters after which values of attributes
+    /// with `truncate_in_box` are cut off in the info boxes of the
+    /// HTML index file (the rest is shown via a "show more"
+    /// expander). `None` (configured as 0) means no truncation.
+    pub box_truncate_length: Option<usize>,
+    /// The file name extensions of the files that are treated as XML
+    /// files (`.xml` only by default).
+    pub extensions: XmlExtensions,
+    /// Whether the "Attachments" attribute (the files referenced by
+    /// the XML document) is extracted, with warnings for missing
+    /// files.
+    pub attachments: bool,
+    /// The colours of the highlighted section titles in the HTML
+    /// index file (the theme).
+    pub highlight_colors: HighlightColors,
+    /// The symbols used in the HTML index file, from the
+    /// `back_to_index_symbol` and `document_symbol` settings (see
+    /// `Symbols`).
+    pub symbols: Symbols,
+}
+
+impl Default for XmlhubConfig {
+    fn default() -> Self {
+        Self {
+            index_order: Vec::new(),
+            allowed_version_range: None,
+            require_attributes: BTreeMap::new(),
+            box_order: Vec::new(),
+            box_truncate_length: Some(BOX_TRUNCATE_LENGTH_DEFAULT),
+            extensions: XmlExtensions::default(),
+            attachments: false,
+            highlight_colors: HighlightColors::default(),
+            symbols: Symbols::default(),
+        }
+    }
+}
+
+impl XmlhubConfig {
+    /// Parse the contents of a `CONFIG_FILE_PATH` file. Unknown
+    /// settings and attribute names that are not indexed are
+    /// reported as errors, so that typos don't go unnoticed.
+    pub fn from_file_contents(contents: &str) -> Result<Self> {
+        let value: Value = contents.parse()?;
+        let table = value
+            .as_table()
+            .ok_or_else(|| anyhow!("expecting a table at the top level"))?;
+        let mut config = Self::default();
+        let mut back_to_index_symbol = None;
+        let mut document_symbol = None;
+        for (key, value) in table {
+            match key.as_str() {
+                "index_order" => {
+                    let names = value
+                        .as_array()
+                        .ok_or_else(|| anyhow!("`index_order`: expecting an array of strings"))?;
+                    for name in names {
+                        let name = name.as_str().ok_or_else(|| {
+                            anyhow!("`index_order`: expecting strings, got {name}")
+                        })?;
+                        let spec = METADATA_SPECIFICATION
+                            .iter()
+                            .find(|spec| spec.key.as_ref() == name)
+                            .ok_or_else(|| anyhow!("`index_order`: unknown attribute {name:?}"))?;
+                        if spec.key_string_preparation().is_none() {
+                            bail!("`index_order`: attribute {name:?} is not indexed")
+                        }
+                        if config.index_order.iter().any(|n| n == name) {
+                            bail!("`index_order`: attribute {name:?} is given more than once")
+                        }
+                        config.index_order.push(name.into());
+                    }
+                }
+                "allowed_version_range" => {
+                    let range = value.as_str().ok_or_else(|| {
+                        anyhow!("`allowed_version_range`: expecting a string, got {value}")
+                    })?;
+                    config.allowed_version_range =
+                        Some(range.parse().context("`allowed_version_range`")?);
+                }
+                "require_attributes" => {
+                    let folders = value.as_table().ok_or_else(|| {
+                        anyhow!("`require_attributes`: expecting a table of folder paths")
+                    })?;
+                    for (folder, names) in folders {
+                        let context = || anyhow!("`require_attributes`: folder {folder:?}");
+                        let folder = folder_path(folder).with_context(context)?;
+                        let names = names
+                            .as_array()
+                            .ok_or_else(|| anyhow!("expecting an array of strings"))
+                            .with_context(context)?
+                            .iter()
+                            .map(|name| {
+                                let name = name
+                                    .as_str()
+                                    .ok_or_else(|| anyhow!("expecting strings, got {name}"))?;
+                                specified_attribute_name(name)
+                            })
+                            .collect::<Result<_>>()
+                            .with_context(context)?;
+                        config.require_attributes.insert(folder, names);
+                    }
+                }
+                "box_order" => {
+                    let names = value
+                        .as_array()
+                        .ok_or_else(|| anyhow!("expecting an array of strings"))
+                        .and_then(|names| {
+                            names
+                                .iter()
+                                .map(|name| {
+                                    name.as_str()
+                                        .ok_or_else(|| anyhow!("expecting strings, got {name}"))
+                                })
+                                .collect::<Result<Vec<_>>>()
+                        })
+                        .context("`box_order`")?;
+                    config.box_order = parse_box_order(names).context("`box_order`")?;
+                }
+                "box_truncate_length" => {
+                    let length = value
+                        .as_integer()
+                        .and_then(|n| usize::try_from(n).ok())
+                        .ok_or_else(|| {
+                            anyhow!("`box_truncate_length`: expecting a number >= 0, got {value}")
+                        })?;
+                    config.box_truncate_length = (length > 0).then_some(length);
+                }
+                "extensions" => {
+                    let names = value
+                        .as_array()
+                        .ok_or_else(|| anyhow!("`extensions`: expecting an array of strings"))?
+                        .iter()
+                        .map(|name| {
+                            name.as_str().ok_or_else(|| {
+                                anyhow!("`extensions`: expecting strings, got {name}")
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    config.extensions = XmlExtensions::from_names(names).context("`extensions`")?;
+                }
+                "attachments" => {
+                    config.attachments = value.as_bool().ok_or_else(|| {
+                        anyhow!("`attachments`: expecting true or false, got {value}")
+                    })?;
+                }
+                "highlight_colors" => {
+                    let colors = value.as_table().ok_or_else(|| {
+                        anyhow!("`highlight_colors`: expecting a table of colours")
+                    })?;
+                    for (name, color) in colors {
+                        let color = color.as_str().ok_or_else(|| {
+                            anyhow!("`highlight_colors`: expecting strings, got {color}")
+                        })?;
+                        config
+                            .highlight_colors
+                            .set(name, color)
+                            .context("`highlight_colors`")?;
+                    }
+                }
+                "back_to_index_symbol" | "document_symbol" => {
+                    let symbol = value
+                        .as_str()
+                        .ok_or_else(|| anyhow!("`{key}`: expecting a string, got {value}"))?;
+                    if key == "back_to_index_symbol" {
+                        back_to_index_symbol = Some(symbol);
+                    } else {
+                        document_symbol = Some(symbol);
+                    }
+                }
+                _ => bail!("unknown setting `{key}`"),
+            }
+        }
+        config.symbols = Symbols::from_values(back_to_index_symbol, document_symbol);
+        Ok(config)
+    }
+
+    /// Read the configuration of the repository at `base_path`;
+    /// returns the defaults if there is no configuration file.
+    pub fn read(base_path: &Path) -> Result<Self> {
+        let path = base_path.join(CONFIG_FILE_PATH);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                Self::from_file_contents(&contents).with_context(|| anyhow!("parsing {path:?}"))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| anyhow!("reading {path:?}")),
+        }
+    }
+
+    /// All attribute specifications, with the ones listed in
+    /// `index_order` first (in that order), followed by the others in
+    /// `METADATA_SPECIFICATION` order.
+    pub fn index_ordered_specifications(&self) -> Vec<&'static AttributeSpecification> {
+        let position = |spec: &AttributeSpecification| {
+            self.index_order
+                .iter()
+                .position(|name| name == spec.key.as_ref())
+                .unwrap_or(usize::MAX)
+        };
+        let mut specs: Vec<&'static AttributeSpecification> =
+            METADATA_SPECIFICATION.iter().collect();
+        // (Stable sort, keeping the spec order for the unlisted ones)
+        specs.sort_by_key(|spec| position(spec));
+        specs
+    }
+}
+
+/// Parse a folder path given in the configuration, relative to the
+/// top of the repository; `""` stands for the top itself.
+fn folder_path(s: &str) -> Result<PathBuf> {
+    let path = Path::new(s);
+    let mut folder = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => folder.push(c),
+            Component::CurDir => (),
+            _ => bail!("expecting a relative path without `..`"),
+        }
+    }
+    Ok(folder)
+}
+
+#[test]
+fn t_xmlhub_config() -> Result<()> {
+    use crate::xmlhub_indexer_defaults::DocumentSymbol;
+
+    assert_eq!(
+        XmlhubConfig::from_file_contents("")?,
+        XmlhubConfig::default()
+    );
+    let config = XmlhubConfig::from_file_contents("index_order = [\"Keywords\", \"Contact\"]\n")?;
+    assert_eq!(config.index_order, ["Keywords", "Contact"]);
+
+    let keys: Vec<&str> = config
+        .index_ordered_specifications()
+        .iter()
+        .map(|spec| spec.key.as_ref())
+        .collect();
+    let default_keys: Vec<&str> = METADATA_SPECIFICATION
+        .iter()
+        .map(|spec| spec.key.as_ref())
+        .collect();
+    assert_eq!(keys[..2], ["Keywords", "Contact"]);
+    assert_eq!(
+        keys[2..],
+        default_keys
+            .iter()
+            .copied()
+            .filter(|key| !["Keywords", "Contact"].contains(key))
+            .collect::<Vec<_>>()
+    );
+
+    let err = |s: &str| XmlhubConfig::from_file_contents(s).unwrap_err().to_string();
+    assert_eq!(
+        err("index_order = [\"Kewords\"]"),
+        "`index_order`: unknown attribute \"Kewords\""
+    );
+    assert_eq!(
+        err("index_order = [\"Keywords\", \"Keywords\"]"),
+        "`index_order`: attribute \"Keywords\" is given more than once"
+    );
+    assert_eq!(err("index_ordr = []"), "unknown setting `index_ordr`");
+
+    let config = XmlhubConfig::from_file_contents("allowed_version_range = \">=2.6, <3.0\"\n")?;
+    assert_eq!(
+        config.allowed_version_range.expect("given").to_string(),
+        ">=2.6, <3.0"
+    );
+    assert!(err("allowed_version_range = \"2.6\"").starts_with("`allowed_version_range`"));
+
+    let config = XmlhubConfig::from_file_contents(
+        "[require_attributes]\n\"\" = [\"doi\"]\n\"a/b/\" = [\"DOI\", \"Contact\"]\n",
+    )?;
+    let names = |folder: &str| -> Vec<&str> {
+        config.require_attributes[Path::new(folder)]
+            .iter()
+            .map(|name| name.as_ref())
+            .collect()
+    };
+    assert_eq!(names(""), ["DOI"]);
+    assert_eq!(names("a/b"), ["DOI", "Contact"]);
+    let err = |s: &str| {
+        format!(
+            "{:#}",
+            XmlhubConfig::from_file_contents(s).expect_err("invalid")
+        )
+    };
+    assert_eq!(
+        err("[require_attributes]\n\"../a\" = [\"DOI\"]"),
+        "`require_attributes`: folder \"../a\": expecting a relative path without `..`"
+    );
+    assert_eq!(
+        err("[require_attributes]\na = [\"Citation via DOI\"]"),
+        "`require_attributes`: folder \"a\": unknown attribute name \"Citation via DOI\""
+    );
+
+    let config = XmlhubConfig::from_file_contents("box_order = [\"Contact\", \"DOI\"]\n")?;
+    assert_eq!(
+        config
+            .box_order
+            .iter()
+            .map(|name| name.as_ref())
+            .collect::<Vec<_>>(),
+        ["Contact", "DOI"]
+    );
+    assert_eq!(
+        err("box_order = [\"Contact\", \"doi\"]"),
+        "`box_order`: unknown attribute name \"doi\""
+    );
+
+    assert_eq!(
+        XmlhubConfig::from_file_contents("")?.box_truncate_length,
+        Some(BOX_TRUNCATE_LENGTH_DEFAULT)
+    );
+    assert_eq!(
+        XmlhubConfig::from_file_contents("box_truncate_length = 0")?.box_truncate_length,
+        None
+    );
+    assert_eq!(
+        err("box_truncate_length = -1"),
+        "`box_truncate_length`: expecting a number >= 0, got -1"
+    );
+
+    let config = XmlhubConfig::from_file_contents("extensions = [\"xml\", \"beast\"]")?;
+    assert!(config.extensions.matches(Path::new("a.beast")));
+    assert!(!XmlhubConfig::default()
+        .extensions
+        .matches(Path::new("a.beast")));
+    assert_eq!(
+        err("extensions = [\"xml\", \"x.beast\"]"),
+        "`extensions`: invalid file name extension \"x.beast\", expecting a name like \
+         \"xml\" or \"beast\""
+    );
+
+    assert!(XmlhubConfig::from_file_contents("attachments = true")?.attachments);
+    assert_eq!(
+        err("attachments = 1"),
+        "`attachments`: expecting true or false, got 1"
+    );
+
+    let config = XmlhubConfig::from_file_contents("[highlight_colors]\nred = \"#c00000\"\n")?;
+    assert_eq!(config.highlight_colors.red, "#c00000");
+    assert_eq!(config.highlight_colors.orange, "orange");
+    assert_eq!(
+        err("[highlight_colors]\ngreen = \"#2e7d32\""),
+        "`highlight_colors`: unknown highlight \"green\", expecting red or orange"
+    );
+
+    let config = XmlhubConfig::from_file_contents(
+        "back_to_index_symbol = \"^\"\ndocument_symbol = \"image:doc.png\"\n",
+    )?;
+    assert_eq!(
+        config.symbols,
+        Symbols {
+            back_to_index: "^".into(),
+            document: DocumentSymbol::Image("doc.png".into())
+        }
+    );
+    assert_eq!(
+        XmlhubConfig::from_file_contents("document_symbol = \"[doc]\"")?
+            .symbols
+            .document,
+        DocumentSymbol::Text("[doc]".into())
+    );
+    Ok(())
+}
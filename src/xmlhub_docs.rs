@@ -20,13 +20,15 @@ use crate::{
     modified_document::{Modification, ModifiedDocument},
     string_tree::StringTree,
     version_info::VersionInfo,
-    xmlhub_attributes::{specifications_to_html, METADATA_SPECIFICATION},
+    xmlhub_attributes::{
+        specifications_to_html, AttributeNeed, AttributeSource, METADATA_SPECIFICATION,
+    },
+    xmlhub_checkouts::{
+        BINARIES_CHECKOUT, SOURCE_CHECKOUT, XMLHUB_CHECKOUT, XMLHUB_EXPERIMENTS_CHECKOUT,
+    },
     xmlhub_global_opts::OpenOrPrintOpts,
     xmlhub_help::{save_basic_standalone_html_page, CSS_CODE_BACKGROUND_COLOR},
-    xmlhub_indexer_defaults::{
-        BINARIES_CHECKOUT, GENERATED_MESSAGE, HTML_ALLOCATOR_POOL, SOURCE_CHECKOUT,
-        XMLHUB_CHECKOUT, XMLHUB_EXPERIMENTS_CHECKOUT,
-    },
+    xmlhub_indexer_defaults::{GENERATED_MESSAGE, HTML_ALLOCATOR_POOL},
 };
 
 #[macro_export]
@@ -573,13 +575,46 @@ pub fn help_contributing_command() -> Result<()> {
 pub struct HelpAttributesOpts {
     #[clap(flatten)]
     open_or_print: OpenOrPrintOpts,
+
+    /// Only print the names of the attributes that can be given in
+    /// the XML files, each with a `[required]` or `[optional]` tag,
+    /// one per line (e.g. for piping into other programs).
+    #[clap(long, conflicts_with_all = ["open", "print"])]
+    short: bool,
+}
+
+/// The names of the attributes that can be given in the XML files,
+/// each followed by `[required]` or `[optional]`, one per line, in
+/// definition order.
+pub fn attributes_short_list() -> String {
+    METADATA_SPECIFICATION
+        .iter()
+        .filter_map(|spec| match &spec.source {
+            AttributeSource::Specified(source_spec) => {
+                let need = match source_spec.need {
+                    AttributeNeed::Optional => "optional",
+                    AttributeNeed::Required => "required",
+                };
+                Some(format!("{} [{need}]\n", spec.key.as_ref()))
+            }
+            AttributeSource::Derived(_) | AttributeSource::Extracted(_) => None,
+        })
+        .collect()
 }
 
 pub fn help_attributes_command(
     command_opts: HelpAttributesOpts,
     program_version: GitVersion<SemVersion>,
 ) -> Result<()> {
-    let HelpAttributesOpts { open_or_print } = command_opts;
+    let HelpAttributesOpts {
+        open_or_print,
+        short,
+    } = command_opts;
+
+    if short {
+        stdout().write_all(attributes_short_list().as_bytes())?;
+        return Ok(());
+    }
 
     if open_or_print.do_open() {
         open_help_page(WhichPage::Attributes, &program_version)?;
@@ -605,3 +640,63 @@ pub fn help_attributes_command(
 
     Ok(())
 }
+
+#[test]
+fn t_attribute_examples() -> Result<()> {
+    let md = make_attributes_md(false)?.to_string();
+    let row = |key: &str| -> &str {
+        md.split("<tr>")
+            .find(|row| row.contains(&format!("<i>{key}</i>")))
+            .unwrap_or_else(|| panic!("no row for {key:?}"))
+    };
+    assert!(
+        row("Version").contains("<i>Example:</i> <code>2.7.5</code>"),
+        "{}",
+        row("Version")
+    );
+    assert!(!row("Description").contains("Example:"));
+
+    let plain = |key: &str| -> String {
+        METADATA_SPECIFICATION
+            .iter()
+            .find(|spec| spec.key.as_ref() == key)
+            .expect("present")
+            .to_string()
+    };
+    assert!(plain("Version").contains("    example: 2.7.5\n"));
+    assert!(!plain("Description").contains("example:"));
+
+    // The examples are valid values
+    for spec in METADATA_SPECIFICATION {
+        if let Some(example) = spec.example {
+            crate::xmlhub_fileinfo::AttributeValue::from_str_and_spec(example, spec)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn t_attributes_short_list() {
+    let list = attributes_short_list();
+    let lines: Vec<&str> = list.lines().collect();
+    assert!(lines.contains(&"Keywords [required]"));
+    assert!(lines.contains(&"DOI [optional]"));
+    // Calculated attributes can't be given, thus are not listed
+    assert!(!list.contains("Citation via DOI"));
+    let mut n = 0;
+    for spec in METADATA_SPECIFICATION {
+        if let AttributeSource::Specified(source_spec) = &spec.source {
+            let tag = match source_spec.need {
+                AttributeNeed::Optional => "[optional]",
+                AttributeNeed::Required => "[required]",
+            };
+            assert!(
+                lines.contains(&format!("{} {tag}", spec.key.as_ref()).as_str()),
+                "{:?}",
+                spec.key
+            );
+            n += 1;
+        }
+    }
+    assert_eq!(lines.len(), n);
+}
@@ -0,0 +1,68 @@
+//! The error type of the library functions meant to be used by other
+//! programs (currently `xmlhub_read_file::read_file_metadata` and
+//! `xmlhub_index_documents::build_index_documents`), so
+//! that callers can tell the kinds of errors apart. The rest of the
+//! library and the `xmlhub` program use `anyhow`, into which
+//! `XmlhubError` converts via `?`.
+
+use std::path::{Path, PathBuf};
+
+use crate::{xmlhub_file_issues::FileErrors, xmlhub_fileinfo::Issue};
+
+#[derive(thiserror::Error, Debug)]
+pub enum XmlhubError {
+    /// The file could not be read
+    #[error("reading file {path:?}: {source}")]
+    IoError {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The file could be read, but is not valid XML, or its metadata
+    /// (the XML comments at the top) is invalid or incomplete
+    #[error("file {path:?}: {}", issue_messages(errors))]
+    ParseError { path: PathBuf, errors: Vec<Issue> },
+    /// Running Git in the repository at `path` failed (e.g. because
+    /// it is not a Git repository)
+    #[error("running git in {path:?}: {source:#}")]
+    GitError {
+        path: PathBuf,
+        #[source]
+        source: anyhow::Error,
+    },
+    /// The configuration file (`CONFIG_FILE_PATH`) at `path` could
+    /// not be read or is invalid
+    #[error("{source:#}")]
+    ConfigError {
+        path: PathBuf,
+        #[source]
+        source: anyhow::Error,
+    },
+}
+
+fn issue_messages(issues: &[Issue]) -> String {
+    let messages: Vec<&str> = issues.iter().map(|issue| issue.message.as_str()).collect();
+    messages.join("; ")
+}
+
+impl XmlhubError {
+    /// The path of the file (or, for `GitError`, the repository)
+    /// the error is about
+    pub fn path(&self) -> &Path {
+        match self {
+            XmlhubError::IoError { path, .. }
+            | XmlhubError::ParseError { path, .. }
+            | XmlhubError::GitError { path, .. }
+            | XmlhubError::ConfigError { path, .. } => path,
+        }
+    }
+}
+
+impl From<FileErrors> for XmlhubError {
+    fn from(FileErrors { path, errors }: FileErrors) -> Self {
+        XmlhubError::ParseError {
+            path: path.full_path(),
+            errors,
+        }
+    }
+}
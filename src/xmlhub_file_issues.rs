@@ -3,13 +3,18 @@
 // the end reporting them all (both on the command line and in the
 // output page).
 
-use std::io::Write;
+use std::{borrow::Cow, io::Write};
 
 use ahtml::{att, flat::Flat, util::SoftPre, HtmlAllocator, Node};
 use anyhow::Result;
-use run_git::git::BaseAndRelPath;
+use run_git::base_and_rel_path::BaseAndRelPath;
+use serde::Serialize;
 
-use crate::{hints::Hints, xmlhub_fileinfo::Issue, xmlhub_indexer_defaults::document_symbol};
+use crate::{
+    hints::Hints,
+    xmlhub_fileinfo::{FileId, Issue},
+    xmlhub_indexer_defaults::Symbols,
+};
 
 /// An error report with all errors that happened while processing one
 /// particular file. An error prevents the file from being included in
@@ -29,7 +34,7 @@ impl FileIssues for FileErrors {
         &self.errors
     }
 
-    fn info_box_id(&self) -> Option<usize> {
+    fn info_box_id(&self) -> Option<FileId> {
         None
     }
 }
@@ -42,7 +47,7 @@ impl FileIssues for FileErrors {
 #[derive(Debug)]
 pub struct FileWarnings<'t> {
     pub path: &'t BaseAndRelPath,
-    pub id: usize,
+    pub id: FileId,
     pub warnings: &'t Vec<Issue>,
 }
 
@@ -55,17 +60,63 @@ impl<'t> FileIssues for FileWarnings<'t> {
         &self.warnings
     }
 
-    fn info_box_id(&self) -> Option<usize> {
+    fn info_box_id(&self) -> Option<FileId> {
         Some(self.id)
     }
 }
 
+/// The issues (errors or warnings) of one particular file, owning
+/// the data, for reports that are not tied to the lifetime of the
+/// `FileInfo`s, e.g. for JSON output.
+#[derive(Debug, Serialize)]
+pub struct OwnedFileIssues {
+    pub path: String,
+    pub issues: Vec<Issue>,
+}
+
+impl From<FileErrors> for OwnedFileIssues {
+    fn from(value: FileErrors) -> Self {
+        let FileErrors { path, errors } = value;
+        Self {
+            path: path.rel_path().into(),
+            issues: errors,
+        }
+    }
+}
+
+impl FileIssues for OwnedFileIssues {
+    fn rel_path(&self) -> &str {
+        &self.path
+    }
+
+    fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+
+    fn info_box_id(&self) -> Option<FileId> {
+        None
+    }
+}
+
+/// All the hint texts in `issuess`, e.g. to pass to
+/// `Hints::with_messages`.
+pub fn issues_hints<'i, F: FileIssues + 'i>(
+    issuess: impl IntoIterator<Item = &'i F>,
+) -> impl Iterator<Item = &'i Cow<'static, str>> {
+    issuess.into_iter().flat_map(|issues| {
+        issues
+            .issues()
+            .iter()
+            .filter_map(|issue| issue.hint.as_ref())
+    })
+}
+
 pub trait FileIssues {
     fn rel_path(&self) -> &str;
     fn issues(&self) -> &[Issue];
     /// id for linking to html box (fallback is to link to the document itself
     /// via rel_path)
-    fn info_box_id(&self) -> Option<usize>;
+    fn info_box_id(&self) -> Option<FileId>;
 
     fn is_empty(&self) -> bool {
         self.issues().is_empty()
@@ -77,6 +128,8 @@ pub trait FileIssues {
         &self,
         show_path: bool,
         info_box_id_prefix: &str,
+        file_link_base: &str,
+        symbols: &Symbols,
         hints: &mut Hints,
         html: &HtmlAllocator,
     ) -> Result<Flat<Node>> {
@@ -89,6 +142,7 @@ pub trait FileIssues {
 
         let mut dt_body = html.new_vec();
         if show_path {
+            let file_href = format!("{file_link_base}{}", self.rel_path());
             dt_body.push(html.text("For ")?)?;
             if let Some(info_box_id) = self.info_box_id() {
                 dt_body.push(html.a(
@@ -100,16 +154,16 @@ pub trait FileIssues {
                 )?)?;
                 dt_body.push(html.nbsp()?)?;
                 dt_body.push(html.a(
-                    [att("href", self.rel_path()), att("title", "Open the file")],
-                    document_symbol(html)?,
+                    [att("href", &file_href), att("title", "Open the file")],
+                    symbols.document_html(html)?,
                 )?)?;
             } else {
                 dt_body.push(html.a(
-                    [att("href", self.rel_path()), att("title", "Open the file")],
+                    [att("href", &file_href), att("title", "Open the file")],
                     [
                         html.text(self.rel_path())?,
                         html.nbsp()?,
-                        document_symbol(html)?,
+                        symbols.document_html(html)?,
                     ],
                 )?)?;
             }
@@ -117,8 +171,17 @@ pub trait FileIssues {
         }
 
         let mut ul_body = html.new_vec();
-        for Issue { message, hint } in self.issues() {
-            let msg_html = SOFT_PRE.format(message, html)?;
+        for Issue {
+            message,
+            hint,
+            location,
+        } in self.issues()
+        {
+            let msg_html = if let Some(location) = location {
+                SOFT_PRE.format(&format!("{location}: {message}"), html)?
+            } else {
+                SOFT_PRE.format(message, html)?
+            };
             let item_html = if let Some(hint) = hint {
                 Flat::Two(msg_html, hints.intern(hint.clone()).to_html(html)?)
             } else {
@@ -134,7 +197,19 @@ pub trait FileIssues {
     /// Print as plaintext, for error reporting to stderr.
     fn print_plain<O: Write>(&self, hints: &mut Hints, out: &mut O) -> Result<()> {
         writeln!(out, "    For {:?}:", self.rel_path())?;
-        for Issue { message, hint } in self.issues() {
+        for Issue {
+            message,
+            hint,
+            location,
+        } in self.issues()
+        {
+            // Prefix with `path:line:col: ` if the location is known,
+            // for editors and CI tools to pick up.
+            let location_str = if let Some(location) = location {
+                format!("{}:{location}: ", self.rel_path())
+            } else {
+                "".into()
+            };
             let hint_ref_str = if let Some(hint) = hint {
                 hints.intern(hint.clone()).to_plain()
             } else {
@@ -146,10 +221,57 @@ pub trait FileIssues {
                 let is_last = i == lines.len() - 1;
 
                 let prefix = if is_first { "      * " } else { "        " };
+                let location_str = if is_first { &location_str } else { "" };
                 let postfix = if is_last { &hint_ref_str } else { "" };
-                writeln!(out, "{prefix}{}{postfix}", line)?;
+                writeln!(out, "{prefix}{location_str}{}{postfix}", line)?;
             }
         }
         Ok(())
     }
 }
+
+#[test]
+fn t_hints_dedup_stable() -> Result<()> {
+    let file = |path: &str, hints: &[&'static str]| OwnedFileIssues {
+        path: path.into(),
+        issues: hints
+            .iter()
+            .map(|hint| Issue {
+                message: format!("problem in {path}"),
+                hint: Some(Cow::Borrowed(*hint)),
+                location: None,
+            })
+            .collect(),
+    };
+    let print = |files: &[OwnedFileIssues]| -> Result<String> {
+        let mut hints = Hints::with_messages("t", issues_hints(files));
+        let mut out = Vec::new();
+        for file in files {
+            file.print_plain(&mut hints, &mut out)?;
+        }
+        hints.print_plain(&mut out)?;
+        Ok(String::from_utf8(out)?)
+    };
+
+    let a = file("a.xml", &["second hint"]);
+    let b = file("b.xml", &["first hint", "second hint"]);
+    let out = print(&[a, b])?;
+    assert_eq!(
+        out,
+        "    For \"a.xml\":\n\
+         \x20     * problem in a.xml [2]\n\
+         \x20   For \"b.xml\":\n\
+         \x20     * problem in b.xml [1]\n\
+         \x20     * problem in b.xml [2]\n\
+         \x20 1. first hint\n\
+         \x20 2. second hint\n"
+    );
+
+    // Processing the files in another order keeps the numbers
+    let a = file("a.xml", &["second hint"]);
+    let b = file("b.xml", &["first hint", "second hint"]);
+    let out2 = print(&[b, a])?;
+    assert!(out2.ends_with("  1. first hint\n  2. second hint\n"));
+    assert!(out2.contains("problem in a.xml [2]"));
+    Ok(())
+}
@@ -3,28 +3,36 @@
 //! operations (`impl` blocks) including parsing that information from
 //! strings and formatting the information as HTML.
 
-use std::{borrow::Cow, collections::BTreeMap, marker::PhantomData};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    marker::PhantomData,
+};
 
-use ahtml::{att, flat::Flat, util::SoftPre, AId, HtmlAllocator, Node};
+use ahtml::{att, flat::Flat, opt_att, util::SoftPre, AId, HtmlAllocator, Node};
 use anyhow::{bail, Result};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use pluraless::pluralized;
-use run_git::git::BaseAndRelPath;
+use run_git::base_and_rel_path::BaseAndRelPath;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
 use crate::{
     hints::Hints,
     html_util::anchor,
     util::{self, bool_to_yes_no, list_get_by_key},
-    xml_document::XMLDocument,
+    xml_document::{LineAndColumn, XMLDocument},
     xmlhub_attributes::{
-        sort_in_definition_order, AttributeKind, AttributeName, AttributeNeed, AttributeSource,
-        AttributeSpecification, DerivationSpecification, ExtractionSpecification,
-        METADATA_SPECIFICATION,
+        sort_in_box_order, sort_in_definition_order, AnchorNames, AttributeKind, AttributeName,
+        AttributeNeed, AttributeSource, AttributeSpecification, DerivationSpecification,
+        ExtractionSpecification, SourceSpecification, METADATA_SPECIFICATION,
     },
     xmlhub_autolink::Autolink,
-    xmlhub_file_issues::{FileIssues, FileWarnings},
+    xmlhub_file_issues::{issues_hints, FileIssues, FileWarnings},
     xmlhub_indexer_defaults::{
-        document_symbol, BACK_TO_INDEX_SYMBOL, FILEINFO_METADATA_BGCOLOR, FILEINFO_PATH_BGCOLOR,
+        Symbols, DEFAULT_SYMBOLS, FILEINFO_METADATA_BGCOLOR, FILEINFO_PATH_BGCOLOR,
         FILEINFO_WARNINGS_BGCOLOR,
     },
 };
@@ -37,6 +45,9 @@ use crate::{
 pub struct AttributeValue {
     spec: &'static AttributeSpecification,
     value: AttributeValueKind,
+    /// List items that were given more than once; only the first
+    /// occurrence is kept in `value`.
+    removed_duplicates: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -51,9 +62,25 @@ impl AttributeValue {
     /// Parse an input into the representation required by the given
     /// AttributeSpecification (like, a single string or
     /// lists). Returns an error if it couldn't do that, which happens
-    /// if the input is only whitespace but a value is required by the
-    /// spec.
+    /// if the input is only whitespace (or, for lists, only empty
+    /// items) but a value is required by the spec. List items given
+    /// more than once are only kept at their first position.
     pub fn from_str_and_spec(val: &str, spec: &'static AttributeSpecification) -> Result<Self> {
+        let need = match &spec.source {
+            AttributeSource::Specified(source_spec) => source_spec.need,
+            AttributeSource::Derived(_) | AttributeSource::Extracted(_) => AttributeNeed::Optional,
+        };
+        Self::from_str_spec_and_need(val, spec, need)
+    }
+
+    /// Same as `from_str_and_spec` but with the `need` given
+    /// explicitly instead of taken from the spec (used when optional
+    /// attributes are made required for some files).
+    pub fn from_str_spec_and_need(
+        val: &str,
+        spec: &'static AttributeSpecification,
+        need: AttributeNeed,
+    ) -> Result<Self> {
         let source_spec = match &spec.source {
             AttributeSource::Specified(source_spec) => source_spec,
             AttributeSource::Derived(_) | AttributeSource::Extracted(_) => bail!(
@@ -62,9 +89,12 @@ impl AttributeValue {
                 spec.key.as_ref()
             ),
         };
-        let value: AttributeValueKind = if val.is_empty() || val == "NA" {
-            match source_spec.need {
-                AttributeNeed::Optional => AttributeValueKind::NA,
+        // A value consisting only of whitespace (or, for lists, only
+        // of empty items, like ", , ,") counts as not given, the same
+        // as an empty value or "NA".
+        let missing = || -> Result<AttributeValueKind> {
+            match need {
+                AttributeNeed::Optional => Ok(AttributeValueKind::NA),
                 AttributeNeed::Required => {
                     bail!(
                         "attribute {:?} requires {}, but none given",
@@ -77,20 +107,35 @@ impl AttributeValue {
                     )
                 }
             }
+        };
+        let val = val.trim();
+        let mut removed_duplicates = Vec::new();
+        let value: AttributeValueKind = if val.is_empty() || val == "NA" {
+            missing()?
         } else {
             match source_spec.kind {
                 AttributeKind::String {
                     normalize_whitespace,
                 } => {
-                    let value = val.trim();
                     let value = if normalize_whitespace {
-                        util::normalize_whitespace(value)
+                        util::normalize_whitespace(val)
                     } else {
-                        value.into()
+                        val.into()
                     };
                     AttributeValueKind::String(value)
                 }
-                AttributeKind::StringList { input_separator } => {
+                AttributeKind::Flag => {
+                    if let Some(value) = util::parse_yes_no(val) {
+                        AttributeValueKind::Boolean(value)
+                    } else {
+                        bail!(
+                            "attribute {:?} expects \"yes\" or \"no\", but got {val:?}",
+                            spec.key.as_ref()
+                        )
+                    }
+                }
+                AttributeKind::StringList { input_separator }
+                | AttributeKind::Location { input_separator } => {
                     // (Note: there is no need to replace '\n' with ' '
                     // in `val` first, because the trim will remove
                     // those around values, and normalize_whitespace will
@@ -101,22 +146,59 @@ impl AttributeValue {
                         .filter(|s| !s.is_empty())
                         .collect();
                     if vals.is_empty() {
-                        match source_spec.need {
-                            AttributeNeed::Optional => AttributeValueKind::NA,
-                            AttributeNeed::Required => {
-                                bail!(
-                                    "values for attribute {:?} are required but missing",
-                                    spec.key
-                                )
-                            }
-                        }
+                        missing()?
                     } else {
+                        // Keep the first occurrence of each item
+                        let mut seen = HashSet::new();
+                        let (vals, duplicates): (Vec<String>, Vec<String>) =
+                            vals.into_iter().partition(|val| seen.insert(val.clone()));
+                        removed_duplicates = duplicates;
                         AttributeValueKind::StringList(vals)
                     }
                 }
             }
         };
-        Ok(AttributeValue { spec, value })
+        Ok(AttributeValue {
+            spec,
+            value,
+            removed_duplicates,
+        })
+    }
+
+    /// The value as it is written in the XML comment, in canonical
+    /// form: `NA` if unavailable, list items joined with the
+    /// separator followed by a space (with a space before it, too, if
+    /// it is not a comma).
+    pub fn to_comment_value(&self) -> String {
+        match &self.value {
+            AttributeValueKind::NA => "NA".into(),
+            AttributeValueKind::String(value) => value.clone(),
+            AttributeValueKind::StringList(values) => {
+                let separator = match &self.spec.source {
+                    AttributeSource::Specified(SourceSpecification {
+                        kind:
+                            AttributeKind::StringList { input_separator }
+                            | AttributeKind::Location { input_separator },
+                        ..
+                    }) => {
+                        if *input_separator == "," {
+                            ", ".into()
+                        } else {
+                            format!(" {input_separator} ")
+                        }
+                    }
+                    _ => ", ".into(),
+                };
+                values.join(&separator)
+            }
+            AttributeValueKind::Boolean(b) => bool_to_yes_no(*b).into(),
+        }
+    }
+
+    /// The list items that were given more than once (and removed,
+    /// see `from_str_and_spec`), in the order of their repetition.
+    pub fn removed_duplicates(&self) -> &[String] {
+        &self.removed_duplicates
     }
 
     /// Also works for single-value and unavailable attributes,
@@ -148,20 +230,29 @@ impl AttributeValue {
     /// StringList, to HTML. This is used for the file info boxes for
     /// both .html and .md files. An `ASlice<Node>` is a list of
     /// elements (nodes), directly usable as the body (child elements)
-    /// for another element.
-    fn to_html(&self, html: &HtmlAllocator) -> Result<Flat<Node>> {
-        let AttributeValue { spec, value } = self;
+    /// for another element. If `opts.truncate_length` is given and
+    /// the spec has `truncate_in_box`, longer string values are cut
+    /// off with a "show more" expander for the rest.
+    fn to_html(&self, opts: InfoBoxOpts, html: &HtmlAllocator) -> Result<Flat<Node>> {
+        let AttributeValue {
+            spec,
+            value,
+            removed_duplicates: _,
+        } = self;
         // Make a function `possibly_link_back` that takes the raw
         // `key_value` string and the prepared value and adds a link
         // to the index for `spec`key`, to the entry for `key_value`,
-        // if the spec says it is indexed.
+        // if the spec says it is indexed (and the index is present).
         let possibly_link_back = {
-            let key_string_preparation = spec.indexing.key_string_preparation();
+            let key_string_preparation =
+                spec.key_string_preparation().filter(|_| opts.link_to_index);
             move |key_value, body: Flat<Node>| -> Result<Flat<Node>> {
                 if let Some(key_string_preparation) = &key_string_preparation {
-                    let anchor_name = spec
-                        .key
-                        .anchor_name(&key_string_preparation.prepare_key_string(key_value));
+                    let key_string = key_string_preparation.prepare_key_string(key_value);
+                    let anchor_name = match opts.index_anchor_names.get(&spec.key) {
+                        Some(anchor_names) => anchor_names.get(&key_string),
+                        None => spec.key.anchor_name(&key_string),
+                    };
                     let mut vec = html.new_vec();
                     vec.push_flat(body)?;
                     // vec.push(html.nbsp()?)?;
@@ -170,7 +261,7 @@ impl AttributeValue {
                             att("href", format!("#{anchor_name}")),
                             att("title", "jump to index entry"),
                         ],
-                        html.text(BACK_TO_INDEX_SYMBOL)?,
+                        html.text(&opts.symbols.back_to_index)?,
                     )?)?;
                     Ok(Flat::Slice(vec.as_slice()))
                 } else {
@@ -193,7 +284,32 @@ impl AttributeValue {
                     input_line_separator: "\n",
                     trailing_br: false,
                 };
-                possibly_link_back(value, Flat::One(softpre.format(value.trim(), html)?))
+                let trimmed = value.trim();
+                let truncated = opts
+                    .truncate_length
+                    .filter(|_| spec.truncate_in_box)
+                    .and_then(|length| truncation_split(trimmed, length));
+                let body = if let Some((head, rest)) = truncated {
+                    html.details(
+                        [att("class", "truncated")],
+                        [
+                            html.summary(
+                                [],
+                                [
+                                    softpre.format(head, html)?,
+                                    html.span(
+                                        [att("class", "show_more")],
+                                        html.text("… show more")?,
+                                    )?,
+                                ],
+                            )?,
+                            softpre.format(rest.trim_start(), html)?,
+                        ],
+                    )?
+                } else {
+                    softpre.format(trimmed, html)?
+                };
+                possibly_link_back(value, Flat::One(body))
             }
             AttributeValueKind::StringList(value) => {
                 let mut body = html.new_vec();
@@ -215,6 +331,51 @@ impl AttributeValue {
     }
 }
 
+/// Options for the info boxes, see `FileInfo::to_info_box_html`.
+#[derive(Debug, Clone, Copy)]
+pub struct InfoBoxOpts<'c> {
+    /// Cut off long values after this many characters (see
+    /// `AttributeValue::to_html`)
+    pub truncate_length: Option<usize>,
+    /// Link indexed values to their entry in the "Index by
+    /// attribute" section; false if that section is left out.
+    pub link_to_index: bool,
+    /// The attributes to show first, in this order; the others
+    /// follow in `METADATA_SPECIFICATION` order.
+    pub box_order: &'c [AttributeName],
+    /// The symbols for the links to the file and back to the index
+    pub symbols: &'c Symbols,
+    /// The anchor names of the entries in the index, by attribute
+    /// (attributes missing here use `AttributeName::anchor_name`)
+    pub index_anchor_names: &'c BTreeMap<AttributeName, AnchorNames>,
+}
+
+static NO_INDEX_ANCHOR_NAMES: BTreeMap<AttributeName, AnchorNames> = BTreeMap::new();
+
+impl Default for InfoBoxOpts<'_> {
+    fn default() -> Self {
+        Self {
+            truncate_length: None,
+            link_to_index: true,
+            box_order: &[],
+            symbols: &DEFAULT_SYMBOLS,
+            index_anchor_names: &NO_INDEX_ANCHOR_NAMES,
+        }
+    }
+}
+
+/// Split `value` into the part shown before the "show more" expander
+/// and the rest, if it is longer than `length` characters. Cuts at
+/// the last whitespace within the limit, if there is any.
+fn truncation_split(value: &str, length: usize) -> Option<(&str, &str)> {
+    let (cut, _) = value.char_indices().nth(length)?;
+    let cut = match value[..cut].rfind(char::is_whitespace) {
+        Some(space) if space > 0 => space,
+        _ => cut,
+    };
+    Some(value.split_at(cut))
+}
+
 pub trait HavingDerivedValues {}
 
 /// Metadata stage 1: has only values extracted from XML comments
@@ -263,19 +424,59 @@ impl<H: HavingDerivedValues> Metadata<H> {
         })
     }
 
-    /// The entries in the same order as given in
-    /// `METADATA_SPECIFICATION`, with gaps where a key wasn't given
-    /// in the file.
-    fn sorted_entries(&self) -> Vec<(AttributeName, Option<&AttributeValue>)> {
+    /// A warning for each attribute in which list items were given
+    /// more than once (see `AttributeValue::removed_duplicates`).
+    pub fn duplicate_list_item_warnings(&self) -> Vec<Issue> {
         sort_in_definition_order(self.values.iter().map(|(k, v)| (*k, v)))
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let duplicates = value?.removed_duplicates();
+                if duplicates.is_empty() {
+                    return None;
+                }
+                let duplicates: Vec<&String> = duplicates.iter().unique().collect();
+                pluralized! { duplicates.len() => items, are }
+                Some(Issue {
+                    message: format!(
+                        "attribute {:?}: the {items} {} {are} given more than once",
+                        key.as_ref(),
+                        duplicates.iter().map(|item| format!("{item:?}")).join(", ")
+                    ),
+                    hint: Some("Please remove the repetitions.".into()),
+                    location: None,
+                })
+            })
+            .collect()
     }
 
-    /// An HTML table with all metadata.
-    fn to_html(&self, html: &HtmlAllocator) -> Result<AId<Node>> {
+    /// The entries in the same order as given in
+    /// `METADATA_SPECIFICATION` except for those listed in `order`
+    /// coming first, with gaps where a key wasn't given in the file.
+    fn sorted_entries(
+        &self,
+        order: &[AttributeName],
+    ) -> Vec<(AttributeName, Option<&AttributeValue>)> {
+        sort_in_box_order(self.values.iter().map(|(k, v)| (*k, v)), order)
+    }
+
+    /// An HTML table with all metadata, with the attributes listed in
+    /// `opts.box_order` first.
+    fn to_html(&self, opts: InfoBoxOpts, html: &HtmlAllocator) -> Result<AId<Node>> {
         let mut table_body = html.new_vec();
-        for (attribute_name, opt_attval) in self.sorted_entries() {
+        for (attribute_name, opt_attval) in self.sorted_entries(opts.box_order) {
+            let spec = list_get_by_key(METADATA_SPECIFICATION, |spec| &spec.key, &attribute_name);
+            if opt_attval.is_none()
+                && matches!(
+                    spec.map(|spec| &spec.source),
+                    Some(AttributeSource::Extracted(_))
+                )
+            {
+                // Extracted attributes are only missing when they
+                // don't apply to the file
+                continue;
+            }
             let attval_html: Flat<Node> = if let Some(attval) = opt_attval {
-                attval.to_html(html)?
+                attval.to_html(opts, html)?
             } else {
                 // Entry is missing in the file; show that fact.
                 // (Also report that top-level as a warning? That
@@ -303,6 +504,8 @@ impl<H: HavingDerivedValues> Metadata<H> {
                             // The above CSS is lost via Markdown, thus also try:
                             att("valign", "top"),
                             att("align", "right"),
+                            // Inline help for the reader, on hover
+                            opt_att("title", spec.map(|spec| spec.description())),
                         ],
                         html.i([], [html.text(attribute_name.as_ref())?, html.text(":")?])?,
                     )?,
@@ -317,24 +520,43 @@ impl<H: HavingDerivedValues> Metadata<H> {
 impl Metadata<WithCommentsOnly> {
     /// Generate attributes extracted from the XML document body as
     /// listed in `METADATA_SPECIFICATION` with
-    /// `AttributeSource::Extracted`
+    /// `AttributeSource::Extracted`, except for those in `skip`
+    /// (attributes that are disabled in the repository
+    /// configuration).
     pub fn add_extracted_attributes(
         self,
         document: &XMLDocument,
+        skip: &[AttributeName],
         warnings: &mut Vec<Issue>,
     ) -> Metadata<WithExtractedValues> {
         let mut values = self.values;
 
         for spec in METADATA_SPECIFICATION {
+            if skip.contains(&spec.key) {
+                continue;
+            }
             if let AttributeSpecification {
-                key,
+                key: _,
                 source: AttributeSource::Extracted(ExtractionSpecification { extractor }),
                 autolink: _,
                 indexing: _,
+                extract_index_regex: _,
+                example: _,
+                truncate_in_box: _,
             } = spec
             {
-                let value = extractor(document, warnings);
-                values.insert(*key, AttributeValue { spec, value });
+                if let Some(value) = extractor(document, warnings) {
+                    insert_reconciled(
+                        &mut values,
+                        AttributeValue {
+                            spec,
+                            value,
+                            removed_duplicates: Vec::new(),
+                        },
+                        "the XML document",
+                        warnings,
+                    );
+                }
             }
         }
 
@@ -353,7 +575,7 @@ impl Metadata<WithExtractedValues> {
 
         for spec in METADATA_SPECIFICATION {
             if let AttributeSpecification {
-                key,
+                key: _,
                 source:
                     AttributeSource::Derived(DerivationSpecification {
                         derived_from,
@@ -361,6 +583,9 @@ impl Metadata<WithExtractedValues> {
                     }),
                 autolink: _,
                 indexing: _,
+                extract_index_regex: _,
+                example: _,
+                truncate_in_box: _,
             } = spec
             {
                 // XX could re-use `from` across for loops with
@@ -370,7 +595,16 @@ impl Metadata<WithExtractedValues> {
                     from.push(values.get(from_key));
                 }
                 let value = derivation(&from, warnings);
-                values.insert(*key, AttributeValue { spec, value });
+                insert_reconciled(
+                    &mut values,
+                    AttributeValue {
+                        spec,
+                        value,
+                        removed_duplicates: Vec::new(),
+                    },
+                    "the derivation from other attributes",
+                    warnings,
+                );
             }
         }
 
@@ -381,28 +615,140 @@ impl Metadata<WithExtractedValues> {
     }
 }
 
-#[derive(Debug)]
+/// Compare a value for the attribute `key` that was obtained from
+/// `other_source` (like the XML document) with the value that is
+/// already present for it (usually the one provided by the user). If
+/// the two values don't `agree`, return a warning showing both.
+pub fn check_value_conflict<T: Display + ?Sized>(
+    key: AttributeName,
+    existing_value: &T,
+    other_source: &str,
+    other_value: &T,
+    agree: impl FnOnce(&T, &T) -> bool,
+    hint: Option<Cow<'static, str>>,
+) -> Option<Issue> {
+    if agree(existing_value, other_value) {
+        None
+    } else {
+        Some(Issue {
+            message: format!(
+                "conflicting values for attribute {:?}: the value given in the file is \
+                 \"{existing_value}\", but {other_source} gives \"{other_value}\"",
+                key.as_ref()
+            ),
+            hint,
+            location: None,
+        })
+    }
+}
+
+/// Insert a calculated (extracted or derived) value into `values`.
+/// If a value for the same attribute is already present and
+/// differs, keep the existing value and report the discrepancy in
+/// `warnings` instead of silently overwriting it.
+fn insert_reconciled(
+    values: &mut BTreeMap<AttributeName, AttributeValue>,
+    value: AttributeValue,
+    source: &str,
+    warnings: &mut Vec<Issue>,
+) {
+    let key = value.spec.key;
+    if let Some(existing) = values.get(&key) {
+        if let Some(issue) = check_value_conflict(
+            key,
+            existing.as_string_list().join(", ").as_str(),
+            source,
+            value.as_string_list().join(", ").as_str(),
+            |a, b| a == b,
+            None,
+        ) {
+            warnings.push(issue);
+        }
+    } else {
+        values.insert(key, value);
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct Issue {
     pub message: String,
     pub hint: Option<Cow<'static, str>>,
+    /// Where in the file the issue is, if known
+    pub location: Option<LineAndColumn>,
+}
+
+/// The id of a file, used to refer to its info box in
+/// document-local links in the generated HTML/Markdown files. It is
+/// derived from a hash of the file's path, so that adding or
+/// removing a file does not change the ids (and thus the anchors) of
+/// the other files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u64);
+
+impl FileId {
+    /// The id for `rel_path`; `attempt` is increased to get
+    /// alternative ids in case of hash collisions.
+    pub fn from_rel_path(rel_path: &str, attempt: u32) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(rel_path.as_bytes());
+        if attempt > 0 {
+            hasher.update(attempt.to_be_bytes());
+        }
+        let hash = hasher.finalize();
+        let mut bytes = [0; 8];
+        bytes[2..].copy_from_slice(&hash[..6]);
+        FileId(u64::from_be_bytes(bytes))
+    }
+
+    /// The ids for the given paths, in the same order; paths that
+    /// collide get alternative ids (deterministically, if `paths` is
+    /// sorted).
+    pub fn for_paths(paths: &[BaseAndRelPath]) -> Vec<Self> {
+        let mut seen = HashSet::new();
+        paths
+            .iter()
+            .map(|path| {
+                let mut attempt = 0;
+                loop {
+                    let id = FileId::from_rel_path(path.rel_path(), attempt);
+                    if seen.insert(id) {
+                        return id;
+                    }
+                    attempt += 1;
+                }
+            })
+            .collect()
+    }
+}
+
+impl Display for FileId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:012x}", self.0)
+    }
 }
 
 /// The whole, concrete, information on one particular file.
 #[derive(Debug)]
 pub struct FileInfo<H: HavingDerivedValues> {
-    pub id: usize,
+    pub id: FileId,
     pub path: BaseAndRelPath,
     pub metadata: Metadata<H>,
     pub warnings: Vec<Issue>,
+    /// The header comments that are not metadata, shown as "Notes"
+    /// in the info box (only collected with `--show-file-comments`)
+    pub notes: Vec<String>,
 }
 
 // For FileInfo to go into a BTreeSet (`BTreeSet<&FileInfo>` further
-// below), it needs to be orderable. Only `id` is relevant for that
-// (and the other types have no Ord implementation), thus write
-// implementations manually:
+// below), it needs to be orderable. Files are listed by path, with
+// `id` to disambiguate (the other types have no Ord
+// implementation), thus write implementations manually:
 impl<H: HavingDerivedValues> Ord for FileInfo<H> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.id.cmp(&other.id)
+        self.path
+            .rel_path()
+            .cmp(other.path.rel_path())
+            .then(self.id.cmp(&other.id))
     }
 }
 impl<H: HavingDerivedValues> PartialOrd for FileInfo<H> {
@@ -412,11 +758,34 @@ impl<H: HavingDerivedValues> PartialOrd for FileInfo<H> {
 }
 impl<H: HavingDerivedValues> PartialEq for FileInfo<H> {
     fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+        self.cmp(other).is_eq()
     }
 }
 impl<H: HavingDerivedValues> Eq for FileInfo<H> {}
 
+impl FileInfo<WithExtractedValues> {
+    /// Build the derived attribute values (see
+    /// `Metadata::add_derived_attributes`). Errors during this phase
+    /// are stored as warnings, since some could be temporary.
+    pub fn add_derived_attributes(self) -> FileInfo<WithDerivedValues> {
+        let FileInfo {
+            id,
+            path,
+            metadata,
+            mut warnings,
+            notes,
+        } = self;
+        let metadata = metadata.add_derived_attributes(&mut warnings);
+        FileInfo {
+            id,
+            path,
+            metadata,
+            warnings,
+            notes,
+        }
+    }
+}
+
 impl<H: HavingDerivedValues> FileInfo<H> {
     /// Give a temporary FileWarnings object with the same trait as
     /// FileErrors, for warnings display.
@@ -432,12 +801,17 @@ impl<H: HavingDerivedValues> FileInfo<H> {
         }
     }
 
-    /// Show in a box with a table of the metadata
+    /// Show in a box with a table of the metadata. The link to the
+    /// file is its path prefixed with `file_link_base` (the path from
+    /// the index files to the top of the repository, e.g. `""`).
+    /// How the values are shown is configured via `opts`.
     pub fn to_info_box_html(
         &self,
         html: &HtmlAllocator,
         id_prefix: &str,
         file_path_or_name: &str,
+        file_link_base: &str,
+        opts: InfoBoxOpts,
     ) -> Result<AId<Node>> {
         let id_string = format!("{id_prefix}-{}", self.id);
         anchor(
@@ -462,18 +836,14 @@ impl<H: HavingDerivedValues> FileInfo<H> {
                                     [
                                         att(
                                             "href",
-                                            // (This would need path
-                                            // calculation if the index files
-                                            // weren't written to the
-                                            // top-level directory)
-                                            self.path.rel_path(),
+                                            format!("{file_link_base}{}", self.path.rel_path()),
                                         ),
                                         att("title", "Open the file"),
                                     ],
                                     [
                                         html.text(file_path_or_name)?,
                                         html.nbsp()?,
-                                        document_symbol(html)?,
+                                        opts.symbols.document_html(html)?,
                                     ],
                                 )?,
                             )?,
@@ -483,19 +853,43 @@ impl<H: HavingDerivedValues> FileInfo<H> {
                         [att("class", "fileinfo_metadata")],
                         html.td(
                             [att("bgcolor", FILEINFO_METADATA_BGCOLOR)],
-                            self.metadata.to_html(html)?,
+                            self.metadata.to_html(opts, html)?,
                         )?,
                     )?,
+                    if self.notes.is_empty() {
+                        html.empty_node()?
+                    } else {
+                        let softpre = SoftPre::default();
+                        let mut notes = html.new_vec();
+                        for note in &self.notes {
+                            notes.push(html.div([], softpre.format(note, html)?)?)?;
+                        }
+                        html.tr(
+                            [att("class", "fileinfo_notes")],
+                            html.td(
+                                [att("bgcolor", FILEINFO_METADATA_BGCOLOR)],
+                                [
+                                    html.div([], html.b([], html.text("Notes:")?)?)?,
+                                    html.div([], notes)?,
+                                ],
+                            )?,
+                        )?
+                    },
                     if let Some(warnings) = self.opt_warnings() {
                         pluralized! { warnings.issues().len() => Warnings }
                         html.tr(
                             [att("class", "fileinfo_warnings")],
                             html.td([att("bgcolor", FILEINFO_WARNINGS_BGCOLOR)], {
                                 let hints_id = format!("file{}", self.id);
-                                let mut hints = Hints::new(&hints_id);
+                                let mut hints =
+                                    Hints::with_messages(&hints_id, issues_hints([&warnings]));
                                 let items = warnings.to_html(
                                     false, // XX where is this defined?
-                                    "box", &mut hints, html,
+                                    "box",
+                                    file_link_base,
+                                    opts.symbols,
+                                    &mut hints,
+                                    html,
                                 )?;
                                 let hints_html = hints.to_html(html)?;
                                 let mut body = items.to_vec(html)?;
@@ -515,3 +909,182 @@ impl<H: HavingDerivedValues> FileInfo<H> {
         )
     }
 }
+
+#[test]
+fn t_whitespace_only_values() {
+    use crate::xmlhub_attributes::attribute_specification_by_name;
+
+    let spec = |name| attribute_specification_by_name(name).expect("present");
+    // Required list, required string, optional list, optional string,
+    // optional flag
+    let keywords = spec("Keywords");
+    let contact = spec("Contact");
+    let doi = spec("DOI");
+    let description = spec("Description");
+    let published = spec("Published");
+    for val in ["", " ", ", , ,", " \n\t", " NA "] {
+        for (spec, expected) in [(keywords, "values"), (contact, "a value")] {
+            if spec.key == contact.key && val.contains(',') {
+                // Not a list, thus a valid value
+                continue;
+            }
+            let err = AttributeValue::from_str_and_spec(val, spec).expect_err(val);
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "attribute {:?} requires {expected}, but none given",
+                    spec.key.as_ref()
+                ),
+                "{val:?}"
+            );
+        }
+        for spec in [doi, description, published] {
+            if spec.key != doi.key && val.contains(',') {
+                continue;
+            }
+            let value = AttributeValue::from_str_and_spec(val, spec).expect(val);
+            assert!(
+                matches!(value.value, AttributeValueKind::NA),
+                "{val:?} for {:?} gives {value:?}",
+                spec.key
+            );
+        }
+    }
+    let value = AttributeValue::from_str_and_spec(" , foo, ,", keywords).expect("has a value");
+    assert_eq!(value.as_string_list().as_ref(), ["foo"]);
+}
+
+#[test]
+fn t_duplicate_list_items() {
+    let keywords =
+        crate::xmlhub_attributes::attribute_specification_by_name("Keywords").expect("present");
+    let value = AttributeValue::from_str_and_spec("phylo, foo, phylo, bar, foo, phylo", keywords)
+        .expect("valid");
+    assert_eq!(value.as_string_list().as_ref(), ["phylo", "foo", "bar"]);
+    assert_eq!(value.removed_duplicates(), ["phylo", "foo", "phylo"]);
+    let value = AttributeValue::from_str_and_spec("foo, bar", keywords).expect("valid");
+    assert!(value.removed_duplicates().is_empty());
+}
+
+#[test]
+fn t_check_value_conflict() {
+    let key = crate::xmlhub_attributes::attribute_specification_by_name("Version")
+        .expect("present")
+        .key;
+    let same_major = |a: &str, b: &str| a.split('.').take(2).eq(b.split('.').take(2));
+    assert!(
+        check_value_conflict(key, "2.7.3", "the document", "2.7.1", same_major, None).is_none()
+    );
+    let issue = check_value_conflict(key, "2.6", "the document", "2.7.1", same_major, None)
+        .expect("conflict");
+    assert_eq!(
+        issue.message,
+        "conflicting values for attribute \"Version\": the value given in the file is \
+         \"2.6\", but the document gives \"2.7.1\""
+    );
+}
+
+#[test]
+fn t_insert_reconciled() {
+    let spec = crate::xmlhub_attributes::attribute_specification_by_name("Contains sequence data")
+        .expect("present");
+    let mut values = BTreeMap::new();
+    let mut warnings = Vec::new();
+    let value = |b| AttributeValue {
+        spec,
+        value: AttributeValueKind::Boolean(b),
+        removed_duplicates: Vec::new(),
+    };
+
+    insert_reconciled(&mut values, value(true), "the first source", &mut warnings);
+    insert_reconciled(&mut values, value(true), "the second source", &mut warnings);
+    assert!(warnings.is_empty());
+
+    insert_reconciled(&mut values, value(false), "the third source", &mut warnings);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].message.contains("\"yes\""));
+    assert!(warnings[0]
+        .message
+        .contains("the third source gives \"no\""));
+    // The existing value is kept
+    assert_eq!(values[&spec.key].as_string_list().as_ref(), ["yes"]);
+}
+
+#[test]
+fn t_metadata_key_tooltips() -> Result<()> {
+    use crate::xmlhub_attributes::attribute_specification_by_name;
+    use crate::xmlhub_indexer_defaults::HTML_ALLOCATOR_POOL;
+
+    let keywords = attribute_specification_by_name("Keywords").expect("present");
+    let doi = attribute_specification_by_name("Citation via DOI").expect("present");
+    let mut values = BTreeMap::new();
+    values.insert(
+        keywords.key,
+        AttributeValue::from_str_and_spec("foo, bar", keywords)?,
+    );
+    let metadata: Metadata<WithCommentsOnly> = Metadata::new(values);
+    let html = HTML_ALLOCATOR_POOL.get();
+    let table = html.preserialize(metadata.to_html(InfoBoxOpts::default(), &html)?)?;
+    let table = table.as_str();
+    assert!(table.contains(
+        "<td class=\"metadata_key\" valign=\"top\" align=\"right\" \
+         title=\"Words for the keyword index, for useful finding. \
+         Example: sampling-through-time, birth-death skyline\">"
+    ));
+    // Also present for missing entries, and for calculated attributes
+    assert!(table.contains(
+        "title=\"DOI of papers that this file was used for, or that describe it. \
+         Example: 10.1371/journal.pcbi.1006650\""
+    ));
+    assert!(table.contains(&format!(
+        "title=\"{}\"",
+        doi.description().replace('"', "&quot;")
+    )));
+    Ok(())
+}
+
+#[test]
+fn t_metadata_box_order() -> Result<()> {
+    use crate::xmlhub_attributes::{attribute_specification_by_name, parse_box_order};
+    use crate::xmlhub_indexer_defaults::HTML_ALLOCATOR_POOL;
+
+    let keywords = attribute_specification_by_name("Keywords").expect("present");
+    let contact = attribute_specification_by_name("Contact").expect("present");
+    let mut values = BTreeMap::new();
+    values.insert(
+        keywords.key,
+        AttributeValue::from_str_and_spec("foo, bar", keywords)?,
+    );
+    values.insert(
+        contact.key,
+        AttributeValue::from_str_and_spec("Some One", contact)?,
+    );
+    let metadata: Metadata<WithCommentsOnly> = Metadata::new(values);
+    let html = HTML_ALLOCATOR_POOL.get();
+    let positions = |box_order: &[AttributeName]| -> Result<Vec<usize>> {
+        let table = html.preserialize(metadata.to_html(
+            InfoBoxOpts {
+                box_order,
+                ..Default::default()
+            },
+            &html,
+        )?)?;
+        let table = table.as_str();
+        Ok(["Contact:", "Keywords:", "Version:", "Description:"]
+            .iter()
+            .map(|key| table.find(key).expect("present"))
+            .collect())
+    };
+
+    // Default: definition order
+    let p = positions(&[])?;
+    assert!(p[1] < p[2] && p[2] < p[3] && p[3] < p[0]);
+
+    // Configured: Contact and Keywords first, the rest in definition order
+    let p = positions(&parse_box_order(["Contact", "Keywords"])?)?;
+    assert!(p[0] < p[1] && p[1] < p[2] && p[2] < p[3]);
+
+    assert!(parse_box_order(["Contact", "keywords"]).is_err());
+    assert!(parse_box_order(["Contact", "Contact"]).is_err());
+    Ok(())
+}
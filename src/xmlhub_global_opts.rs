@@ -2,6 +2,19 @@
 //! program options, but options that are used across multiple
 //! subcommands.
 
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    path::{Component, Path},
+};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    xmlhub_indexer_defaults::DEFAULT_OUTPUT_FILES,
+    xmlhub_types::{OutputFile, OutputFiles, XmlExtensions},
+};
+
 #[derive(clap::Args, Debug, Clone)]
 pub struct VerbosityOpt {
     /// Show external modifying commands that are run. (Note that this
@@ -10,7 +23,7 @@ pub struct VerbosityOpt {
     pub verbose: bool,
 }
 
-#[derive(clap::Args, Debug, Clone)]
+#[derive(clap::Args, Debug, Clone, Default)]
 pub struct QuietOpt {
     /// Suppress some unimportant output. (Note that this does
     /// not disable `--verbose` if that option is given.)
@@ -54,7 +67,7 @@ pub struct DrynessOpt {
     pub dry_run: bool,
 }
 
-#[derive(clap::Args, Debug)]
+#[derive(clap::Args, Debug, Clone)]
 pub struct VersionCheckOpt {
     /// Do not check the program version against versions specified in
     /// the automatic commit messages in the xmlhub repo. Only use if
@@ -129,6 +142,149 @@ pub struct BlindingOpts {
     /// file, though; if the file is still too large even after
     /// blinding, you may want to use the `--blind-all` option or find
     /// out why your file is so large.
-    #[clap(long, default_value = "5000000")]
+    #[clap(long, default_value_t = RECOMMENDED_MAX_FILE_SIZE_BYTES_DEFAULT)]
     pub recommended_max_file_size_bytes: usize,
 }
+
+/// The default for `--recommended-max-file-size-bytes`.
+pub const RECOMMENDED_MAX_FILE_SIZE_BYTES_DEFAULT: usize = 5000000;
+
+impl Default for BlindingOpts {
+    /// The same as when no options are given: the sequences are
+    /// blinded.
+    fn default() -> Self {
+        Self {
+            no_blind: false,
+            blind_all: false,
+            blind_comment: None,
+            recommended_max_file_size_bytes: RECOMMENDED_MAX_FILE_SIZE_BYTES_DEFAULT,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct TrashOpts {
+    /// Overwrite existing files directly instead of moving them to
+    /// the system trash bin first (useful on servers or CI systems
+    /// without a trash bin). Asks for confirmation unless `--yes` is
+    /// given.
+    #[clap(long)]
+    pub no_trash: bool,
+
+    /// Do not ask for confirmation when overwriting files with
+    /// `--no-trash`.
+    #[clap(long)]
+    pub yes: bool,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct OutputFilesOpts {
+    /// The path (relative to the top of the repository) of the index
+    /// file in HTML format. The default is `README.html`.
+    #[clap(long)]
+    html_file: Option<String>,
+
+    /// The path (relative to the top of the repository) of the index
+    /// file in Markdown format. The default is `README.md`.
+    #[clap(long)]
+    md_file: Option<String>,
+
+    /// The path (relative to the top of the repository) of the file
+    /// describing the attributes. The default is `attributes.md`.
+    #[clap(long)]
+    attributes_file: Option<String>,
+}
+
+impl OutputFilesOpts {
+    /// The output files with the defaults replaced by the given
+    /// options. Errors for absolute paths, paths with `..`
+    /// components (which could point outside the repository), and
+    /// duplicate paths.
+    pub fn output_files(&self) -> Result<OutputFiles> {
+        let Self {
+            html_file,
+            md_file,
+            attributes_file,
+        } = self;
+        let output_file = |opt: &Option<String>, default: OutputFile| -> Result<OutputFile> {
+            if let Some(path) = opt {
+                let is_inside_repository = Path::new(path)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+                if path.is_empty() || !is_inside_repository {
+                    bail!(
+                        "output file path must be a non-empty relative path without `..`, \
+                         got {path:?}"
+                    )
+                }
+                Ok(OutputFile {
+                    path_from_repo_top: Cow::Owned(path.clone()),
+                })
+            } else {
+                Ok(default)
+            }
+        };
+        let output_files = OutputFiles {
+            html_file: output_file(html_file, DEFAULT_OUTPUT_FILES.html_file)?,
+            md_file: output_file(md_file, DEFAULT_OUTPUT_FILES.md_file)?,
+            attributes_file: output_file(attributes_file, DEFAULT_OUTPUT_FILES.attributes_file)?,
+        };
+        let paths = output_files.paths_from_repo_top();
+        if paths.iter().collect::<HashSet<_>>().len() != paths.len() {
+            bail!("the output file paths must all be different, got {paths:?}")
+        }
+        Ok(output_files)
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ExtensionsOpt {
+    /// The file name extensions of the files to treat as XML files,
+    /// comma-separated, e.g. `xml,beast` (which also covers files
+    /// ending in `.xml.beast`). Overrides the `extensions` setting in
+    /// `xmlhub.toml`, whose default is `xml`.
+    #[clap(long)]
+    extensions: Option<String>,
+}
+
+impl ExtensionsOpt {
+    /// The extensions if given, `None` to use the configured ones.
+    pub fn xml_extensions(&self) -> Result<Option<XmlExtensions>> {
+        let Self { extensions } = self;
+        extensions
+            .as_deref()
+            .map(XmlExtensions::from_list)
+            .transpose()
+    }
+}
+
+#[test]
+fn t_output_files_opts() {
+    let opts = |html_file: &str| OutputFilesOpts {
+        html_file: Some(html_file.into()),
+        md_file: None,
+        attributes_file: None,
+    };
+    assert_eq!(
+        opts("docs/index.html")
+            .output_files()
+            .unwrap()
+            .html_file
+            .path_from_repo_top,
+        "docs/index.html"
+    );
+    for path in [
+        "",
+        "/tmp/index.html",
+        "../index.html",
+        "docs/../../index.html",
+    ] {
+        let error = opts(path).output_files().expect_err(path);
+        assert!(error.to_string().contains("without `..`"), "{error}");
+    }
+    let error = opts("README.md").output_files().expect_err("duplicate");
+    assert!(
+        error.to_string().contains("must all be different"),
+        "{error}"
+    );
+}
@@ -0,0 +1,225 @@
+//! Reading all the documents of an XML Hub repository, as the first
+//! step of `build` does, for programs embedding the library. Errors
+//! are reported as the typed `XmlhubError`.
+
+use std::path::Path;
+
+use run_git::git::{BaseAndRelPath, GitWorkingDir};
+
+use crate::{
+    build_progress::{BuildProgress, NoProgress},
+    xmlhub_config::{XmlhubConfig, CONFIG_FILE_PATH},
+    xmlhub_error::XmlhubError,
+    xmlhub_fileinfo::{FileInfo, WithDerivedValues},
+    xmlhub_read_file::{read_file_infos, AttributeRules},
+};
+
+/// The documents of a repository as read by `build_index_documents`.
+#[derive(Debug)]
+pub struct IndexDocuments {
+    /// The successfully read documents, sorted by path, with their
+    /// derived attribute values
+    pub file_infos: Vec<FileInfo<WithDerivedValues>>,
+    /// The documents that could not be read or have invalid
+    /// metadata (these are left out of the index by `build`)
+    pub errors: Vec<XmlhubError>,
+}
+
+/// Read the metadata of all the XML files tracked by Git in the
+/// repository at `working_dir_path`, the same way as `build
+/// --ignore-untracked` does with its default options: the
+/// configuration in `CONFIG_FILE_PATH` is applied (which files are
+/// XML files, the attributes required per folder, the allowed
+/// version range, attachments, see `AttributeRules::with_config`),
+/// and symlinks are skipped. Fails with `XmlhubError::GitError` if
+/// the files could not be listed, or `XmlhubError::ConfigError` if
+/// the configuration is invalid; errors for individual files are
+/// collected in `IndexDocuments::errors`.
+pub fn build_index_documents(working_dir_path: &Path) -> Result<IndexDocuments, XmlhubError> {
+    build_index_documents_with_progress(working_dir_path, &NoProgress)
+}
+
+/// Same as `build_index_documents`, but reports each file to
+/// `progress` as soon as it has been read.
+pub fn build_index_documents_with_progress(
+    working_dir_path: &Path,
+    progress: &dyn BuildProgress,
+) -> Result<IndexDocuments, XmlhubError> {
+    let config =
+        XmlhubConfig::read(working_dir_path).map_err(|source| XmlhubError::ConfigError {
+            path: working_dir_path.join(CONFIG_FILE_PATH),
+            source,
+        })?;
+    let attribute_rules = AttributeRules::default().with_config(&config);
+
+    let git_working_dir = GitWorkingDir::from(working_dir_path.to_owned());
+    let mut paths: Vec<BaseAndRelPath> =
+        git_working_dir
+            .git_ls_files()
+            .map_err(|source| XmlhubError::GitError {
+                path: working_dir_path.to_owned(),
+                source,
+            })?;
+    paths.retain(|path| {
+        config.extensions.matches(&path.rel_path)
+            && !path
+                .full_path()
+                .symlink_metadata()
+                .map(|metadata| metadata.file_type().is_symlink())
+                .unwrap_or(false)
+    });
+    paths.sort_by(|a, b| a.rel_path().cmp(b.rel_path()));
+
+    let mut file_infos = Vec::new();
+    let mut errors = Vec::new();
+    for result in read_file_infos(paths, &attribute_rules, progress) {
+        match result {
+            Ok(file_info) => file_infos.push(file_info.add_derived_attributes()),
+            Err(file_errors) => errors.push(file_errors.into()),
+        }
+    }
+    Ok(IndexDocuments { file_infos, errors })
+}
+
+/// Run git with `args` in `dir`, for tests.
+#[cfg(test)]
+fn test_git(dir: &Path, args: &[&str]) -> anyhow::Result<()> {
+    if !std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .status()?
+        .success()
+    {
+        anyhow::bail!("git {args:?} failed in {dir:?}")
+    }
+    Ok(())
+}
+
+#[test]
+fn t_build_index_documents() -> anyhow::Result<()> {
+    use crate::{
+        build_progress::{ProgressEvent, RecordingProgress},
+        utillib::temp_dir::TempDir,
+    };
+
+    let dir = TempDir::new("xmlhub-t_build_index_documents")?;
+
+    // Not a Git repository
+    match build_index_documents(&dir).expect_err("not a Git repository") {
+        XmlhubError::GitError { path, .. } => assert_eq!(path, dir.to_path_buf()),
+        e => panic!("expecting GitError, got {e:?}"),
+    }
+
+    std::fs::create_dir_all(dir.join("sub"))?;
+    std::fs::write(
+        dir.join("sub/a.xml"),
+        "<?xml version=\"1.0\"?>\n\
+         <!-- Keywords: foo -->\n\
+         <!-- Version: 2.7 -->\n\
+         <!-- Packages: BEAST 2.7.0 -->\n\
+         <!-- Contact: Some One -->\n\
+         <beast version=\"2.7\"></beast>\n",
+    )?;
+    std::fs::write(
+        dir.join("b.xml"),
+        "<?xml version=\"1.0\"?>\n<!-- Keywords: foo -->\n<beast><a></beast>\n",
+    )?;
+    // Not tracked, thus ignored
+    std::fs::write(dir.join("c.xml"), "<beast>")?;
+    test_git(&dir, &["init", "-q"])?;
+    test_git(&dir, &["add", "sub/a.xml", "b.xml"])?;
+
+    let progress = RecordingProgress::default();
+    let IndexDocuments { file_infos, errors } =
+        build_index_documents_with_progress(&dir, &progress)?;
+    assert_eq!(file_infos.len(), 1);
+    assert_eq!(file_infos[0].path.rel_path(), Path::new("sub/a.xml"));
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].path(), dir.join("b.xml"));
+    match &errors[0] {
+        XmlhubError::ParseError { errors, .. } => {
+            assert!(errors[0].message.contains("parsing the XML markup"))
+        }
+        e => panic!("expecting ParseError, got {e:?}"),
+    }
+    let mut events = progress.events();
+    // Files are read in parallel, thus reported in any order
+    events.sort();
+    assert_eq!(
+        events,
+        [
+            ProgressEvent::FileParsed {
+                rel_path: "b.xml".into(),
+                errors: 1,
+                warnings: 0
+            },
+            ProgressEvent::FileParsed {
+                rel_path: "sub/a.xml".into(),
+                errors: 0,
+                warnings: 0
+            },
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn t_build_index_documents_config() -> anyhow::Result<()> {
+    use crate::utillib::temp_dir::TempDir;
+
+    let dir = TempDir::new("xmlhub-t_build_index_documents_config")?;
+    let content = "<?xml version=\"1.0\"?>\n\
+                   <!-- Keywords: foo -->\n\
+                   <!-- Version: 2.7 -->\n\
+                   <!-- Packages: BEAST 2.7.0 -->\n\
+                   <!-- Contact: Some One -->\n\
+                   <beast version=\"2.7\"></beast>\n";
+    std::fs::create_dir_all(dir.join("published"))?;
+    for path in [
+        "a.xml",
+        "b.xml.beast",
+        "c.beast",
+        "d.txt",
+        "published/e.xml",
+    ] {
+        std::fs::write(dir.join(path), content)?;
+    }
+    test_git(&dir, &["init", "-q"])?;
+    test_git(&dir, &["add", "."])?;
+
+    let rel_paths = |documents: &IndexDocuments| -> Vec<String> {
+        documents
+            .file_infos
+            .iter()
+            .map(|file_info| file_info.path.rel_path().to_owned())
+            .collect()
+    };
+
+    // Without configuration, only the .xml files are read
+    let documents = build_index_documents(&dir)?;
+    assert_eq!(rel_paths(&documents), ["a.xml", "published/e.xml"]);
+    assert!(documents.errors.is_empty());
+
+    std::fs::write(
+        dir.join(CONFIG_FILE_PATH),
+        "extensions = [\"xml\", \"beast\"]\n\
+         [require_attributes]\n\
+         \"published\" = [\"DOI\"]\n",
+    )?;
+    let documents = build_index_documents(&dir)?;
+    assert_eq!(rel_paths(&documents), ["a.xml", "b.xml.beast", "c.beast"]);
+    let [error] = &documents.errors[..] else {
+        panic!("expecting one error, got {:?}", documents.errors)
+    };
+    assert_eq!(error.path(), dir.join("published/e.xml"));
+    assert!(error.to_string().contains("DOI"), "{error}");
+
+    std::fs::write(dir.join(CONFIG_FILE_PATH), "extensions = 1\n")?;
+    match build_index_documents(&dir).expect_err("invalid configuration") {
+        XmlhubError::ConfigError { path, .. } => assert_eq!(path, dir.join(CONFIG_FILE_PATH)),
+        e => panic!("expecting ConfigError, got {e:?}"),
+    }
+
+    Ok(())
+}
@@ -3,66 +3,15 @@
 
 //! `xmlhub_indexer_defaults` was supposed to be xmlhub specific but
 //! is also covering application upgrades now. (TODO: clean up)
-use std::sync::Arc;
+use std::{borrow::Cow, sync::Arc};
 
 use ahtml::{att, AId, HtmlAllocator, HtmlAllocatorPool, Node};
 use anyhow::Result;
 use lazy_static::lazy_static;
-use run_git::git::GitWorkingDir;
-
-use crate::{
-    checkout_context::CheckoutContext,
-    git_version::{GitVersion, SemVersion},
-    ref_or_owned::RefOrOwned,
-    xmlhub_check_version::XmlhubCheckVersion,
-    xmlhub_types::OutputFile,
-};
-
-pub const XMLHUB_BINARY_FILE_NAME: &str = "xmlhub";
-
-/// Information on the Git checkout of the xmlhub repo; used
-/// by xmlhub.rs
-pub const XMLHUB_CHECKOUT: CheckoutContext<&str> = CheckoutContext {
-    // This path is replaced with the BASE_PATH argument
-    working_dir_path: ".",
-    branch_name: "master",
-    supposed_upstream_git_url: "git@cevo-git.ethz.ch:cevo-resources/xmlhub.git",
-    supposed_upstream_web_url: "https://cevo-git.ethz.ch/cevo-resources/xmlhub",
-    expected_sub_paths: &["attributes.md"],
-};
-
-/// Information on the Git checkout of the xmlhub-experiments repo;
-/// used by xmlhub.rs
-pub const XMLHUB_EXPERIMENTS_CHECKOUT: CheckoutContext<&str> = CheckoutContext {
-    // This path is replaced with the BASE_PATH argument
-    working_dir_path: ".",
-    branch_name: "master",
-    supposed_upstream_git_url: "git@cevo-git.ethz.ch:cevo-resources/xmlhub-experiments.git",
-    supposed_upstream_web_url: "https://cevo-git.ethz.ch/cevo-resources/xmlhub-experiments",
-    expected_sub_paths: &["attributes.md"],
-};
 
-/// Information on the Git checkout of the xmlhub-indexer repo; used
-/// by both xmlhub.rs and make-release.rs
-pub const SOURCE_CHECKOUT: CheckoutContext<&str> = CheckoutContext {
-    // This path is only used by make-release.rs and
-    // replaced with the program argument for xmlhub.rs
-    working_dir_path: ".",
-    branch_name: "master",
-    supposed_upstream_git_url: "git@cevo-git.ethz.ch:cevo-resources/xmlhub-indexer.git",
-    supposed_upstream_web_url: "https://cevo-git.ethz.ch/cevo-resources/xmlhub-indexer",
-    expected_sub_paths: &["Cargo.toml", "src/bin/xmlhub.rs"],
-};
+use crate::xmlhub_types::{OutputFile, OutputFiles};
 
-/// Information on the Git checkout of the xmlhub-indexer-binaries repo; currently used
-/// only by make-release.rs
-pub const BINARIES_CHECKOUT: CheckoutContext<&str> = CheckoutContext {
-    working_dir_path: "../xmlhub-indexer-binaries/",
-    branch_name: "master",
-    supposed_upstream_git_url: "git@cevo-git.ethz.ch:cevo-resources/xmlhub-indexer-binaries.git",
-    supposed_upstream_web_url: "https://cevo-git.ethz.ch/cevo-resources/xmlhub-indexer-binaries",
-    expected_sub_paths: &["macOS", "keys"],
-};
+pub const XMLHUB_BINARY_FILE_NAME: &str = "xmlhub";
 
 /// The name of the command line program.
 pub const PROGRAM_NAME: &str = XMLHUB_BINARY_FILE_NAME;
@@ -70,34 +19,43 @@ pub const PROGRAM_NAME: &str = XMLHUB_BINARY_FILE_NAME;
 /// The index file in HTML format (the one viewed when using `--open`
 /// locally).
 pub const HTML_FILE: OutputFile = OutputFile {
-    path_from_repo_top: "README.html",
+    path_from_repo_top: Cow::Borrowed("README.html"),
 };
 
 /// The index file in markdown format (the one viewed on GitLab).
 pub const MD_FILE: OutputFile = OutputFile {
-    path_from_repo_top: "README.md",
+    path_from_repo_top: Cow::Borrowed("README.md"),
 };
 
-pub fn git_log_version_checker<'t>(
-    program_version: GitVersion<SemVersion>,
-    no_version_check: bool,
-    git_working_dir: RefOrOwned<'t, GitWorkingDir>,
-) -> XmlhubCheckVersion<'t> {
-    XmlhubCheckVersion {
-        program_name: PROGRAM_NAME,
-        program_version: program_version.into(),
-        no_version_check,
-        git_working_dir,
-        html_file: (&HTML_FILE).into(),
-        md_file: (&MD_FILE).into(),
-    }
-}
+/// The file describing the attributes (for contributors).
+pub const ATTRIBUTES_FILE: OutputFile = OutputFile {
+    path_from_repo_top: Cow::Borrowed("attributes.md"),
+};
+
+/// The file name of the table of contents written as a standalone
+/// HTML fragment with `build --toc-fragment`.
+pub const TOC_FRAGMENT_FILENAME: &str = "toc.html";
+
+/// The Git notes ref in which the program version is recorded on
+/// index commits when using `build --version-notes` (instead of
+/// writing it into the commit message). The version checker reads
+/// both.
+pub const VERSION_NOTES_REF: &str = "refs/notes/xmlhub-version";
+
+/// The output files used unless overridden via `OutputFilesOpts`.
+pub const DEFAULT_OUTPUT_FILES: OutputFiles = OutputFiles {
+    html_file: HTML_FILE,
+    md_file: MD_FILE,
+    attributes_file: ATTRIBUTES_FILE,
+};
+
+/// The web page of the xmlhub-indexer repository (see
+/// `xmlhub_checkouts::SOURCE_CHECKOUT`).
+pub const SOURCE_WEB_URL: &str = "https://cevo-git.ethz.ch/cevo-resources/xmlhub-indexer";
 
 lazy_static! {
-    pub static ref GENERATED_MESSAGE: String = format!(
-        "auto-generated by {PROGRAM_NAME}, {}",
-        SOURCE_CHECKOUT.supposed_upstream_web_url
-    );
+    pub static ref GENERATED_MESSAGE: String =
+        format!("auto-generated by {PROGRAM_NAME}, {SOURCE_WEB_URL}");
 }
 
 /// The name of XML elements containing sequences; currently searching
@@ -129,28 +87,103 @@ lazy_static! {
 }
 
 /// Used afer a value for linking back to the index entry (similar to
-/// linking back from a footnote)
+/// linking back from a footnote). This is the default, can be
+/// overridden in the repository configuration, see `Symbols`.
 pub const BACK_TO_INDEX_SYMBOL: &str = "↑";
 
-/// Return the html code for loading the document symbol image
-pub fn document_symbol(html: &HtmlAllocator) -> Result<AId<Node>> {
-    /// The symbol to use in the index page for links to the original
-    /// XML file.
-    const DOCUMENT_SYMBOL_PATH: &str = ".index/document.svg";
+/// The symbol to use in the index page for links to the original XML
+/// file, by default.
+const DOCUMENT_SYMBOL_PATH: &str = ".index/document.svg";
+
+/// How the document symbol is shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocumentSymbol {
+    /// Path to an image, relative to the index files
+    Image(String),
+    Text(String),
+}
+
+/// The symbols used in the generated HTML, for environments that
+/// render some of them poorly. Taken from the `back_to_index_symbol`
+/// (text) and `document_symbol` (text, or with an `image:` prefix,
+/// the path to an image relative to the index files) settings in
+/// the repository configuration (see `XmlhubConfig`), defaulting to
+/// `BACK_TO_INDEX_SYMBOL` and the image at `DOCUMENT_SYMBOL_PATH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbols {
+    pub back_to_index: String,
+    pub document: DocumentSymbol,
+}
+
+impl Default for Symbols {
+    fn default() -> Self {
+        Self {
+            back_to_index: BACK_TO_INDEX_SYMBOL.into(),
+            document: DocumentSymbol::Image(DOCUMENT_SYMBOL_PATH.into()),
+        }
+    }
+}
+
+impl Symbols {
+    /// Build from the configured values (see the type docs); `None`
+    /// or empty strings mean to use the default.
+    pub fn from_values(back_to_index: Option<&str>, document: Option<&str>) -> Self {
+        let Symbols {
+            back_to_index: default_back_to_index,
+            document: default_document,
+        } = Self::default();
+        let back_to_index = match back_to_index {
+            Some(s) if !s.is_empty() => s.into(),
+            _ => default_back_to_index,
+        };
+        let document = match document {
+            Some(s) if !s.is_empty() => match s.strip_prefix("image:") {
+                Some(path) => DocumentSymbol::Image(path.into()),
+                None => DocumentSymbol::Text(s.into()),
+            },
+            _ => default_document,
+        };
+        Self {
+            back_to_index,
+            document,
+        }
+    }
+
+    /// Return the html code for the document symbol.
+    pub fn document_html(&self, html: &HtmlAllocator) -> Result<AId<Node>> {
+        match &self.document {
+            DocumentSymbol::Image(path) => html.img(
+                [
+                    att("src", path.as_str()),
+                    att("style", "vertical-align: -2px;"),
+                ],
+                [],
+            ),
+            DocumentSymbol::Text(text) => html.text(text.as_str()),
+        }
+    }
+}
 
-    html.img(
-        [
-            att("src", DOCUMENT_SYMBOL_PATH),
-            att("style", "vertical-align: -2px;"),
-        ],
-        [],
-    )
+lazy_static! {
+    /// The symbols used when the repository configuration doesn't
+    /// change them.
+    pub static ref DEFAULT_SYMBOLS: Symbols = Symbols::default();
 }
 
+/// The default for `box_truncate_length` in the repository
+/// configuration (see `XmlhubConfig`).
+pub const BOX_TRUNCATE_LENGTH_DEFAULT: usize = 300;
+
 pub const FILEINFO_PATH_BGCOLOR: &str = "#cec7f2";
 pub const FILEINFO_METADATA_BGCOLOR: &str = "#e3e7ff";
 pub const FILEINFO_WARNINGS_BGCOLOR: &str = "#f8e080";
 
+/// The default colours of highlighted section titles (see
+/// `Highlight`), as CSS colour values; can be changed via
+/// `highlight_colors` in the repository configuration.
+pub const HIGHLIGHT_RED_COLOR: &str = "red";
+pub const HIGHLIGHT_ORANGE_COLOR: &str = "orange";
+
 /// CSS style information; only useful for the .html file, not
 /// included in the .md file as GitLab will ignore it anyway when
 /// formatting that file.
@@ -199,6 +232,10 @@ h3 {
 .metadata_value {
   padding: 2px;
 }
+/* a SPAN in the SUMMARY of a truncated value */
+details[open] > summary > .show_more {
+  display: none;
+}
 .key_dl {
 }
 .key_dt {
@@ -207,6 +244,23 @@ h3 {
 }
 .key_dd {
 }
+.key_table {
+  border-collapse: collapse;
+}
+.key_table th {
+  text-align: left;
+}
+/* a TD */
+.key_td {
+  vertical-align: top;
+  padding-top: 0.8em;
+  padding-right: 1.5em;
+}
+/* a TD */
+.key_files_td {
+  vertical-align: top;
+  padding-top: 0.8em;
+}
 /* a STRONG */
 .key {
 }
@@ -217,3 +271,25 @@ h3 {
     ]
     .join("")
 }
+
+#[test]
+fn t_symbols() -> Result<()> {
+    let html = HTML_ALLOCATOR_POOL.get();
+    let to_string = |symbols: &Symbols| -> Result<String> {
+        Ok(html.to_html_string(symbols.document_html(&html)?, false))
+    };
+
+    let default = Symbols::from_values(None, Some(""));
+    assert_eq!(default, Symbols::default());
+    assert_eq!(default.back_to_index, "↑");
+    assert!(to_string(&default)?.contains("src=\".index/document.svg\""));
+
+    let text = Symbols::from_values(Some("^"), Some("[xml]"));
+    assert_eq!(text.back_to_index, "^");
+    assert_eq!(to_string(&text)?, "[xml]");
+
+    let image = Symbols::from_values(None, Some("image:.index/doc.png"));
+    assert_eq!(image.back_to_index, "↑");
+    assert!(to_string(&image)?.contains("src=\".index/doc.png\""));
+    Ok(())
+}
@@ -0,0 +1,588 @@
+//! Parsing of the metadata from the XML comments (and `<?xmlhub
+//! ...?>` processing instructions), without any
+//! filesystem or Git access, so that it can also be used from
+//! environments like a browser-based validator (WASM). Keep this
+//! module, and the modules it uses (also indirectly), free of imports
+//! of the IO, Git and daemon related parts.
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use anyhow::{bail, Context};
+use pluraless::pluralized;
+
+use crate::{
+    util::format_string_list,
+    xml_document::{
+        LineAndColumn, XMLDocument, XMLDocumentComment, XMLDocumentProcessingInstruction,
+    },
+    xmlhub_attributes::{
+        sort_in_definition_order, AttributeName, AttributeNeed, AttributeSource,
+        AttributeSpecification, SourceSpecification, METADATA_SPECIFICATION,
+    },
+    xmlhub_fileinfo::{AttributeValue, Issue, Metadata, WithCommentsOnly},
+};
+
+/// The target name of the processing instructions that metadata is
+/// read from, as in `<?xmlhub Keywords="foo, bar" Contact="Some
+/// One"?>`.
+pub const METADATA_PI_TARGET: &str = "xmlhub";
+
+/// A `name: value` metadata entry from the header of an XML file.
+struct HeaderEntry<'a> {
+    location: LineAndColumn,
+    /// An error message if the entry could not be split into name
+    /// and value
+    name_and_value: Result<(&'a str, Cow<'a, str>), String>,
+    from_processing_instruction: bool,
+    /// The text of the comment, or the value of the processing
+    /// instruction
+    text: &'a str,
+}
+
+impl<'a> HeaderEntry<'a> {
+    /// The entry for a comment with the text `string` (without the
+    /// `<!--` and `-->`) at `location`.
+    fn from_comment_text(location: LineAndColumn, string: &'a str) -> Self {
+        HeaderEntry {
+            location,
+            name_and_value: string
+                .split_once(":")
+                .map(|(name, value)| (name, Cow::from(value)))
+                .ok_or_else(|| "comment does not start with a keyword name and ':'".into()),
+            from_processing_instruction: false,
+            text: string,
+        }
+    }
+}
+
+impl<'a> From<XMLDocumentComment<'a>> for HeaderEntry<'a> {
+    fn from(comment: XMLDocumentComment<'a>) -> Self {
+        HeaderEntry::from_comment_text(comment.location.start(), comment.string)
+    }
+}
+
+/// Replace the predefined XML entities in `s`.
+fn unescape_xml_entities(s: &str) -> Cow<'_, str> {
+    if s.contains('&') {
+        s.replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&")
+            .into()
+    } else {
+        s.into()
+    }
+}
+
+/// Split the value of a processing instruction into its
+/// `name="value"` (or `name='value'`) pseudo-attributes.
+fn parse_pseudo_attributes(s: &str) -> Result<Vec<(&str, Cow<'_, str>)>, String> {
+    let mut pairs = Vec::new();
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        let (name, after) = rest
+            .split_once('=')
+            .ok_or_else(|| format!("expecting name=\"value\", got {rest:?}"))?;
+        let name = name.trim_end();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("invalid name {name:?}, expecting name=\"value\""));
+        }
+        let after = after.trim_start();
+        let quote = after
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| format!("the value for {name:?} is not quoted"))?;
+        let (value, after) = after[1..]
+            .split_once(quote)
+            .ok_or_else(|| format!("missing closing quote for the value for {name:?}"))?;
+        pairs.push((name, unescape_xml_entities(value)));
+        rest = after.trim_start();
+    }
+    Ok(pairs)
+}
+
+impl<'a> HeaderEntry<'a> {
+    /// The entries given in a `<?xmlhub ...?>` processing
+    /// instruction. Underscores and hyphens in the names stand for
+    /// spaces (e.g. `Contains_sequence_data="yes"`).
+    fn from_processing_instruction(pi: XMLDocumentProcessingInstruction<'a>) -> Vec<Self> {
+        let location = pi.location.start();
+        let text = pi.value.unwrap_or("");
+        match parse_pseudo_attributes(text) {
+            Ok(pairs) => pairs
+                .into_iter()
+                .map(|pair| HeaderEntry {
+                    location,
+                    name_and_value: Ok(pair),
+                    from_processing_instruction: true,
+                    text,
+                })
+                .collect(),
+            Err(e) => vec![HeaderEntry {
+                location,
+                name_and_value: Err(e),
+                from_processing_instruction: true,
+                text,
+            }],
+        }
+    }
+}
+
+/// Parse the metadata from the header of `xmldocument`, i.e. the XML
+/// comments and `<?xmlhub name="value" ...?>` processing
+/// instructions above the first XML opening element. The two can be
+/// mixed; if an attribute is given both in a comment and in a
+/// processing instruction, the comment takes precedence (so that a
+/// value generated by a tool can be overridden by hand). Giving an
+/// attribute twice in the same kind of place is an error. If `notes`
+/// is given, comments that are not metadata are collected there
+/// instead of being reported as errors: those that do not start with
+/// a name and ':', and, if `unknown_attribute_warnings` is given,
+/// also those with unknown attribute names (which are still warned
+/// about). The other arguments are as for `parse_comments`.
+pub fn parse_header(
+    xmldocument: &XMLDocument,
+    dry: bool,
+    required_attributes: &[AttributeName],
+    unknown_attribute_warnings: Option<&mut Vec<Issue>>,
+    notes: Option<&mut Vec<String>>,
+) -> Result<Metadata<WithCommentsOnly>, Vec<Issue>> {
+    parse_header_entries(
+        xmldocument.header_comments().map(HeaderEntry::from).chain(
+            xmldocument
+                .header_processing_instructions(METADATA_PI_TARGET)
+                .flat_map(HeaderEntry::from_processing_instruction),
+        ),
+        dry,
+        required_attributes,
+        unknown_attribute_warnings,
+        notes,
+    )
+}
+
+/// Parse all XML comments from above the first XML opening element
+/// out of one file as `Metadata`. The comments are passed as an
+/// iterator over `XMLDocumentComment`, which has the string and
+/// location of the comment. The `XMLDocumentComment` has a limited
+/// lifetime (validity span) indicated by the context of the call to
+/// `parse_comments`, hence passed as lifetime parameter `'a`. If
+/// `dry` is true, does not parse the values; this is used in
+/// `prepare_file` to check whether headers are complete without
+/// checking the validity of the values. The attributes listed in
+/// `required_attributes` are treated as required even if their
+/// specification says they are optional. If
+/// `unknown_attribute_warnings` is given, comments with unknown
+/// attribute names are reported there instead of as errors (and
+/// otherwise ignored).
+pub fn parse_comments<'a>(
+    comments: impl Iterator<Item = XMLDocumentComment<'a>>,
+    dry: bool,
+    required_attributes: &[AttributeName],
+    unknown_attribute_warnings: Option<&mut Vec<Issue>>,
+) -> Result<Metadata<WithCommentsOnly>, Vec<Issue>> {
+    parse_header_entries(
+        comments.map(HeaderEntry::from),
+        dry,
+        required_attributes,
+        unknown_attribute_warnings,
+        None,
+    )
+}
+
+/// The implementation of `parse_comments` and `parse_header`.
+/// Entries from comments must come before those from processing
+/// instructions.
+fn parse_header_entries<'a>(
+    entries: impl Iterator<Item = HeaderEntry<'a>>,
+    dry: bool,
+    required_attributes: &[AttributeName],
+    mut unknown_attribute_warnings: Option<&mut Vec<Issue>>,
+    mut notes: Option<&mut Vec<String>>,
+) -> Result<Metadata<WithCommentsOnly>, Vec<Issue>> {
+    let need_of = |spec: &AttributeSpecification, source_spec: &SourceSpecification| {
+        if required_attributes.contains(&spec.key) {
+            AttributeNeed::Required
+        } else {
+            source_spec.need
+        }
+    };
+    let spec_by_lowercase_key: BTreeMap<String, &AttributeSpecification> = METADATA_SPECIFICATION
+        .iter()
+        .map(|spec| (spec.key.as_ref().to_lowercase(), spec))
+        .collect();
+    let mut unseen_specs_by_lowercase_key = spec_by_lowercase_key.clone();
+    let mut map: BTreeMap<AttributeName, AttributeValue> = BTreeMap::new();
+    // Where each attribute was seen first (and whether in a
+    // processing instruction), for reporting duplicates
+    let mut first_locations: BTreeMap<AttributeName, (LineAndColumn, bool)> = BTreeMap::new();
+
+    // Collect all errors instead of stopping at the first one.
+    let mut errors: Vec<Issue> = Vec::new();
+    for entry in entries {
+        let what = if entry.from_processing_instruction {
+            "xmlhub processing instruction"
+        } else {
+            "XML comment"
+        };
+        // Using a function without arguments and calling it right
+        // away to capture the result (Ok or Err).
+        let result = (|| {
+            // Comments that are not metadata are collected as notes,
+            // if requested
+            let notes_for_comment = notes
+                .as_deref_mut()
+                .filter(|_| !entry.from_processing_instruction);
+            let (key_, value) = match &entry.name_and_value {
+                Ok(name_and_value) => name_and_value,
+                Err(e) => {
+                    if let Some(notes) = notes_for_comment {
+                        notes.push(entry.text.trim().into());
+                        return Ok(());
+                    }
+                    bail!("{e}")
+                }
+            };
+            let mut lc_key = key_.trim().to_lowercase();
+            if entry.from_processing_instruction {
+                lc_key = lc_key.replace(['_', '-'], " ");
+            }
+            let value = value.trim();
+
+            if let Some(spec) = spec_by_lowercase_key.get(&lc_key) {
+                unseen_specs_by_lowercase_key.remove(&lc_key);
+                let location = entry.location;
+                if let Some((first_location, first_from_processing_instruction)) =
+                    first_locations.get(&spec.key)
+                {
+                    if entry.from_processing_instruction && !first_from_processing_instruction {
+                        // The comment takes precedence
+                        return Ok(());
+                    }
+                    bail!(
+                        "duplicate entry for attribute name {lc_key:?} \
+                         (first given at {first_location}, again at {location})"
+                    )
+                } else {
+                    first_locations.insert(spec.key, (location, entry.from_processing_instruction));
+                    if !dry {
+                        let value = match &spec.source {
+                            AttributeSource::Specified(source_spec) => {
+                                AttributeValue::from_str_spec_and_need(
+                                    value,
+                                    spec,
+                                    need_of(spec, source_spec),
+                                )?
+                            }
+                            AttributeSource::Derived(_) | AttributeSource::Extracted(_) => {
+                                AttributeValue::from_str_and_spec(value, spec)?
+                            }
+                        };
+                        map.insert(spec.key, value);
+                    }
+                }
+            } else if let Some(warnings) = unknown_attribute_warnings.as_deref_mut() {
+                warnings.push(Issue {
+                    message: format!("{what}: unknown attribute name {lc_key:?} given"),
+                    hint: None,
+                    location: Some(entry.location),
+                });
+                if let Some(notes) = notes_for_comment {
+                    notes.push(entry.text.trim().into());
+                }
+            } else {
+                bail!("unknown attribute name {lc_key:?} given")
+            }
+            Ok(())
+        })()
+        .context(what);
+        if let Err(e) = result {
+            errors.push(Issue {
+                message: format!("{e:#}"),
+                hint: None,
+                location: Some(entry.location),
+            });
+        }
+    }
+
+    let missing: Vec<AttributeName> = unseen_specs_by_lowercase_key
+        .into_values()
+        .filter_map(|spec| {
+            let source_spec = match &spec.source {
+                AttributeSource::Specified(source_spec) => source_spec,
+                AttributeSource::Derived(_) | AttributeSource::Extracted(_) => return None,
+            };
+            // Do not report as missing if it's optional
+            if need_of(spec, source_spec) == AttributeNeed::Optional {
+                None
+            } else {
+                Some(spec.key)
+            }
+        })
+        .collect();
+    if !missing.is_empty() {
+        let sorted_missing: Vec<AttributeName> =
+            sort_in_definition_order(missing.into_iter().map(|k| (k, ())))
+                .into_iter()
+                .filter_map(|(k, v)| {
+                    v?;
+                    Some(k)
+                })
+                .collect();
+
+        pluralized! { sorted_missing.len() => attributes, these, names, are }
+        errors.push(Issue {
+            message: format!(
+                "{attributes} with {these} {names} {are} missing: {}",
+                // Show just the names, not the AttributeName wrappers
+                format_string_list(&sorted_missing),
+            ),
+            hint: None,
+            location: None,
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(Metadata::new(map))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Parse the metadata from the XML comments (and processing
+/// instructions) at the top of the XML document given as a string
+/// (the in-memory equivalent of reading a file and calling
+/// `parse_header` on it).
+/// Errors in the XML markup are reported as an `Issue`, too.
+pub fn parse_metadata_from_str(xml: &str) -> Result<Metadata<WithCommentsOnly>, Vec<Issue>> {
+    let xmldocument = XMLDocument::from_string(xml.into()).map_err(|e| {
+        vec![Issue {
+            message: format!("{e:#}"),
+            hint: None,
+            location: LineAndColumn::of_parse_error(&e),
+        }]
+    })?;
+    parse_header(&xmldocument, false, &[], None, None)
+}
+
+/// Validate a block of metadata comments on its own, without the
+/// rest of an XML document (e.g. as it is being typed in an
+/// editor). Each non-empty line is taken as one `key: value` comment;
+/// the `<!--` and `-->` around it are optional. Returns the issues
+/// that parsing the header of a file with these comments would give
+/// (unknown or duplicate attribute names, invalid values, missing
+/// required attributes), i.e. none if the block is valid. Locations
+/// refer to the lines of `text`.
+pub fn validate_comment_block(text: &str) -> Vec<Issue> {
+    let entries = text.lines().enumerate().filter_map(|(i, line)| {
+        let trimmed = line.trim_start();
+        let column = line[..line.len() - trimmed.len()].chars().count() + 1;
+        let trimmed = trimmed.trim_end();
+        if trimmed.is_empty() {
+            return None;
+        }
+        let string = match trimmed
+            .strip_prefix("<!--")
+            .and_then(|s| s.strip_suffix("-->"))
+        {
+            Some(inner) => inner,
+            None => trimmed,
+        };
+        Some(HeaderEntry::from_comment_text(
+            LineAndColumn {
+                line: i + 1,
+                column,
+            },
+            string,
+        ))
+    });
+    match parse_header_entries(entries, false, &[], None, None) {
+        Ok(_) => Vec::new(),
+        Err(issues) => issues,
+    }
+}
+
+#[test]
+fn t_validate_comment_block() {
+    let messages = |text: &str| -> Vec<String> {
+        validate_comment_block(text)
+            .into_iter()
+            .map(|issue| match issue.location {
+                Some(location) => format!("{location}: {}", issue.message),
+                None => issue.message,
+            })
+            .collect()
+    };
+    let valid = "<!-- Keywords: foo, bar -->\n\
+                 Version: 2.7.1\n\
+                 \n\
+                 \x20 <!-- Packages: BEAST 2.7.0 -->\n\
+                 Contact: Some One\n";
+    assert!(messages(valid).is_empty(), "{:?}", messages(valid));
+
+    assert_eq!(
+        messages(&format!("{valid}  Colour: red\n")),
+        ["6:3: XML comment: unknown attribute name \"colour\" given"]
+    );
+    assert_eq!(
+        messages("Keywords: foo\nVersion: 2.7.1\n"),
+        ["attributes with these names are missing: \"Packages\", \"Contact\""]
+    );
+    assert_eq!(
+        messages(&format!("{valid}Keywords: baz\n")),
+        [
+            "6:1: XML comment: duplicate entry for attribute name \"keywords\" \
+          (first given at 1:1, again at 6:1)"
+        ]
+    );
+}
+
+#[test]
+fn t_parse_metadata_from_str() {
+    let metadata = parse_metadata_from_str(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <!-- Keywords: foo, bar -->\n\
+         <!-- Version: 2.7.1 -->\n\
+         <!-- Packages: BEAST 2.7.0 -->\n\
+         <!-- Contact: Some One -->\n\
+         <beast version=\"2.7\"></beast>\n",
+    )
+    .expect("valid");
+    let keywords = crate::xmlhub_attributes::attribute_specification_by_name("Keywords")
+        .expect("present")
+        .key;
+    assert_eq!(
+        metadata.get(keywords).expect("present").as_string_list(),
+        ["foo".to_string(), "bar".to_string()].as_slice()
+    );
+
+    let messages = |xml: &str| -> Vec<String> {
+        parse_metadata_from_str(xml)
+            .expect_err("invalid")
+            .into_iter()
+            .map(|issue| match issue.location {
+                Some(location) => format!("{location}: {}", issue.message),
+                None => issue.message,
+            })
+            .collect()
+    };
+    assert_eq!(
+        messages(
+            "<!-- Keywords: foo -->\n\
+             <!-- Colour: red -->\n\
+             <beast></beast>"
+        ),
+        [
+            "2:1: XML comment: unknown attribute name \"colour\" given",
+            "attributes with these names are missing: \"Version\", \"Packages\", \"Contact\"",
+        ]
+    );
+    let markup_errors = messages("<!-- Keywords: foo -->\n<beast>");
+    assert_eq!(markup_errors.len(), 1);
+    assert!(markup_errors[0].contains("parsing the XML markup"));
+}
+
+#[test]
+fn t_parse_comments_duplicate_locations() {
+    let errors = parse_metadata_from_str(
+        "<!-- Keywords: foo -->\n\
+         <!-- Version: 2.7.1 -->\n\
+         <!-- Packages: BEAST 2.7.0 -->\n\
+         <!-- Contact: Some One -->\n\
+         <!-- keywords: bar -->\n\
+         <beast></beast>",
+    )
+    .expect_err("duplicate is an error");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].message,
+        "XML comment: duplicate entry for attribute name \"keywords\" \
+         (first given at 1:1, again at 5:1)"
+    );
+    assert_eq!(
+        errors[0].location.map(|l| l.to_string()).as_deref(),
+        Some("5:1")
+    );
+}
+
+#[test]
+fn t_parse_header_processing_instructions() {
+    let contact = crate::xmlhub_attributes::attribute_specification_by_name("Contact")
+        .expect("present")
+        .key;
+    let keywords = crate::xmlhub_attributes::attribute_specification_by_name("Keywords")
+        .expect("present")
+        .key;
+    let values = |metadata: &Metadata<WithCommentsOnly>, key| -> Vec<String> {
+        metadata
+            .get(key)
+            .expect("present")
+            .as_string_list()
+            .into_owned()
+    };
+    let parse = |header: &str| {
+        parse_metadata_from_str(&format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{header}<beast version=\"2.7\"/>\n"
+        ))
+    };
+
+    // Only a processing instruction
+    let metadata = parse(
+        "<?xmlhub Keywords=\"foo, bar\" Version='2.7.1' Packages=\"BEAST 2.7.0\"\n  \
+         Contact=\"Some &quot;One&quot;\" DOI=\"10.1000/xyz\"?>\n",
+    )
+    .expect("valid");
+    assert_eq!(values(&metadata, keywords), ["foo", "bar"]);
+    assert_eq!(values(&metadata, contact), ["Some \"One\""]);
+
+    // Only comments
+    let metadata = parse(
+        "<!-- Keywords: foo -->\n<!-- Version: 2.7.1 -->\n\
+         <!-- Packages: BEAST 2.7.0 -->\n<!-- Contact: Some One -->\n",
+    )
+    .expect("valid");
+    assert_eq!(values(&metadata, contact), ["Some One"]);
+
+    // Both: the comment takes precedence, in any order; the rest is
+    // merged
+    for header in [
+        "<!-- Contact: Comment Person -->\n\
+         <?xmlhub Keywords=\"foo\" Version=\"2.7.1\" Packages=\"BEAST 2.7.0\" \
+         Contact=\"PI Person\"?>\n",
+        "<?xmlhub Keywords=\"foo\" Version=\"2.7.1\" Packages=\"BEAST 2.7.0\" \
+         Contact=\"PI Person\"?>\n<!-- Contact: Comment Person -->\n",
+    ] {
+        let metadata = parse(header).expect("valid");
+        assert_eq!(values(&metadata, contact), ["Comment Person"]);
+        assert_eq!(values(&metadata, keywords), ["foo"]);
+    }
+
+    // Other processing instructions are ignored
+    let messages = |header: &str| -> Vec<String> {
+        parse(header)
+            .expect_err("invalid")
+            .into_iter()
+            .map(|issue| issue.message)
+            .collect()
+    };
+    assert_eq!(
+        messages("<?other Keywords=\"foo\"?>\n<!-- Keywords: foo -->\n<!-- Version: 2.7 -->\n"),
+        ["attributes with these names are missing: \"Packages\", \"Contact\""]
+    );
+    assert_eq!(
+        messages(
+            "<?xmlhub Keywords=\"foo\" Keywords=\"bar\" Version=\"2.7\" \
+             Packages=\"BEAST 2.7.0\" Contact=\"Some One\"?>\n"
+        )[0],
+        "xmlhub processing instruction: duplicate entry for attribute name \"keywords\" \
+         (first given at 2:1, again at 2:1)"
+    );
+    // Underscores stand for spaces
+    assert!(messages("<?xmlhub Contains_sequence_data=\"no\"?>\n")[0]
+        .contains("\"Contains sequence data\" is calculated automatically"));
+    assert_eq!(
+        messages("<?xmlhub Keywords=foo?>\n")[0],
+        "xmlhub processing instruction: the value for \"Keywords\" is not quoted"
+    );
+}
@@ -0,0 +1,570 @@
+//! Reading the metadata of a single XML file: parsing the header
+//! comments, extracting and deriving the attribute values, and
+//! checking them for issues (reported as warnings in the
+//! `FileInfo`).
+
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use run_git::git::BaseAndRelPath;
+
+use crate::{
+    beast_version::{check_beast_version, BeastVersion, BeastVersionRange},
+    build_progress::BuildProgress,
+    location::canonical_country,
+    util::strip_prefixes,
+    xml_document::{read_xml_file, LineAndColumn, XMLDocument},
+    xmlhub_attributes::{
+        attribute_specification_by_name, specified_attribute_name, AttributeKind, AttributeName,
+        AttributeSource, SourceSpecification, METADATA_SPECIFICATION,
+    },
+    xmlhub_config::{XmlhubConfig, CONFIG_FILE_PATH},
+    xmlhub_error::XmlhubError,
+    xmlhub_file_issues::FileErrors,
+    xmlhub_fileinfo::{
+        check_value_conflict, AttributeValue, FileId, FileInfo, HavingDerivedValues, Issue,
+        Metadata, WithDerivedValues, WithExtractedValues,
+    },
+    xmlhub_metadata_parser::parse_header,
+};
+
+lazy_static! {
+    static ref VERSION_KEY: AttributeName = attribute_specification_by_name("Version")
+        .map(|spec| spec.key)
+        .expect("'Version' attribute definition should always be present");
+    static ref ATTACHMENTS_KEY: AttributeName = attribute_specification_by_name("Attachments")
+        .map(|spec| spec.key)
+        .expect("'Attachments' attribute definition should always be present");
+}
+
+/// Warnings for the attached files (as listed in the "Attachments"
+/// attribute) that do not exist; their paths are relative to the
+/// directory of the XML file at `path`. Absolute paths and paths
+/// with `..` components are not accepted (and not looked at), since
+/// attachments have to be stored alongside the XML file.
+pub fn missing_attachments_warnings(path: &BaseAndRelPath, file_names: &[String]) -> Vec<Issue> {
+    let full_path = path.full_path();
+    let dir = full_path.parent().unwrap_or(Path::new(""));
+    file_names
+        .iter()
+        .filter_map(|file_name| {
+            let is_outside = Path::new(file_name)
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+            if is_outside {
+                Some(Issue {
+                    message: format!(
+                        "the attached file {file_name:?} referenced by the document \
+                         is not inside the folder of the document"
+                    ),
+                    hint: Some(
+                        "Attachments must be given as relative paths without `..`; \
+                         please move the file next to the XML file (or into a \
+                         subfolder) and update the reference."
+                            .into(),
+                    ),
+                    location: None,
+                })
+            } else if !dir.join(file_name).exists() {
+                Some(Issue {
+                    message: format!(
+                        "the attached file {file_name:?} referenced by the document is missing"
+                    ),
+                    hint: None,
+                    location: None,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Warnings for the values of attributes of kind
+/// `AttributeKind::Location` that are not recognized as naming a
+/// country; they are indexed as given.
+fn unknown_location_warnings(metadata: &Metadata<WithExtractedValues>) -> Vec<Issue> {
+    METADATA_SPECIFICATION
+        .iter()
+        .filter(|spec| {
+            matches!(
+                spec.source,
+                AttributeSource::Specified(SourceSpecification {
+                    kind: AttributeKind::Location { .. },
+                    ..
+                })
+            )
+        })
+        .filter_map(|spec| Some((spec.key, metadata.get(spec.key)?)))
+        .flat_map(|(key, value)| {
+            value
+                .as_string_list()
+                .iter()
+                .filter(|location| canonical_country(location).is_none())
+                .map(|location| Issue {
+                    message: format!(
+                        "{:?}: {location:?} is not known as a country, thus it is \
+                         indexed as given",
+                        key.as_ref()
+                    ),
+                    hint: Some(
+                        "Countries are indexed under a common name, whether given by \
+                         name or by their two or three letter ISO code (e.g. \"CH\" or \
+                         \"Switzerland\"). Other locations (like cities) are fine, too, \
+                         but are indexed separately."
+                            .into(),
+                    ),
+                    location: None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The BEAST version given in the "Version" attribute (which may
+/// be prefixed with "BEAST").
+fn user_specified_version<H: HavingDerivedValues>(metadata: &Metadata<H>) -> Result<BeastVersion> {
+    let att_val: &AttributeValue = metadata
+        .get(*VERSION_KEY)
+        .context("missing 'Version' entry")?;
+    let att_vals = att_val.as_string_list();
+    let user_specified_str = att_vals.first().context("'Version' entry is empty")?;
+    // Have to skip any "BEAST "
+    let user_specified_version_str = strip_prefixes(
+        user_specified_str,
+        &["BEAST2 ", "BEAST2", "BEAST ", "BEAST"],
+    )
+    .trim();
+    BeastVersion::from_str(user_specified_version_str)
+}
+
+/// An issue if the "Version" given in `metadata` is outside of
+/// `range` (the `allowed_version_range` of the repository). Versions
+/// that can't be parsed are not reported here, since
+/// `read_file_info` already warns about them.
+pub fn version_range_issue<H: HavingDerivedValues>(
+    metadata: &Metadata<H>,
+    range: &BeastVersionRange,
+) -> Option<Issue> {
+    let version = user_specified_version(metadata).ok()?;
+    if range.contains(&version) {
+        return None;
+    }
+    Some(Issue {
+        message: format!(
+            "\"Version\": {:?} is outside of the allowed range {:?}",
+            version.string,
+            range.to_string()
+        ),
+        hint: Some(
+            format!(
+                "The BEAST versions accepted in this repository are set via \
+                 `allowed_version_range` in the file `{CONFIG_FILE_PATH}`."
+            )
+            .into(),
+        ),
+        location: None,
+    })
+}
+
+/// Read the file at `path` (with the id `id`, see `FileId`) and
+/// extract its metadata, treating the attributes in
+/// `required_attributes` as required even if they are optional in
+/// `METADATA_SPECIFICATION`. If `lenient_unknown_attributes` is
+/// true, unknown attribute names are reported as warnings instead
+/// of errors. If `show_file_comments` is true, the header comments
+/// that are not metadata are kept as `FileInfo::notes` (see
+/// `parse_header`). The "Attachments" attribute is only extracted
+/// if `attachments` is true (see `XmlhubConfig::attachments`).
+pub fn read_file_info(
+    id: FileId,
+    path: BaseAndRelPath,
+    required_attributes: &[AttributeName],
+    lenient_unknown_attributes: bool,
+    show_file_comments: bool,
+    attachments: bool,
+) -> Result<FileInfo<WithExtractedValues>, FileErrors> {
+    let xmldocument =
+        read_xml_file(&path.full_path()).map_err(|e| document_file_errors(&path, e))?;
+    file_info_from_document(
+        id,
+        path,
+        &xmldocument,
+        required_attributes,
+        lenient_unknown_attributes,
+        show_file_comments,
+        attachments,
+    )
+}
+
+/// The error report for a file that could not be read or parsed.
+fn document_file_errors(path: &BaseAndRelPath, e: anyhow::Error) -> FileErrors {
+    FileErrors {
+        path: path.clone(),
+        errors: vec![Issue {
+            message: format!("{e:#}"),
+            hint: None,
+            location: LineAndColumn::of_parse_error(&e),
+        }],
+    }
+}
+
+/// The part of `read_file_info` after reading the file.
+fn file_info_from_document(
+    id: FileId,
+    path: BaseAndRelPath,
+    xmldocument: &XMLDocument,
+    required_attributes: &[AttributeName],
+    lenient_unknown_attributes: bool,
+    show_file_comments: bool,
+    attachments: bool,
+) -> Result<FileInfo<WithExtractedValues>, FileErrors> {
+    let mut warnings: Vec<Issue> = Vec::new();
+    let mut notes: Vec<String> = Vec::new();
+
+    let metadata = parse_header(
+        xmldocument,
+        false,
+        required_attributes,
+        lenient_unknown_attributes.then_some(&mut warnings),
+        show_file_comments.then_some(&mut notes),
+    )
+    .map_err(|errors| FileErrors {
+        path: path.clone(),
+        errors,
+    })?;
+
+    if let Some(content) = xmldocument.content_before_declaration() {
+        warnings.push(Issue {
+            message: format!(
+                "there is content before the XML declaration, which was ignored: \
+                 {content:?}"
+            ),
+            hint: Some(
+                "The XML declaration (`<?xml ...?>`) must be at the very start of the \
+                 file; please remove anything before it."
+                    .into(),
+            ),
+            location: None,
+        });
+    }
+    if let Some(encoding) = xmldocument.transcoded_from() {
+        warnings.push(Issue {
+            message: format!(
+                "the file is encoded in {encoding} and was converted to UTF-8 \
+                 for reading; consider converting the file itself to UTF-8"
+            ),
+            hint: None,
+            location: None,
+        });
+    }
+
+    let skip: &[AttributeName] = if attachments {
+        &[]
+    } else {
+        &[*ATTACHMENTS_KEY]
+    };
+    let metadata = metadata.add_extracted_attributes(xmldocument, skip, &mut warnings);
+    if let Some(attachments) = metadata.get(*ATTACHMENTS_KEY) {
+        warnings.extend(missing_attachments_warnings(
+            &path,
+            &attachments.as_string_list(),
+        ));
+    }
+    warnings.extend(unknown_location_warnings(&metadata));
+
+    // Check the version in the XML: verify that it fits
+    // what the user provided in the XML comment.
+    match (|| -> Result<_> {
+        let user_specified_version = user_specified_version(&metadata)?;
+        user_specified_version.major.context(
+            "provided 'Version' has no BEAST2-major number part \
+             or is not a BEAST2 version",
+        )?;
+
+        let document_version = check_beast_version(xmldocument.document(), path.rel_path(), false)?;
+        if let Some(issue) = check_value_conflict(
+            *VERSION_KEY,
+            &user_specified_version,
+            "the <beast> element in the document",
+            &document_version,
+            // Only the BEAST2 major number needs to agree,
+            // and only if the document specifies one
+            |user, document| document.major.is_none() || document.major == user.major,
+            Some(
+                "The BEAST2 major version numbers differ. Please edit the file \
+                 to make both versions match the BEAST version you're \
+                 actually using."
+                    .into(),
+            ),
+        ) {
+            warnings.push(issue);
+        }
+        Ok(())
+    })() {
+        Ok(()) => (),
+        // XX why a warning for an error?
+        Err(e) => warnings.push(Issue {
+            message: format!("{e:#}"),
+            hint: None,
+            location: None,
+        }),
+    }
+
+    Ok(FileInfo {
+        id,
+        path,
+        metadata,
+        warnings,
+        notes,
+    })
+}
+
+/// Read the metadata of the single XML file at `path`, including the
+/// derived attribute values, as `build` does for each file (but
+/// without needing a Git repository, and without any per-folder
+/// required attributes).
+pub fn read_file_metadata(path: &Path) -> Result<FileInfo<WithDerivedValues>, XmlhubError> {
+    let bytes = std::fs::read(path).map_err(|source| XmlhubError::IoError {
+        path: path.to_owned(),
+        source,
+    })?;
+    let path = BaseAndRelPath::new(None, path.to_owned());
+    let id = FileId::from_rel_path(path.rel_path(), 0);
+    let xmldocument = XMLDocument::from_bytes(bytes).map_err(|e| document_file_errors(&path, e))?;
+    Ok(
+        file_info_from_document(id, path, &xmldocument, &[], false, false, false)?
+            .add_derived_attributes(),
+    )
+}
+
+/// How strictly the attributes of the files are checked. Which of
+/// the optional attributes are to be treated as required: those
+/// given via `--require-attributes` for all files, plus those listed
+/// under `require_attributes` in the repository configuration for
+/// the folder of a file or any of its parent folders.
+/// And whether unknown attribute names are only warned about
+/// (`--lenient-unknown-attributes`), whether list items given more
+/// than once are warned about (`--warn-duplicate-list-items`), and
+/// whether comments that are not metadata are kept as notes
+/// (`--show-file-comments`), and whether a "Version" outside of the
+/// `allowed_version_range` of the repository (taken from its
+/// configuration, see `with_config`) is an error
+/// (`--strict-version-range`), and whether the "Attachments"
+/// attribute is extracted (also from the configuration).
+#[derive(Debug, Default, Clone)]
+pub struct AttributeRules {
+    pub everywhere: Vec<AttributeName>,
+    pub lenient_unknown_attributes: bool,
+    pub warn_duplicate_list_items: bool,
+    pub show_file_comments: bool,
+    pub strict_version_range: bool,
+    pub by_folder: BTreeMap<PathBuf, Vec<AttributeName>>,
+    pub allowed_version_range: Option<BeastVersionRange>,
+    pub attachments: bool,
+}
+
+impl AttributeRules {
+    pub fn from_names(names: &[String]) -> Result<Self> {
+        let everywhere = names
+            .iter()
+            .map(|name| specified_attribute_name(name.trim()))
+            .collect::<Result<_>>()
+            .context("parsing the --require-attributes option")?;
+        Ok(Self {
+            everywhere,
+            lenient_unknown_attributes: false,
+            warn_duplicate_list_items: false,
+            show_file_comments: false,
+            strict_version_range: false,
+            by_folder: BTreeMap::new(),
+            allowed_version_range: None,
+            attachments: false,
+        })
+    }
+
+    /// Take the settings from the repository configuration into
+    /// account.
+    pub fn with_config(self, config: &XmlhubConfig) -> Self {
+        Self {
+            by_folder: config.require_attributes.clone(),
+            allowed_version_range: config.allowed_version_range.clone(),
+            attachments: config.attachments,
+            ..self
+        }
+    }
+
+    /// The attributes required for the file at `path`.
+    pub fn required_for_path(&self, path: &BaseAndRelPath) -> Vec<AttributeName> {
+        let mut names = self.everywhere.clone();
+        for dir in path.rel_path.ancestors().skip(1) {
+            for name in self.by_folder.get(dir).into_iter().flatten() {
+                if !names.contains(name) {
+                    names.push(*name);
+                }
+            }
+        }
+        names
+    }
+}
+
+/// Map each file to the info extracted from it (or `FileErrors`
+/// when there were errors), including path and an id, held in a
+/// `FileInfo` struct (see `read_file_info`). The ids are derived from the paths (see
+/// `FileId`), and are used to refer to each item in document-local
+/// links in the generated HTML/Markdown files. Reports each file to
+/// `progress` as soon as it has been read.
+pub fn read_file_infos(
+    paths: Vec<BaseAndRelPath>,
+    attribute_rules: &AttributeRules,
+    progress: &dyn BuildProgress,
+) -> Vec<Result<FileInfo<WithExtractedValues>, FileErrors>> {
+    let ids = FileId::for_paths(&paths);
+    ids.into_par_iter()
+        .zip(paths)
+        .map(|(id, path)| {
+            let result = (|| -> Result<FileInfo<WithExtractedValues>, FileErrors> {
+                let required = attribute_rules.required_for_path(&path);
+                let mut file_info = read_file_info(
+                    id,
+                    path,
+                    &required,
+                    attribute_rules.lenient_unknown_attributes,
+                    attribute_rules.show_file_comments,
+                    attribute_rules.attachments,
+                )?;
+                if let Some(range) = &attribute_rules.allowed_version_range {
+                    if let Some(issue) = version_range_issue(&file_info.metadata, range) {
+                        if attribute_rules.strict_version_range {
+                            return Err(FileErrors {
+                                path: file_info.path,
+                                errors: vec![issue],
+                            });
+                        }
+                        file_info.warnings.push(issue);
+                    }
+                }
+                Ok(file_info)
+            })();
+            match &result {
+                Ok(file_info) => {
+                    progress.on_file_parsed(file_info.path.rel_path(), 0, file_info.warnings.len())
+                }
+                Err(file_errors) => progress.on_file_parsed(
+                    file_errors.path.rel_path(),
+                    file_errors.errors.len(),
+                    0,
+                ),
+            }
+            result
+        })
+        .collect()
+}
+
+#[test]
+fn t_read_file_metadata() {
+    use crate::utillib::temp_dir::TempDir;
+
+    let dir = TempDir::new("xmlhub-t_read_file_metadata").unwrap();
+    let path = dir.join("a.xml");
+    std::fs::write(
+        &path,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <!-- Keywords: foo, bar -->\n\
+         <!-- Version: 2.7 -->\n\
+         <!-- Packages: BEAST 2.7.0 -->\n\
+         <!-- Contact: Some One -->\n\
+         <!-- DOI: 10.1000/xyz123 -->\n\
+         <beast version=\"2.7\"></beast>\n",
+    )
+    .unwrap();
+
+    let file_info = read_file_metadata(&path).unwrap();
+    let values = |name: &'static str| -> Vec<String> {
+        let key = attribute_specification_by_name(name).unwrap().key;
+        file_info
+            .metadata
+            .get(key)
+            .map(|value| value.as_string_list().into_owned())
+            .unwrap_or_default()
+    };
+    assert_eq!(values("Keywords"), ["foo", "bar"]);
+    assert_eq!(values("Version"), ["2.7"]);
+    assert_eq!(values("Contact"), ["Some One"]);
+    // Derived from "DOI"
+    assert_eq!(values("Citation via DOI"), ["10.1000/xyz123"]);
+    assert!(file_info.warnings.is_empty(), "{:?}", file_info.warnings);
+
+    let missing = dir.join("missing.xml");
+    match read_file_metadata(&missing).unwrap_err() {
+        XmlhubError::IoError { path, source } => {
+            assert_eq!(path, missing);
+            assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+        }
+        e => panic!("expecting IoError, got {e:?}"),
+    }
+
+    let invalid = dir.join("invalid.xml");
+    std::fs::write(
+        &invalid,
+        "<?xml version=\"1.0\"?>\n<!-- Keywords: foo -->\n<beast><a></beast>\n",
+    )
+    .unwrap();
+    let e = read_file_metadata(&invalid).unwrap_err();
+    assert_eq!(e.path(), invalid);
+    match &e {
+        XmlhubError::ParseError { path, errors } => {
+            assert_eq!(path, &invalid);
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0].message.contains("parsing the XML markup"));
+            assert!(errors[0].location.is_some());
+        }
+        e => panic!("expecting ParseError, got {e:?}"),
+    }
+    // Converts into anyhow::Error, as used in the binary
+    let e: anyhow::Error = e.into();
+    assert!(e.to_string().contains("parsing the XML markup"));
+
+    // Missing metadata is a ParseError, too
+    std::fs::write(
+        &invalid,
+        "<?xml version=\"1.0\"?>\n<beast version=\"2.7\"/>\n",
+    )
+    .unwrap();
+    match read_file_metadata(&invalid).unwrap_err() {
+        XmlhubError::ParseError { errors, .. } => assert!(!errors.is_empty()),
+        e => panic!("expecting ParseError, got {e:?}"),
+    }
+}
+
+#[test]
+fn t_read_file_metadata_content_before_declaration() {
+    use crate::utillib::temp_dir::TempDir;
+
+    let dir = TempDir::new("xmlhub-t_read_file_metadata_content_before_declaration").unwrap();
+    let contents = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+                    <!-- Keywords: foo -->\n\
+                    <!-- Version: 2.7 -->\n\
+                    <!-- Packages: BEAST 2.7.0 -->\n\
+                    <!-- Contact: Some One -->\n\
+                    <beast version=\"2.7\"></beast>\n";
+
+    let bom = dir.join("bom.xml");
+    std::fs::write(&bom, format!("\u{FEFF}{contents}")).unwrap();
+    let file_info = read_file_metadata(&bom).unwrap();
+    assert!(file_info.warnings.is_empty(), "{:?}", file_info.warnings);
+
+    let junk = dir.join("junk.xml");
+    std::fs::write(&junk, format!("oops\n{contents}")).unwrap();
+    let file_info = read_file_metadata(&junk).unwrap();
+    assert_eq!(file_info.warnings.len(), 1);
+    assert!(file_info.warnings[0]
+        .message
+        .contains("content before the XML declaration"));
+}
@@ -1,7 +1,155 @@
 //! Various types used by xmlhub-indexer
 
-#[derive(Clone)]
+use std::{borrow::Cow, fmt::Display, path::Path};
+
+use anyhow::{anyhow, bail, Context, Result};
+use itertools::Itertools;
+
+use crate::xmlhub_indexer_defaults::TOC_FRAGMENT_FILENAME;
+
+#[derive(Clone, Debug)]
 pub struct OutputFile {
     /// Relative path from the top of the xmlhub repository
-    pub path_from_repo_top: &'static str,
+    pub path_from_repo_top: Cow<'static, str>,
+}
+
+/// The set of files written by the `build` command.
+#[derive(Clone, Debug)]
+pub struct OutputFiles {
+    /// The index in HTML format
+    pub html_file: OutputFile,
+    /// The index in Markdown format
+    pub md_file: OutputFile,
+    /// The file describing the attributes (for contributors)
+    pub attributes_file: OutputFile,
+}
+
+impl OutputFiles {
+    /// The same files, but placed in the directory `dir` (relative
+    /// to the top of the repository, without trailing slash).
+    pub fn in_subdir(&self, dir: &str) -> Self {
+        let in_dir = |file: &OutputFile| OutputFile {
+            path_from_repo_top: format!("{dir}/{}", file.path_from_repo_top).into(),
+        };
+        let Self {
+            html_file,
+            md_file,
+            attributes_file,
+        } = self;
+        Self {
+            html_file: in_dir(html_file),
+            md_file: in_dir(md_file),
+            attributes_file: in_dir(attributes_file),
+        }
+    }
+
+    pub fn paths_from_repo_top(&self) -> [&str; 3] {
+        let Self {
+            html_file,
+            md_file,
+            attributes_file,
+        } = self;
+        [
+            &html_file.path_from_repo_top,
+            &md_file.path_from_repo_top,
+            &attributes_file.path_from_repo_top,
+        ]
+    }
+
+    /// The path of the table of contents fragment (`build
+    /// --toc-fragment`): in the same directory as the HTML file.
+    pub fn toc_fragment_path_from_repo_top(&self) -> String {
+        match self.html_file.path_from_repo_top.rsplit_once('/') {
+            Some((dir, _)) => format!("{dir}/{TOC_FRAGMENT_FILENAME}"),
+            None => TOC_FRAGMENT_FILENAME.into(),
+        }
+    }
+}
+
+/// The file name extensions (lower-case, without the dot) of the
+/// files that are treated as XML files, i.e. indexed by `build` and
+/// accepted by `check`, `prepare` and `add-to`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct XmlExtensions(Vec<String>);
+
+impl Default for XmlExtensions {
+    fn default() -> Self {
+        Self(vec!["xml".into()])
+    }
+}
+
+impl XmlExtensions {
+    /// Parse a comma-separated list like `xml,beast` (the dots are
+    /// optional).
+    pub fn from_list(list: &str) -> Result<Self> {
+        Self::from_names(list.split(',').map(str::trim))
+            .with_context(|| anyhow!("in the list of extensions {list:?}"))
+    }
+
+    /// Build from a list like `["xml", "beast"]` (the dots are
+    /// optional).
+    pub fn from_names<'s>(names: impl IntoIterator<Item = &'s str>) -> Result<Self> {
+        let mut extensions: Vec<String> = Vec::new();
+        for name in names {
+            let extension = name.strip_prefix('.').unwrap_or(name);
+            if extension.is_empty() || extension.contains(['.', '/']) {
+                bail!(
+                    "invalid file name extension {name:?}, expecting a name like \
+                     \"xml\" or \"beast\""
+                )
+            }
+            let extension = extension.to_lowercase();
+            if !extensions.contains(&extension) {
+                extensions.push(extension);
+            }
+        }
+        if extensions.is_empty() {
+            bail!("expecting at least one file name extension")
+        }
+        Ok(Self(extensions))
+    }
+
+    /// Whether the (last) extension of `path` is one of the
+    /// extensions, compared case-insensitively. (Thus `beast` also
+    /// covers `.xml.beast` files.)
+    pub fn matches(&self, path: &Path) -> bool {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) => self.0.iter().any(|e| e.eq_ignore_ascii_case(extension)),
+            None => false,
+        }
+    }
+}
+
+impl Display for XmlExtensions {
+    /// E.g. `.xml or .beast`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.iter().map(|e| format!(".{e}")).join(" or "))
+    }
+}
+
+#[test]
+fn t_xml_extensions() -> Result<()> {
+    let default = XmlExtensions::default();
+    assert!(default.matches(Path::new("a/b.xml")));
+    assert!(default.matches(Path::new("b.XML")));
+    assert!(!default.matches(Path::new("b.xml.beast")));
+    assert!(!default.matches(Path::new("xml")));
+    assert_eq!(default.to_string(), ".xml");
+
+    let extensions = XmlExtensions::from_names(["xml", ".Beast", "beast"])?;
+    assert!(extensions.matches(Path::new("b.xml.beast")));
+    assert!(extensions.matches(Path::new("b.beast")));
+    assert!(!extensions.matches(Path::new("b.xml.bak")));
+    assert_eq!(extensions.to_string(), ".xml or .beast");
+
+    assert!(XmlExtensions::from_names(["xml", ""]).is_err());
+    assert!(XmlExtensions::from_names(["xml.beast"]).is_err());
+    assert!(XmlExtensions::from_names([]).is_err());
+
+    assert_eq!(
+        XmlExtensions::from_list("xml, .Beast,beast")?,
+        XmlExtensions::from_names(["xml", "beast"])?
+    );
+    assert!(XmlExtensions::from_list("xml,").is_err());
+    Ok(())
 }